@@ -0,0 +1,66 @@
+use assimilation::rules::{simulate_selection, BoardTopology, Tile, TileState};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const SIZES: [i32; 5] = [10, 25, 50, 100, 200];
+const NUM_IDS: u32 = 5;
+
+// A square board of random colors, with the player owning the top left tile
+fn random_board(size: i32, player: u32) -> Vec<Tile<u32>> {
+    let mut rng = StdRng::seed_from_u64(size as u64);
+    board(size, player, |_, _| rng.gen_range(0..NUM_IDS))
+}
+
+fn board(size: i32, player: u32, mut color: impl FnMut(i32, i32) -> u32) -> Vec<Tile<u32>> {
+    let mut tiles = vec![];
+    for row in 0..size {
+        for column in 0..size {
+            let state = if (row, column) == (0, 0) {
+                TileState::Owned(player)
+            } else {
+                TileState::Unowned(color(row, column))
+            };
+            tiles.push(Tile {
+                row,
+                column,
+                state,
+                bonus: None,
+                weight: 1,
+            });
+        }
+    }
+    tiles
+}
+
+fn capture(c: &mut Criterion) {
+    // The rules only need to tell players apart
+    let player = 0;
+
+    let mut group = c.benchmark_group("capture");
+    group.sample_size(20);
+    for size in SIZES {
+        // A typical move, capturing a handful of tiles
+        let tiles = random_board(size, player);
+        group.bench_with_input(BenchmarkId::new("random", size), &tiles, |b, tiles| {
+            b.iter_batched(
+                || tiles.clone(),
+                |mut tiles| simulate_selection(&mut tiles, BoardTopology::Hex, 1, player),
+                BatchSize::LargeInput,
+            )
+        });
+
+        // Every tile is the selected color, so the whole board is captured
+        let tiles = board(size, player, |_, _| 1);
+        group.bench_with_input(BenchmarkId::new("flood", size), &tiles, |b, tiles| {
+            b.iter_batched(
+                || tiles.clone(),
+                |mut tiles| simulate_selection(&mut tiles, BoardTopology::Hex, 1, player),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, capture);
+criterion_main!(benches);
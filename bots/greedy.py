@@ -0,0 +1,52 @@
+#!/usr/bin/env python3
+"""A bot for Assimilation, played over the game's stdio protocol.
+
+Pick "External program" as the bot on the setup screen, with the command
+`python3 bots/greedy.py`. The game writes each turn to the bot's standard
+input a line at a time:
+
+    board <rows> <columns> <hex|square4|square8>
+    <one line per row, a token per tile>
+    scores <yours> <best opponent's>
+    captures <color>=<tiles> ...
+    moves <color> ...
+    go
+
+and waits for `select <color>` in reply. Tiles are '.' where there is no
+tile, '#' for walls, '@' for your own, '*' for an opponent's, or the color
+of an unowned tile. `info <text>` lines are logged by the game, and it sends
+`quit` once the game is over. This bot picks whichever color captures the
+most tiles.
+"""
+
+import sys
+
+
+def choose(captures, moves):
+    # Ties go to the lowest color, like the built in bots
+    return max(moves, key=lambda color: (captures.get(color, 0), -color))
+
+
+def main():
+    captures = {}
+    moves = []
+    for line in sys.stdin:
+        command, _, rest = line.strip().partition(" ")
+        if command == "captures":
+            captures = {
+                int(color): int(tiles)
+                for color, tiles in (pair.split("=") for pair in rest.split())
+            }
+        elif command == "moves":
+            moves = [int(color) for color in rest.split()]
+        elif command == "go":
+            if moves:
+                print(f"select {choose(captures, moves)}", flush=True)
+            else:
+                print("info no colors to pick", flush=True)
+        elif command == "quit":
+            break
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,805 @@
+use crate::book::{self, OpeningBook};
+use crate::core::{
+    simulate_selection, simulate_selection_value, GamePhase, GameState, MoveCache, Player,
+    PlayerKind, SelectEvent, SwapEvent, Tile, TileState,
+};
+use crate::game::GameStep;
+use crate::{decay, storage, TIME_STEP};
+use bevy::prelude::*;
+use bevy::utils::Instant;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+#[cfg(not(target_family = "wasm"))]
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Scores a candidate selection for the given player, higher is better
+pub type Evaluation = fn(&[Tile], &GameState, Entity, u32) -> i32;
+
+// Controls how quickly bots move, mostly useful when watching bot-only games
+pub struct Playback {
+    pub paused: bool,
+    pub speed: f32,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+        }
+    }
+}
+
+// The most recent score of every candidate color, for each bot
+#[derive(Default)]
+pub struct BotEvaluations(pub HashMap<Entity, Vec<(u32, i32)>>);
+
+// Evaluations already made, by position and personality, so positions that
+// come up again don't have to be evaluated again
+#[derive(Default)]
+pub struct EvaluationMemo(pub HashMap<(u64, Personality), Vec<(u32, i32)>>);
+
+// Each player's estimated chance of winning, redone after every move while
+// shown next to the scores
+#[derive(Default)]
+pub struct WinChances(pub HashMap<Entity, f32>);
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct WinChanceDisplay {
+    pub enabled: bool,
+}
+
+impl WinChanceDisplay {
+    pub fn load() -> Self {
+        storage::load(WIN_CHANCE_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(WIN_CHANCE_KEY, self)
+    }
+}
+
+const WIN_CHANCE_KEY: &str = "win_chances";
+// How much a tile on a player's frontier counts for, next to one they own
+const FRONTIER_WEIGHT: f32 = 0.5;
+// How far apart players need to be for the estimate to call the game, for
+// every tile still up for grabs
+const WIN_CHANCE_SPREAD: f32 = 0.75;
+// Bounds the memory used by long games, the memo is simply started over
+const MAX_MEMO_ENTRIES: usize = 4096;
+// Searches which run on the game's steps get this much time in each one
+const SLICE_TIME: Duration = Duration::from_millis(4);
+// How many moves ahead the deep bot looks, at most
+const DEEP_SEARCH_DEPTH: u32 = 3;
+// After thinking this long, the deep bot plays the best move it found so far
+const MAX_THINK_TIME: f32 = 3.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Personality {
+    // Maximizes immediate captures
+    Aggressive,
+    // Minimizes the opponents' best reply
+    Defensive,
+    // Maximizes the number of unowned tiles bordering its territory
+    Expansionist,
+    // Looks several moves ahead, assuming everyone plays their biggest capture
+    Deep,
+}
+
+impl Personality {
+    pub const ALL: [Personality; 4] = [
+        Personality::Aggressive,
+        Personality::Defensive,
+        Personality::Expansionist,
+        Personality::Deep,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Personality::Aggressive => "Aggressive",
+            Personality::Defensive => "Defensive",
+            Personality::Expansionist => "Expansionist",
+            Personality::Deep => "Deep",
+        }
+    }
+
+    pub fn evaluation(&self) -> Evaluation {
+        match self {
+            Personality::Aggressive => evaluate_captures,
+            Personality::Defensive => evaluate_denial,
+            Personality::Expansionist => evaluate_frontier,
+            Personality::Deep => evaluate_captures,
+        }
+    }
+
+    // How many moves ahead the bot searches
+    pub fn depth(&self) -> u32 {
+        match self {
+            Personality::Deep => DEEP_SEARCH_DEPTH,
+            _ => 1,
+        }
+    }
+}
+
+fn evaluate_captures(tiles: &[Tile], state: &GameState, player: Entity, id: u32) -> i32 {
+    let mut board = tiles.to_vec();
+    simulate_selection_value(&mut board, state.topology, id, player) as i32
+}
+
+fn evaluate_denial(tiles: &[Tile], state: &GameState, player: Entity, id: u32) -> i32 {
+    let mut board = tiles.to_vec();
+    let captured = simulate_selection_value(&mut board, state.topology, id, player) as i32;
+
+    let mut next_state = state.clone();
+    next_state.last_selections.insert(player, id);
+
+    let best_reply = state
+        .players
+        .iter()
+        .filter(|opponent| **opponent != player)
+        .flat_map(|opponent| {
+            let board = &board;
+            let next_state = &next_state;
+            next_state
+                .ids
+                .keys()
+                .filter(move |reply| next_state.is_selection_allowed(*opponent, **reply))
+                .map(move |reply| {
+                    let mut board = board.clone();
+                    simulate_selection_value(&mut board, next_state.topology, *reply, *opponent)
+                })
+        })
+        .max()
+        .unwrap_or(0) as i32;
+
+    captured - best_reply
+}
+
+fn evaluate_frontier(tiles: &[Tile], state: &GameState, player: Entity, id: u32) -> i32 {
+    let mut board = tiles.to_vec();
+    simulate_selection(&mut board, state.topology, id, player);
+    frontier_size(&board, state, player) as i32
+}
+
+// How much more score the move captures than the opponents' best replies,
+// looking the given number of moves ahead
+fn lookahead(tiles: &[Tile], state: &GameState, player: Entity, id: u32, depth: u32) -> i32 {
+    let mut board = tiles.to_vec();
+    let captured = simulate_selection_value(&mut board, state.topology, id, player) as i32;
+    if depth <= 1 {
+        return captured;
+    }
+
+    let mut next_state = state.clone();
+    next_state.last_selections.insert(player, id);
+    next_state.end_turn();
+    let opponent = next_state.players[0];
+    let best_reply = next_state
+        .ids
+        .keys()
+        .filter(|reply| next_state.is_selection_allowed(opponent, **reply))
+        .map(|reply| lookahead(&board, &next_state, opponent, *reply, depth - 1))
+        .max()
+        .unwrap_or(0);
+
+    captured - best_reply
+}
+
+// Each player's chance of winning, from their score and how much they can
+// reach next. The more tiles are left to capture, the less a lead counts for
+pub fn win_chances(
+    tiles: &[Tile],
+    state: &GameState,
+    scores: &HashMap<Entity, u32>,
+) -> HashMap<Entity, f32> {
+    match state.phase {
+        GamePhase::Over(winner) => {
+            return state
+                .players
+                .iter()
+                .map(|player| (*player, if *player == winner { 1.0 } else { 0.0 }))
+                .collect()
+        }
+        GamePhase::Draw => {
+            let share = 1.0 / state.players.len().max(1) as f32;
+            return state
+                .players
+                .iter()
+                .map(|player| (*player, share))
+                .collect();
+        }
+        _ => (),
+    }
+
+    let remaining = state.unowned.values().sum::<u32>() as f32;
+    let spread = WIN_CHANCE_SPREAD * remaining.sqrt().max(1.0);
+    let strengths = state
+        .players
+        .iter()
+        .map(|player| {
+            let score = scores.get(player).cloned().unwrap_or(0) as f32;
+            let frontier = frontier_size(tiles, state, *player) as f32;
+            (*player, (score + frontier * FRONTIER_WEIGHT) / spread)
+        })
+        .collect::<Vec<_>>();
+
+    // Shifted by the strongest, so the exponentials can't overflow
+    let strongest = strengths
+        .iter()
+        .map(|(_, strength)| *strength)
+        .fold(f32::MIN, f32::max);
+    let weights = strengths
+        .iter()
+        .map(|(player, strength)| (*player, (strength - strongest).exp()))
+        .collect::<Vec<_>>();
+    let total = weights.iter().map(|(_, weight)| weight).sum::<f32>();
+    weights
+        .into_iter()
+        .map(|(player, weight)| (player, weight / total))
+        .collect()
+}
+
+pub fn update_win_chances(
+    display: Res<WinChanceDisplay>,
+    state: Res<GameState>,
+    tiles: Query<&Tile>,
+    players: Query<&Player>,
+    mut chances: ResMut<WinChances>,
+    mut estimated: Local<Option<(u64, GamePhase)>>,
+) {
+    if !display.enabled {
+        return;
+    }
+    let position = Some((state.board_hash.value, state.phase));
+    if *estimated == position {
+        return;
+    }
+    *estimated = position;
+
+    let tiles = tiles.iter().cloned().collect::<Vec<_>>();
+    let scores = state
+        .players
+        .iter()
+        .filter_map(|player| Some((*player, players.get(*player).ok()?.score)))
+        .collect();
+    chances.0 = win_chances(&tiles, &state, &scores);
+    debug!(chances = ?chances.0, "estimated win chances");
+}
+
+// The number of unowned tiles adjacent to the player's territory
+pub fn frontier_size(tiles: &[Tile], state: &GameState, player: Entity) -> usize {
+    let owned = tiles
+        .iter()
+        .filter(|tile| matches!(tile.state, TileState::Owned(owner) if owner == player))
+        .map(|tile| (tile.row, tile.column))
+        .collect::<HashSet<_>>();
+
+    tiles
+        .iter()
+        .filter(|tile| matches!(tile.state, TileState::Unowned(_)))
+        .filter(|tile| {
+            state
+                .topology
+                .neighbor_offsets(tile.row)
+                .iter()
+                .any(|(row_offset, column_offset)| {
+                    owned.contains(&(tile.row + row_offset, tile.column + column_offset))
+                })
+        })
+        .count()
+}
+
+pub fn evaluate_moves(
+    tiles: &[Tile],
+    state: &GameState,
+    cache: &MoveCache,
+    player: Entity,
+    personality: Personality,
+) -> Vec<(u32, i32)> {
+    Search::new(tiles.to_vec(), state.clone(), cache, player, personality)
+        .step(usize::MAX)
+        .unwrap_or_default()
+}
+
+// A bot's evaluation of its moves, which can be done a few moves at a time.
+// Deeper bots search one more move ahead at a time, so there's always a best
+// move so far to fall back on
+pub struct Search {
+    tiles: Vec<Tile>,
+    state: GameState,
+    player: Entity,
+    personality: Personality,
+    candidates: Vec<u32>,
+    // The depth being searched, and the moves evaluated at it so far
+    depth: u32,
+    evaluations: Vec<(u32, i32)>,
+    // The evaluations of the deepest search finished so far
+    best: Option<Vec<(u32, i32)>>,
+}
+
+impl Search {
+    pub fn new(
+        tiles: Vec<Tile>,
+        state: GameState,
+        cache: &MoveCache,
+        player: Entity,
+        personality: Personality,
+    ) -> Self {
+        let candidates = state
+            .ids
+            .keys()
+            .cloned()
+            // Only consider moves which actually capture something
+            .filter(|id| {
+                state.is_selection_allowed(player, *id) && cache.capture_count(player, *id) > 0
+            })
+            .collect();
+        Self {
+            tiles,
+            state,
+            player,
+            personality,
+            candidates,
+            depth: 1,
+            evaluations: vec![],
+            best: None,
+        }
+    }
+
+    // Evaluates up to the given number of moves, returning the evaluations
+    // once the search has gone as deep as it can
+    pub fn step(&mut self, budget: usize) -> Option<Vec<(u32, i32)>> {
+        let mut budget = budget;
+        while self.depth <= self.personality.depth() {
+            if self.evaluations.len() == self.candidates.len() {
+                self.best = Some(std::mem::take(&mut self.evaluations));
+                self.depth += 1;
+                continue;
+            }
+            if budget == 0 {
+                return None;
+            }
+            budget -= 1;
+
+            let id = self.candidates[self.evaluations.len()];
+            let score = if self.depth == 1 {
+                (self.personality.evaluation())(&self.tiles, &self.state, self.player, id)
+            } else {
+                lookahead(&self.tiles, &self.state, self.player, id, self.depth)
+            };
+            let loss = decay::expected_loss(&self.tiles, &self.state, self.player, id);
+            self.evaluations.push((id, score - loss));
+        }
+        self.best.clone()
+    }
+
+    // Runs the search until it finishes or the time is up
+    fn step_for(&mut self, time: Duration) -> Option<Vec<(u32, i32)>> {
+        let start = Instant::now();
+        loop {
+            if let Some(evaluations) = self.step(1) {
+                return Some(evaluations);
+            }
+            if start.elapsed() >= time {
+                return None;
+            }
+        }
+    }
+
+    // How much of the search is done, from zero to one
+    pub fn progress(&self) -> f32 {
+        let depth = self.personality.depth();
+        let searched = self.depth.min(depth + 1) - 1;
+        let partial = self.evaluations.len() as f32 / self.candidates.len().max(1) as f32;
+        ((searched as f32 + partial) / depth as f32).min(1.0)
+    }
+}
+
+// Where a search thread leaves its evaluations
+#[cfg(not(target_family = "wasm"))]
+type ResultSlot = Arc<Mutex<Option<Vec<(u32, i32)>>>>;
+
+// Quick searches run on their own thread natively. Deep ones, and every
+// search in the browser, are time sliced across the game's steps instead, so
+// they can be cut short and show how far along they are
+enum Job {
+    #[cfg(not(target_family = "wasm"))]
+    Thread(ResultSlot),
+    Sliced(Box<Search>),
+}
+
+impl Job {
+    fn start(search: Search) -> Self {
+        #[cfg(not(target_family = "wasm"))]
+        if search.personality.depth() == 1 {
+            let mut search = search;
+            let result = ResultSlot::default();
+            let slot = result.clone();
+            std::thread::spawn(move || {
+                let evaluations = search.step(usize::MAX);
+                *slot.lock().unwrap() = evaluations;
+            });
+            return Job::Thread(result);
+        }
+        Job::Sliced(Box::new(search))
+    }
+
+    // The evaluations, once the search has finished. When in a hurry, the
+    // best found so far will do
+    fn poll(&mut self, hurry: bool) -> Option<Vec<(u32, i32)>> {
+        match self {
+            #[cfg(not(target_family = "wasm"))]
+            Job::Thread(result) => result.lock().unwrap().take(),
+            Job::Sliced(search) => search
+                .step_for(SLICE_TIME)
+                .or_else(|| search.best.clone().filter(|_| hurry)),
+        }
+    }
+}
+
+struct PendingSearch {
+    // The position and personality the search was started for
+    key: (u64, Personality),
+    player: Entity,
+    job: Job,
+    // How long the bot has been thinking, in seconds
+    elapsed: f32,
+}
+
+// The search the current bot is waiting on, if any
+#[derive(Default)]
+pub struct BotSearch(Option<PendingSearch>);
+
+impl BotSearch {
+    // How far along the player's search is, for searches deep enough to be
+    // worth showing
+    pub fn progress(&self, player: Entity) -> Option<f32> {
+        match &self.0 {
+            Some(PendingSearch {
+                player: searching,
+                job: Job::Sliced(search),
+                ..
+            }) if *searching == player && search.personality.depth() > 1 => Some(search.progress()),
+            _ => None,
+        }
+    }
+}
+
+pub fn choose_move(
+    tiles: &[Tile],
+    state: &GameState,
+    cache: &MoveCache,
+    player: Entity,
+    personality: Personality,
+) -> Option<u32> {
+    best_move(&evaluate_moves(tiles, state, cache, player, personality))
+}
+
+// The highest scoring move, preferring lower ids on ties
+fn best_move(evaluations: &[(u32, i32)]) -> Option<u32> {
+    let mut best: Option<(u32, i32)> = None;
+    for (id, score) in evaluations {
+        match best {
+            Some((_, best_score)) if best_score >= *score => (),
+            _ => best = Some((*id, *score)),
+        }
+    }
+    best.map(|(id, _)| id)
+}
+
+// The move capturing the most tiles, picked for scripted and external bots
+// which fail to pick one of their own
+pub fn biggest_capture(state: &GameState, cache: &MoveCache, player: Entity) -> Option<u32> {
+    state
+        .ids
+        .keys()
+        .filter(|id| state.is_selection_allowed(player, **id))
+        .max_by_key(|id| cache.capture_count(player, **id))
+        .cloned()
+}
+
+// Bots pick their moves on the game's fixed timestep
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Playback>()
+            .init_resource::<BotEvaluations>()
+            .init_resource::<EvaluationMemo>()
+            .init_resource::<BotSearch>()
+            .init_resource::<WinChances>()
+            .insert_resource(WinChanceDisplay::load())
+            .add_asset::<OpeningBook>()
+            .init_asset_loader::<book::OpeningBookLoader>()
+            .add_startup_system(book::load_book)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(GameStep)
+                    .with_system(perform_ai_move.before(crate::core::perform_selection))
+                    .with_system(
+                        crate::script::perform_scripted_move.before(crate::core::perform_selection),
+                    ),
+            )
+            .add_system(update_win_chances.after(crate::core::update_scores))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(crate::game::run_if_swap_offered)
+                    .with_system(decide_swap),
+            );
+        // Bot programs run as child processes, which browsers don't offer
+        #[cfg(not(target_family = "wasm"))]
+        app.add_system_set(SystemSet::new().with_run_criteria(GameStep).with_system(
+            crate::external::perform_external_move.before(crate::core::perform_selection),
+        ));
+    }
+}
+
+// Bots take over the first mover's side whenever its opening came out ahead
+pub fn decide_swap(
+    state: Res<GameState>,
+    players: Query<&Player>,
+    playback: Res<Playback>,
+    mut decisions: EventWriter<SwapEvent>,
+) {
+    let (player, first) = match (state.players.first(), state.history.first()) {
+        (Some(player), Some(first)) => (*player, first.player),
+        _ => return,
+    };
+    let score = |player| players.get(player).map(|player| player.score).unwrap_or(0);
+    match players.get(player) {
+        Ok(bot) if bot.kind.is_bot() && !playback.paused => decisions.send(SwapEvent {
+            player,
+            swap: score(first) > score(player),
+        }),
+        _ => (),
+    }
+}
+
+// Runs on the fixed timestep, so the bot's think time is counted in steps.
+// Moves which haven't been evaluated before are searched for in the
+// background, and played on the first step after the search finishes or the
+// bot runs out of time
+#[allow(clippy::too_many_arguments)]
+pub fn perform_ai_move(
+    state: Res<GameState>,
+    mut players: Query<&mut Player>,
+    cache: Res<MoveCache>,
+    playback: Res<Playback>,
+    mut evaluations: ResMut<BotEvaluations>,
+    mut memo: ResMut<EvaluationMemo>,
+    mut search: ResMut<BotSearch>,
+    book: Res<book::BookHandle>,
+    books: Res<Assets<OpeningBook>>,
+    tiles: Query<&Tile>,
+    mut selections: EventWriter<SelectEvent>,
+) {
+    if playback.paused {
+        return;
+    }
+
+    if let Some(pending) = search.0.as_mut() {
+        pending.elapsed += TIME_STEP * playback.speed;
+        let moves = match pending.job.poll(pending.elapsed >= MAX_THINK_TIME) {
+            Some(moves) => moves,
+            None => return,
+        };
+        let PendingSearch { key, player, .. } = search.0.take().unwrap();
+        // The game may have moved on while the bot was thinking
+        if state.players.first() != Some(&player) || state.position_hash() != key.0 {
+            return;
+        }
+        if memo.0.len() >= MAX_MEMO_ENTRIES {
+            memo.0.clear();
+        }
+        memo.0.insert(key, moves.clone());
+        play_move(
+            &state,
+            player,
+            key.1,
+            moves,
+            &mut evaluations,
+            &mut selections,
+        );
+        return;
+    }
+
+    let (player, personality) = match players.get_mut(state.players[0]) {
+        Ok(mut player) => match player.kind {
+            PlayerKind::Bot(ref mut timer, personality) => {
+                let step = Duration::from_secs_f32(TIME_STEP * playback.speed);
+                if !timer.tick(step).finished() {
+                    return;
+                }
+                timer.reset();
+                (state.players[0], personality)
+            }
+            _ => return,
+        },
+        Err(_) => return,
+    };
+
+    // The deep bot knows the best openings on the stock boards by heart
+    let book_move = books
+        .get(&book.0)
+        .filter(|_| personality == Personality::Deep)
+        .and_then(|book| book.lookup(&state, &cache, player));
+    if let Some(id) = book_move {
+        debug!(id, "bot played a book move");
+        selections.send(SelectEvent { player, id });
+        return;
+    }
+
+    let key = (state.position_hash(), personality);
+    match memo.0.get(&key) {
+        Some(moves) => play_move(
+            &state,
+            player,
+            personality,
+            moves.clone(),
+            &mut evaluations,
+            &mut selections,
+        ),
+        None => {
+            let tiles = tiles.iter().cloned().collect::<Vec<_>>();
+            let job = Job::start(Search::new(
+                tiles,
+                state.clone(),
+                &cache,
+                player,
+                personality,
+            ));
+            search.0 = Some(PendingSearch {
+                key,
+                player,
+                job,
+                elapsed: 0.0,
+            });
+        }
+    }
+}
+
+fn play_move(
+    state: &GameState,
+    player: Entity,
+    personality: Personality,
+    moves: Vec<(u32, i32)>,
+    evaluations: &mut BotEvaluations,
+    selections: &mut EventWriter<SelectEvent>,
+) {
+    let _turn = info_span!("turn", number = state.moves, player = ?player).entered();
+    debug!(personality = personality.name(), evaluations = ?moves, "bot evaluated moves");
+    if let Some(id) = best_move(&moves) {
+        selections.send(SelectEvent { player, id });
+    }
+    evaluations.0.insert(player, moves);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::{load_level, BoardTopology, GamePhase, RulesConfig};
+    use std::collections::{BTreeMap, HashMap};
+
+    fn test_state() -> GameState {
+        GameState {
+            players: vec![Entity::from_raw(0), Entity::from_raw(1)],
+            phase: GamePhase::Running,
+            ids: BTreeMap::from([(0, Color::GREEN), (1, Color::YELLOW)]),
+            topology: BoardTopology::Square4,
+            last_selections: HashMap::new(),
+            rules: RulesConfig::default(),
+            moves: 0,
+            double_mover: None,
+            moved_this_turn: false,
+            pending_selections: HashMap::new(),
+            stalemate: false,
+            seed: None,
+            board_hash: Default::default(),
+            positions: HashMap::new(),
+            unowned: BTreeMap::new(),
+            history: vec![],
+            blind: false,
+        }
+    }
+
+    fn test_board() -> (GameState, Vec<Tile>, MoveCache) {
+        let state = test_state();
+        let mut tiles = load_level("1 | | | 2", &state.players, vec![0], None);
+        tiles[1].state = TileState::Unowned(1);
+        tiles[2].state = TileState::Unowned(0);
+        tiles[3].state = TileState::Unowned(0);
+
+        let mut cache = MoveCache::default();
+        for id in state.ids.keys() {
+            let mut board = tiles.clone();
+            let count = simulate_selection(&mut board, state.topology, *id, state.players[0]);
+            cache
+                .captures
+                .entry(state.players[0])
+                .or_default()
+                .insert(*id, count);
+        }
+        (state, tiles, cache)
+    }
+
+    #[test]
+    fn leaders_are_more_likely_to_win() {
+        let (mut state, tiles, _) = test_board();
+        state.track_board(&tiles);
+        let (first, second) = (state.players[0], state.players[1]);
+
+        let even = win_chances(&tiles, &state, &HashMap::from([(first, 1), (second, 1)]));
+        assert!((even[&first] - 0.5).abs() < 0.01);
+
+        let ahead = win_chances(&tiles, &state, &HashMap::from([(first, 5), (second, 1)]));
+        assert!(ahead[&first] > 0.5);
+        assert!((ahead[&first] + ahead[&second] - 1.0).abs() < 0.01);
+
+        state.phase = GamePhase::Over(second);
+        assert_eq!(win_chances(&tiles, &state, &HashMap::new())[&second], 1.0);
+    }
+
+    #[test]
+    fn aggressive_takes_largest_capture() {
+        let (state, tiles, cache) = test_board();
+        assert_eq!(
+            choose_move(
+                &tiles,
+                &state,
+                &cache,
+                state.players[0],
+                Personality::Aggressive
+            ),
+            Some(1)
+        );
+        assert_eq!(frontier_size(&tiles, &state, state.players[0]), 1);
+    }
+
+    #[test]
+    fn sliced_searches_match_full_ones() {
+        let (state, tiles, cache) = test_board();
+        let player = state.players[0];
+        let mut search = Search::new(
+            tiles.clone(),
+            state.clone(),
+            &cache,
+            player,
+            Personality::Aggressive,
+        );
+        assert_eq!(search.step(0), None);
+        assert_eq!(
+            search.step(1),
+            Some(evaluate_moves(
+                &tiles,
+                &state,
+                &cache,
+                player,
+                Personality::Aggressive
+            ))
+        );
+    }
+
+    #[test]
+    fn deep_searches_report_progress() {
+        let (state, tiles, cache) = test_board();
+        let mut search = Search::new(
+            tiles,
+            state.clone(),
+            &cache,
+            state.players[0],
+            Personality::Deep,
+        );
+        assert_eq!(search.step(0), None);
+        assert_eq!(search.progress(), 0.0);
+
+        // There's a best move to fall back on once the first move ahead is done
+        assert_eq!(search.step(1), None);
+        assert_eq!(search.progress(), 1.0 / DEEP_SEARCH_DEPTH as f32);
+        assert_eq!(best_move(search.best.as_ref().unwrap()), Some(1));
+
+        let evaluations = search.step(usize::MAX).unwrap();
+        assert_eq!(best_move(&evaluations), Some(1));
+        assert_eq!(search.progress(), 1.0);
+    }
+}
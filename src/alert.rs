@@ -0,0 +1,223 @@
+use crate::core::{GamePhase, GameState, Player, PlayerKind};
+use crate::{attract, storage};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const TURN_ALERTS_KEY: &str = "turn_alerts";
+// How long the other side has to take before the turn coming back is worth
+// pointing out
+const SLOW_TURN_SECONDS: f64 = 3.0;
+#[cfg(target_family = "wasm")]
+const FLASH_TITLE: &str = "Your turn!";
+// How often the tab's title switches while it's flashing
+#[cfg(target_family = "wasm")]
+const FLASH_SECONDS: f64 = 1.0;
+// The chime's notes, in Hz, each this long
+#[cfg(target_family = "wasm")]
+const CHIME_NOTES: [f32; 2] = [659.3, 987.8];
+#[cfg(target_family = "wasm")]
+const NOTE_SECONDS: f64 = 0.18;
+
+// Lets a player who looked away know it's their move again, after waiting on
+// someone online or a slow bot. In the browser a chime plays and the tab's
+// title flashes until the page has focus, natively the window asks for
+// attention
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TurnAlerts {
+    pub chime: bool,
+    pub flash: bool,
+}
+
+impl Default for TurnAlerts {
+    fn default() -> Self {
+        Self {
+            chime: true,
+            flash: true,
+        }
+    }
+}
+
+impl TurnAlerts {
+    pub fn load() -> Self {
+        storage::load(TURN_ALERTS_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(TURN_ALERTS_KEY, self)
+    }
+}
+
+// The tab's own title while it's flashing, and when it last switched
+#[cfg(target_family = "wasm")]
+#[derive(Default)]
+pub struct TitleFlash {
+    original: Option<String>,
+    switched: f64,
+}
+
+pub struct AlertPlugin;
+
+impl Plugin for AlertPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TurnAlerts::load())
+            .add_system(alert_turns);
+        #[cfg(target_family = "wasm")]
+        app.init_resource::<TitleFlash>()
+            .add_system(flash_title.after(alert_turns));
+    }
+}
+
+// Whether the player has been kept waiting long enough to be told their turn
+// has come around
+fn is_slow(waiting_since: Option<f64>, now: f64) -> bool {
+    waiting_since.map_or(false, |since| now - since >= SLOW_TURN_SECONDS)
+}
+
+fn alert_turns(
+    time: Res<Time>,
+    alerts: Res<TurnAlerts>,
+    attract: Res<attract::Attract>,
+    state: Res<GameState>,
+    players: Query<&Player>,
+    #[cfg(target_family = "wasm")] mut flash: ResMut<TitleFlash>,
+    #[cfg(not(target_family = "wasm"))] windows: NonSend<bevy::winit::WinitWindows>,
+    mut waiting_since: Local<Option<f64>>,
+) {
+    let now = time.seconds_since_startup();
+    let running = state.phase == GamePhase::Running && !attract.demo;
+    let to_move = state
+        .players
+        .first()
+        .and_then(|player| players.get(*player).ok())
+        .map(|player| &player.kind);
+    let has_human = players
+        .iter()
+        .any(|player| matches!(player.kind, PlayerKind::Human));
+
+    match to_move {
+        Some(PlayerKind::Human) if running => {
+            if !is_slow(waiting_since.take(), now) {
+                return;
+            }
+            if alerts.chime {
+                chime();
+            }
+            if alerts.flash {
+                #[cfg(not(target_family = "wasm"))]
+                if let Some(window) = windows.get_window(bevy::window::WindowId::primary()) {
+                    window.request_user_attention(Some(
+                        winit::window::UserAttentionType::Informational,
+                    ));
+                }
+                #[cfg(target_family = "wasm")]
+                start_flashing(&mut flash, now);
+            }
+        }
+        Some(PlayerKind::Neutral) => (),
+        Some(_) if running && has_human => {
+            waiting_since.get_or_insert(now);
+        }
+        _ => *waiting_since = None,
+    }
+}
+
+// Natively there's nothing to play the chime with, the window asking for
+// attention has to do
+#[cfg(not(target_family = "wasm"))]
+fn chime() {}
+
+#[cfg(target_family = "wasm")]
+fn chime() {
+    if let Err(e) = play_chime() {
+        warn!("Unable to play the turn chime: {:?}", e);
+    }
+}
+
+// A couple of rising notes, made up on the spot so no sound file is needed
+#[cfg(target_family = "wasm")]
+fn play_chime() -> Result<(), wasm_bindgen::JsValue> {
+    thread_local! {
+        // Browsers only allow a few audio contexts, so the one is kept around
+        static CONTEXT: std::cell::RefCell<Option<web_sys::AudioContext>> =
+            std::cell::RefCell::new(None);
+    }
+    CONTEXT.with(|context| {
+        let mut context = context.borrow_mut();
+        if context.is_none() {
+            *context = Some(web_sys::AudioContext::new()?);
+        }
+        let context = match &*context {
+            Some(context) => context,
+            None => return Ok(()),
+        };
+        for (idx, frequency) in CHIME_NOTES.iter().enumerate() {
+            let start = context.current_time() + idx as f64 * NOTE_SECONDS;
+            let oscillator = context.create_oscillator()?;
+            oscillator.set_type(web_sys::OscillatorType::Sine);
+            oscillator.frequency().set_value(*frequency);
+            let gain = context.create_gain()?;
+            gain.gain().set_value_at_time(0.2, start)?;
+            gain.gain()
+                .exponential_ramp_to_value_at_time(0.001, start + NOTE_SECONDS)?;
+            oscillator.connect_with_audio_node(&gain)?;
+            gain.connect_with_audio_node(&context.destination())?;
+            oscillator.start_with_when(start)?;
+            oscillator.stop_with_when(start + NOTE_SECONDS)?;
+        }
+        Ok(())
+    })
+}
+
+// Only worth doing while the player is looking at something else
+#[cfg(target_family = "wasm")]
+fn start_flashing(flash: &mut TitleFlash, now: f64) {
+    let document = match web_sys::window().and_then(|window| window.document()) {
+        Some(document) => document,
+        None => return,
+    };
+    if flash.original.is_none() && !document.has_focus().unwrap_or(true) {
+        flash.original = Some(document.title());
+        flash.switched = now - FLASH_SECONDS;
+    }
+}
+
+// Switches between the tab's title and a reminder, until the page has focus
+#[cfg(target_family = "wasm")]
+fn flash_title(time: Res<Time>, mut flash: ResMut<TitleFlash>) {
+    let original = match &flash.original {
+        Some(original) => original.clone(),
+        None => return,
+    };
+    let document = match web_sys::window().and_then(|window| window.document()) {
+        Some(document) => document,
+        None => return,
+    };
+    if document.has_focus().unwrap_or(true) {
+        document.set_title(&original);
+        flash.original = None;
+        return;
+    }
+
+    let now = time.seconds_since_startup();
+    if now - flash.switched >= FLASH_SECONDS {
+        flash.switched = now;
+        let title = match document.title() == FLASH_TITLE {
+            true => original,
+            false => FLASH_TITLE.to_string(),
+        };
+        document.set_title(&title);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_slow_turns_are_alerted() {
+        assert!(!is_slow(None, 10.0));
+        assert!(!is_slow(Some(9.0), 10.0));
+        assert!(is_slow(Some(10.0 - SLOW_TURN_SECONDS), 10.0));
+    }
+}
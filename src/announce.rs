@@ -0,0 +1,147 @@
+use crate::core::{CaptureEvent, GameState, Player};
+use crate::{attract, palette, storage};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const ANNOUNCER_KEY: &str = "announcer";
+// The element screen readers watch for announcements in the browser
+#[cfg(target_family = "wasm")]
+const LIVE_REGION_ID: &str = "announcements";
+
+// Describes every move in words for players following the game with a screen
+// reader. Written to an ARIA live region in the browser, and to stdout natively
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Announcer {
+    pub enabled: bool,
+}
+
+impl Announcer {
+    pub fn load() -> Self {
+        storage::load(ANNOUNCER_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(ANNOUNCER_KEY, self)
+    }
+}
+
+pub fn describe_move(name: &str, captured: u32, color: &str, scores: &[u32]) -> String {
+    let scores = scores
+        .iter()
+        .map(|score| score.to_string())
+        .collect::<Vec<_>>()
+        .join("–");
+    let tiles = if captured == 1 { "tile" } else { "tiles" };
+    format!(
+        "{} captured {} {} {}; score {}",
+        name, captured, color, tiles, scores
+    )
+}
+
+pub fn announce_moves(
+    announcer: Res<Announcer>,
+    attract: Res<attract::Attract>,
+    state: Res<GameState>,
+    players: Query<&Player>,
+    mut captures: EventReader<CaptureEvent>,
+    mut announced: Local<usize>,
+) {
+    let mut captured: HashMap<Entity, u32> = HashMap::new();
+    for capture in captures.iter() {
+        *captured.entry(capture.player).or_insert(0) += 1;
+    }
+
+    // A new game has started
+    if state.history.len() < *announced {
+        *announced = 0;
+    }
+    // Demos behind the title screen play out silently
+    if !announcer.enabled || attract.demo {
+        *announced = state.history.len();
+        return;
+    }
+
+    let scores: Vec<u32> = state
+        .board_hash
+        .players()
+        .iter()
+        .map(|player| players.get(*player).map(|player| player.score).unwrap_or(0))
+        .collect();
+    for made in state.history.iter().skip(*announced) {
+        let name = match players.get(made.player) {
+            Ok(player) => &player.name,
+            Err(_) => continue,
+        };
+        let color = state
+            .ids
+            .get(&made.id)
+            .map(|color| palette::color_name(*color))
+            .unwrap_or_default();
+        announce(&describe_move(
+            name,
+            captured.get(&made.player).cloned().unwrap_or(0),
+            &color,
+            &scores,
+        ));
+    }
+    *announced = state.history.len();
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn announce(text: &str) {
+    println!("{}", text);
+}
+
+#[cfg(target_family = "wasm")]
+fn announce(text: &str) {
+    if let Err(e) = write_live_region(text) {
+        error!("Unable to announce move: {:?}", e);
+    }
+}
+
+// The live region is made on first use, and kept out of sight
+#[cfg(target_family = "wasm")]
+fn write_live_region(text: &str) -> Result<(), wasm_bindgen::JsValue> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("Missing document")?;
+    let region = match document.get_element_by_id(LIVE_REGION_ID) {
+        Some(region) => region,
+        None => {
+            let region = document.create_element("div")?;
+            region.set_id(LIVE_REGION_ID);
+            region.set_attribute("role", "status")?;
+            region.set_attribute("aria-live", "polite")?;
+            region.set_attribute(
+                "style",
+                "position: absolute; width: 1px; height: 1px; overflow: hidden; \
+                 clip: rect(0 0 0 0);",
+            )?;
+            document
+                .body()
+                .ok_or("Missing body")?
+                .append_child(&region)?;
+            region
+        }
+    };
+    region.set_text_content(Some(text));
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn moves_are_described() {
+        assert_eq!(
+            describe_move("Bot", 7, "blue", &[23, 19]),
+            "Bot captured 7 blue tiles; score 23–19"
+        );
+        assert_eq!(
+            describe_move("Player", 1, "dark red", &[5, 4]),
+            "Player captured 1 dark red tile; score 5–4"
+        );
+    }
+}
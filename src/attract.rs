@@ -0,0 +1,169 @@
+use crate::menu::{self, AppState};
+use crate::{board, core, game, GameConfigState, GameStartEvent};
+use bevy::ecs::schedule::ShouldRun;
+use bevy::ecs::system::SystemParam;
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+// How long the title screen sits untouched before a demo game starts, in
+// seconds
+const IDLE_TIME: f32 = 30.0;
+// How long a finished demo stays up before the next one starts
+const RESTART_TIME: f32 = 5.0;
+// The demo is played slowly, so it can be followed from behind the menu
+const DEMO_THINK_TIME: f32 = 1.5;
+// How dark the demo is under the menu, out of 255
+const DIM_ALPHA: u8 = 170;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Transition {
+    StartDemo,
+    StopDemo,
+}
+
+// Plays bot games behind the title screen while nobody is using it
+#[derive(Default)]
+pub struct Attract {
+    // Seconds since the last input on the title screen
+    idle: f32,
+    // Seconds since the demo game finished
+    finished: f32,
+    pub demo: bool,
+}
+
+impl Attract {
+    fn update(&mut self, delta: f32, active: bool, demo_finished: bool) -> Option<Transition> {
+        if active {
+            self.idle = 0.0;
+            if self.demo {
+                self.demo = false;
+                return Some(Transition::StopDemo);
+            }
+            return None;
+        }
+
+        self.idle += delta;
+        if self.demo {
+            if !demo_finished {
+                return None;
+            }
+            self.finished += delta;
+            if self.finished < RESTART_TIME {
+                return None;
+            }
+        } else if self.idle < IDLE_TIME {
+            return None;
+        }
+        self.demo = true;
+        self.finished = 0.0;
+        Some(Transition::StartDemo)
+    }
+}
+
+pub struct AttractPlugin;
+
+impl Plugin for AttractPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Attract>().add_system_set(
+            SystemSet::on_update(AppState::MainMenu)
+                .with_system(run_attract_mode.before(menu::show_main_menu)),
+        );
+    }
+}
+
+// Anything the player does on the title screen
+#[derive(SystemParam)]
+pub struct Activity<'w, 's> {
+    keys: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    touches: Res<'w, Touches>,
+    cursor_moves: EventReader<'w, 's, CursorMoved>,
+    scrolls: EventReader<'w, 's, MouseWheel>,
+}
+
+impl<'w, 's> Activity<'w, 's> {
+    fn any(&mut self) -> bool {
+        // Every reader is drained, so old events don't count next frame
+        let moved = self.cursor_moves.iter().count() > 0;
+        let scrolled = self.scrolls.iter().count() > 0;
+        moved
+            || scrolled
+            || self.keys.get_just_pressed().next().is_some()
+            || self.mouse.get_just_pressed().next().is_some()
+            || self.buttons.get_just_pressed().next().is_some()
+            || self.touches.iter_just_pressed().next().is_some()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_attract_mode(
+    mut attract: ResMut<Attract>,
+    mut activity: Activity,
+    config: Res<GameConfigState>,
+    time: Res<Time>,
+    state: ResMut<core::GameState>,
+    board: ResMut<board::Board>,
+    entities: game::GameEntities,
+    commands: Commands,
+    mut game_start: EventWriter<GameStartEvent>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    let active = activity.any();
+    match attract.update(time.delta_seconds(), active, state.phase.is_finished()) {
+        Some(Transition::StartDemo) => {
+            game_start.send(menu::demo_event(&config, DEMO_THINK_TIME));
+        }
+        Some(Transition::StopDemo) => {
+            game::clear_game(state, board, entities, commands);
+            return;
+        }
+        None => (),
+    }
+
+    if attract.demo {
+        let ctx = egui_ctx.ctx_mut();
+        ctx.layer_painter(egui::LayerId::background()).rect_filled(
+            ctx.input().screen_rect(),
+            0.0,
+            egui::Color32::from_black_alpha(DIM_ALPHA),
+        );
+    }
+}
+
+// Keeps the game's own windows from showing over the title screen during a
+// demo, chained after their usual run criteria
+pub fn unless_demo(In(should_run): In<ShouldRun>, attract: Res<Attract>) -> ShouldRun {
+    if attract.demo {
+        ShouldRun::No
+    } else {
+        should_run
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn demos_start_when_idle_and_stop_on_input() {
+        let mut attract = Attract::default();
+        assert_eq!(attract.update(IDLE_TIME - 1.0, false, false), None);
+        assert_eq!(
+            attract.update(1.0, false, false),
+            Some(Transition::StartDemo)
+        );
+
+        // A finished demo is followed by another, after a moment
+        assert_eq!(attract.update(1.0, false, true), None);
+        assert_eq!(
+            attract.update(RESTART_TIME, false, true),
+            Some(Transition::StartDemo)
+        );
+
+        assert_eq!(attract.update(1.0, true, false), Some(Transition::StopDemo));
+        assert!(!attract.demo);
+        assert_eq!(attract.update(IDLE_TIME / 2.0, false, false), None);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::storage;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const AVATARS_KEY: &str = "avatars";
+
+// The icon shown next to a player's name. The images are white, and tinted
+// wherever they're drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Avatar {
+    Person,
+    Robot,
+    Cat,
+    Star,
+    Heart,
+    Ghost,
+}
+
+impl Default for Avatar {
+    fn default() -> Self {
+        Avatar::Person
+    }
+}
+
+impl Avatar {
+    pub const ALL: [Avatar; 6] = [
+        Avatar::Person,
+        Avatar::Robot,
+        Avatar::Cat,
+        Avatar::Star,
+        Avatar::Heart,
+        Avatar::Ghost,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Avatar::Person => "Person",
+            Avatar::Robot => "Robot",
+            Avatar::Cat => "Cat",
+            Avatar::Star => "Star",
+            Avatar::Heart => "Heart",
+            Avatar::Ghost => "Ghost",
+        }
+    }
+
+    fn path(&self) -> String {
+        format!("avatars/{}.png", self.name().to_lowercase())
+    }
+}
+
+// The avatars last picked on the setup screen, remembered between sessions
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Avatars {
+    pub player: Avatar,
+    pub bot: Avatar,
+}
+
+impl Default for Avatars {
+    fn default() -> Self {
+        Self {
+            player: Avatar::Person,
+            bot: Avatar::Robot,
+        }
+    }
+}
+
+impl Avatars {
+    pub fn load() -> Self {
+        storage::load(AVATARS_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(AVATARS_KEY, self)
+    }
+}
+
+// Every avatar's image, loaded once at startup
+#[derive(Default)]
+pub struct AvatarImages(HashMap<Avatar, Handle<Image>>);
+
+impl AvatarImages {
+    pub fn get(&self, avatar: Avatar) -> Handle<Image> {
+        self.0.get(&avatar).cloned().unwrap_or_default()
+    }
+}
+
+pub fn load_avatars(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let images = Avatar::ALL
+        .into_iter()
+        .map(|avatar| (avatar, asset_server.load(&avatar.path())))
+        .collect();
+    commands.insert_resource(AvatarImages(images));
+}
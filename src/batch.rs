@@ -0,0 +1,163 @@
+use crate::board::Board;
+use crate::core::BoardTopology;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy_prototype_lyon::prelude::*;
+
+// Boards with more tiles than this draw them as sprites sharing one texture,
+// which are batched into a handful of draw calls, rather than tessellating a
+// shape for every tile
+pub const BATCH_THRESHOLD: usize = 2500;
+// The height of the hexagon texture, in pixels
+const TEXTURE_SIZE: u32 = 64;
+// Edge pixels are sampled this many times in each direction
+const SUBSAMPLES: u32 = 4;
+// How much of a tile's outline color shows in its sprite, as sprites have no
+// outline of their own. Keeps highlighted tiles standing out
+const OUTLINE_TINT: f32 = 0.25;
+
+// Marks tiles drawn as sprites. They still carry a DrawMode, so everything
+// recoloring tiles works the same either way, and their sprite is tinted to
+// match whenever it changes
+#[derive(Component)]
+pub struct BatchedTile;
+
+pub struct TileTextures {
+    pub hexagon: Handle<Image>,
+}
+
+pub fn is_batched(num_tiles: usize) -> bool {
+    num_tiles > BATCH_THRESHOLD
+}
+
+pub fn create_tile_textures(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    commands.insert_resource(TileTextures {
+        hexagon: images.add(hexagon_image(TEXTURE_SIZE)),
+    });
+}
+
+// How much of the pixel at the given position is covered by a pointy-topped
+// hexagon filling an image of the given size
+fn hexagon_coverage(x: u32, y: u32, width: u32, height: u32) -> f32 {
+    let radius = height as f32 / 2.0;
+    let mut inside = 0;
+    for sample in 0..SUBSAMPLES * SUBSAMPLES {
+        let offset = |index: u32| (index as f32 + 0.5) / SUBSAMPLES as f32;
+        let sample_x = (x as f32 + offset(sample % SUBSAMPLES) - width as f32 / 2.0).abs();
+        let sample_y = (y as f32 + offset(sample / SUBSAMPLES) - radius).abs();
+        if sample_x <= radius * 3.0_f32.sqrt() / 2.0
+            && sample_y <= radius - sample_x / 3.0_f32.sqrt()
+        {
+            inside += 1;
+        }
+    }
+    inside as f32 / (SUBSAMPLES * SUBSAMPLES) as f32
+}
+
+// A white hexagon, to be tinted by each sprite
+fn hexagon_image(height: u32) -> Image {
+    let width = (height as f32 * 3.0_f32.sqrt() / 2.0).round() as u32;
+    let data = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .flat_map(|(x, y)| {
+            let alpha = (hexagon_coverage(x, y, width, height) * 255.0).round() as u8;
+            [255, 255, 255, alpha]
+        })
+        .collect();
+    Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+// The single color a sprite can show for the draw mode
+pub fn sprite_color(mode: &DrawMode) -> Color {
+    match mode {
+        DrawMode::Fill(fill_mode) => fill_mode.color,
+        DrawMode::Stroke(outline_mode) => outline_mode.color,
+        DrawMode::Outlined {
+            fill_mode,
+            outline_mode,
+        } => {
+            let [r, g, b, a] = fill_mode.color.as_rgba_f32();
+            let [outline_r, outline_g, outline_b, _] = outline_mode.color.as_rgba_f32();
+            let mix = |fill: f32, outline: f32| fill + (outline - fill) * OUTLINE_TINT;
+            Color::rgba(mix(r, outline_r), mix(g, outline_g), mix(b, outline_b), a)
+        }
+    }
+}
+
+pub fn tile_sprite_bundle(
+    board: &Board,
+    textures: &TileTextures,
+    mode: DrawMode,
+    transform: Transform,
+) -> SpriteBundle {
+    let (width, height) = board.spacing();
+    let (texture, size) = match board.topology {
+        BoardTopology::Hex => (
+            textures.hexagon.clone(),
+            Vec2::new(width, board.tile_radius * 2.0),
+        ),
+        // The default texture is plain white
+        BoardTopology::Square4 | BoardTopology::Square8 => (default(), Vec2::new(width, height)),
+    };
+    SpriteBundle {
+        sprite: Sprite {
+            color: sprite_color(&mode),
+            custom_size: Some(size),
+            ..default()
+        },
+        texture,
+        transform,
+        ..default()
+    }
+}
+
+// Batched tiles whose draw mode has changed since they were last tinted
+type RecoloredTiles<'w, 's> =
+    Query<'w, 's, (&'static DrawMode, &'static mut Sprite), (With<BatchedTile>, Changed<DrawMode>)>;
+
+pub fn tint_batched_tiles(mut tiles: RecoloredTiles) {
+    for (mode, mut sprite) in tiles.iter_mut() {
+        sprite.color = sprite_color(mode);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hexagon_texture_has_clear_corners() {
+        let image = hexagon_image(TEXTURE_SIZE);
+        let width = image.texture_descriptor.size.width;
+        let alpha = |x: u32, y: u32| image.data[((y * width + x) * 4 + 3) as usize];
+
+        assert_eq!(alpha(width / 2, TEXTURE_SIZE / 2), 255);
+        assert_eq!(alpha(0, 0), 0);
+        assert_eq!(alpha(width - 1, TEXTURE_SIZE - 1), 0);
+        // The points of the hexagon reach the top and bottom edges
+        assert!(alpha(width / 2, 0) > 0);
+    }
+
+    #[test]
+    fn highlights_tint_sprites() {
+        let mode = |outline| DrawMode::Outlined {
+            fill_mode: FillMode::color(Color::rgb(0.5, 0.5, 0.5)),
+            outline_mode: StrokeMode::new(outline, 1.0),
+        };
+        let brightness = |mode: &DrawMode| sprite_color(mode).as_rgba_f32()[0];
+        assert!(brightness(&mode(Color::WHITE)) > brightness(&mode(Color::BLACK)));
+        assert_eq!(
+            sprite_color(&DrawMode::Fill(FillMode::color(Color::RED))),
+            Color::RED
+        );
+    }
+}
@@ -0,0 +1,223 @@
+// A small server for correspondence games, keeping a room for each game so
+// two people can play it over hours or days. Rooms are written out to a
+// directory as they change, so they outlive restarts
+//
+//     assimilation-server [address] [directory]
+//
+// GET /rooms/<id>?since=<moves>   the room, without the first moves
+// PUT /rooms/<id>                 creates the room, with the setup as the body
+// POST /rooms/<id>/join           takes the second seat, with the guest as the body
+// POST /rooms/<id>/moves          makes the next move, a RON MoveSubmission
+//
+// Creating and joining answer with the seat's secret, which its moves are
+// sent with
+use assimilation::rooms::{self, MoveSubmission, Room, RoomError};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use warp::http::StatusCode;
+use warp::hyper::body::Bytes;
+use warp::Filter;
+
+const DEFAULT_ADDRESS: &str = "0.0.0.0:3030";
+const DEFAULT_DIRECTORY: &str = "rooms";
+// Room for the longest setup, and then some
+const MAX_BODY_LENGTH: u64 = 2 * rooms::MAX_TEXT_LENGTH as u64;
+const SECRET_LENGTH: usize = 24;
+
+type Reply = warp::reply::WithStatus<String>;
+
+struct Rooms {
+    directory: PathBuf,
+    rooms: HashMap<String, Room>,
+}
+
+impl Rooms {
+    // Rooms are read from the directory the first time they're asked for
+    fn get(&mut self, id: &str) -> Option<&mut Room> {
+        if !self.rooms.contains_key(id) {
+            let room = std::fs::read_to_string(self.path(id))
+                .ok()
+                .and_then(|contents| ron::from_str(&contents).ok())?;
+            self.rooms.insert(id.to_string(), room);
+        }
+        self.rooms.get_mut(id)
+    }
+
+    fn save(&self, id: &str) -> Result<(), String> {
+        let room = self.rooms.get(id).ok_or("No such room")?;
+        let contents = ron::to_string(room).map_err(|e| e.to_string())?;
+        std::fs::write(self.path(id), contents).map_err(|e| e.to_string())
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.directory.join(format!("{}.ron", id))
+    }
+}
+
+type Shared = Arc<Mutex<Rooms>>;
+
+#[derive(Deserialize)]
+struct Since {
+    #[serde(default)]
+    since: usize,
+}
+
+fn reply(status: StatusCode, body: impl Into<String>) -> Reply {
+    warp::reply::with_status(body.into(), status)
+}
+
+fn rejected(e: RoomError) -> Reply {
+    let status = match e {
+        RoomError::TooLong => StatusCode::PAYLOAD_TOO_LARGE,
+        RoomError::NotSeated => StatusCode::FORBIDDEN,
+        _ => StatusCode::CONFLICT,
+    };
+    reply(status, e.to_string())
+}
+
+fn secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+// Saves the changed room, answering with the body or whatever went wrong
+fn saved(rooms: &Rooms, id: &str, status: StatusCode, body: impl Into<String>) -> Reply {
+    match rooms.save(id) {
+        Ok(()) => reply(status, body),
+        Err(e) => {
+            eprintln!("Unable to save room {}: {}", id, e);
+            reply(StatusCode::INTERNAL_SERVER_ERROR, "Unable to save the room")
+        }
+    }
+}
+
+async fn fetch(id: String, since: Since, rooms: Shared) -> Result<Reply, Infallible> {
+    let mut rooms = rooms.lock().await;
+    Ok(match rooms.get(&id) {
+        Some(room) => match ron::to_string(&room.since(since.since)) {
+            Ok(update) => reply(StatusCode::OK, update),
+            Err(e) => reply(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        },
+        None => reply(StatusCode::NOT_FOUND, "No such room"),
+    })
+}
+
+async fn create(id: String, setup: String, rooms: Shared) -> Result<Reply, Infallible> {
+    let mut rooms = rooms.lock().await;
+    if rooms.get(&id).is_some() {
+        return Ok(reply(StatusCode::CONFLICT, "The room already exists"));
+    }
+    let secret = secret();
+    Ok(match Room::new(setup, secret.clone()) {
+        Ok(room) => {
+            rooms.rooms.insert(id.clone(), room);
+            saved(&rooms, &id, StatusCode::CREATED, secret)
+        }
+        Err(e) => rejected(e),
+    })
+}
+
+async fn join(id: String, guest: String, rooms: Shared) -> Result<Reply, Infallible> {
+    let secret = secret();
+    let mut rooms = rooms.lock().await;
+    Ok(
+        match rooms.get(&id).map(|room| room.join(guest, secret.clone())) {
+            Some(Ok(())) => saved(&rooms, &id, StatusCode::OK, secret),
+            Some(Err(e)) => rejected(e),
+            None => reply(StatusCode::NOT_FOUND, "No such room"),
+        },
+    )
+}
+
+async fn submit(id: String, body: String, rooms: Shared) -> Result<Reply, Infallible> {
+    let submission = match ron::from_str::<MoveSubmission>(&body) {
+        Ok(submission) => submission,
+        Err(e) => return Ok(reply(StatusCode::BAD_REQUEST, e.to_string())),
+    };
+    let mut rooms = rooms.lock().await;
+    Ok(match rooms.get(&id).map(|room| room.submit(submission)) {
+        Some(Ok(())) => saved(&rooms, &id, StatusCode::OK, ""),
+        Some(Err(e)) => rejected(e),
+        None => reply(StatusCode::NOT_FOUND, "No such room"),
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let address: SocketAddr = match args
+        .next()
+        .unwrap_or_else(|| DEFAULT_ADDRESS.into())
+        .parse()
+    {
+        Ok(address) => address,
+        Err(e) => return eprintln!("Invalid address: {}", e),
+    };
+    let directory = PathBuf::from(args.next().unwrap_or_else(|| DEFAULT_DIRECTORY.into()));
+    if let Err(e) = std::fs::create_dir_all(&directory) {
+        return eprintln!("Unable to create {}: {}", directory.display(), e);
+    }
+
+    let shared = Arc::new(Mutex::new(Rooms {
+        directory,
+        rooms: HashMap::new(),
+    }));
+    let rooms = warp::any().map(move || shared.clone());
+    let room = warp::path("rooms")
+        .and(warp::path::param::<String>())
+        .and_then(|id: String| async move {
+            match rooms::is_valid_room_id(&id) {
+                true => Ok(id),
+                false => Err(warp::reject::not_found()),
+            }
+        });
+    let body = warp::body::content_length_limit(MAX_BODY_LENGTH)
+        .and(warp::body::bytes())
+        .map(|body: Bytes| String::from_utf8_lossy(&body).into_owned());
+
+    let fetch = warp::get()
+        .and(room)
+        .and(warp::path::end())
+        .and(warp::query::<Since>())
+        .and(rooms.clone())
+        .and_then(fetch);
+    let create = warp::put()
+        .and(room)
+        .and(warp::path::end())
+        .and(body)
+        .and(rooms.clone())
+        .and_then(create);
+    let join = warp::post()
+        .and(room)
+        .and(warp::path!("join"))
+        .and(body)
+        .and(rooms.clone())
+        .and_then(join);
+    let submit = warp::post()
+        .and(room)
+        .and(warp::path!("moves"))
+        .and(body)
+        .and(rooms)
+        .and_then(submit);
+
+    // The game in the browser is usually served from somewhere else
+    let cors = warp::cors()
+        .allow_any_origin()
+        .allow_methods(["GET", "PUT", "POST"])
+        .allow_header("content-type");
+
+    println!("Serving correspondence games on {}", address);
+    warp::serve(fetch.or(create).or(join).or(submit).with(cors))
+        .run(address)
+        .await;
+}
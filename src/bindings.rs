@@ -0,0 +1,204 @@
+use crate::storage;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const BINDINGS_KEY: &str = "bindings";
+
+// Everything the player can do with a key or button. Systems ask whether an
+// action was triggered, rather than checking for particular keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    // Picks the color of the tile under the cursor
+    Select,
+    Pause,
+    HeatMap,
+    Console,
+    Diagnostics,
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [
+        Action::Select,
+        Action::Pause,
+        Action::HeatMap,
+        Action::Console,
+        Action::Diagnostics,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Select => "Select tile",
+            Action::Pause => "Pause",
+            Action::HeatMap => "Cycle heat map",
+            Action::Console => "Console",
+            Action::Diagnostics => "Diagnostics",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => format!("Mouse {:?}", button),
+            Binding::Gamepad(button) => format!("Gamepad {:?}", button),
+        }
+    }
+
+    // Whether the bindings are on the same device, so one replaces the other
+    fn same_device(&self, other: &Binding) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+// The keys and buttons triggering each action, remembered between sessions
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bindings {
+    pub actions: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            actions: BTreeMap::from([
+                (
+                    Action::Select,
+                    vec![
+                        Binding::Mouse(MouseButton::Left),
+                        Binding::Gamepad(GamepadButtonType::South),
+                    ],
+                ),
+                (Action::Pause, vec![Binding::Key(KeyCode::Escape)]),
+                (Action::HeatMap, vec![Binding::Key(KeyCode::Tab)]),
+                (Action::Console, vec![Binding::Key(KeyCode::Grave)]),
+                (Action::Diagnostics, vec![Binding::Key(KeyCode::F3)]),
+            ]),
+        }
+    }
+}
+
+impl Bindings {
+    pub fn load() -> Self {
+        let mut bindings: Bindings = storage::load(BINDINGS_KEY);
+        // Actions added since the bindings were saved keep their defaults
+        for (action, defaults) in Bindings::default().actions {
+            bindings.actions.entry(action).or_insert(defaults);
+        }
+        bindings
+    }
+
+    pub fn save(&self) {
+        storage::save(BINDINGS_KEY, self)
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.actions.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    // Binds the action to the key or button, in place of any other on the
+    // same device
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.actions.entry(action).or_default();
+        bindings.retain(|other| !other.same_device(&binding));
+        bindings.push(binding);
+    }
+}
+
+// The actions triggered this frame, by whatever they're bound to
+#[derive(SystemParam)]
+pub struct ActionInput<'w, 's> {
+    bindings: Res<'w, Bindings>,
+    keys: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    gamepads: Res<'w, Gamepads>,
+    #[system_param(ignore)]
+    marker: std::marker::PhantomData<&'s ()>,
+}
+
+impl<'w, 's> ActionInput<'w, 's> {
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.key_just_pressed(action)
+            || self.mouse_just_pressed(action)
+            || self
+                .gamepads
+                .iter()
+                .any(|gamepad| self.gamepad_just_pressed(*gamepad, action))
+    }
+
+    pub fn key_just_pressed(&self, action: Action) -> bool {
+        self.bindings
+            .get(action)
+            .iter()
+            .any(|binding| matches!(binding, Binding::Key(key) if self.keys.just_pressed(*key)))
+    }
+
+    pub fn mouse_just_pressed(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(
+            |binding| matches!(binding, Binding::Mouse(button) if self.mouse.just_pressed(*button)),
+        )
+    }
+
+    pub fn gamepad_just_pressed(&self, gamepad: Gamepad, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|binding| {
+            matches!(binding, Binding::Gamepad(button)
+                if self.buttons.just_pressed(GamepadButton(gamepad, *button)))
+        })
+    }
+}
+
+// The first key or button pressed this frame, to bind to an action
+pub fn pressed_binding(
+    keys: &Input<KeyCode>,
+    mouse: &Input<MouseButton>,
+    buttons: &Input<GamepadButton>,
+) -> Option<Binding> {
+    keys.get_just_pressed()
+        .next()
+        .map(|key| Binding::Key(*key))
+        .or_else(|| {
+            mouse
+                .get_just_pressed()
+                .next()
+                .map(|button| Binding::Mouse(*button))
+        })
+        .or_else(|| {
+            buttons
+                .get_just_pressed()
+                .next()
+                .map(|button| Binding::Gamepad(button.1))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rebinding_replaces_the_same_device() {
+        let mut bindings = Bindings::default();
+        bindings.rebind(Action::Select, Binding::Key(KeyCode::Space));
+        bindings.rebind(Action::Select, Binding::Mouse(MouseButton::Right));
+        assert_eq!(
+            bindings.get(Action::Select),
+            [
+                Binding::Gamepad(GamepadButtonType::South),
+                Binding::Key(KeyCode::Space),
+                Binding::Mouse(MouseButton::Right),
+            ]
+        );
+
+        let contents = ron::to_string(&bindings).unwrap();
+        assert_eq!(ron::from_str::<Bindings>(&contents).unwrap(), bindings);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::core::BoardTopology;
+use crate::TILE_RADIUS;
+use assimilation::hex::Axial;
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+// Everything making up a game in progress, removed before the next game starts
+#[derive(Component)]
+pub struct BoardEntity;
+
+// The layout of the current board: its size, where its tiles are in the world,
+// and the tile entity at each row and column
+pub struct Board {
+    pub topology: BoardTopology,
+    pub rows: i32,
+    pub columns: i32,
+    pub tile_radius: f32,
+    // The world position of the center of the tile in row 0, column 0
+    pub origin: Vec2,
+    pub tiles: HashMap<(i32, i32), Entity>,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new(BoardTopology::Hex, 0, 0)
+    }
+}
+
+impl Board {
+    // A board of the given size, centered on the world origin
+    pub fn new(topology: BoardTopology, rows: i32, columns: i32) -> Self {
+        let mut board = Self {
+            topology,
+            rows,
+            columns,
+            tile_radius: TILE_RADIUS,
+            origin: Vec2::ZERO,
+            tiles: HashMap::new(),
+        };
+
+        let (column_width, row_height) = board.spacing();
+        let row_shift = match topology {
+            BoardTopology::Hex => column_width / 2.0,
+            BoardTopology::Square4 | BoardTopology::Square8 => 0.0,
+        };
+        board.origin = Vec2::new(
+            -(column_width * columns as f32) / 2.0 + row_shift,
+            (row_height * rows as f32) / 2.0,
+        );
+        board
+    }
+
+    // The horizontal and vertical distance between adjacent tile centers
+    pub fn spacing(&self) -> (f32, f32) {
+        let width = self.tile_radius * 3.0_f32.sqrt();
+        match self.topology {
+            BoardTopology::Hex => (width, self.tile_radius * 1.5),
+            BoardTopology::Square4 | BoardTopology::Square8 => (width, width),
+        }
+    }
+
+    // The area covered by every tile of the board
+    pub fn size(&self) -> Vec2 {
+        let (column_width, row_height) = self.spacing();
+        Vec2::new(
+            column_width * (self.columns as f32 + 0.5),
+            row_height * self.rows as f32 + self.tile_radius,
+        )
+    }
+
+    // The center of the tile at the given row and column
+    pub fn tile_to_world(&self, row: i32, column: i32) -> Vec2 {
+        let (x, y) = match self.topology {
+            BoardTopology::Hex => {
+                let (x, y) = Axial::from_offset(row, column).to_pixel();
+                (x * self.tile_radius, y * self.tile_radius)
+            }
+            BoardTopology::Square4 | BoardTopology::Square8 => {
+                let (column_width, row_height) = self.spacing();
+                (column as f32 * column_width, row as f32 * row_height)
+            }
+        };
+        self.origin + Vec2::new(x, -y)
+    }
+
+    // The row and column of the tile covering the given world position, which
+    // may be outside of the board
+    pub fn world_to_tile(&self, point: Vec2) -> (i32, i32) {
+        let x = point.x - self.origin.x;
+        let y = self.origin.y - point.y;
+
+        match self.topology {
+            BoardTopology::Hex => {
+                Axial::from_pixel(x / self.tile_radius, y / self.tile_radius).to_offset()
+            }
+            BoardTopology::Square4 | BoardTopology::Square8 => {
+                let (column_width, row_height) = self.spacing();
+                (
+                    (y / row_height).round() as i32,
+                    (x / column_width).round() as i32,
+                )
+            }
+        }
+    }
+
+    pub fn tile_at(&self, point: Vec2) -> Option<Entity> {
+        self.tiles.get(&self.world_to_tile(point)).cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn world_to_tile_inverts_tile_to_world() {
+        for topology in [BoardTopology::Hex, BoardTopology::Square4] {
+            let board = Board::new(topology, 6, 5);
+            let (column_width, _) = board.spacing();
+
+            for (row, column) in [(0, 0), (1, 0), (1, 3), (2, 2), (5, 4)] {
+                let center = board.tile_to_world(row, column);
+                assert_eq!(board.world_to_tile(center), (row, column));
+                assert_eq!(
+                    board.world_to_tile(center + Vec2::new(column_width / 3.0, 0.0)),
+                    (row, column)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::core::{GameState, MoveCache};
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Where the book is kept among the assets
+const BOOK_PATH: &str = "openings.book";
+
+// Good opening moves for the deep bot, by position, so it can play them right
+// away instead of searching. Positions only come up again on boards colored
+// from the same seed
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "6fbe1954-fe9d-4a7a-aad2-4e1b098bc0d6"]
+pub struct OpeningBook {
+    pub moves: BTreeMap<u64, u32>,
+}
+
+impl OpeningBook {
+    // The book move for the current position, if there is one the player can
+    // actually make
+    pub fn lookup(&self, state: &GameState, cache: &MoveCache, player: Entity) -> Option<u32> {
+        let id = *self.moves.get(&state.position_hash())?;
+        if state.is_selection_allowed(player, id) && cache.capture_count(player, id) > 0 {
+            Some(id)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct OpeningBookLoader;
+
+impl AssetLoader for OpeningBookLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let book: OpeningBook = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(book));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["book"]
+    }
+}
+
+pub struct BookHandle(pub Handle<OpeningBook>);
+
+pub fn load_book(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(BookHandle(asset_server.load(BOOK_PATH)));
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub use generator::run;
+
+// Builds the book by having the deep bot search the openings ahead of time
+#[cfg(not(target_family = "wasm"))]
+mod generator {
+    use super::OpeningBook;
+    use crate::ai::{self, Personality};
+    use crate::core::{self, GameState, MoveCache, Tile};
+    use crate::{levels, palette};
+    use bevy::prelude::*;
+
+    // The levels the book covers
+    const STOCK_LEVELS: [&str; 2] = ["Hexagon", "Square"];
+
+    const USAGE: &str = "usage: assimilation book [--seeds <count>] [--colors <count>]";
+
+    fn best_move(tiles: &[Tile], state: &GameState, player: Entity) -> Option<u32> {
+        let cache = MoveCache::new(state, tiles);
+        ai::choose_move(tiles, state, &cache, player, Personality::Deep)
+    }
+
+    // The deep bot's first move on the board colored from each seed, and its
+    // reply to every first move the other player could make
+    pub fn generate(level: &levels::Level, num_ids: u32, seeds: u64) -> OpeningBook {
+        let mut book = OpeningBook::default();
+        let players: Vec<Entity> = (0..2).map(Entity::from_raw).collect();
+        for seed in 0..seeds {
+            let mut state = GameState {
+                players: players.clone(),
+                ids: (0..num_ids).map(|id| (id, Color::NONE)).collect(),
+                topology: level.topology,
+                seed: Some(seed),
+                ..default()
+            };
+            let tiles = core::load_level(
+                &level.layout,
+                &players,
+                state.ids.keys().cloned().collect(),
+                Some(seed),
+            );
+            state.track_board(&tiles);
+
+            if let Some(id) = best_move(&tiles, &state, players[0]) {
+                book.moves.insert(state.position_hash(), id);
+            }
+
+            let cache = MoveCache::new(&state, &tiles);
+            let first_moves = state
+                .ids
+                .keys()
+                .filter(|id| cache.capture_count(players[0], **id) > 0)
+                .cloned()
+                .collect::<Vec<_>>();
+            for first in first_moves {
+                let mut state = state.clone();
+                let mut board = tiles.clone();
+                core::simulate_selection(&mut board, state.topology, first, players[0]);
+                for (before, after) in tiles.iter().zip(board.iter()) {
+                    state.update_tile(before, &after.state);
+                }
+                state.last_selections.insert(players[0], first);
+                state.moves += 1;
+                state.end_turn();
+
+                if let Some(id) = best_move(&board, &state, players[1]) {
+                    book.moves.insert(state.position_hash(), id);
+                }
+            }
+        }
+        book
+    }
+
+    // Prints a book for the stock levels, to be saved as the book asset
+    pub fn run(args: &[String]) -> Result<(), String> {
+        let (mut seeds, mut num_ids) = (10, 5);
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for {}\n{}", arg, USAGE))?;
+            match arg.as_str() {
+                "--seeds" => {
+                    seeds = value
+                        .parse()
+                        .map_err(|_| format!("Invalid number of seeds '{}'", value))?
+                }
+                "--colors" => {
+                    num_ids = value
+                        .parse()
+                        .ok()
+                        .filter(|num_ids| (2..=palette::MAX_IDS).contains(num_ids))
+                        .ok_or_else(|| format!("Invalid number of colors '{}'", value))?
+                }
+                _ => return Err(format!("Unknown option {}\n{}", arg, USAGE)),
+            }
+        }
+
+        let mut book = OpeningBook::default();
+        for level in levels::LEVELS
+            .iter()
+            .filter(|level| STOCK_LEVELS.contains(&level.name.as_ref()))
+        {
+            book.moves.extend(generate(level, num_ids, seeds).moves);
+        }
+        let contents = ron::ser::to_string_pretty(&book, Default::default())
+            .map_err(|e| format!("Unable to write the book: {}", e))?;
+        println!("{}", contents);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::generator::generate;
+    use super::*;
+    use crate::core::{load_level, BoardTopology};
+    use crate::levels;
+    use std::borrow::Cow;
+
+    #[test]
+    fn book_moves_are_found_again() {
+        let level = levels::Level {
+            name: Cow::Borrowed("Test"),
+            layout: Cow::Borrowed("1 | | | | | | | | 2"),
+            topology: BoardTopology::Hex,
+            info: levels::LevelInfo::NONE,
+        };
+        let book = generate(&level, 3, 2);
+        assert!(!book.moves.is_empty());
+
+        let contents = ron::to_string(&book).unwrap();
+        assert_eq!(ron::from_str::<OpeningBook>(&contents).unwrap(), book);
+
+        // The same seed gives the same position, so the book has a move for it
+        let players: Vec<Entity> = (0..2).map(Entity::from_raw).collect();
+        let mut state = GameState {
+            players: players.clone(),
+            ids: (0..3).map(|id| (id, Color::NONE)).collect(),
+            topology: level.topology,
+            ..default()
+        };
+        let tiles = load_level(&level.layout, &players, vec![0, 1, 2], Some(1));
+        state.track_board(&tiles);
+        let cache = MoveCache::new(&state, &tiles);
+        assert_eq!(
+            book.lookup(&state, &cache, players[0]),
+            crate::ai::choose_move(
+                &tiles,
+                &state,
+                &cache,
+                players[0],
+                crate::ai::Personality::Deep
+            )
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use crate::{lobby, net, GameConfigState};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use std::collections::VecDeque;
+
+const MAX_LINES: usize = 50;
+const MAX_MESSAGE_LENGTH: usize = 200;
+// At most this many messages are sent within the throttle window, in seconds
+const BURST_SIZE: usize = 3;
+const THROTTLE_WINDOW: f64 = 5.0;
+
+// Quick messages sent with the number keys
+const EMOTES: [(KeyCode, &str); 5] = [
+    (KeyCode::Key1, "Good luck!"),
+    (KeyCode::Key2, "Nice move!"),
+    (KeyCode::Key3, "Oops"),
+    (KeyCode::Key4, "Hurry up!"),
+    (KeyCode::Key5, "Good game!"),
+];
+
+// Limits how many messages can be sent in a short time
+#[derive(Default)]
+pub struct Throttle {
+    recent: VecDeque<f64>,
+}
+
+impl Throttle {
+    // Whether a message sent at the given time goes through
+    pub fn allow(&mut self, now: f64) -> bool {
+        while let Some(time) = self.recent.front() {
+            if now - time < THROTTLE_WINDOW {
+                break;
+            }
+            self.recent.pop_front();
+        }
+
+        if self.recent.len() >= BURST_SIZE {
+            return false;
+        }
+        self.recent.push_back(now);
+        true
+    }
+}
+
+#[derive(Default)]
+pub struct Chat {
+    lines: Vec<String>,
+    input: String,
+    hidden: bool,
+    // Lines received while the chat was hidden
+    unread: usize,
+    sent: Throttle,
+    // The other side is throttled too, in case it doesn't do so itself
+    received: Throttle,
+}
+
+impl Chat {
+    fn push(&mut self, line: String) {
+        self.lines.push(line);
+        if self.hidden {
+            self.unread += 1;
+        }
+        let excess = self.lines.len().saturating_sub(MAX_LINES);
+        self.lines.drain(..excess);
+    }
+
+    pub fn receive(&mut self, name: &str, text: &str, now: f64) {
+        if self.received.allow(now) {
+            self.push(format!("{}: {}", name, clean(text)));
+        }
+    }
+}
+
+// Drops anything that could mess up the chat's layout, and shortens overly
+// long messages
+fn clean(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_MESSAGE_LENGTH)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+// Sends the message to the other side, if it isn't being sent too often
+fn say(room: &mut lobby::Room, name: &str, text: &str, now: f64) {
+    let text = clean(text);
+    if text.is_empty() {
+        return;
+    }
+    if !room.chat.sent.allow(now) {
+        room.chat
+            .push("Slow down, only a few messages can be sent at a time".into());
+        return;
+    }
+    room.send(&net::Message::Chat(text.clone()));
+    room.chat.push(format!("{}: {}", name, text));
+}
+
+pub fn show_chat(
+    config: Res<GameConfigState>,
+    time: Res<Time>,
+    mut lobby: ResMut<lobby::Lobby>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    let room = match lobby.room.as_mut() {
+        Some(room) if room.in_game => room,
+        _ => return,
+    };
+
+    if room.chat.hidden {
+        egui::Area::new("show_chat")
+            .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -10.0])
+            .show(egui_ctx.ctx_mut(), |ui| {
+                let label = match room.chat.unread {
+                    0 => "chat".to_string(),
+                    unread => format!("chat ({})", unread),
+                };
+                if ui.button(label).clicked() {
+                    room.chat.hidden = false;
+                    room.chat.unread = 0;
+                }
+            });
+        return;
+    }
+
+    let mut submitted = false;
+    egui::Window::new("Chat")
+        .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -10.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(120.0)
+                .stick_to_bottom()
+                .show(ui, |ui| {
+                    for line in room.chat.lines.iter() {
+                        ui.label(line);
+                    }
+                });
+
+            let response = ui.text_edit_singleline(&mut room.chat.input);
+            submitted = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+            ui.horizontal(|ui| {
+                ui.small("1-5 send emotes");
+                if ui.button("hide").clicked() {
+                    room.chat.hidden = true;
+                }
+            });
+        });
+
+    if submitted {
+        let text = std::mem::take(&mut room.chat.input);
+        say(
+            room,
+            &config.names.player,
+            &text,
+            time.seconds_since_startup(),
+        );
+    }
+}
+
+pub fn send_emotes(
+    config: Res<GameConfigState>,
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mut lobby: ResMut<lobby::Lobby>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    let room = match lobby.room.as_mut() {
+        Some(room) if room.in_game => room,
+        _ => return,
+    };
+    // The keys are just text while typing a message
+    if egui_ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    for (key, emote) in EMOTES {
+        if keys.just_pressed(key) {
+            say(
+                room,
+                &config.names.player,
+                emote,
+                time.seconds_since_startup(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bursts_are_throttled() {
+        let mut throttle = Throttle::default();
+        for _ in 0..BURST_SIZE {
+            assert!(throttle.allow(1.0));
+        }
+        assert!(!throttle.allow(2.0));
+        assert!(!throttle.allow(1.0 + THROTTLE_WINDOW - 0.1));
+        assert!(throttle.allow(1.0 + THROTTLE_WINDOW));
+    }
+
+    #[test]
+    fn messages_are_cleaned() {
+        assert_eq!(clean("  hi\nthere\u{7} "), "hithere");
+        assert_eq!(clean(&"a".repeat(500)).len(), MAX_MESSAGE_LENGTH);
+    }
+}
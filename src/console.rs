@@ -0,0 +1,355 @@
+use crate::{ai, bindings, board, core, game, theme, ui};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use bevy_prototype_lyon::prelude::*;
+
+const MAX_OUTPUT_LINES: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Owner {
+    Human,
+    Bot,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileSpec {
+    Owned(Owner),
+    Unowned(u32),
+    Wall,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhaseSpec {
+    Running,
+    Paused,
+    // Won by whoever has the highest score
+    Over,
+    Draw,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    // Recolors every unowned tile
+    Fill(u32),
+    SetTile {
+        row: i32,
+        column: i32,
+        state: TileSpec,
+    },
+    // Shows how each bot personality rates the current player's moves
+    AiEval,
+    Seed,
+    // Prints the board hash, to compare against a game's log
+    Hash,
+    Phase(PhaseSpec),
+}
+
+const HELP: &str = "commands: fill <color>, set_tile <row> <column> \
+    <owned:player|owned:bot|unowned:<color>|wall>, ai_eval, seed, \
+    hash, phase <running|paused|over|draw>";
+
+fn number<T: std::str::FromStr>(word: &str) -> Result<T, String> {
+    word.parse()
+        .map_err(|_| format!("Expected a number, found '{}'", word))
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["fill", id] => Ok(Command::Fill(number(id)?)),
+        ["set_tile", row, column, state] => Ok(Command::SetTile {
+            row: number(row)?,
+            column: number(column)?,
+            state: match state.split_once(':') {
+                Some(("owned", "player")) => TileSpec::Owned(Owner::Human),
+                Some(("owned", "bot")) => TileSpec::Owned(Owner::Bot),
+                Some(("unowned", id)) => TileSpec::Unowned(number(id)?),
+                None if *state == "wall" => TileSpec::Wall,
+                _ => return Err(format!("Unknown tile state '{}'", state)),
+            },
+        }),
+        ["ai_eval"] => Ok(Command::AiEval),
+        ["seed"] => Ok(Command::Seed),
+        ["hash"] => Ok(Command::Hash),
+        ["phase", phase] => Ok(Command::Phase(match *phase {
+            "running" => PhaseSpec::Running,
+            "paused" => PhaseSpec::Paused,
+            "over" => PhaseSpec::Over,
+            "draw" => PhaseSpec::Draw,
+            _ => return Err(format!("Unknown phase '{}'", phase)),
+        })),
+        _ => Err(HELP.into()),
+    }
+}
+
+// The command line, and what previous commands printed
+#[derive(Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    output: Vec<String>,
+}
+
+// Everything commands are allowed to change
+#[derive(SystemParam)]
+pub struct ConsoleTarget<'w, 's> {
+    state: ResMut<'w, core::GameState>,
+    cache: ResMut<'w, core::MoveCache>,
+    board: Res<'w, board::Board>,
+    theme: Res<'w, theme::Theme>,
+    players: Query<'w, 's, &'static core::Player>,
+    tiles: Query<
+        'w,
+        's,
+        (
+            &'static mut core::Tile,
+            &'static mut DrawMode,
+            &'static mut Transform,
+        ),
+    >,
+}
+
+impl<'w, 's> ConsoleTarget<'w, 's> {
+    fn run(&mut self, command: Command) -> Result<String, String> {
+        if self.state.players.is_empty() {
+            return Err("No game in progress".into());
+        }
+
+        match command {
+            Command::Fill(id) => {
+                self.check_id(id)?;
+                let mut filled = 0;
+                for (row, column) in self.board.tiles.keys().cloned().collect::<Vec<_>>() {
+                    if matches!(
+                        self.tile_state(row, column),
+                        Some(core::TileState::Unowned(_))
+                    ) {
+                        self.set_tile(row, column, core::TileState::Unowned(id))?;
+                        filled += 1;
+                    }
+                }
+                Ok(format!("Filled {} tiles", filled))
+            }
+            Command::SetTile { row, column, state } => {
+                let state = match state {
+                    TileSpec::Owned(owner) => core::TileState::Owned(self.find_player(owner)?),
+                    TileSpec::Unowned(id) => {
+                        self.check_id(id)?;
+                        core::TileState::Unowned(id)
+                    }
+                    TileSpec::Wall => core::TileState::Wall,
+                };
+                self.set_tile(row, column, state)?;
+                Ok(format!("Set tile {} {}", row, column))
+            }
+            Command::AiEval => {
+                let tiles: Vec<core::Tile> = self.tiles.iter().map(|tile| tile.0.clone()).collect();
+                let player = self.state.players[0];
+                Ok(ai::Personality::ALL
+                    .into_iter()
+                    .map(|personality| {
+                        let moves = ai::evaluate_moves(
+                            &tiles,
+                            &self.state,
+                            &self.cache,
+                            player,
+                            personality,
+                        )
+                        .into_iter()
+                        .map(|(id, score)| format!("{}: {}", id, score))
+                        .collect::<Vec<_>>();
+                        format!("{}: {}", personality.name(), moves.join(", "))
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            Command::Seed => Ok(match self.state.seed {
+                Some(seed) => seed.to_string(),
+                None => "The board was colored at random".into(),
+            }),
+            Command::Hash => Ok(format!(
+                "board {}, position {}",
+                self.state.board_hash.value,
+                self.state.position_hash()
+            )),
+            Command::Phase(phase) => {
+                self.state.phase = match phase {
+                    PhaseSpec::Running => core::GamePhase::Running,
+                    PhaseSpec::Paused => core::GamePhase::Paused,
+                    PhaseSpec::Over => {
+                        let players = &self.players;
+                        let leader = self
+                            .state
+                            .players
+                            .iter()
+                            .max_by_key(|id| players.get(**id).map(|player| player.score).ok())
+                            .cloned()
+                            .expect("No players");
+                        core::GamePhase::Over(leader)
+                    }
+                    PhaseSpec::Draw => core::GamePhase::Draw,
+                };
+                Ok("Phase changed".into())
+            }
+        }
+    }
+
+    fn check_id(&self, id: u32) -> Result<(), String> {
+        if self.state.ids.contains_key(&id) {
+            Ok(())
+        } else {
+            Err(format!("Unknown color {}", id))
+        }
+    }
+
+    fn find_player(&self, owner: Owner) -> Result<Entity, String> {
+        self.state
+            .players
+            .iter()
+            .find(|id| {
+                self.players
+                    .get(**id)
+                    .map(|player| match owner {
+                        Owner::Human => matches!(player.kind, core::PlayerKind::Human),
+                        Owner::Bot => player.kind.is_bot(),
+                    })
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .ok_or_else(|| match owner {
+                Owner::Human => "No human player".to_string(),
+                Owner::Bot => "No bot player".to_string(),
+            })
+    }
+
+    fn tile_state(&self, row: i32, column: i32) -> Option<core::TileState> {
+        let entity = self.board.tiles.get(&(row, column))?;
+        self.tiles
+            .get(*entity)
+            .ok()
+            .map(|(tile, _, _)| tile.state.clone())
+    }
+
+    // Changes a tile and redraws it, leaving the move cache to be rebuilt
+    fn set_tile(&mut self, row: i32, column: i32, state: core::TileState) -> Result<(), String> {
+        let entity = *self
+            .board
+            .tiles
+            .get(&(row, column))
+            .ok_or_else(|| format!("No tile at {} {}", row, column))?;
+        let (mut tile, mut mode, mut transform) = self
+            .tiles
+            .get_mut(entity)
+            .map_err(|_| format!("No tile at {} {}", row, column))?;
+
+        if matches!(state, core::TileState::Empty) {
+            return Err("Tiles can't be removed".into());
+        }
+        let (new_mode, z_pos) =
+            game::tile_appearance(&self.theme, &self.state, &self.players, &state)
+                .ok_or_else(|| "Unknown player".to_string())?;
+        *mode = new_mode;
+        transform.translation.z = z_pos;
+        self.state.update_tile(&tile, &state);
+        tile.state = state;
+        self.cache.stale = true;
+        Ok(())
+    }
+}
+
+// A developer console for trying out rules and levels, opened with backtick
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .add_system(toggle_console)
+            .add_system(show_console.after(toggle_console));
+    }
+}
+
+fn toggle_console(input: bindings::ActionInput, mut console: ResMut<Console>) {
+    if input.just_pressed(bindings::Action::Console) {
+        console.open = !console.open;
+    }
+}
+
+fn show_console(
+    mut console: ResMut<Console>,
+    streamer: Res<ui::StreamerMode>,
+    mut target: ConsoleTarget,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    if !console.open || streamer.enabled {
+        return;
+    }
+
+    let mut submitted = false;
+    let console = &mut *console;
+    egui::Window::new("Console")
+        .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -10.0])
+        .resizable(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .stick_to_bottom()
+                .show(ui, |ui| {
+                    for line in console.output.iter() {
+                        ui.monospace(line);
+                    }
+                });
+
+            // The key opening the console shouldn't end up in the command
+            console.input.retain(|c| c != '`');
+            let response = ui.text_edit_singleline(&mut console.input);
+            submitted = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+            response.request_focus();
+        });
+
+    if !submitted || console.input.trim().is_empty() {
+        return;
+    }
+
+    let line = std::mem::take(&mut console.input);
+    console.output.push(format!("> {}", line));
+    match parse(&line).and_then(|command| target.run(command)) {
+        Ok(result) | Err(result) => console.output.extend(result.lines().map(String::from)),
+    }
+    let excess = console.output.len().saturating_sub(MAX_OUTPUT_LINES);
+    console.output.drain(..excess);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(parse("fill 3"), Ok(Command::Fill(3)));
+        assert_eq!(
+            parse(" set_tile 4 5  owned:player"),
+            Ok(Command::SetTile {
+                row: 4,
+                column: 5,
+                state: TileSpec::Owned(Owner::Human)
+            })
+        );
+        assert_eq!(
+            parse("set_tile 0 1 unowned:2"),
+            Ok(Command::SetTile {
+                row: 0,
+                column: 1,
+                state: TileSpec::Unowned(2)
+            })
+        );
+        assert_eq!(parse("ai_eval"), Ok(Command::AiEval));
+        assert_eq!(parse("hash"), Ok(Command::Hash));
+        assert_eq!(parse("phase over"), Ok(Command::Phase(PhaseSpec::Over)));
+
+        assert!(parse("fill red").is_err());
+        assert!(parse("set_tile 1 2 owned:nobody").is_err());
+        assert!(parse("phase later").is_err());
+        assert!(parse("dance").is_err());
+    }
+}
@@ -1,238 +1,627 @@
+use crate::ai::Personality;
+use assimilation::rules;
+pub use assimilation::rules::{
+    for_each_selected_tile, grow_start_region, mirror_colors, simulate_selection,
+    simulate_selection_value, solve, BoardTopology, TileBonus,
+};
 use bevy::prelude::*;
-use rand::{seq::SliceRandom, thread_rng};
-use std::collections::{BTreeMap, HashSet};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::DerefMut;
 
-#[derive(Clone, Debug)]
-pub enum TileState {
-    Empty,
-    Owned(Entity),
-    Unowned(u32),
-}
+// The rules' board, with tiles owned by player entities
+pub type Tile = rules::Tile<Entity>;
+pub type TileState = rules::TileState<Entity>;
+pub type BoardHash = rules::BoardHash<Entity>;
 
-#[derive(Clone, Component)]
-pub struct Tile {
-    pub row: i32,
-    pub column: i32,
-    pub state: TileState,
-}
+pub const BONUS_POINTS: u32 = 5;
+// Awarded every turn for each hill tile the moving player holds
+pub const HILL_POINTS: u32 = 2;
+// The most starting positions a level may define
+pub const MAX_PLAYERS: usize = 4;
+// With chain scoring, a point is awarded for every this many tiles captured in
+// a single move
+pub const CHAIN_SIZE: u32 = 5;
+// The game is drawn to a close once a position comes up this many times
+pub const REPETITION_LIMIT: u32 = 3;
+// The most a single tile can be worth, written as e.g. '|3' in levels
+pub const MAX_TILE_WEIGHT: u32 = 9;
+// How many rounds blitz games last
+pub const BLITZ_ROUNDS: u32 = 15;
+// Boards are colored again while random games on them favor one start this
+// much, up to a few times. Bigger boards aren't checked, as playing out
+// enough games on them would hold up the start
+const MIN_FAIRNESS: f32 = 0.4;
+const FAIR_ATTEMPTS: u64 = 4;
+const FAIRNESS_PLAYOUTS: u32 = 16;
+const MAX_ANALYZED_TILES: usize = 400;
 
 pub struct SelectEvent {
     pub id: u32,
     pub player: Entity,
 }
 
+// The decision made when offered to swap sides
+pub struct SwapEvent {
+    pub player: Entity,
+    pub swap: bool,
+}
+
 pub struct CaptureEvent {
     pub row: i32,
     pub column: i32,
     pub player: Entity,
 }
 
+// A selection accepted during a game, along with the board it was made on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Move {
+    pub player: Entity,
+    pub id: u32,
+    pub board_hash: u64,
+}
+
 #[derive(Clone, Debug)]
 pub enum PlayerKind {
     Human,
-    Bot,
+    // The timer controls how long the bot 'thinks' before moving
+    Bot(Timer, Personality),
+    // Moves are made on another machine, and arrive over the network
+    Remote,
+    // Owns tiles without taking turns, like the virus
+    Neutral,
+    // Moves are picked by a script the player wrote, given the same time to
+    // think as bots
+    Scripted(Timer, crate::script::Script),
+    // Moves are picked by a separate program, over its standard input and
+    // output
+    #[cfg(not(target_family = "wasm"))]
+    External(Timer, crate::external::ExternalBot),
+}
+
+impl PlayerKind {
+    // Whether the player moves on their own, by a built in bot, a script or
+    // another program
+    pub fn is_bot(&self) -> bool {
+        match self {
+            PlayerKind::Bot(..) | PlayerKind::Scripted(..) => true,
+            #[cfg(not(target_family = "wasm"))]
+            PlayerKind::External(..) => true,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Component)]
 pub struct Player {
     pub color: Color,
     pub name: String,
+    pub avatar: crate::avatar::Avatar,
     pub kind: PlayerKind,
     pub score: u32,
+    // Score awarded by bonus tiles, on top of owned territory
+    pub bonus: u32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum GamePhase {
+    // No game has been started yet
     Config,
+    // The board is shown, but no moves can be made yet
+    Countdown,
     Running,
+    Paused,
+    // With the pie rule, the player after the first mover decides whether to
+    // take over the first mover's side instead of moving
+    Swap,
     Over(Entity),
+    Draw,
+}
+
+impl Default for GamePhase {
+    fn default() -> Self {
+        GamePhase::Config
+    }
+}
+
+impl GamePhase {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, GamePhase::Over(_) | GamePhase::Draw)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Objective {
+    // The game ends once nobody can move, and the most tiles wins
+    Territory,
+    // Holding hill tiles scores every turn, and the game ends after the
+    // given number of rounds
+    KingOfTheHill { rounds: u32 },
+}
+
+impl Default for Objective {
+    fn default() -> Self {
+        Objective::Territory
+    }
 }
 
-#[derive(Clone)]
+// A head start given to one of the players
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Handicap {
+    None,
+    // The starting tiles are grown outwards by the given number of rings
+    ExtraTiles { rings: u32 },
+    // Two moves are made for every move of the other players
+    DoubleMove,
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Handicap::None
+    }
+}
+
+// The rule variants a game is played with, picked on the setup screen. They
+// all default to off, giving the plain game
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RulesConfig {
+    pub forbid_opponent_color: bool,
+    pub forbid_repeat_color: bool,
+    pub objective: Objective,
+    // The number of rounds after which the game ends, if any
+    pub turn_limit: Option<u32>,
+    // When set, picks are kept hidden and resolved together once every
+    // player has made one
+    pub simultaneous: bool,
+    // Big captures award extra points on top of the captured territory
+    pub chain_scoring: bool,
+    // The first move may be taken over by the next player, so it is best not
+    // to make it too strong
+    pub pie_rule: bool,
+    // Territory away from its owner's frontier reverts to unowned tiles at
+    // the end of every this many rounds
+    pub decay: Option<u32>,
+}
+
+// Curated sets of rule variants, for players who'd rather not pick them one
+// by one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ruleset {
+    Classic,
+    // Short games where big captures count for more
+    Blitz,
+    // Every color pick is constrained, so moves take planning
+    Puzzle,
+    // Hidden, simultaneous picks with chain bonuses
+    Chaos,
+}
+
+impl Ruleset {
+    pub const ALL: [Ruleset; 4] = [
+        Ruleset::Classic,
+        Ruleset::Blitz,
+        Ruleset::Puzzle,
+        Ruleset::Chaos,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Ruleset::Classic => "Classic",
+            Ruleset::Blitz => "Blitz",
+            Ruleset::Puzzle => "Puzzle",
+            Ruleset::Chaos => "Chaos",
+        }
+    }
+
+    pub fn rules(&self) -> RulesConfig {
+        match self {
+            Ruleset::Classic => RulesConfig::default(),
+            Ruleset::Blitz => RulesConfig {
+                turn_limit: Some(BLITZ_ROUNDS),
+                chain_scoring: true,
+                ..default()
+            },
+            Ruleset::Puzzle => RulesConfig {
+                forbid_opponent_color: true,
+                forbid_repeat_color: true,
+                ..default()
+            },
+            Ruleset::Chaos => RulesConfig {
+                simultaneous: true,
+                chain_scoring: true,
+                ..default()
+            },
+        }
+    }
+
+    // The preset giving exactly these rules, if there is one
+    pub fn matching(rules: &RulesConfig) -> Option<Ruleset> {
+        Ruleset::ALL
+            .into_iter()
+            .find(|ruleset| ruleset.rules() == *rules)
+    }
+}
+
+impl Handicap {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Handicap::None => "None",
+            Handicap::ExtraTiles { .. } => "Extra starting tiles",
+            Handicap::DoubleMove => "Double moves",
+        }
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct GameState {
     // The head of this vec is always the 'current' player
     pub players: Vec<Entity>,
     pub phase: GamePhase,
     pub ids: BTreeMap<u32, Color>,
+    pub topology: BoardTopology,
+    // The most recent color id picked by each player
+    pub last_selections: HashMap<Entity, u32>,
+    pub rules: RulesConfig,
+    // The number of moves made so far, by all players
+    pub moves: u32,
+    // The player given two moves per turn, if any
+    pub double_mover: Option<Entity>,
+    // Whether the double mover has already used their first move this turn
+    pub moved_this_turn: bool,
+    // Picks kept hidden until every player has made one, in simultaneous games
+    pub pending_selections: HashMap<Entity, u32>,
+    // Set when a simultaneous round captured nothing, as every following
+    // round would likely be contested the same way, or when a position keeps
+    // repeating
+    pub stalemate: bool,
+    // The seed the board was colored with, if it wasn't random
+    pub seed: Option<u64>,
+    // Kept up to date with every capture
+    pub board_hash: BoardHash,
+    // How many times each position has come up, to notice when the players
+    // are only going around in circles
+    pub positions: HashMap<u64, u32>,
+    // The number of unowned tiles left of each color
+    pub unowned: BTreeMap<u32, u32>,
+    // Every selection accepted so far, in the order they were made
+    pub history: Vec<Move>,
+    // Unowned tiles are drawn gray once the game is underway, leaving the
+    // hover preview the only way to see what a move would take
+    pub blind: bool,
 }
 
-pub fn for_each_selected_tile<T>(
-    mut tiles: Vec<T>,
-    selection: u32,
-    player: Entity,
-    mut callback: impl FnMut(&mut Tile),
-) where
-    T: core::ops::DerefMut<Target = Tile>,
-{
-    let mut owned_tiles = tiles
-        .iter()
-        .filter(|tile| match tile.state {
-            TileState::Owned(owner) => owner == player,
-            _ => false,
-        })
-        .map(|tile| (tile.row, tile.column))
-        .collect::<HashSet<(i32, i32)>>();
+impl GameState {
+    pub fn end_turn(&mut self) {
+        self.players.rotate_right(1);
+        self.moved_this_turn = false;
+    }
 
-    loop {
-        let mut did_capture = false;
+    // Starts tracking a newly loaded board
+    pub fn track_board(&mut self, tiles: &[Tile]) {
+        self.board_hash = BoardHash::new(&self.players, tiles);
+        self.positions.clear();
+        self.unowned = self.ids.keys().map(|id| (*id, 0)).collect();
+        for tile in tiles {
+            if let TileState::Unowned(id) = tile.state {
+                *self.unowned.entry(id).or_default() += 1;
+            }
+        }
+    }
 
-        for mut tile in tiles.iter_mut() {
-            if owned_tiles.contains(&(tile.row, tile.column)) {
-                continue;
+    // Keeps the board hash and color counts in step with the tile, must be
+    // called before the tile is changed
+    pub fn update_tile(&mut self, tile: &Tile, state: &TileState) {
+        self.board_hash.update(tile, state);
+        if let TileState::Unowned(id) = tile.state {
+            if let Some(count) = self.unowned.get_mut(&id) {
+                *count = count.saturating_sub(1);
             }
+        }
+        if let TileState::Unowned(id) = state {
+            *self.unowned.entry(*id).or_default() += 1;
+        }
+    }
 
-            // TODO: if we capture (or skip) we should jump out to the next tile
-            for row_offset in [-1, 0, 1] {
-                for column_offset in [-1, 0, 1] {
-                    if row_offset == 0 && column_offset == 0 {
-                        continue;
-                    }
+    pub fn position_hash(&self) -> u64 {
+        self.board_hash
+            .position(self.players[0], &self.last_selections)
+    }
 
-                    if tile.row % 2 == 0 && row_offset != 0 && column_offset == -1 {
-                        continue;
-                    }
+    // Counts the position reached by the last move, calling the game once the
+    // same one has come up too often for anything to change
+    pub fn record_position(&mut self) {
+        let count = self.positions.entry(self.position_hash()).or_default();
+        *count += 1;
+        if *count >= REPETITION_LIMIT {
+            self.stalemate = true;
+        }
+    }
 
-                    if tile.row % 2 != 0 && row_offset != 0 && column_offset == 1 {
-                        continue;
-                    }
+    // The round currently being played, starting at 1
+    pub fn round(&self) -> u32 {
+        self.moves / self.players.len().max(1) as u32 + 1
+    }
 
-                    if !owned_tiles.contains(&(tile.row + row_offset, tile.column + column_offset))
-                    {
-                        continue;
-                    }
+    // The last round to be played, from either the objective or turn limit
+    pub fn last_round(&self) -> Option<u32> {
+        let objective_rounds = match self.rules.objective {
+            Objective::Territory => None,
+            Objective::KingOfTheHill { rounds } => Some(rounds),
+        };
+        match (objective_rounds, self.rules.turn_limit) {
+            (Some(rounds), Some(limit)) => Some(rounds.min(limit)),
+            (rounds, limit) => rounds.or(limit),
+        }
+    }
 
-                    match tile.state {
-                        TileState::Unowned(id) => {
-                            if id == selection {
-                                owned_tiles.insert((tile.row, tile.column));
-                                did_capture = true;
-                                callback(&mut tile);
-                            }
-                        }
-                        _ => {
-                            continue;
-                        }
-                    }
-                }
+    pub fn is_out_of_rounds(&self) -> bool {
+        matches!(self.last_round(), Some(last_round) if self.round() > last_round)
+    }
+
+    // The extra points awarded for capturing the given number of tiles at once
+    pub fn chain_bonus(&self, captured: u32) -> u32 {
+        if self.rules.chain_scoring {
+            captured / CHAIN_SIZE
+        } else {
+            0
+        }
+    }
+
+    pub fn is_selection_allowed(&self, player: Entity, id: u32) -> bool {
+        // Colors with no unowned tiles left can't capture anything
+        if self.unowned.get(&id) == Some(&0) {
+            return false;
+        }
+
+        if self.rules.forbid_repeat_color && self.last_selections.get(&player) == Some(&id) {
+            return false;
+        }
+
+        if self.rules.forbid_opponent_color {
+            let held_by_opponent = self
+                .last_selections
+                .iter()
+                .any(|(owner, selection)| *owner != player && *selection == id);
+            if held_by_opponent {
+                return false;
             }
         }
+        true
+    }
+
+    // The moves made by people at this computer from the given turn on, with
+    // their turns, which online and correspondence games send to the other side
+    pub fn local_moves(&self, players: &Query<&Player>, from: usize) -> Vec<(usize, Move)> {
+        self.history
+            .iter()
+            .enumerate()
+            .skip(from)
+            .filter(|(_, made)| {
+                players
+                    .get(made.player)
+                    .map_or(false, |player| matches!(player.kind, PlayerKind::Human))
+            })
+            .map(|(turn, made)| (turn, *made))
+            .collect()
+    }
+}
+
+// Caches how many tiles each player would capture with each color, so the
+// flood fills only need to be redone when the board actually changes
+pub struct MoveCache {
+    pub captures: HashMap<Entity, BTreeMap<u32, u32>>,
+    pub stale: bool,
+}
+
+impl Default for MoveCache {
+    fn default() -> Self {
+        Self {
+            captures: HashMap::new(),
+            stale: true,
+        }
+    }
+}
 
-        if !did_capture {
-            break;
+impl MoveCache {
+    // Counts the captures on a board kept outside of the ECS
+    pub fn new(state: &GameState, tiles: &[Tile]) -> Self {
+        let mut cache = Self {
+            captures: HashMap::new(),
+            stale: false,
+        };
+        for player in state.players.iter() {
+            for id in state.ids.keys() {
+                let count = simulate_selection(&mut tiles.to_vec(), state.topology, *id, *player);
+                cache
+                    .captures
+                    .entry(*player)
+                    .or_default()
+                    .insert(*id, count);
+            }
         }
+        cache
+    }
+
+    pub fn capture_count(&self, player: Entity, id: u32) -> u32 {
+        self.captures
+            .get(&player)
+            .and_then(|captures| captures.get(&id))
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    pub fn has_move(&self, state: &GameState, player: Entity) -> bool {
+        state.ids.keys().any(|id| {
+            state.is_selection_allowed(player, *id) && self.capture_count(player, *id) > 0
+        })
     }
 }
 
-pub fn perform_ai_move(
+pub fn update_move_cache(
     state: Res<GameState>,
-    players: Query<&Player>,
-    mut selections: EventWriter<SelectEvent>,
+    mut cache: ResMut<MoveCache>,
+    mut captures: EventReader<CaptureEvent>,
     mut tiles: Query<&mut Tile>,
 ) {
-    let player = match players.get(state.players[0]) {
-        Ok(player) => match player.kind {
-            PlayerKind::Bot => state.players[0],
-            _ => return,
-        },
-        Err(_) => return,
-    };
+    if captures.iter().count() == 0 && !cache.stale {
+        return;
+    }
 
-    let mut best_score = 0;
-    let mut best_move = 0;
-    for id in 0..state.ids.len() as u32 {
-        let mut score = 0;
-        for_each_selected_tile(tiles.iter_mut().collect(), id, player, |_| {
-            score += 1;
-        });
-        if score > best_score {
-            best_score = score;
-            best_move = id;
+    cache.stale = false;
+    cache.captures.clear();
+    for player in state.players.iter() {
+        for id in state.ids.keys() {
+            let mut count = 0;
+            for_each_selected_tile(
+                tiles.iter_mut().collect(),
+                state.topology,
+                *id,
+                *player,
+                |_| count += 1,
+            );
+            cache
+                .captures
+                .entry(*player)
+                .or_default()
+                .insert(*id, count);
         }
     }
-
-    selections.send(SelectEvent {
-        player,
-        id: best_move,
-    });
 }
 
 pub fn update_scores(
     mut state: ResMut<GameState>,
+    cache: Res<MoveCache>,
     mut players: Query<(Entity, &mut Player)>,
-    mut tiles: Query<&mut Tile>,
+    tiles: Query<&Tile>,
 ) {
-    for mut player in players.iter_mut() {
-        player.1.score = 0;
+    // Nothing to do unless the board or the turn order has changed
+    if !cache.is_changed() && !state.is_changed() {
+        return;
     }
 
-    let mut total_unowned = 0;
-    for tile in tiles.iter() {
-        match tile.state {
-            TileState::Owned(player) => {
-                if let Ok(mut player) = players.get_mut(player) {
-                    player.1.score += 1;
-                }
-            }
-            TileState::Unowned(_) => total_unowned += 1,
-            _ => continue,
-        }
+    for (entity, mut player) in players.iter_mut() {
+        player.score = player.bonus + rules::territory_score(tiles.iter(), entity);
     }
 
-    //For now, the game is over if either player can't move
-    let mut player_no_moves = None;
-    for player in players.iter() {
-        let mut possible_captures = false;
-        for possible_selection in state.ids.keys() {
-            for_each_selected_tile(
-                tiles.iter_mut().collect(),
-                *possible_selection,
-                player.0,
-                |_| {
-                    possible_captures = true;
-                },
-            );
-        }
+    let movable_players = state
+        .players
+        .iter()
+        .filter(|player| cache.has_move(&state, **player))
+        .cloned()
+        .collect::<HashSet<_>>();
 
-        if !possible_captures {
-            player_no_moves = Some(player.0);
-            break;
+    // Players who can't capture anything this turn are skipped, the game
+    // only ends once nobody is able to move or the rounds are used up
+    if !movable_players.is_empty() && !state.is_out_of_rounds() && !state.stalemate {
+        if !movable_players.contains(&state.players[0]) {
+            state.end_turn();
         }
-    }
-
-    let player_no_moves = if let Some(player_no_moves) = player_no_moves {
-        player_no_moves
-    } else {
         return;
-    };
-
-    for mut player in players.iter_mut() {
-        if player.0 != player_no_moves {
-            player.1.score += total_unowned;
-            break;
-        }
     }
 
-    let winner = players
+    let best_score = state
+        .players
+        .iter()
+        .filter_map(|player| players.get(*player).ok())
+        .map(|player| player.1.score)
+        .max()
+        .unwrap_or(0);
+
+    let winners = state
+        .players
         .iter()
-        .max_by(|player1, player2| player1.1.score.cmp(&player2.1.score))
-        .expect("Missing winner");
+        .filter(|player| {
+            players
+                .get(**player)
+                .map(|player| player.1.score == best_score)
+                .unwrap_or(false)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
 
-    state.phase = GamePhase::Over(winner.0);
+    state.phase = match winners.as_slice() {
+        [winner] => GamePhase::Over(*winner),
+        _ => GamePhase::Draw,
+    };
 }
 
 pub fn perform_selection(
     mut state: ResMut<GameState>,
+    cache: Res<MoveCache>,
     mut selections: EventReader<SelectEvent>,
     mut tiles: Query<&mut Tile>,
+    mut players: Query<&mut Player>,
     mut captures: EventWriter<CaptureEvent>,
 ) {
     for selection in selections.iter() {
+        if state.players[0] != selection.player
+            || !state.is_selection_allowed(selection.player, selection.id)
+        {
+            continue;
+        }
+
+        let _turn = info_span!(
+            "turn",
+            number = state.moves,
+            player = ?selection.player,
+            color = selection.id
+        )
+        .entered();
+        let board_hash = state.board_hash.value;
+        state.history.push(Move {
+            player: selection.player,
+            id: selection.id,
+            board_hash,
+        });
+
+        if state.rules.simultaneous {
+            debug!("pick held until every player has made one");
+            state
+                .pending_selections
+                .insert(selection.player, selection.id);
+            state.moves += 1;
+            state.end_turn();
+
+            // Players unable to move don't hold up the round
+            let waiting = state.players.iter().any(|player| {
+                !state.pending_selections.contains_key(player) && cache.has_move(&state, *player)
+            });
+            if !waiting {
+                resolve_simultaneous_selections(
+                    &mut state,
+                    &mut tiles,
+                    &mut players,
+                    &mut captures,
+                );
+            }
+            continue;
+        }
+
+        state.last_selections.insert(selection.player, selection.id);
+
+        let mut extra_turn = false;
+        let mut bonus_points = 0;
+        let mut captured = 0;
+        let topology = state.topology;
         for_each_selected_tile(
             tiles.iter_mut().collect(),
+            topology,
             selection.id,
             selection.player,
             |tile| {
-                tile.state = TileState::Owned(selection.player);
+                let owned = TileState::Owned(selection.player);
+                state.update_tile(tile, &owned);
+                tile.state = owned;
+                captured += 1;
+                match tile.bonus {
+                    Some(TileBonus::ExtraTurn) => extra_turn = true,
+                    Some(TileBonus::Points(points)) => bonus_points += points,
+                    Some(TileBonus::Hill) | None => (),
+                }
+                if tile.bonus != Some(TileBonus::Hill) {
+                    tile.bonus = None;
+                }
                 captures.send(CaptureEvent {
                     row: tile.row,
                     column: tile.column,
@@ -240,8 +629,169 @@ pub fn perform_selection(
                 });
             },
         );
+        bonus_points += state.chain_bonus(captured);
+
+        if let Objective::KingOfTheHill { .. } = state.rules.objective {
+            let hills = tiles
+                .iter()
+                .filter(|tile| tile.bonus == Some(TileBonus::Hill))
+                .filter(|tile| matches!(tile.state, TileState::Owned(owner) if owner == selection.player))
+                .count() as u32;
+            bonus_points += hills * HILL_POINTS;
+        }
+
+        if let Ok(mut player) = players.get_mut(selection.player) {
+            player.bonus += bonus_points;
+        }
+        info!(
+            captured,
+            bonus_points,
+            extra_turn,
+            board_hash = state.board_hash.value,
+            "selection made"
+        );
+
+        // The double mover's first move is free, and doesn't count towards
+        // the rounds played
+        let first_of_double =
+            state.double_mover == Some(selection.player) && !state.moved_this_turn;
+        if !first_of_double {
+            state.moves += 1;
+        }
+        if !extra_turn {
+            if first_of_double {
+                state.moved_this_turn = true;
+            } else {
+                state.end_turn();
+            }
+        }
+        state.record_position();
+
+        // The turn has passed on from the first mover for the first time
+        let first_move_over = state.players[0] != selection.player
+            && state
+                .history
+                .iter()
+                .all(|made| made.player == selection.player);
+        if state.rules.pie_rule && first_move_over {
+            state.phase = GamePhase::Swap;
+        }
+    }
+}
+
+// Hands the first mover's tiles and last pick to the player deciding to swap,
+// and theirs to the first mover, using up the swapping player's turn. Returns
+// the first mover and the swapping player
+pub fn swap_sides(
+    state: &mut GameState,
+    tiles: impl IntoIterator<Item = impl DerefMut<Target = Tile>>,
+) -> Option<(Entity, Entity)> {
+    let swapper = *state.players.first()?;
+    let first = state.history.first()?.player;
+    if swapper == first {
+        return None;
+    }
+
+    for mut tile in tiles {
+        let swapped = match tile.state {
+            TileState::Owned(owner) if owner == first => TileState::Owned(swapper),
+            TileState::Owned(owner) if owner == swapper => TileState::Owned(first),
+            _ => continue,
+        };
+        state.update_tile(&tile, &swapped);
+        tile.state = swapped;
+    }
+
+    let first_selection = state.last_selections.remove(&first);
+    let swapper_selection = state.last_selections.remove(&swapper);
+    if let Some(id) = first_selection {
+        state.last_selections.insert(swapper, id);
+    }
+    if let Some(id) = swapper_selection {
+        state.last_selections.insert(first, id);
+    }
+
+    state.moves += 1;
+    state.end_turn();
+    state.record_position();
+    Some((first, swapper))
+}
+
+// Every pick is applied to the board as it was before the round. Tiles claimed
+// by more than one player are contested, and stay unowned
+fn resolve_simultaneous_selections(
+    state: &mut GameState,
+    tiles: &mut Query<&mut Tile>,
+    players: &mut Query<&mut Player>,
+    captures: &mut EventWriter<CaptureEvent>,
+) {
+    let board = tiles.iter().cloned().collect::<Vec<_>>();
+    let mut claims: HashMap<(i32, i32), Vec<Entity>> = HashMap::new();
+    for (player, id) in state.pending_selections.iter() {
+        let mut board = board.clone();
+        for_each_selected_tile(
+            board.iter_mut().collect(),
+            state.topology,
+            *id,
+            *player,
+            |tile| {
+                claims
+                    .entry((tile.row, tile.column))
+                    .or_default()
+                    .push(*player)
+            },
+        );
+    }
+
+    let mut bonus_points: HashMap<Entity, u32> = HashMap::new();
+    let mut captured: HashMap<Entity, u32> = HashMap::new();
+    for mut tile in tiles.iter_mut() {
+        let player = match claims.get(&(tile.row, tile.column)).map(Vec::as_slice) {
+            Some([player]) => *player,
+            _ => continue,
+        };
+        *captured.entry(player).or_default() += 1;
 
-        state.players.rotate_right(1);
+        let owned = TileState::Owned(player);
+        state.update_tile(&tile, &owned);
+        tile.state = owned;
+        if let Some(TileBonus::Points(points)) = tile.bonus {
+            *bonus_points.entry(player).or_default() += points;
+        }
+        if tile.bonus != Some(TileBonus::Hill) {
+            tile.bonus = None;
+        }
+        captures.send(CaptureEvent {
+            row: tile.row,
+            column: tile.column,
+            player,
+        });
+    }
+
+    state.stalemate = captured.is_empty();
+    info!(
+        ?captured,
+        contested = claims.values().filter(|claim| claim.len() > 1).count(),
+        board_hash = state.board_hash.value,
+        "round resolved"
+    );
+
+    for (player, id) in std::mem::take(&mut state.pending_selections) {
+        state.last_selections.insert(player, id);
+
+        let mut points = bonus_points.get(&player).cloned().unwrap_or(0)
+            + state.chain_bonus(captured.get(&player).cloned().unwrap_or(0));
+        if let Objective::KingOfTheHill { .. } = state.rules.objective {
+            let hills = tiles
+                .iter()
+                .filter(|tile| tile.bonus == Some(TileBonus::Hill))
+                .filter(|tile| matches!(tile.state, TileState::Owned(owner) if owner == player))
+                .count() as u32;
+            points += hills * HILL_POINTS;
+        }
+        if let Ok(mut player) = players.get_mut(player) {
+            player.bonus += points;
+        }
     }
 }
 
@@ -249,46 +799,151 @@ pub fn load_level(
     level: impl AsRef<str>,
     players: &[Entity],
     ids: Vec<u32>,
-    _random: bool,
+    seed: Option<u64>,
 ) -> Vec<Tile> {
+    parse_level(level, players, ids, seed).unwrap_or_else(|e| panic!("{}", e))
+}
+
+// How evenly the board's coloring treats the starting positions, see
+// rules::fairness. Boards too big to check, or with a single start, give none
+pub fn board_fairness(tiles: &[Tile], topology: BoardTopology, players: &[Entity]) -> Option<f32> {
+    if players.len() < 2 || tiles.len() > MAX_ANALYZED_TILES {
+        return None;
+    }
+    // Always played out the same way, so every side of an online game agrees
+    let potential = rules::capture_potential(tiles, topology, players, FAIRNESS_PLAYOUTS, 0);
+    Some(rules::fairness(&potential))
+}
+
+// Like load_level, but colors the board again while it's grossly unfair,
+// giving the board along with how fair it was found to be. Seeded boards try
+// the following seeds, so they still come out the same every time
+pub fn load_fair_level(
+    level: impl AsRef<str>,
+    players: &[Entity],
+    ids: Vec<u32>,
+    seed: Option<u64>,
+    topology: BoardTopology,
+) -> (Vec<Tile>, Option<f32>) {
+    let mut fairest = None;
+    for attempt in 0..FAIR_ATTEMPTS {
+        let seed = seed.map(|seed| seed.wrapping_add(attempt));
+        let tiles = load_level(&level, players, ids.clone(), seed);
+        let fairness = match board_fairness(&tiles, topology, players) {
+            Some(fairness) => fairness,
+            None => return (tiles, None),
+        };
+        if fairness >= MIN_FAIRNESS {
+            return (tiles, Some(fairness));
+        }
+        debug!(
+            fairness,
+            attempt, "colored the board again, as it was unfair"
+        );
+        if fairest
+            .as_ref()
+            .map_or(true, |(_, best): &(Vec<Tile>, f32)| fairness > *best)
+        {
+            fairest = Some((tiles, fairness));
+        }
+    }
+    // None of the colorings were fair enough, so the least unfair one is used
+    fairest.map_or_else(
+        || (load_level(&level, players, ids, seed), None),
+        |(tiles, fairness)| (tiles, Some(fairness)),
+    )
+}
+
+pub fn parse_level(
+    level: impl AsRef<str>,
+    players: &[Entity],
+    ids: Vec<u32>,
+    seed: Option<u64>,
+) -> Result<Vec<Tile>, String> {
     //TODO: check the level is square
     let mut tiles = vec![];
+    // The same seed always colors the board the same way
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
     let level = level.as_ref().trim();
+    if level.is_empty() {
+        return Err("Level is empty".into());
+    }
+
     for (row, line) in level.lines().enumerate() {
         for (column, tile_desc) in line.split_whitespace().enumerate() {
             let row = row as i32;
             let column = column as i32;
+            // Colored tiles may be followed by how much they are worth
+            let (tile_desc, weight) = match tile_desc.strip_prefix('|') {
+                Some(weight) if !weight.is_empty() => {
+                    let weight = weight
+                        .parse()
+                        .ok()
+                        .filter(|weight| (1..=MAX_TILE_WEIGHT).contains(weight))
+                        .ok_or_else(|| {
+                            format!(
+                                "Invalid tile weight in level: {} (max {})",
+                                tile_desc, MAX_TILE_WEIGHT
+                            )
+                        })?;
+                    ("|", weight)
+                }
+                _ => (tile_desc, 1),
+            };
+            let bonus = match tile_desc {
+                "*" => Some(TileBonus::ExtraTurn),
+                "+" => Some(TileBonus::Points(BONUS_POINTS)),
+                "^" => Some(TileBonus::Hill),
+                _ => None,
+            };
+            let mut random_id = || {
+                ids.as_slice()
+                    .choose(&mut rng)
+                    .copied()
+                    .ok_or("Unable to make choice")
+            };
             let state = match tile_desc {
                 "-" => TileState::Empty,
-                "|" => TileState::Unowned(
-                    *ids.as_slice()
-                        .choose(&mut thread_rng())
-                        .expect("Unable to make choice"),
-                ),
+                "#" => TileState::Wall,
+                "|" | "*" | "+" | "^" => TileState::Unowned(random_id()?),
                 val => {
                     let player_num: usize = val
                         .parse()
-                        .expect(&format!("Unexpected value in level: {}", val));
+                        .map_err(|_| format!("Unexpected value in level: {}", val))?;
 
-                    if player_num == 0 || player_num - 1 >= players.len() {
-                        panic!(
+                    if player_num == 0 || player_num > MAX_PLAYERS {
+                        return Err(format!(
                             "Invalid player number in level: {} (max {})",
-                            val,
-                            players.len()
-                        );
+                            val, MAX_PLAYERS
+                        ));
+                    }
+
+                    // Starting positions without a player are just regular tiles
+                    match players.get(player_num - 1) {
+                        Some(player) => TileState::Owned(*player),
+                        None => TileState::Unowned(random_id()?),
                     }
-                    TileState::Owned(players[player_num - 1])
                 }
             };
-            tiles.push(Tile { row, column, state })
+            tiles.push(Tile {
+                row,
+                column,
+                state,
+                bonus,
+                weight,
+            })
         }
     }
-    tiles
+    Ok(tiles)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use assimilation::hex::Axial;
     use bevy::ecs::event::Events;
     use std::time::Duration;
 
@@ -301,7 +956,9 @@ mod test {
             .spawn()
             .insert(Player {
                 name: "Player".into(),
+                avatar: crate::avatar::Avatar::Person,
                 score: 0,
+                bonus: 0,
                 kind: PlayerKind::Human,
                 color: Color::CYAN,
             })
@@ -312,8 +969,13 @@ mod test {
             .spawn()
             .insert(Player {
                 name: "Bot".into(),
+                avatar: crate::avatar::Avatar::Robot,
                 score: 0,
-                kind: PlayerKind::Bot,
+                bonus: 0,
+                kind: PlayerKind::Bot(
+                    Timer::new(Duration::from_secs(0), false),
+                    Personality::Aggressive,
+                ),
                 color: Color::RED,
             })
             .id();
@@ -322,13 +984,29 @@ mod test {
             players: vec![player_id, bot_id],
             phase: GamePhase::Running,
             ids: BTreeMap::from([(0, Color::GREEN), (1, Color::YELLOW)]),
+            topology: BoardTopology::Hex,
+            last_selections: HashMap::new(),
+            rules: RulesConfig::default(),
+            moves: 0,
+            double_mover: None,
+            moved_this_turn: false,
+            pending_selections: HashMap::new(),
+            stalemate: false,
+            seed: None,
+            board_hash: BoardHash::default(),
+            positions: HashMap::new(),
+            unowned: BTreeMap::new(),
+            history: vec![],
+            blind: false,
         };
 
         app.add_event::<CaptureEvent>();
         app.add_event::<SelectEvent>();
         app.insert_resource(state.clone());
+        app.init_resource::<MoveCache>();
         app.add_system(update_scores);
-        app.add_system(perform_selection.before(update_scores));
+        app.add_system(perform_selection.before(update_move_cache));
+        app.add_system(update_move_cache.before(update_scores));
 
         (app, state)
     }
@@ -345,12 +1023,76 @@ mod test {
             desc,
             &state.players,
             state.ids.keys().cloned().collect(),
-            true,
+            None,
         );
 
         assert_eq!(tiles.len(), 14);
     }
 
+    #[test]
+    fn seeded_levels_repeat() {
+        let (_, state) = test_app_setup();
+
+        let ids: Vec<u32> = state.ids.keys().cloned().collect();
+        let colors = |seed| {
+            load_level(
+                "1 | | | | | | | | | | | | | | | | 2",
+                &state.players,
+                ids.clone(),
+                Some(seed),
+            )
+            .into_iter()
+            .map(|tile| match tile.state {
+                TileState::Unowned(id) => Some(id),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(colors(42), colors(42));
+        assert_ne!(colors(42), colors(43));
+    }
+
+    #[test]
+    fn unused_start_positions_become_neutral() {
+        let (_, state) = test_app_setup();
+
+        let tiles = load_level("1 3 | 4 2", &state.players, vec![0], None);
+        assert!(matches!(tiles[1].state, TileState::Unowned(0)));
+        assert!(matches!(tiles[3].state, TileState::Unowned(0)));
+        assert!(matches!(tiles[4].state, TileState::Owned(owner) if owner == state.players[1]));
+
+        assert!(parse_level("1 5", &state.players, vec![0], None).is_err());
+    }
+
+    #[test]
+    fn weighted_tiles_score_more() {
+        let (mut app, state) = test_app_setup();
+
+        let tiles = load_level("1 |3 | |2 2", &state.players, vec![0], None);
+        assert_eq!(
+            tiles.iter().map(|tile| tile.weight).collect::<Vec<_>>(),
+            vec![1, 3, 1, 2, 1]
+        );
+        for tile in tiles {
+            app.world.spawn().insert(tile);
+        }
+        for invalid in ["1 |0", "1 |x", "1 |10"] {
+            assert!(parse_level(invalid, &state.players, vec![0], None).is_err());
+        }
+
+        app.world
+            .resource_mut::<Events<SelectEvent>>()
+            .send(SelectEvent {
+                player: state.players[0],
+                id: 0,
+            });
+        app.update();
+
+        let player = app.world.get::<Player>(state.players[0]).unwrap();
+        assert_eq!(player.score, 7);
+    }
+
     #[test]
     fn do_selection() {
         let (mut app, state) = test_app_setup();
@@ -361,6 +1103,8 @@ mod test {
                     row,
                     column,
                     state: TileState::Unowned(0),
+                    bonus: None,
+                    weight: 1,
                 });
             }
         }
@@ -384,4 +1128,616 @@ mod test {
         // The play owns no tiles, so should have no score
         assert_eq!(player.score, 0);
     }
+
+    #[test]
+    fn square_topology_adjacency() {
+        let (_, state) = test_app_setup();
+
+        let desc = r#"
+1 - | |
+- | | |
+"#;
+
+        let count_captures = |topology| {
+            let mut tiles = load_level(desc, &state.players, vec![0], None);
+            let mut captured = 0;
+            for_each_selected_tile(
+                tiles.iter_mut().collect(),
+                topology,
+                0,
+                state.players[0],
+                |_| captured += 1,
+            );
+            captured
+        };
+
+        // The only route out of the corner is diagonal
+        assert_eq!(count_captures(BoardTopology::Square4), 0);
+        assert_eq!(count_captures(BoardTopology::Square8), 5);
+
+        // Each tile should only be reported once, even when it borders
+        // several owned tiles
+        let mut tiles = load_level(desc, &state.players, vec![0], None);
+        let mut seen = HashSet::new();
+        for_each_selected_tile(
+            tiles.iter_mut().collect(),
+            BoardTopology::Square8,
+            0,
+            state.players[0],
+            |tile| assert!(seen.insert((tile.row, tile.column))),
+        );
+    }
+
+    #[test]
+    fn walls_block_captures() {
+        let (_, state) = test_app_setup();
+
+        let desc = r#"
+1 # | |
+# # | |
+"#;
+        let mut tiles = load_level(desc, &state.players, vec![0], None);
+        assert!(matches!(tiles[1].state, TileState::Wall));
+
+        let mut captured = 0;
+        for_each_selected_tile(
+            tiles.iter_mut().collect(),
+            BoardTopology::Square8,
+            0,
+            state.players[0],
+            |_| captured += 1,
+        );
+        assert_eq!(captured, 0);
+    }
+
+    #[test]
+    fn bonus_tiles() {
+        let (mut app, state) = test_app_setup();
+
+        let desc = r#"
+1 * + |
+"#;
+        for tile in load_level(desc, &state.players, vec![0], None) {
+            app.world.spawn().insert(tile);
+        }
+
+        let mut events = app.world.resource_mut::<Events<SelectEvent>>();
+        events.send(SelectEvent {
+            player: state.players[0],
+            id: 0,
+        });
+
+        app.update();
+
+        // Capturing the '*' tile lets the same player go again
+        let new_state = app.world.resource::<GameState>();
+        assert_eq!(new_state.players[0], state.players[0]);
+
+        let player = app.world.get::<Player>(state.players[0]).unwrap();
+        assert_eq!(player.bonus, BONUS_POINTS);
+        assert_eq!(player.score, 4 + BONUS_POINTS);
+    }
+
+    #[test]
+    fn forbid_opponent_color() {
+        let (mut app, state) = test_app_setup();
+        app.world
+            .resource_mut::<GameState>()
+            .rules
+            .forbid_opponent_color = true;
+
+        let desc = r#"
+1 | | | | 2
+"#;
+        for tile in load_level(desc, &state.players, vec![0], None) {
+            app.world.spawn().insert(tile);
+        }
+
+        let mut events = app.world.resource_mut::<Events<SelectEvent>>();
+        events.send(SelectEvent {
+            player: state.players[0],
+            id: 0,
+        });
+        app.update();
+
+        let new_state = app.world.resource::<GameState>();
+        assert!(!new_state.is_selection_allowed(state.players[1], 0));
+        assert!(new_state.is_selection_allowed(state.players[1], 1));
+        assert!(new_state.is_selection_allowed(state.players[0], 0));
+    }
+
+    #[test]
+    fn forbid_repeat_color() {
+        let (mut app, state) = test_app_setup();
+        app.world
+            .resource_mut::<GameState>()
+            .rules
+            .forbid_repeat_color = true;
+
+        let desc = r#"
+1 | | | | 2
+"#;
+        for tile in load_level(desc, &state.players, vec![0], None) {
+            app.world.spawn().insert(tile);
+        }
+
+        let mut events = app.world.resource_mut::<Events<SelectEvent>>();
+        events.send(SelectEvent {
+            player: state.players[0],
+            id: 0,
+        });
+        app.update();
+
+        let new_state = app.world.resource::<GameState>();
+        assert!(!new_state.is_selection_allowed(state.players[0], 0));
+        assert!(new_state.is_selection_allowed(state.players[0], 1));
+        assert!(new_state.is_selection_allowed(state.players[1], 0));
+    }
+
+    #[test]
+    fn skip_player_without_moves() {
+        let (mut app, state) = test_app_setup();
+
+        // The first player is walled in, so only the second can move
+        let desc = r#"
+1 # | | 2
+"#;
+        for tile in load_level(desc, &state.players, vec![0], None) {
+            app.world.spawn().insert(tile);
+        }
+
+        app.update();
+        let new_state = app.world.resource::<GameState>();
+        assert_eq!(new_state.players[0], state.players[1]);
+        assert!(matches!(new_state.phase, GamePhase::Running));
+
+        let mut events = app.world.resource_mut::<Events<SelectEvent>>();
+        events.send(SelectEvent {
+            player: state.players[1],
+            id: 0,
+        });
+        app.update();
+
+        let new_state = app.world.resource::<GameState>();
+        match new_state.phase {
+            GamePhase::Over(winner) => assert_eq!(winner, state.players[1]),
+            _ => panic!("Game should be over"),
+        }
+    }
+
+    #[test]
+    fn draw_when_scores_tie() {
+        let (mut app, state) = test_app_setup();
+
+        let desc = r#"
+1 # 2
+"#;
+        for tile in load_level(desc, &state.players, vec![0], None) {
+            app.world.spawn().insert(tile);
+        }
+
+        app.update();
+        assert!(matches!(
+            app.world.resource::<GameState>().phase,
+            GamePhase::Draw
+        ));
+    }
+
+    #[test]
+    fn king_of_the_hill() {
+        let (mut app, state) = test_app_setup();
+        app.world.resource_mut::<GameState>().rules.objective =
+            Objective::KingOfTheHill { rounds: 1 };
+
+        let desc = r#"
+1 ^ | | | | 2
+"#;
+        let mut tiles = load_level(desc, &state.players, vec![0], None);
+        tiles[2].state = TileState::Unowned(1);
+        tiles[3].state = TileState::Unowned(1);
+        for tile in tiles {
+            app.world.spawn().insert(tile);
+        }
+
+        app.update();
+        app.world
+            .resource_mut::<Events<SelectEvent>>()
+            .send(SelectEvent {
+                id: 0,
+                player: state.players[0],
+            });
+        app.update();
+
+        // The hill is kept after capture, and scores for its holder
+        let player = app.world.get::<Player>(state.players[0]).unwrap();
+        assert_eq!(player.bonus, HILL_POINTS);
+        assert!(app
+            .world
+            .query::<&Tile>()
+            .iter(&app.world)
+            .any(|tile| tile.bonus == Some(TileBonus::Hill)));
+
+        app.world
+            .resource_mut::<Events<SelectEvent>>()
+            .send(SelectEvent {
+                id: 0,
+                player: state.players[1],
+            });
+        app.update();
+
+        // Both players have moved once, ending the only round
+        assert!(matches!(
+            app.world.resource::<GameState>().phase,
+            GamePhase::Over(winner) if winner == state.players[0]
+        ));
+    }
+
+    #[test]
+    fn turn_limit() {
+        let (mut app, state) = test_app_setup();
+        app.world.resource_mut::<GameState>().rules.turn_limit = Some(1);
+
+        let desc = r#"
+1 | | | | | | 2
+"#;
+        let mut tiles = load_level(desc, &state.players, vec![0], None);
+        for index in [1, 2, 3, 5] {
+            tiles[index].state = TileState::Unowned(1);
+        }
+        for tile in tiles {
+            app.world.spawn().insert(tile);
+        }
+
+        app.update();
+        for (player, id) in [(state.players[0], 1), (state.players[1], 0)] {
+            assert!(matches!(
+                app.world.resource::<GameState>().phase,
+                GamePhase::Running
+            ));
+            app.world
+                .resource_mut::<Events<SelectEvent>>()
+                .send(SelectEvent { id, player });
+            app.update();
+        }
+
+        // Moves remain, but the first player ends with more tiles
+        assert!(matches!(
+            app.world.resource::<GameState>().phase,
+            GamePhase::Over(winner) if winner == state.players[0]
+        ));
+    }
+
+    #[test]
+    fn grow_start_region_by_rings() {
+        let player = Entity::from_raw(0);
+        let mut tiles = load_level("1 | | | # |", &[player], vec![0], None);
+        grow_start_region(&mut tiles, BoardTopology::Square4, player, 2);
+
+        // Walls stop the growth, and tiles past the rings stay unowned
+        let owned = tiles
+            .iter()
+            .filter(|tile| matches!(tile.state, TileState::Owned(owner) if owner == player))
+            .count();
+        assert_eq!(owned, 3);
+        assert!(matches!(tiles[3].state, TileState::Unowned(_)));
+    }
+
+    #[test]
+    fn mirrored_boards_are_symmetric() {
+        let players = vec![Entity::from_raw(0), Entity::from_raw(1)];
+        let mut tiles = load_level(crate::levels::HEXAGON, &players, (0..6).collect(), Some(3));
+        mirror_colors(&mut tiles, BoardTopology::Hex);
+
+        let states = tiles
+            .iter()
+            .map(|tile| ((tile.row, tile.column), &tile.state))
+            .collect::<HashMap<_, _>>();
+        for tile in tiles.iter() {
+            if let TileState::Unowned(id) = tile.state {
+                let center = Axial::from_offset(6, 6);
+                let counterpart = Axial::from_offset(tile.row, tile.column)
+                    .rotate_half(center)
+                    .to_offset();
+                assert!(matches!(
+                    states.get(&counterpart),
+                    Some(TileState::Unowned(other)) if *other == id
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn double_move() {
+        let (mut app, state) = test_app_setup();
+        app.world.resource_mut::<GameState>().double_mover = Some(state.players[0]);
+
+        let desc = r#"
+1 | | | | | | 2
+"#;
+        let mut tiles = load_level(desc, &state.players, vec![0], None);
+        for index in [1, 3, 5] {
+            tiles[index].state = TileState::Unowned(1);
+        }
+        for tile in tiles {
+            app.world.spawn().insert(tile);
+        }
+
+        app.update();
+        for id in [1, 0] {
+            assert_eq!(
+                app.world.resource::<GameState>().players[0],
+                state.players[0]
+            );
+            app.world
+                .resource_mut::<Events<SelectEvent>>()
+                .send(SelectEvent {
+                    id,
+                    player: state.players[0],
+                });
+            app.update();
+        }
+
+        // Both moves count as a single turn
+        let game = app.world.resource::<GameState>();
+        assert_eq!(game.players[0], state.players[1]);
+        assert_eq!(game.moves, 1);
+    }
+
+    #[test]
+    fn pie_rule_swaps_sides() {
+        let (mut app, state) = test_app_setup();
+        app.world.resource_mut::<GameState>().rules.pie_rule = true;
+
+        let desc = r#"
+1 | | | | | 2
+"#;
+        let mut tiles = load_level(desc, &state.players, vec![0], None);
+        tiles[4].state = TileState::Unowned(1);
+        for tile in tiles {
+            app.world.spawn().insert(tile);
+        }
+
+        app.update();
+        app.world
+            .resource_mut::<Events<SelectEvent>>()
+            .send(SelectEvent {
+                id: 0,
+                player: state.players[0],
+            });
+        app.update();
+
+        // The second player is offered the first player's side
+        let mut game = app.world.resource::<GameState>().clone();
+        assert_eq!(game.phase, GamePhase::Swap);
+        assert_eq!(game.players[0], state.players[1]);
+
+        let mut tiles = app.world.query::<&mut Tile>();
+        assert_eq!(
+            swap_sides(&mut game, tiles.iter_mut(&mut app.world)),
+            Some((state.players[0], state.players[1]))
+        );
+        let owners = tiles
+            .iter(&app.world)
+            .filter_map(|tile| match tile.state {
+                TileState::Owned(owner) => Some(owner),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let owned = |player| owners.iter().filter(|owner| **owner == player).count();
+        assert_eq!(owned(state.players[1]), 4);
+        assert_eq!(owned(state.players[0]), 1);
+        assert_eq!(game.last_selections.get(&state.players[1]), Some(&0));
+        assert_eq!(game.players[0], state.players[0]);
+        assert_eq!(game.moves, 2);
+    }
+
+    #[test]
+    fn simultaneous_moves() {
+        let (mut app, state) = test_app_setup();
+        app.world.resource_mut::<GameState>().rules.simultaneous = true;
+
+        let desc = r#"
+1 | | | 2
+"#;
+        let mut tiles = load_level(desc, &state.players, vec![0], None);
+        tiles[1].state = TileState::Unowned(1);
+        tiles[3].state = TileState::Unowned(1);
+        for tile in tiles {
+            app.world.spawn().insert(tile);
+        }
+        app.update();
+
+        let unowned = |app: &mut App| {
+            app.world
+                .query::<&Tile>()
+                .iter(&app.world)
+                .filter(|tile| matches!(tile.state, TileState::Unowned(_)))
+                .count()
+        };
+
+        let pick = |app: &mut App, player, id| {
+            app.world
+                .resource_mut::<Events<SelectEvent>>()
+                .send(SelectEvent { id, player });
+            app.update();
+        };
+
+        // The first pick is hidden until the second is made
+        pick(&mut app, state.players[0], 1);
+        assert_eq!(unowned(&mut app), 3);
+        pick(&mut app, state.players[1], 1);
+        assert_eq!(unowned(&mut app), 1);
+
+        // Both players reach the last tile with the same color, so it stays
+        // unowned and the game can't progress any further
+        pick(&mut app, state.players[0], 0);
+        pick(&mut app, state.players[1], 0);
+        assert_eq!(unowned(&mut app), 1);
+        assert!(app.world.resource::<GameState>().phase.is_finished());
+    }
+
+    #[test]
+    fn repeated_positions_end_the_game() {
+        let (mut app, state) = test_app_setup();
+
+        let desc = r#"
+1 | | | 2
+"#;
+        let mut tiles = load_level(desc, &state.players, vec![0], None);
+        tiles[2].state = TileState::Unowned(1);
+        app.world.resource_mut::<GameState>().track_board(&tiles);
+        for tile in tiles {
+            app.world.spawn().insert(tile);
+        }
+        app.update();
+
+        // Picking a color nobody borders captures nothing, so the players
+        // keep coming back to the same position
+        for player in state.players.iter().cycle().take(5) {
+            app.world
+                .resource_mut::<Events<SelectEvent>>()
+                .send(SelectEvent {
+                    id: 1,
+                    player: *player,
+                });
+            app.update();
+            assert!(!app.world.resource::<GameState>().phase.is_finished());
+        }
+
+        app.world
+            .resource_mut::<Events<SelectEvent>>()
+            .send(SelectEvent {
+                id: 1,
+                player: state.players[1],
+            });
+        app.update();
+        assert!(app.world.resource::<GameState>().phase.is_finished());
+    }
+
+    #[test]
+    fn unowned_counts_follow_captures() {
+        let (mut app, state) = test_app_setup();
+
+        let desc = r#"
+1 | | | | 2
+"#;
+        let mut tiles = load_level(desc, &state.players, vec![0], None);
+        tiles[3].state = TileState::Unowned(1);
+        app.world.resource_mut::<GameState>().track_board(&tiles);
+        for tile in tiles {
+            app.world.spawn().insert(tile);
+        }
+        app.update();
+        assert_eq!(
+            app.world.resource::<GameState>().unowned,
+            BTreeMap::from([(0, 3), (1, 1)])
+        );
+
+        app.world
+            .resource_mut::<Events<SelectEvent>>()
+            .send(SelectEvent {
+                id: 0,
+                player: state.players[0],
+            });
+        app.update();
+        assert_eq!(
+            app.world.resource::<GameState>().unowned,
+            BTreeMap::from([(0, 1), (1, 1)])
+        );
+    }
+
+    #[test]
+    fn exhausted_colors_are_rejected() {
+        let (mut app, state) = test_app_setup();
+
+        let desc = r#"
+1 | | 2
+"#;
+        let mut tiles = load_level(desc, &state.players, vec![0], None);
+        tiles[2].state = TileState::Unowned(1);
+        app.world.resource_mut::<GameState>().track_board(&tiles);
+        for tile in tiles {
+            app.world.spawn().insert(tile);
+        }
+        app.update();
+
+        for player in state.players.iter() {
+            app.world
+                .resource_mut::<Events<SelectEvent>>()
+                .send(SelectEvent {
+                    id: 0,
+                    player: *player,
+                });
+            app.update();
+        }
+
+        // The first pick took the last tile of its color, so the second is
+        // turned down
+        let game = app.world.resource::<GameState>();
+        assert!(!game.is_selection_allowed(state.players[1], 0));
+        assert!(game.is_selection_allowed(state.players[1], 1));
+        assert_eq!(game.players[0], state.players[1]);
+        assert_eq!(game.moves, 1);
+    }
+
+    #[test]
+    fn chain_scoring() {
+        let (mut app, state) = test_app_setup();
+        app.world.resource_mut::<GameState>().rules.chain_scoring = true;
+
+        let desc = r#"
+1 | | | | | | 2
+"#;
+        for tile in load_level(desc, &state.players, vec![0], None) {
+            app.world.spawn().insert(tile);
+        }
+
+        app.update();
+        app.world
+            .resource_mut::<Events<SelectEvent>>()
+            .send(SelectEvent {
+                id: 0,
+                player: state.players[0],
+            });
+        app.update();
+
+        // Six tiles in one move is a single full chain
+        let player = app.world.get::<Player>(state.players[0]).unwrap();
+        assert_eq!(player.bonus, 1);
+        assert_eq!(player.score, 8);
+    }
+    #[test]
+    fn rules_survive_serialization() {
+        let rules = RulesConfig {
+            forbid_repeat_color: true,
+            objective: Objective::KingOfTheHill { rounds: 12 },
+            turn_limit: Some(20),
+            chain_scoring: true,
+            ..default()
+        };
+        let contents = ron::to_string(&rules).unwrap();
+        assert_eq!(ron::from_str::<RulesConfig>(&contents).unwrap(), rules);
+
+        let state = GameState { rules, ..default() };
+        assert_eq!(state.last_round(), Some(12));
+        assert_eq!(state.chain_bonus(12), 2);
+    }
+
+    #[test]
+    fn rulesets_are_recognized() {
+        assert_eq!(
+            Ruleset::matching(&RulesConfig::default()),
+            Some(Ruleset::Classic)
+        );
+        for ruleset in Ruleset::ALL {
+            assert_eq!(Ruleset::matching(&ruleset.rules()), Some(ruleset));
+        }
+
+        // Tweaking a preset makes for a custom set of rules
+        let rules = RulesConfig {
+            pie_rule: true,
+            ..Ruleset::Blitz.rules()
+        };
+        assert_eq!(Ruleset::matching(&rules), None);
+    }
 }
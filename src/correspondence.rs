@@ -0,0 +1,570 @@
+use crate::http;
+use crate::menu::{self, AppState};
+use crate::{avatar, core, game, levels, palette, stats, storage, GameConfigState, GameStartEvent};
+use assimilation::rooms::{MoveSubmission, RoomMove, RoomUpdate, CODE_ALPHABET};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+const CORRESPONDENCE_KEY: &str = "correspondence";
+const DEFAULT_SERVER: &str = "http://localhost:3030";
+// How often the server is asked whether anything has changed
+const POLL_SECONDS: f64 = 5.0;
+const ROOM_LENGTH: usize = 8;
+
+// One side of a correspondence game
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Correspondent {
+    pub name: String,
+    pub color: Color,
+}
+
+// How the host set the game up, kept on the server for the guest and for
+// either side coming back to the game later
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Setup {
+    pub level: String,
+    pub num_ids: u32,
+    pub seed: u64,
+    pub host: Correspondent,
+}
+
+// Which game this side is playing, and on which seat
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Seat {
+    pub room: String,
+    pub host: bool,
+    // What the server handed out for the seat, which moves are sent with
+    #[serde(default)]
+    pub secret: String,
+}
+
+// The server last used and the game last played on it, remembered between
+// sessions so the game can be picked back up
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CorrespondenceConfig {
+    pub server: String,
+    pub last_seat: Option<Seat>,
+}
+
+impl Default for CorrespondenceConfig {
+    fn default() -> Self {
+        Self {
+            server: DEFAULT_SERVER.into(),
+            last_seat: None,
+        }
+    }
+}
+
+impl CorrespondenceConfig {
+    pub fn load() -> Self {
+        storage::load(CORRESPONDENCE_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(CORRESPONDENCE_KEY, self)
+    }
+}
+
+// What a request to the server was for
+enum Pending {
+    Create,
+    Join,
+    Fetch,
+    Move(usize),
+}
+
+// A game played through the server, one move at a time and at whatever pace
+// suits the two players. The whole game is kept on the server, so it's started
+// again from its setup and every move replayed whenever a side comes back
+#[derive(Default)]
+pub struct Correspondence {
+    pub seat: Option<Seat>,
+    setup: Option<Setup>,
+    guest: Option<Correspondent>,
+    pub in_game: bool,
+    // Moves the server has which haven't been made here yet, with their turns
+    incoming: VecDeque<(usize, RoomMove)>,
+    // How many moves the server is known to have
+    known: usize,
+    // How much of the game's history has been looked at for moves to send
+    sent: usize,
+    request: Option<(Pending, http::Request)>,
+    last_poll: Option<f64>,
+    // Nothing more is sent until then after a request failed
+    retry_at: f64,
+    // Set once the game can't go on, with the reason why
+    error: Option<String>,
+    room_input: String,
+}
+
+impl Correspondence {
+    fn url(&self, config: &CorrespondenceConfig, path: &str) -> String {
+        let room = self.seat.as_ref().map_or("", |seat| seat.room.as_str());
+        format!(
+            "{}/rooms/{}{}",
+            config.server.trim_end_matches('/'),
+            room,
+            path
+        )
+    }
+
+    fn send(
+        &mut self,
+        config: &CorrespondenceConfig,
+        pending: Pending,
+        method: &str,
+        path: &str,
+        body: Option<String>,
+    ) {
+        match http::Request::send(method, &self.url(config, path), body) {
+            Ok(request) => self.request = Some((pending, request)),
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    // Queues the moves the server sent which haven't been made here, checking
+    // the ones which have were made on the same board
+    fn receive_moves(&mut self, update: &RoomUpdate, history: &[core::Move]) -> Result<(), String> {
+        for (turn, made) in (update.first..).zip(update.moves.iter()) {
+            match history.get(turn) {
+                Some(local) if local.id != made.id || local.board_hash != made.board_hash => {
+                    return Err(format!("The game no longer matches at move {}", turn + 1));
+                }
+                Some(_) => (),
+                None if turn >= self.known => self.incoming.push_back((turn, *made)),
+                None => (),
+            }
+        }
+        self.known = self.known.max(update.first + update.moves.len());
+        Ok(())
+    }
+}
+
+pub struct CorrespondencePlugin;
+
+impl Plugin for CorrespondencePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CorrespondenceConfig::load())
+            .init_resource::<Correspondence>()
+            .add_system_set(
+                SystemSet::on_update(AppState::Correspondence).with_system(show_correspondence),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::MainMenu).with_system(leave_game))
+            .add_system(update_correspondence.before(game::game_start))
+            .add_system(show_correspondence_problems)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::GameStep)
+                    .with_system(
+                        apply_moves
+                            .after(game::game_start)
+                            .before(core::perform_selection),
+                    ),
+            );
+    }
+}
+
+fn local_correspondent(config: &GameConfigState) -> Correspondent {
+    Correspondent {
+        name: config.names.player.clone(),
+        color: config.player_color,
+    }
+}
+
+fn room_id() -> String {
+    (0..ROOM_LENGTH)
+        .map(|_| CODE_ALPHABET[rand::random::<usize>() % CODE_ALPHABET.len()] as char)
+        .collect()
+}
+
+// The host always moves first, with both sides listing the players in the
+// same order so every move is made by the same player on each. The setup comes
+// from the server, so it's checked before anything is built from it
+fn start_event(
+    setup: &Setup,
+    guest: &Correspondent,
+    is_host: bool,
+) -> Result<GameStartEvent, String> {
+    let level = levels::find(&setup.level)
+        .ok_or_else(|| format!("Unknown level {}", setup.level))?
+        .clone();
+    if !(2..=palette::MAX_IDS).contains(&setup.num_ids) {
+        return Err(format!("Invalid number of colors {}", setup.num_ids));
+    }
+    let player = |correspondent: &Correspondent, local: bool| core::Player {
+        color: correspondent.color,
+        name: correspondent.name.clone(),
+        avatar: avatar::Avatar::Person,
+        kind: if local {
+            core::PlayerKind::Human
+        } else {
+            core::PlayerKind::Remote
+        },
+        score: 0,
+        bonus: 0,
+    };
+
+    Ok(GameStartEvent {
+        players: vec![player(&setup.host, is_host), player(guest, !is_host)],
+        ids: palette::generate(setup.num_ids, &[setup.host.color, guest.color])
+            .into_iter()
+            .enumerate()
+            .map(|(id, color)| (id as u32, color))
+            .collect(),
+        level,
+        seed: Some(setup.seed),
+        colors: None,
+        daily: None,
+        rules: core::RulesConfig::default(),
+        handicap: core::Handicap::None,
+        handicapped_player: 0,
+        mirrored: false,
+        virus: false,
+        tutorial: false,
+        training: false,
+        blind: false,
+        par: None,
+    })
+}
+
+pub fn show_correspondence(
+    mut config: ResMut<GameConfigState>,
+    player_stats: Res<stats::Stats>,
+    mut correspondence_config: ResMut<CorrespondenceConfig>,
+    mut correspondence: ResMut<Correspondence>,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    let correspondence = &mut *correspondence;
+    menu::menu_screen(
+        &mut egui_ctx,
+        &mut app_state,
+        "Play by Correspondence",
+        |ui| {
+            if let Some(seat) = &correspondence.seat {
+                let room = seat.room.clone();
+                ui.horizontal(|ui| {
+                    ui.label(format!("Room: {}", room));
+                    if ui.button("copy").clicked() {
+                        ui.output().copied_text = room;
+                    }
+                });
+                match (&correspondence.setup, &correspondence.guest) {
+                    (Some(_), None) => ui.label("Waiting for someone to join..."),
+                    _ => ui.label("Fetching the game..."),
+                };
+                if let Some(error) = &correspondence.error {
+                    ui.colored_label(egui::Color32::LIGHT_RED, error);
+                }
+                if ui.button("leave").clicked() {
+                    *correspondence = Correspondence::default();
+                }
+                return;
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Server");
+                ui.text_edit_singleline(&mut correspondence_config.server);
+            });
+            ui.separator();
+            menu::board_settings(ui, &mut config, &player_stats.completed_levels, None);
+            if ui.button("start a game").clicked() {
+                let setup = Setup {
+                    level: config.level_name.into(),
+                    num_ids: config.num_ids,
+                    seed: rand::random(),
+                    host: local_correspondent(&config),
+                };
+                correspondence.seat = Some(Seat {
+                    room: room_id(),
+                    host: true,
+                    secret: String::new(),
+                });
+                correspondence.setup = Some(setup.clone());
+                let body = ron::to_string(&setup).ok();
+                correspondence.send(&correspondence_config, Pending::Create, "PUT", "", body);
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Room");
+                ui.text_edit_singleline(&mut correspondence.room_input);
+                if ui.button("join").clicked() {
+                    correspondence.seat = Some(Seat {
+                        room: correspondence.room_input.trim().to_uppercase(),
+                        host: false,
+                        secret: String::new(),
+                    });
+                    let body = ron::to_string(&local_correspondent(&config)).ok();
+                    correspondence.send(
+                        &correspondence_config,
+                        Pending::Join,
+                        "POST",
+                        "/join",
+                        body,
+                    );
+                }
+            });
+            if let Some(seat) = correspondence_config.last_seat.clone() {
+                if ui
+                    .button("continue the last game")
+                    .on_hover_text(format!("Room {}", seat.room))
+                    .clicked()
+                {
+                    correspondence.seat = Some(seat);
+                }
+            }
+            if let Some(error) = &correspondence.error {
+                ui.colored_label(egui::Color32::LIGHT_RED, error);
+            }
+        },
+    );
+}
+
+fn leave_game(mut correspondence: ResMut<Correspondence>) {
+    *correspondence = Correspondence::default();
+}
+
+// Talks to the server, one request at a time: sending the moves made here
+// first, and otherwise checking now and then for the other side's
+#[allow(clippy::too_many_arguments)]
+fn update_correspondence(
+    time: Res<Time>,
+    mut config: ResMut<CorrespondenceConfig>,
+    mut correspondence: ResMut<Correspondence>,
+    state: Res<core::GameState>,
+    players: Query<&core::Player>,
+    mut app_state: ResMut<State<AppState>>,
+    mut game_start: EventWriter<GameStartEvent>,
+) {
+    let correspondence = &mut *correspondence;
+    let mut seat = match &correspondence.seat {
+        Some(seat) if correspondence.error.is_none() => seat.clone(),
+        _ => return,
+    };
+    let now = time.seconds_since_startup();
+
+    if let Some((_, request)) = &correspondence.request {
+        let response = match request.poll() {
+            Some(response) => response,
+            None => return,
+        };
+        let pending = match correspondence.request.take() {
+            Some((pending, _)) => pending,
+            None => return,
+        };
+        let (response, pending) = match (response, pending) {
+            (Ok(response), pending) => (response, pending),
+            (Err(e), Pending::Create | Pending::Join) => {
+                correspondence.error = Some(e);
+                return;
+            }
+            // The server may well be back a little later
+            (Err(e), pending) => {
+                if let Pending::Move(turn) = pending {
+                    correspondence.sent = turn;
+                }
+                correspondence.retry_at = now + POLL_SECONDS;
+                return warn!("Correspondence request failed: {}", e);
+            }
+        };
+        if !response.is_success() {
+            correspondence.error = Some(match response.status {
+                404 => "The room doesn't exist".into(),
+                _ => response.body,
+            });
+            return;
+        }
+
+        match pending {
+            Pending::Create | Pending::Join => {
+                seat.secret = response.body.trim().to_string();
+                correspondence.seat = Some(seat.clone());
+                config.last_seat = Some(seat);
+                config.save();
+            }
+            Pending::Move(turn) => correspondence.known = correspondence.known.max(turn + 1),
+            Pending::Fetch => {
+                let update = match ron::from_str::<RoomUpdate>(&response.body) {
+                    Ok(update) => update,
+                    Err(e) => return warn!("Malformed room from the server: {}", e),
+                };
+                correspondence.setup = ron::from_str(&update.setup).ok();
+                correspondence.guest = update
+                    .guest
+                    .as_ref()
+                    .and_then(|guest| ron::from_str(guest).ok());
+
+                let starting = !correspondence.in_game;
+                if starting {
+                    let event = correspondence
+                        .setup
+                        .as_ref()
+                        .zip(correspondence.guest.as_ref())
+                        .map(|(setup, guest)| start_event(setup, guest, seat.host));
+                    match event {
+                        Some(Ok(event)) => {
+                            info!("Correspondence game {} started", seat.room);
+                            correspondence.in_game = true;
+                            correspondence.sent = 0;
+                            correspondence.known = 0;
+                            correspondence.incoming.clear();
+                            game_start.send(event);
+                            app_state.set(AppState::InGame).ok();
+                        }
+                        Some(Err(e)) => {
+                            correspondence.error = Some(e);
+                            return;
+                        }
+                        None => return,
+                    }
+                }
+                // Everything on the server is replayed once the game starts
+                let history = if starting { &[] } else { &state.history[..] };
+                if let Err(e) = correspondence.receive_moves(&update, history) {
+                    correspondence.error = Some(e);
+                }
+            }
+        }
+        return;
+    }
+    if now < correspondence.retry_at {
+        return;
+    }
+
+    // Moves made here go to the server as soon as they're made. Ones which
+    // came from the server are already there
+    if correspondence.in_game && correspondence.incoming.is_empty() {
+        let from = correspondence.sent.max(correspondence.known);
+        if let Some((turn, made)) = state.local_moves(&players, from).into_iter().next() {
+            let submission = MoveSubmission {
+                secret: seat.secret.clone(),
+                turn,
+                made: RoomMove {
+                    id: made.id,
+                    board_hash: made.board_hash,
+                },
+            };
+            let body = ron::to_string(&submission).ok();
+            correspondence.sent = turn + 1;
+            return correspondence.send(&config, Pending::Move(turn), "POST", "/moves", body);
+        }
+        correspondence.sent = state.history.len();
+    }
+
+    if correspondence.in_game && state.phase.is_finished() {
+        if config.last_seat.as_ref() == Some(&seat) {
+            config.last_seat = None;
+            config.save();
+        }
+        return;
+    }
+    if correspondence
+        .last_poll
+        .map_or(true, |last| now - last >= POLL_SECONDS)
+    {
+        correspondence.last_poll = Some(now);
+        let since = format!("?since={}", correspondence.known);
+        correspondence.send(&config, Pending::Fetch, "GET", &since, None);
+    }
+}
+
+// Moves from the server are made on the game's step, one at a time, whoever
+// they're for, so a game being picked back up is replayed in full
+fn apply_moves(
+    mut correspondence: ResMut<Correspondence>,
+    state: Res<core::GameState>,
+    mut selections: EventWriter<core::SelectEvent>,
+) {
+    if !correspondence.in_game || correspondence.error.is_some() {
+        return;
+    }
+    let (turn, made) = match correspondence.incoming.front() {
+        Some(next) => *next,
+        None => return,
+    };
+    // Waits for a move made here which the server hasn't heard of yet
+    if turn > state.history.len() {
+        return;
+    }
+    correspondence.incoming.pop_front();
+    if turn < state.history.len() || made.board_hash != state.board_hash.value {
+        correspondence.error = Some(format!("The game no longer matches at move {}", turn + 1));
+        return;
+    }
+    if let Some(player) = state.players.first() {
+        selections.send(core::SelectEvent {
+            id: made.id,
+            player: *player,
+        });
+    }
+}
+
+// Shown when the game can't go on
+fn show_correspondence_problems(
+    mut correspondence: ResMut<Correspondence>,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    let error = match &correspondence.error {
+        Some(error) if correspondence.in_game => error.clone(),
+        _ => return,
+    };
+    let mut leave = false;
+    egui::Window::new("Game Stopped")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.label(error);
+            leave = ui.button("leave").clicked();
+        });
+    if leave {
+        correspondence.in_game = false;
+        app_state.set(AppState::MainMenu).ok();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn moves_from_the_server_are_queued_once() {
+        let made = |id, board_hash| RoomMove { id, board_hash };
+        let local = |id, board_hash| core::Move {
+            player: Entity::from_raw(0),
+            id,
+            board_hash,
+        };
+        let update = |first, moves| RoomUpdate {
+            first,
+            moves,
+            ..Default::default()
+        };
+        let mut correspondence = Correspondence::default();
+        let history = [local(1, 10)];
+
+        let moves = vec![made(1, 10), made(2, 20), made(3, 30)];
+        assert!(correspondence
+            .receive_moves(&update(0, moves), &history)
+            .is_ok());
+        assert_eq!(
+            correspondence.incoming,
+            VecDeque::from([(1, made(2, 20)), (2, made(3, 30))])
+        );
+        assert_eq!(correspondence.known, 3);
+
+        // Seen before, so not queued again
+        assert!(correspondence
+            .receive_moves(&update(2, vec![made(3, 30)]), &history)
+            .is_ok());
+        assert_eq!(correspondence.incoming.len(), 2);
+
+        assert!(correspondence
+            .receive_moves(&update(0, vec![made(1, 11)]), &history)
+            .is_err());
+    }
+}
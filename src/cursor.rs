@@ -0,0 +1,200 @@
+use crate::bindings::{Action, ActionInput};
+use crate::{core, BoardCamera, TILE_RADIUS};
+use bevy::prelude::*;
+use bevy_egui::EguiContext;
+use bevy_prototype_lyon::prelude::*;
+
+// How fast the gamepad moves the cursor, in world units per second
+const GAMEPAD_CURSOR_SPEED: f32 = 300.0;
+// Stick movement smaller than this is ignored
+const GAMEPAD_DEAD_ZONE: f32 = 0.2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CursorSource {
+    Mouse,
+    Touch,
+    Gamepad,
+    // Moved by the tutorial, to show the player where to click
+    Tutorial,
+}
+
+impl Default for CursorSource {
+    fn default() -> Self {
+        CursorSource::Mouse
+    }
+}
+
+// Where the player is pointing on the board, whatever they're pointing with.
+// Hovering and selecting tiles only look at this, never at the devices
+#[derive(Default)]
+pub struct Cursor {
+    // In world coordinates
+    pub position: Option<Vec2>,
+    pub source: CursorSource,
+    // Set for the frame the player clicked, tapped or pressed select
+    pub pressed: bool,
+}
+
+impl Cursor {
+    pub fn move_to(&mut self, position: Vec2, source: CursorSource) {
+        self.position = Some(position);
+        self.source = source;
+    }
+}
+
+// Drawn where the cursor is when no pointer of the OS's own is showing it
+#[derive(Component)]
+struct CursorMarker;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+pub struct UpdateCursor;
+
+pub struct CursorPlugin;
+
+impl Plugin for CursorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Cursor>()
+            .add_startup_system(spawn_cursor_marker)
+            .add_system_set(
+                SystemSet::new()
+                    .label(UpdateCursor)
+                    .with_system(mouse_cursor)
+                    .with_system(touch_cursor.after(mouse_cursor))
+                    .with_system(gamepad_cursor.after(touch_cursor)),
+            )
+            .add_system(move_cursor_marker.after(UpdateCursor));
+    }
+}
+
+// Converts a window position to world coordinates through the board camera,
+// so any zoom, pan or window resize is accounted for
+fn window_to_world(
+    window: &Window,
+    cameras: &Query<(&Camera, &GlobalTransform), With<BoardCamera>>,
+    position: Vec2,
+) -> Option<Vec2> {
+    let (camera, camera_transform) = cameras.get_single().ok()?;
+    let window_size = Vec2::new(window.width(), window.height());
+    if window_size.x <= 0.0 || window_size.y <= 0.0 {
+        return None;
+    }
+
+    let ndc = (position / window_size) * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix.inverse();
+    Some(ndc_to_world.project_point3(ndc.extend(-1.0)).truncate())
+}
+
+fn mouse_cursor(
+    mut cursor: ResMut<Cursor>,
+    mut cursor_events: EventReader<CursorMoved>,
+    input: ActionInput,
+    windows: Res<Windows>,
+    cameras: Query<(&Camera, &GlobalTransform), With<BoardCamera>>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    let window = windows.primary();
+    // Only written when it changes, so the cursor only counts as changed when
+    // it's actually been moved or pressed
+    if cursor.pressed {
+        cursor.pressed = false;
+    }
+
+    if let Some(event) = cursor_events.iter().last() {
+        if let Some(position) = window_to_world(window, &cameras, event.position) {
+            cursor.move_to(position, CursorSource::Mouse);
+        }
+    }
+
+    // Clicks on windows drawn over the board, like the minimap, are for them
+    if input.mouse_just_pressed(Action::Select) && !egui_ctx.ctx_mut().is_pointer_over_area() {
+        let position = window
+            .cursor_position()
+            .and_then(|position| window_to_world(window, &cameras, position));
+        if let Some(position) = position {
+            cursor.move_to(position, CursorSource::Mouse);
+            cursor.pressed = true;
+        }
+    } else if input.key_just_pressed(Action::Select) && cursor.position.is_some() {
+        cursor.pressed = true;
+    }
+}
+
+// A tap both moves the cursor and selects what's under it
+fn touch_cursor(
+    mut cursor: ResMut<Cursor>,
+    touches: Res<Touches>,
+    windows: Res<Windows>,
+    cameras: Query<(&Camera, &GlobalTransform), With<BoardCamera>>,
+) {
+    let window = windows.primary();
+    for touch in touches.iter_just_released() {
+        // Touches are measured from the top of the window, unlike the mouse
+        let position = Vec2::new(touch.position().x, window.height() - touch.position().y);
+        if let Some(position) = window_to_world(window, &cameras, position) {
+            cursor.move_to(position, CursorSource::Touch);
+            cursor.pressed = true;
+        }
+    }
+}
+
+// The left stick moves the cursor, and the bottom face button selects
+fn gamepad_cursor(
+    mut cursor: ResMut<Cursor>,
+    time: Res<Time>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    input: ActionInput,
+) {
+    for gamepad in gamepads.iter() {
+        let axis = |axis| {
+            axes.get(GamepadAxis(*gamepad, axis))
+                .filter(|value| value.abs() > GAMEPAD_DEAD_ZONE)
+                .unwrap_or(0.0)
+        };
+        let stick = Vec2::new(
+            axis(GamepadAxisType::LeftStickX),
+            axis(GamepadAxisType::LeftStickY),
+        );
+        if stick != Vec2::ZERO {
+            let position = cursor.position.unwrap_or(Vec2::ZERO)
+                + stick * GAMEPAD_CURSOR_SPEED * time.delta_seconds();
+            cursor.move_to(position, CursorSource::Gamepad);
+        }
+
+        if input.gamepad_just_pressed(*gamepad, Action::Select) {
+            let position = cursor.position.unwrap_or(Vec2::ZERO);
+            cursor.move_to(position, CursorSource::Gamepad);
+            cursor.pressed = true;
+        }
+    }
+}
+
+fn spawn_cursor_marker(mut commands: Commands) {
+    let ring = shapes::Circle {
+        radius: TILE_RADIUS / 2.0,
+        ..shapes::Circle::default()
+    };
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &ring,
+            DrawMode::Stroke(StrokeMode::new(Color::WHITE, 2.0)),
+            Transform::from_xyz(0.0, 0.0, 5.0),
+        ))
+        .insert(Visibility { is_visible: false })
+        .insert(CursorMarker);
+}
+
+// Only the gamepad needs the marker, every other source is already visible
+fn move_cursor_marker(
+    cursor: Res<Cursor>,
+    state: Res<core::GameState>,
+    mut markers: Query<(&mut Transform, &mut Visibility), With<CursorMarker>>,
+) {
+    let visible = cursor.source == CursorSource::Gamepad && state.phase == core::GamePhase::Running;
+    for (mut transform, mut visibility) in markers.iter_mut() {
+        visibility.is_visible = visible;
+        if let Some(position) = cursor.position {
+            transform.translation = position.extend(transform.translation.z);
+        }
+    }
+}
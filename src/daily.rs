@@ -0,0 +1,117 @@
+use crate::levels;
+use bevy::prelude::Color;
+use std::cmp::Ordering;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+// The colored square emoji, by hue
+const EMOJI_HUES: [(f32, &str); 6] = [
+    (0.0, "🟥"),
+    (30.0, "🟧"),
+    (60.0, "🟨"),
+    (120.0, "🟩"),
+    (230.0, "🟦"),
+    (285.0, "🟪"),
+];
+const MOVES_PER_LINE: usize = 10;
+
+// The day of the daily board being played, if any
+#[derive(Default)]
+pub struct DailyBoard(pub Option<u64>);
+
+// Days since the unix epoch, in UTC so everyone shares the same board
+#[cfg(not(target_family = "wasm"))]
+pub fn today() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.as_secs() / SECONDS_PER_DAY)
+        .unwrap_or(0)
+}
+
+#[cfg(target_family = "wasm")]
+pub fn today() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64 / SECONDS_PER_DAY
+}
+
+// Each day cycles through the levels, with its own coloring of the board
+pub fn level(day: u64) -> levels::Level {
+    levels::LEVELS[day as usize % levels::LEVELS.len()].clone()
+}
+
+pub fn seed(day: u64) -> u64 {
+    day
+}
+
+// The day as a YYYY-MM-DD date
+pub fn date(day: u64) -> String {
+    // Days are counted from 0000-03-01, so leap days fall at the end of a year
+    let days = day + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day_of_month)
+}
+
+// The square emoji closest in hue to the color
+fn emoji(color: Color) -> &'static str {
+    let hue = match color.as_hsla() {
+        Color::Hsla { hue, .. } => hue,
+        _ => unreachable!(),
+    };
+    let distance = |other: f32| {
+        let difference = (hue - other).abs();
+        difference.min(360.0 - difference)
+    };
+    EMOJI_HUES
+        .iter()
+        .min_by(|a, b| {
+            distance(a.0)
+                .partial_cmp(&distance(b.0))
+                .unwrap_or(Ordering::Equal)
+        })
+        .map(|(_, emoji)| *emoji)
+        .unwrap_or("⬜")
+}
+
+// A summary of a daily game to share, with the colors picked each move
+pub fn share_text(day: u64, result: &str, moves: &[Color]) -> String {
+    let mut text = format!("Assimilation daily {}\n{}", date(day), result);
+    for line in moves.chunks(MOVES_PER_LINE) {
+        text.push('\n');
+        for color in line {
+            text.push_str(emoji(*color));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(11_016), "2000-02-29");
+        assert_eq!(date(20_742), "2026-10-16");
+    }
+
+    #[test]
+    fn share_text_lines() {
+        let colors = [Color::BLUE, Color::RED, Color::GREEN];
+        let moves: Vec<Color> = (0..12).map(|idx| colors[idx % 3]).collect();
+        assert_eq!(
+            share_text(0, "Won 60-40", &moves),
+            "Assimilation daily 1970-01-01\nWon 60-40\n🟦🟥🟩🟦🟥🟩🟦🟥🟩🟦\n🟥🟩"
+        );
+    }
+}
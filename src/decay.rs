@@ -0,0 +1,151 @@
+use crate::core::{BoardTopology, GameState, MoveCache, Tile, TileState};
+use crate::{game, theme};
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::collections::HashSet;
+
+// The owned tiles with no unowned tile next to them, so away from their
+// owner's frontier
+pub fn decaying_tiles(tiles: &[Tile], topology: BoardTopology) -> Vec<(i32, i32)> {
+    let unowned = tiles
+        .iter()
+        .filter(|tile| matches!(tile.state, TileState::Unowned(_)))
+        .map(|tile| (tile.row, tile.column))
+        .collect::<HashSet<_>>();
+
+    tiles
+        .iter()
+        .filter(|tile| matches!(tile.state, TileState::Owned(_)))
+        .filter(|tile| {
+            !topology
+                .neighbor_offsets(tile.row)
+                .iter()
+                .any(|(row_offset, column_offset)| {
+                    unowned.contains(&(tile.row + row_offset, tile.column + column_offset))
+                })
+        })
+        .map(|tile| (tile.row, tile.column))
+        .collect()
+}
+
+// The color each decaying tile reverts to, picked at random from the board's
+// colors. The same seed always picks the same colors
+pub fn decay(
+    tiles: &[Tile],
+    topology: BoardTopology,
+    ids: &[u32],
+    seed: u64,
+) -> Vec<(i32, i32, u32)> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    decaying_tiles(tiles, topology)
+        .into_iter()
+        .filter_map(|(row, column)| Some((row, column, *ids.choose(&mut rng)?)))
+        .collect()
+}
+
+// Whether territory decays once the current round is over
+pub fn is_due(state: &GameState) -> bool {
+    matches!(state.rules.decay, Some(rounds) if rounds > 0 && state.round() % rounds == 0)
+}
+
+// The score the player would lose to decay after the move, if it comes before
+// their next one. Bots weigh this against what the move captures
+pub fn expected_loss(tiles: &[Tile], state: &GameState, player: Entity, id: u32) -> i32 {
+    if !is_due(state) {
+        return 0;
+    }
+    let mut board = tiles.to_vec();
+    crate::core::simulate_selection(&mut board, state.topology, id, player);
+    let decaying = decaying_tiles(&board, state.topology)
+        .into_iter()
+        .collect::<HashSet<_>>();
+    board
+        .iter()
+        .filter(|tile| matches!(tile.state, TileState::Owned(owner) if owner == player))
+        .filter(|tile| decaying.contains(&(tile.row, tile.column)))
+        .map(|tile| tile.weight as i32)
+        .sum()
+}
+
+// Reverts the tiles away from every player's frontier at the end of every
+// few rounds, so sprawling territory costs its owner. It is seeded from the
+// board, so replays of a game decay the same way
+pub fn decay_territory(
+    mut state: ResMut<GameState>,
+    mut cache: ResMut<MoveCache>,
+    theme: Res<theme::Theme>,
+    mut tiles: Query<(&mut Tile, &mut DrawMode, &mut Transform)>,
+    mut rounds_decayed: Local<u32>,
+) {
+    let period = match state.rules.decay {
+        Some(period) if period > 0 => period,
+        _ => return,
+    };
+
+    let rounds = state.round() - 1;
+    // A new game has started
+    if rounds < *rounds_decayed {
+        *rounds_decayed = rounds;
+    }
+    if rounds == *rounds_decayed {
+        return;
+    }
+    *rounds_decayed = rounds;
+    // Once the board is full there's nothing left to play for
+    if rounds % period != 0 || state.unowned.values().sum::<u32>() == 0 {
+        return;
+    }
+
+    let board = tiles
+        .iter()
+        .map(|(tile, _, _)| tile.clone())
+        .collect::<Vec<_>>();
+    let ids = state.ids.keys().cloned().collect::<Vec<_>>();
+    let seed = state.board_hash.value ^ u64::from(rounds);
+    let reverted = decay(&board, state.topology, &ids, seed);
+    if reverted.is_empty() {
+        return;
+    }
+    debug!(tiles = reverted.len(), round = rounds, "territory decayed");
+
+    for (mut tile, mut mode, mut transform) in tiles.iter_mut() {
+        let id = match reverted
+            .iter()
+            .find(|(row, column, _)| (*row, *column) == (tile.row, tile.column))
+        {
+            Some((_, _, id)) => *id,
+            None => continue,
+        };
+        let unowned = TileState::Unowned(id);
+        state.update_tile(&tile, &unowned);
+        tile.state = unowned;
+        *mode = theme.tile_mode(game::unowned_tile_color(&state, id), false);
+        transform.translation.z = 0.0;
+    }
+    cache.stale = true;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::load_level;
+
+    #[test]
+    fn tiles_away_from_the_frontier_decay() {
+        let player = Entity::from_raw(0);
+        let mut tiles = load_level("1 | | |\n| | | |\n| | | |", &[player], vec![0, 1], None);
+        for tile in tiles.iter_mut().filter(|tile| tile.column < 3) {
+            tile.state = TileState::Owned(player);
+        }
+
+        // Only the first two columns are out of reach of the unowned last one
+        assert_eq!(
+            decaying_tiles(&tiles, BoardTopology::Square4),
+            vec![(0, 0), (0, 1), (1, 0), (1, 1), (2, 0), (2, 1)]
+        );
+        let reverted = decay(&tiles, BoardTopology::Square4, &[0, 1], 7);
+        assert_eq!(reverted.len(), 6);
+        assert_eq!(reverted, decay(&tiles, BoardTopology::Square4, &[0, 1], 7));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::bindings::{Action, ActionInput};
+use crate::{core, cursor, game, input, stats, ui};
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticId, Diagnostics, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin,
+};
+use bevy::prelude::*;
+use bevy::utils::Instant;
+use bevy_egui::{egui, EguiContext};
+use std::marker::PhantomData;
+
+pub const TILE_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(0x3c5b_9e0e_71a4_4d8e_9c1f_2a6f_0b7d_41e3);
+
+// How many measurements each average is taken over
+const HISTORY_LENGTH: usize = 20;
+
+// A system whose run time is measured, by timers running just before and
+// after it. These are the known hot spots on big boards
+pub trait TimedSystem: Send + Sync + 'static {
+    const ID: DiagnosticId;
+    const NAME: &'static str;
+}
+
+pub struct HoverTiming;
+
+impl TimedSystem for HoverTiming {
+    const ID: DiagnosticId = DiagnosticId::from_u128(0x8f2d_6a41_0c3e_4b7a_a5d9_13e8_77c2_f064);
+    const NAME: &'static str = "hover_tile";
+}
+
+pub struct ScoresTiming;
+
+impl TimedSystem for ScoresTiming {
+    const ID: DiagnosticId = DiagnosticId::from_u128(0x51a0_e7c4_9b36_4f12_8e6d_c03b_2d95_a871);
+    const NAME: &'static str = "update_scores";
+}
+
+pub struct SystemTimer<T> {
+    started: Option<Instant>,
+    system: PhantomData<T>,
+}
+
+impl<T> Default for SystemTimer<T> {
+    fn default() -> Self {
+        SystemTimer {
+            started: None,
+            system: PhantomData,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DiagnosticsOverlay {
+    pub shown: bool,
+}
+
+// Frame timings, entity counts and the time spent in the hot systems, along
+// with how fair the board is, shown with F3
+pub struct DiagnosticsPlugin;
+
+impl Plugin for DiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin)
+            .add_plugin(EntityCountDiagnosticsPlugin)
+            .init_resource::<DiagnosticsOverlay>()
+            .init_resource::<SystemTimer<HoverTiming>>()
+            .init_resource::<SystemTimer<ScoresTiming>>()
+            .add_startup_system(setup_diagnostics)
+            .add_system(count_tiles)
+            .add_system(toggle_overlay)
+            .add_system(show_overlay.after(toggle_overlay))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::run_if_game_started)
+                    .with_system(
+                        start_timer::<HoverTiming>
+                            .after(cursor::UpdateCursor)
+                            .before(input::hover_tile),
+                    )
+                    .with_system(stop_timer::<HoverTiming>.after(input::hover_tile)),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::GameStep)
+                    .with_system(
+                        start_timer::<ScoresTiming>
+                            .after(core::update_move_cache)
+                            .before(core::update_scores),
+                    )
+                    .with_system(stop_timer::<ScoresTiming>.after(core::update_scores)),
+            );
+    }
+}
+
+fn setup_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(TILE_COUNT, "tiles", HISTORY_LENGTH));
+    for (id, name) in [
+        (HoverTiming::ID, HoverTiming::NAME),
+        (ScoresTiming::ID, ScoresTiming::NAME),
+    ] {
+        diagnostics.add(Diagnostic::new(id, name, HISTORY_LENGTH).with_suffix("ms"));
+    }
+}
+
+fn count_tiles(mut diagnostics: ResMut<Diagnostics>, tiles: Query<(), With<core::Tile>>) {
+    diagnostics.add_measurement(TILE_COUNT, tiles.iter().count() as f64);
+}
+
+fn start_timer<T: TimedSystem>(mut timer: ResMut<SystemTimer<T>>) {
+    timer.started = Some(Instant::now());
+}
+
+fn stop_timer<T: TimedSystem>(
+    mut timer: ResMut<SystemTimer<T>>,
+    mut diagnostics: ResMut<Diagnostics>,
+) {
+    if let Some(started) = timer.started.take() {
+        diagnostics.add_measurement(T::ID, started.elapsed().as_secs_f64() * 1000.0);
+    }
+}
+
+fn toggle_overlay(input: ActionInput, mut overlay: ResMut<DiagnosticsOverlay>) {
+    if input.just_pressed(Action::Diagnostics) {
+        overlay.shown = !overlay.shown;
+    }
+}
+
+fn show_overlay(
+    overlay: Res<DiagnosticsOverlay>,
+    streamer: Res<ui::StreamerMode>,
+    diagnostics: Res<Diagnostics>,
+    summary: Res<stats::GameSummary>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    // Nothing meant for developers ends up on stream
+    if !overlay.shown || streamer.enabled {
+        return;
+    }
+
+    let average = |id| diagnostics.get(id).and_then(Diagnostic::average);
+    let latest = |id| diagnostics.get(id).and_then(Diagnostic::value);
+    let rows = [
+        ("FPS", average(FrameTimeDiagnosticsPlugin::FPS), 0),
+        (
+            "frame time (ms)",
+            average(FrameTimeDiagnosticsPlugin::FRAME_TIME).map(|seconds| seconds * 1000.0),
+            2,
+        ),
+        (
+            "entities",
+            latest(EntityCountDiagnosticsPlugin::ENTITY_COUNT),
+            0,
+        ),
+        ("tiles", latest(TILE_COUNT), 0),
+        ("hover_tile (ms)", average(HoverTiming::ID), 3),
+        ("update_scores (ms)", average(ScoresTiming::ID), 3),
+        ("board fairness", summary.fairness.map(f64::from), 2),
+    ];
+
+    egui::Window::new("Diagnostics")
+        .anchor(egui::Align2::LEFT_TOP, [10.0, 10.0])
+        .resizable(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            egui::Grid::new("diagnostics").show(ui, |ui| {
+                for (name, value, decimals) in rows {
+                    ui.label(name);
+                    match value {
+                        Some(value) => ui.monospace(format!("{:.*}", decimals, value)),
+                        None => ui.monospace("-"),
+                    };
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timers_measure_the_system_between_them() {
+        fn slow_system() {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        let mut app = App::new();
+        app.init_resource::<Diagnostics>()
+            .init_resource::<SystemTimer<ScoresTiming>>()
+            .add_startup_system(setup_diagnostics)
+            .add_system(start_timer::<ScoresTiming>.before(slow_system))
+            .add_system(slow_system)
+            .add_system(stop_timer::<ScoresTiming>.after(slow_system));
+        app.update();
+
+        let diagnostics = app.world.resource::<Diagnostics>();
+        let elapsed = diagnostics.get(ScoresTiming::ID).unwrap().value().unwrap();
+        assert!(elapsed >= 5.0);
+        // Nothing was timed for hovering
+        assert_eq!(diagnostics.get(HoverTiming::ID).unwrap().value(), None);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::board::BoardEntity;
+use crate::core::{CaptureEvent, GamePhase, GameState, Player, PlayerKind, Tile};
+use crate::TILE_RADIUS;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+
+const PARTICLE_LIFETIME: f32 = 0.5;
+const MIN_PARTICLES_PER_TILE: usize = 2;
+const MAX_PARTICLES_PER_TILE: usize = 6;
+const PARTICLE_SPEED: f32 = 60.0;
+const CONFETTI_COUNT: usize = 120;
+const CONFETTI_LIFETIME: f32 = 3.0;
+const CONFETTI_GRAVITY: f32 = 80.0;
+const CONFETTI_COLORS: [Color; 6] = [
+    Color::RED,
+    Color::ORANGE,
+    Color::YELLOW,
+    Color::LIME_GREEN,
+    Color::CYAN,
+    Color::FUCHSIA,
+];
+// How long the result takes to appear on the end screen, in seconds
+const VICTORY_ENTRANCE: f32 = 0.6;
+const DEFEAT_ENTRANCE: f32 = 1.5;
+
+#[derive(Component)]
+pub struct Particle {
+    velocity: Vec2,
+    // Pulls the particle downwards, in units per second squared
+    gravity: f32,
+    color: Color,
+    lifetime: Timer,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ending {
+    // A human won
+    Victory,
+    // A bot beat the human
+    Defeat,
+}
+
+// How the end of the game is being marked, if at all
+#[derive(Default)]
+pub struct Celebration {
+    pub ending: Option<Ending>,
+    // When the game ended, in seconds since startup
+    pub started: f64,
+}
+
+impl Celebration {
+    // The size and opacity of the result on the end screen, from zero to one.
+    // Victories pop in past their full size, defeats slowly fade in
+    pub fn entrance(&self, now: f64) -> (f32, f32) {
+        let elapsed = (now - self.started) as f32;
+        match self.ending {
+            Some(Ending::Victory) => {
+                let t = (elapsed / VICTORY_ENTRANCE).clamp(0.0, 1.0);
+                // Eases out with a little overshoot
+                let overshoot = 1.7;
+                let t = t - 1.0;
+                (1.0 + t * t * ((overshoot + 1.0) * t + overshoot), 1.0)
+            }
+            Some(Ending::Defeat) => (1.0, (elapsed / DEFEAT_ENTRANCE).clamp(0.0, 1.0)),
+            None => (1.0, 1.0),
+        }
+    }
+}
+
+// Emits a burst of the captured color from every captured tile. Bigger
+// captures get more and faster particles
+pub fn spawn_capture_particles(
+    state: Res<GameState>,
+    mut captures: EventReader<CaptureEvent>,
+    tiles: Query<(&Tile, &Transform)>,
+    mut commands: Commands,
+) {
+    // Every capture is made with the capturing player's latest pick
+    let colors = state
+        .last_selections
+        .iter()
+        .filter_map(|(player, id)| Some((*player, *state.ids.get(id)?)))
+        .collect::<HashMap<_, _>>();
+    let captures = captures.iter().collect::<Vec<_>>();
+    if captures.is_empty() {
+        return;
+    }
+
+    let chain = captures.len();
+    let count = (MIN_PARTICLES_PER_TILE + chain / 5).min(MAX_PARTICLES_PER_TILE);
+    let speed = PARTICLE_SPEED * (1.0 + chain as f32 / 20.0).min(2.0);
+    let shape = shapes::Circle {
+        radius: TILE_RADIUS / 6.0,
+        ..shapes::Circle::default()
+    };
+
+    let mut rng = thread_rng();
+    for capture in captures {
+        let color = match colors.get(&capture.player) {
+            Some(color) => *color,
+            None => continue,
+        };
+        let position = match tiles
+            .iter()
+            .find(|(tile, _)| tile.row == capture.row && tile.column == capture.column)
+        {
+            Some((_, transform)) => transform.translation.truncate(),
+            None => continue,
+        };
+
+        for _ in 0..count {
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let velocity = Vec2::new(angle.cos(), angle.sin()) * speed * rng.gen_range(0.5..1.0);
+            commands
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &shape,
+                    DrawMode::Fill(FillMode::color(color)),
+                    Transform::from_translation(position.extend(3.0)),
+                ))
+                .insert(Particle {
+                    velocity,
+                    gravity: 0.0,
+                    color,
+                    lifetime: Timer::from_seconds(PARTICLE_LIFETIME, false),
+                })
+                .insert(BoardEntity);
+        }
+    }
+}
+
+pub fn update_particles(
+    time: Res<Time>,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform, &mut DrawMode)>,
+    mut commands: Commands,
+) {
+    for (entity, mut particle, mut transform, mut mode) in particles.iter_mut() {
+        if particle.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        particle.velocity.y -= particle.gravity * time.delta_seconds();
+        transform.translation += (particle.velocity * time.delta_seconds()).extend(0.0);
+        let remaining = 1.0 - particle.lifetime.percent();
+        *mode = DrawMode::Fill(FillMode::color(*particle.color.clone().set_a(remaining)));
+    }
+}
+
+// Marks the end of a game with a human in it. Wins get confetti raining down
+// over the board, losses only get a quieter end screen
+pub fn celebrate_game_over(
+    state: Res<GameState>,
+    time: Res<Time>,
+    players: Query<&Player>,
+    tiles: Query<&Transform, With<Tile>>,
+    mut celebration: ResMut<Celebration>,
+    mut celebrated: Local<bool>,
+    mut commands: Commands,
+) {
+    let winner = match state.phase {
+        GamePhase::Over(winner) => winner,
+        _ => {
+            *celebrated = false;
+            return;
+        }
+    };
+    if *celebrated {
+        return;
+    }
+    *celebrated = true;
+
+    let is_human = |player| {
+        players
+            .get(player)
+            .map(|player| matches!(player.kind, PlayerKind::Human))
+            .unwrap_or(false)
+    };
+    celebration.started = time.seconds_since_startup();
+    celebration.ending = if is_human(winner) {
+        Some(Ending::Victory)
+    } else if state.players.iter().any(|player| is_human(*player)) {
+        Some(Ending::Defeat)
+    } else {
+        None
+    };
+    if celebration.ending != Some(Ending::Victory) {
+        return;
+    }
+
+    // Thrown up from just below the top of the board, spread across its width
+    let (min, max) = tiles.iter().fold(
+        (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+        |(min, max), transform| {
+            let position = transform.translation.truncate();
+            (min.min(position), max.max(position))
+        },
+    );
+    if min.x > max.x {
+        return;
+    }
+    let shape = shapes::Rectangle {
+        extents: Vec2::new(TILE_RADIUS / 2.0, TILE_RADIUS / 4.0),
+        origin: shapes::RectangleOrigin::Center,
+    };
+    let mut rng = thread_rng();
+    for _ in 0..CONFETTI_COUNT {
+        let color = CONFETTI_COLORS[rng.gen_range(0..CONFETTI_COLORS.len())];
+        let position = Vec2::new(rng.gen_range(min.x..=max.x), max.y);
+        let velocity = Vec2::new(
+            rng.gen_range(-PARTICLE_SPEED..PARTICLE_SPEED),
+            rng.gen_range(0.0..PARTICLE_SPEED * 2.0),
+        );
+        let rotation = Quat::from_rotation_z(rng.gen_range(0.0..std::f32::consts::TAU));
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shape,
+                DrawMode::Fill(FillMode::color(color)),
+                Transform::from_translation(position.extend(4.0)).with_rotation(rotation),
+            ))
+            .insert(Particle {
+                velocity,
+                gravity: CONFETTI_GRAVITY,
+                color,
+                lifetime: Timer::from_seconds(rng.gen_range(0.5..1.0) * CONFETTI_LIFETIME, false),
+            })
+            .insert(BoardEntity);
+    }
+}
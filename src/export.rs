@@ -0,0 +1,134 @@
+use crate::board::Board;
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+// Pixels per world unit in exported images, and the space around the board
+const EXPORT_SCALE: f32 = 2.0;
+const EXPORT_MARGIN: f32 = 10.0;
+// The fraction of each tile drawn, leaving a gap between tiles of different
+// colors or owners
+const TILE_FILL: f32 = 0.9;
+
+// The color of a tile, and the player owning it if any. Tiles with the same
+// owner are drawn without a gap between them, like the board itself
+pub type TileColors = HashMap<(i32, i32), (Color, Option<Entity>)>;
+
+// Draws the board the same way the camera sees it, so it can be exported
+// without reading anything back from the GPU
+pub fn render_board(board: &Board, tiles: &TileColors, background: Color) -> (u32, u32, Vec<u8>) {
+    let size = board.size() + Vec2::splat(EXPORT_MARGIN * 2.0);
+    let (width, height) = (
+        (size.x * EXPORT_SCALE).ceil() as u32,
+        (size.y * EXPORT_SCALE).ceil() as u32,
+    );
+
+    let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let point = Vec2::new(
+                (x as f32 + 0.5) / EXPORT_SCALE - size.x / 2.0,
+                size.y / 2.0 - (y as f32 + 0.5) / EXPORT_SCALE,
+            );
+            let color = pixel_color(board, tiles, point).unwrap_or(background);
+            rgba.extend(color.as_rgba_f32().map(|c| (c * 255.0) as u8));
+        }
+    }
+    (width, height, rgba)
+}
+
+fn pixel_color(board: &Board, tiles: &TileColors, point: Vec2) -> Option<Color> {
+    let position = board.world_to_tile(point);
+    let (color, owner) = tiles.get(&position)?;
+
+    // Points near the edge of a tile land in a neighbor once pushed outwards
+    let (row, column) = position;
+    let center = board.tile_to_world(row, column);
+    let neighbor = board.world_to_tile(center + (point - center) / TILE_FILL);
+    let joined = neighbor == position
+        || (owner.is_some() && tiles.get(&neighbor).map(|(_, other)| other) == Some(owner));
+    joined.then(|| *color)
+}
+
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>, png::EncodingError> {
+    let mut bytes = vec![];
+    let mut encoder = png::Encoder::new(&mut bytes, width, height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(rgba)?;
+    Ok(bytes)
+}
+
+// Saves the image next to the game natively
+#[cfg(not(target_family = "wasm"))]
+pub fn save_png(png: &[u8]) {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0);
+    let path = format!("assimilation-{}.png", timestamp);
+    match std::fs::write(&path, png) {
+        Ok(_) => info!("Saved board image to {}", path),
+        Err(e) => error!("Unable to save board image: {}", e),
+    }
+}
+
+// Downloads the image in the browser
+#[cfg(target_family = "wasm")]
+pub fn save_png(png: &[u8]) {
+    if let Err(e) = download_png(png) {
+        error!("Unable to download board image: {:?}", e);
+    }
+}
+
+#[cfg(target_family = "wasm")]
+fn download_png(png: &[u8]) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsCast;
+
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(png));
+    let mut options = web_sys::BlobPropertyBag::new();
+    options.type_("image/png");
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("Missing document")?;
+    let link: web_sys::HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    link.set_href(&url);
+    link.set_download("assimilation.png");
+    link.click();
+    web_sys::Url::revoke_object_url(&url)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::BoardTopology;
+
+    #[test]
+    fn render_board_colors() {
+        let mut world = World::new();
+        let player = world.spawn().id();
+        let board = Board::new(BoardTopology::Square4, 1, 3);
+        let tiles = TileColors::from([
+            ((0, 0), (Color::RED, Some(player))),
+            ((0, 1), (Color::RED, Some(player))),
+            ((0, 2), (Color::BLUE, None)),
+        ]);
+
+        let (width, height, rgba) = render_board(&board, &tiles, Color::BLACK);
+        assert_eq!(rgba.len(), (width * height * 4) as usize);
+
+        let color_at = |point: Vec2| pixel_color(&board, &tiles, point);
+        let (left, middle, right) = (
+            board.tile_to_world(0, 0),
+            board.tile_to_world(0, 1),
+            board.tile_to_world(0, 2),
+        );
+        assert_eq!(color_at(left), Some(Color::RED));
+        assert_eq!(color_at(right), Some(Color::BLUE));
+        // Tiles of the same owner join up, others are kept apart
+        assert_eq!(color_at(left.lerp(middle, 0.5)), Some(Color::RED));
+        assert_eq!(color_at(middle.lerp(right, 0.5)), None);
+    }
+}
@@ -0,0 +1,347 @@
+use crate::ai::{self, Playback};
+use crate::core::{
+    BoardTopology, GameState, MoveCache, Player, PlayerKind, SelectEvent, Tile, TileState,
+};
+use crate::{storage, TIME_STEP};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Bots can be separate programs, written in any language, talking to the
+// game over their standard input and output a line at a time. The game sends
+//
+//   hello <version>                once, when the game starts
+//   board <rows> <columns> <hex|square4|square8>
+//   <one line per row>             a token per tile: '.' for no tile, '#' a
+//                                  wall, '@' the bot's, '*' an opponent's, or
+//                                  the color of an unowned tile
+//   scores <bot's> <best opponent's>
+//   captures <color>=<tiles> ...   what each color would capture
+//   moves <color> ...              the colors which may be picked
+//   go                             asks for a move
+//   quit                           when the game is over
+//
+// and the bot answers each 'go' with 'select <color>'. Lines starting with
+// 'info' are logged, anything else is ignored. bots/greedy.py is an example
+
+const PROTOCOL_VERSION: u32 = 1;
+const BOT_COMMAND_KEY: &str = "bot_command";
+// How long a bot program has to answer, on top of the usual think time. Its
+// move is picked for it after that
+const MOVE_TIMEOUT: f32 = 5.0;
+
+// The command line running the bot program, remembered between sessions
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BotCommand {
+    pub command: String,
+}
+
+impl BotCommand {
+    pub fn load() -> Self {
+        storage::load(BOT_COMMAND_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(BOT_COMMAND_KEY, self)
+    }
+
+    // Starts the program, split into arguments on whitespace. It isn't run
+    // through a shell
+    pub fn spawn(&self) -> Result<ExternalBot, String> {
+        let mut args = self.command.split_whitespace();
+        let program = args.next().ok_or("No bot program given")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Unable to start '{}': {}", program, e))?;
+        let mut stdin = child.stdin.take().ok_or("Unable to write to the bot")?;
+        let stdout = child.stdout.take().ok_or("Unable to read from the bot")?;
+
+        // Messages are written and replies read on their own threads, so a
+        // slow bot can't hold up the game, even one which has stopped reading
+        let (messages, to_write) = mpsc::channel::<String>();
+        std::thread::spawn(move || {
+            for message in to_write {
+                if stdin
+                    .write_all(message.as_bytes())
+                    .and_then(|_| stdin.flush())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        let (sender, replies) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let process = Process {
+            child,
+            messages,
+            replies,
+            waiting: None,
+        };
+        process.send(&format!("hello {}\n", PROTOCOL_VERSION))?;
+        info!(command = %self.command, "started bot program");
+        Ok(ExternalBot(Arc::new(Mutex::new(process))))
+    }
+}
+
+pub struct Process {
+    child: Child,
+    messages: Sender<String>,
+    replies: Receiver<String>,
+    // The move a reply is waited on for, and for how long so far
+    waiting: Option<(u32, f32)>,
+}
+
+impl Process {
+    // Queues the message for the bot. It fails once the bot has stopped
+    // taking input
+    fn send(&self, message: &str) -> Result<(), String> {
+        self.messages
+            .send(message.to_string())
+            .map_err(|_| "Unable to write to the bot".to_string())
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.send("quit\n");
+        if !matches!(self.child.try_wait(), Ok(Some(_))) {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+// A running bot program, shared by every copy of the player. It is stopped
+// once the last one is gone
+#[derive(Clone)]
+pub struct ExternalBot(Arc<Mutex<Process>>);
+
+impl std::fmt::Debug for ExternalBot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ExternalBot")
+    }
+}
+
+impl ExternalBot {
+    fn is_waiting(&self) -> bool {
+        self.0
+            .lock()
+            .map_or(false, |process| process.waiting.is_some())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Reply<'a> {
+    Select(u32),
+    Info(&'a str),
+    Unknown,
+}
+
+fn parse_reply(line: &str) -> Reply<'_> {
+    let line = line.trim();
+    match line.split_once(' ') {
+        Some(("select", id)) => id.trim().parse().map_or(Reply::Unknown, Reply::Select),
+        Some(("info", text)) => Reply::Info(text.trim()),
+        _ => Reply::Unknown,
+    }
+}
+
+// Everything the bot is told about its turn, ending with 'go'
+fn turn_message(
+    tiles: &[Tile],
+    state: &GameState,
+    cache: &MoveCache,
+    player: Entity,
+    scores: (u32, u32),
+) -> String {
+    let rows = tiles.iter().map(|tile| tile.row + 1).max().unwrap_or(0);
+    let columns = tiles.iter().map(|tile| tile.column + 1).max().unwrap_or(0);
+    let mut grid = vec![vec![".".to_string(); columns as usize]; rows as usize];
+    for tile in tiles {
+        grid[tile.row as usize][tile.column as usize] = match tile.state {
+            TileState::Empty => continue,
+            TileState::Wall => "#".into(),
+            TileState::Owned(owner) if owner == player => "@".into(),
+            TileState::Owned(_) => "*".into(),
+            TileState::Unowned(id) => id.to_string(),
+        };
+    }
+    let topology = match state.topology {
+        BoardTopology::Hex => "hex",
+        BoardTopology::Square4 => "square4",
+        BoardTopology::Square8 => "square8",
+    };
+
+    let mut message = format!("board {} {} {}\n", rows, columns, topology);
+    for row in grid {
+        message += &row.join(" ");
+        message += "\n";
+    }
+    message += &format!("scores {} {}\n", scores.0, scores.1);
+    let captures = state
+        .ids
+        .keys()
+        .map(|id| format!("{}={}", id, cache.capture_count(player, *id)))
+        .collect::<Vec<_>>();
+    message += &format!("captures {}\n", captures.join(" "));
+    let moves = state
+        .ids
+        .keys()
+        .filter(|id| state.is_selection_allowed(player, **id))
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>();
+    message += &format!("moves {}\ngo\n", moves.join(" "));
+    message
+}
+
+// Runs on the fixed timestep like the built in bots. The turn is sent once
+// the bot's think time is up, and its reply is looked for every step after.
+// Bots which don't answer in time, answer with a color they can't pick or
+// have stopped have the biggest capture picked for them
+pub fn perform_external_move(
+    state: Res<GameState>,
+    mut players: Query<&mut Player>,
+    cache: Res<MoveCache>,
+    playback: Res<Playback>,
+    tiles: Query<&Tile>,
+    mut selections: EventWriter<SelectEvent>,
+) {
+    let player = match state.players.first() {
+        Some(player) if !playback.paused => *player,
+        _ => return,
+    };
+    let score = |mine: bool| {
+        state
+            .players
+            .iter()
+            .filter(|other| (**other == player) == mine)
+            .filter_map(|other| Some(players.get(*other).ok()?.score))
+            .max()
+            .unwrap_or(0)
+    };
+    let scores = (score(true), score(false));
+    let bot = match players.get_mut(player) {
+        Ok(mut player) => match player.kind {
+            PlayerKind::External(ref mut timer, ref bot) => {
+                if !bot.is_waiting() {
+                    let step = Duration::from_secs_f32(TIME_STEP * playback.speed);
+                    if !timer.tick(step).finished() {
+                        return;
+                    }
+                    timer.reset();
+                }
+                bot.clone()
+            }
+            _ => return,
+        },
+        Err(_) => return,
+    };
+    let mut process = match bot.0.lock() {
+        Ok(process) => process,
+        Err(_) => return,
+    };
+    let mut fall_back = |reason: String| {
+        warn!("bot program {}, picking its move for it", reason);
+        if let Some(id) = ai::biggest_capture(&state, &cache, player) {
+            selections.send(SelectEvent { player, id });
+        }
+    };
+
+    let (moves, waited) = match process.waiting {
+        Some(waiting) => waiting,
+        None => {
+            // Replies to moves which were already picked for the bot
+            while process.replies.try_recv().is_ok() {}
+            let tiles = tiles.iter().cloned().collect::<Vec<_>>();
+            let message = turn_message(&tiles, &state, &cache, player, scores);
+            match process.send(&message) {
+                Ok(()) => process.waiting = Some((state.moves, 0.0)),
+                Err(e) => fall_back(e),
+            }
+            return;
+        }
+    };
+    // The game has moved on without the bot
+    if moves != state.moves {
+        process.waiting = None;
+        return;
+    }
+
+    loop {
+        let reply = match process.replies.try_recv() {
+            Ok(reply) => reply,
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Disconnected) => {
+                process.waiting = None;
+                return fall_back("has stopped".into());
+            }
+        };
+        match parse_reply(&reply) {
+            Reply::Select(id)
+                if state.ids.contains_key(&id) && state.is_selection_allowed(player, id) =>
+            {
+                process.waiting = None;
+                debug!(id, "bot program picked a color");
+                selections.send(SelectEvent { player, id });
+                return;
+            }
+            Reply::Select(id) => {
+                process.waiting = None;
+                return fall_back(format!("picked {}, which it may not", id));
+            }
+            Reply::Info(text) => info!("bot program: {}", text),
+            Reply::Unknown => debug!(reply = %reply, "ignored bot program output"),
+        }
+    }
+
+    let waited = waited + TIME_STEP;
+    process.waiting = Some((moves, waited));
+    if waited >= MOVE_TIMEOUT {
+        process.waiting = None;
+        fall_back("took too long".into());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::load_level;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn turns_are_sent_as_text() {
+        let (player, opponent) = (Entity::from_raw(0), Entity::from_raw(1));
+        let tiles = load_level("1 | |\n| | 2", &[player, opponent], vec![0], None);
+        let mut state = GameState {
+            players: vec![player, opponent],
+            ids: BTreeMap::from([(0, Color::RED)]),
+            topology: BoardTopology::Square4,
+            ..default()
+        };
+        state.track_board(&tiles);
+        let cache = MoveCache::new(&state, &tiles);
+
+        assert_eq!(
+            turn_message(&tiles, &state, &cache, player, (1, 1)),
+            "board 2 3 square4\n@ 0 0\n0 0 *\nscores 1 1\ncaptures 0=4\nmoves 0\ngo\n"
+        );
+        assert_eq!(parse_reply("select 3\n"), Reply::Select(3));
+        assert_eq!(parse_reply("info thinking"), Reply::Info("thinking"));
+        assert_eq!(parse_reply("select red"), Reply::Unknown);
+    }
+}
@@ -0,0 +1,691 @@
+use crate::{
+    ai, announce, batch, board, core, daily, decay, effects, overlay, share, stats, territory,
+    theme, tutorial, tween, virus, BoardCamera, GameStartEvent, TILE_RADIUS, TIME_STEP,
+};
+use bevy::ecs::schedule::ShouldRun;
+use bevy::{core::FixedTimestep, prelude::*};
+use bevy_prototype_lyon::prelude::*;
+use std::f64::consts::PI;
+
+// How long the board is shown before the first move can be made, in seconds
+pub const COUNTDOWN_TIME: f32 = 2.4;
+// The time it takes the whole board to appear, and each single tile
+const BOARD_SPAWN_TIME: f32 = 1.2;
+const TILE_SPAWN_TIME: f32 = 0.3;
+// The fraction of the window the board may take up, and how far small boards
+// may be zoomed in to fill it
+const BOARD_MARGIN: f32 = 0.85;
+const MAX_BOARD_ZOOM: f32 = 2.5;
+// Tiles of huge boards aren't shrunk smaller than this, in pixels. The board
+// overflows the window instead, and the camera pans over it
+const MIN_TILE_RADIUS: f32 = 4.0;
+// Every unowned tile of a blind game, lighter than the virus and the walls
+pub const BLIND_TILE_COLOR: Color = Color::rgb(0.75, 0.75, 0.75);
+
+// Runs gameplay systems on the fixed timestep, while a game is running
+#[derive(RunCriteriaLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GameStep;
+
+#[derive(Component)]
+struct BonusMarker;
+
+pub struct CountdownTimer(pub Timer);
+
+// The camera scale needed to fit the board in the window
+struct BoardView {
+    scale: f32,
+}
+
+impl Default for BoardView {
+    fn default() -> Self {
+        Self { scale: 1.0 }
+    }
+}
+
+// Starting and tearing down games, the rules, and the board they are played on
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<core::SelectEvent>()
+            .add_event::<core::CaptureEvent>()
+            .add_event::<core::SwapEvent>()
+            .add_event::<GameStartEvent>()
+            .init_resource::<core::GameState>()
+            .init_resource::<core::MoveCache>()
+            .init_resource::<BoardView>()
+            .init_resource::<board::Board>()
+            .init_resource::<overlay::HeatMap>()
+            .insert_resource(CountdownTimer(Timer::from_seconds(COUNTDOWN_TIME, false)))
+            .init_resource::<stats::GameTally>()
+            .init_resource::<stats::GameSummary>()
+            .insert_resource(announce::Announcer::load())
+            .insert_resource(stats::Stats::load())
+            .init_resource::<tutorial::Tutorial>()
+            .init_resource::<daily::DailyBoard>()
+            .add_startup_system(batch::create_tile_textures)
+            .add_system(game_start)
+            .add_system(clear_game_on_start.before(game_start))
+            .add_system_set(
+                SystemSet::on_exit(crate::menu::AppState::InGame).with_system(clear_game),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_counting_down)
+                    .with_system(update_countdown),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_swap_offered)
+                    .with_system(resolve_swap),
+            )
+            .init_resource::<effects::Celebration>()
+            .add_system(effects::update_particles)
+            .add_system(effects::celebrate_game_over)
+            .add_system(tween::animate_tweens)
+            .add_system(batch::tint_batched_tiles.after(tween::animate_tweens))
+            .add_system(territory::animate_territory_borders)
+            .add_system(fit_board_to_window.after(game_start))
+            // Turns and scoring advance at a fixed rate, whatever the framerate
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(
+                        FixedTimestep::step(TIME_STEP as f64)
+                            .chain(run_step_if_game_started)
+                            .label(GameStep),
+                    )
+                    .with_system(stats::track_moves.after(core::perform_selection))
+                    .with_system(stats::track_summary.after(core::update_scores))
+                    .with_system(announce::announce_moves.after(core::update_scores))
+                    .with_system(
+                        stats::record_game_result
+                            .after(core::update_scores)
+                            .after(stats::track_moves),
+                    )
+                    .with_system(core::update_scores)
+                    .with_system(core::perform_selection.before(core::update_move_cache))
+                    .with_system(
+                        virus::spread_virus
+                            .after(core::perform_selection)
+                            .before(core::update_move_cache),
+                    )
+                    .with_system(
+                        decay::decay_territory
+                            .after(virus::spread_virus)
+                            .before(core::update_move_cache),
+                    )
+                    .with_system(core::update_move_cache.before(core::update_scores))
+                    .with_system(tutorial::update_tutorial.after(core::update_move_cache)),
+            )
+            // The board is redrawn every frame
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_game_started)
+                    .with_system(tutorial::point_tutorial_arrow.after(tutorial::update_tutorial))
+                    .with_system(update_tile_colors.after(core::perform_selection))
+                    .with_system(territory::update_territory_borders.after(core::perform_selection))
+                    .with_system(
+                        overlay::update_heat_map
+                            .after(overlay::toggle_heat_map)
+                            .after(core::update_move_cache),
+                    )
+                    .with_system(effects::spawn_capture_particles.after(core::perform_selection))
+                    .with_system(clear_bonus_markers.after(core::perform_selection))
+                    .with_system(update_forbidden_colors.after(core::perform_selection)),
+            );
+    }
+}
+
+// How a tile is drawn, and its depth. Empty tiles aren't drawn at all
+pub fn tile_appearance(
+    theme: &theme::Theme,
+    state: &core::GameState,
+    players: &Query<&core::Player>,
+    tile: &core::TileState,
+) -> Option<(DrawMode, f32)> {
+    match tile {
+        core::TileState::Owned(player) => {
+            let color = players.get(*player).ok()?.color;
+            Some((theme.owned_tile_mode(color), 1.0))
+        }
+        core::TileState::Unowned(id) => {
+            Some((theme.tile_mode(unowned_tile_color(state, *id), false), 0.0))
+        }
+        core::TileState::Wall => Some((theme.tile_mode(theme.wall_color(), false), 0.0)),
+        core::TileState::Empty => None,
+    }
+}
+
+// Colors which can't currently be selected are drawn desaturated. Blind games
+// only show the colors during the countdown
+pub fn unowned_tile_color(state: &core::GameState, id: u32) -> Color {
+    if state.blind && state.phase != core::GamePhase::Countdown {
+        return BLIND_TILE_COLOR;
+    }
+    let color = state.ids[&id];
+    if state.is_selection_allowed(state.players[0], id) {
+        return color;
+    }
+
+    match color.as_hsla() {
+        Color::Hsla {
+            hue,
+            saturation,
+            lightness,
+            alpha,
+        } => Color::hsla(hue, saturation * 0.15, lightness * 0.8, alpha),
+        _ => unreachable!(),
+    }
+}
+
+fn update_forbidden_colors(
+    state: Res<core::GameState>,
+    theme: Res<theme::Theme>,
+    mut selections: EventReader<core::SelectEvent>,
+    mut tiles: Query<(&core::Tile, &mut DrawMode)>,
+) {
+    if selections.iter().count() == 0 {
+        return;
+    }
+
+    for (tile, mut mode) in tiles.iter_mut() {
+        if let core::TileState::Unowned(id) = tile.state {
+            *mode = theme.tile_mode(unowned_tile_color(&state, id), false);
+        }
+    }
+}
+
+fn tile_shape_bundle(board: &board::Board, mode: DrawMode, transform: Transform) -> ShapeBundle {
+    match board.topology {
+        core::BoardTopology::Hex => {
+            let shape = shapes::RegularPolygon {
+                sides: 6,
+                feature: shapes::RegularPolygonFeature::Radius(board.tile_radius),
+                ..shapes::RegularPolygon::default()
+            };
+            GeometryBuilder::build_as(
+                &shape,
+                mode,
+                transform.with_rotation(Quat::from_rotation_z(PI as f32 / 6.0)),
+            )
+        }
+        core::BoardTopology::Square4 | core::BoardTopology::Square8 => {
+            let (width, height) = board.spacing();
+            let shape = shapes::Rectangle {
+                extents: Vec2::new(width, height),
+                origin: shapes::RectangleOrigin::Center,
+            };
+            GeometryBuilder::build_as(&shape, mode, transform)
+        }
+    }
+}
+
+// Redraws the tiles which have changed hands. Unowned tiles are left to the
+// hover and forbidden color highlights, and new ones to their spawn tween
+fn update_tile_colors(
+    theme: Res<theme::Theme>,
+    state: Res<core::GameState>,
+    players: Query<&core::Player>,
+    mut tiles: Query<
+        (
+            &core::Tile,
+            ChangeTrackers<core::Tile>,
+            &mut DrawMode,
+            &mut Transform,
+        ),
+        Changed<core::Tile>,
+    >,
+) {
+    for (tile, tracker, mut mode, mut transform) in tiles.iter_mut() {
+        if tracker.is_added() || !matches!(tile.state, core::TileState::Owned(_)) {
+            continue;
+        }
+        if let Some((new_mode, z_pos)) = tile_appearance(&theme, &state, &players, &tile.state) {
+            *mode = new_mode;
+            transform.translation.z = z_pos;
+        }
+    }
+}
+
+// Carries out the decision of the player offered to swap sides, trading
+// their tiles and bonus points with the first mover's. The swapped tiles are
+// redrawn along with any other tiles changing hands
+fn resolve_swap(
+    mut state: ResMut<core::GameState>,
+    mut cache: ResMut<core::MoveCache>,
+    mut decisions: EventReader<core::SwapEvent>,
+    mut tiles: Query<&mut core::Tile>,
+    mut players: Query<&mut core::Player>,
+) {
+    let decision = match decisions
+        .iter()
+        .find(|decision| state.players.first() == Some(&decision.player))
+    {
+        Some(decision) => decision,
+        None => return,
+    };
+    state.phase = core::GamePhase::Running;
+    if !decision.swap {
+        info!("playing on without swapping sides");
+        return;
+    }
+
+    let (first, swapper) = match core::swap_sides(&mut state, tiles.iter_mut()) {
+        Some(sides) => sides,
+        None => return,
+    };
+    info!(board_hash = state.board_hash.value, "swapped sides");
+
+    let bonus = |player| players.get(player).map_or(0, |player| player.bonus);
+    let (first_bonus, swapper_bonus) = (bonus(first), bonus(swapper));
+    for (player, points) in [(first, swapper_bonus), (swapper, first_bonus)] {
+        if let Ok(mut player) = players.get_mut(player) {
+            player.bonus = points;
+        }
+    }
+    cache.stale = true;
+}
+
+fn clear_bonus_markers(
+    mut commands: Commands,
+    tiles: Query<(&core::Tile, &Children), Changed<core::Tile>>,
+    markers: Query<(), With<BonusMarker>>,
+) {
+    for (tile, children) in tiles.iter() {
+        if tile.bonus.is_some() {
+            continue;
+        }
+
+        for child in children.iter() {
+            if markers.get(*child).is_ok() {
+                commands.entity(*child).despawn();
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn game_start(
+    mut gamestate: ResMut<core::GameState>,
+    mut move_cache: ResMut<core::MoveCache>,
+    mut tally: ResMut<stats::GameTally>,
+    mut summary: ResMut<stats::GameSummary>,
+    mut tutorial: ResMut<tutorial::Tutorial>,
+    mut daily: ResMut<daily::DailyBoard>,
+    mut memo: ResMut<ai::EvaluationMemo>,
+    mut playback: ResMut<ai::Playback>,
+    mut board: ResMut<board::Board>,
+    mut countdown: ResMut<CountdownTimer>,
+    theme: Res<theme::Theme>,
+    asset_server: Res<AssetServer>,
+    textures: Res<batch::TileTextures>,
+    mut start_event: EventReader<GameStartEvent>,
+    mut commands: Commands,
+) {
+    for start_settings in start_event.iter() {
+        let ids = start_settings.players.clone().into_iter().map(|player| {
+            commands
+                .spawn()
+                .insert(player)
+                .insert(board::BoardEntity)
+                .id()
+        });
+
+        gamestate.phase = core::GamePhase::Countdown;
+        countdown.0.reset();
+        gamestate.ids = start_settings.ids.clone();
+        gamestate.players = ids.collect();
+        gamestate.topology = start_settings.level.topology;
+        gamestate.last_selections.clear();
+        gamestate.history.clear();
+        gamestate.rules = start_settings.rules.clone();
+        gamestate.moves = 0;
+        gamestate.moved_this_turn = false;
+        gamestate.pending_selections.clear();
+        gamestate.stalemate = false;
+        gamestate.seed = start_settings.seed;
+        gamestate.blind = start_settings.blind;
+        *move_cache = core::MoveCache::default();
+        *tally = stats::GameTally::default();
+        *summary = stats::GameSummary {
+            level: start_settings.level.name.to_string(),
+            ..default()
+        };
+        *tutorial = tutorial::Tutorial::new(start_settings.tutorial);
+        daily.0 = start_settings.daily;
+
+        // Mirrored boards are fair by design, and shared ones are played as
+        // they were shared
+        let (mut tiles, fairness) = if start_settings.mirrored || start_settings.colors.is_some() {
+            let tiles = core::load_level(
+                &start_settings.level.layout,
+                &gamestate.players,
+                gamestate.ids.keys().cloned().collect(),
+                start_settings.seed,
+            );
+            (tiles, None)
+        } else {
+            core::load_fair_level(
+                &start_settings.level.layout,
+                &gamestate.players,
+                gamestate.ids.keys().cloned().collect(),
+                start_settings.seed,
+                gamestate.topology,
+            )
+        };
+        if start_settings.mirrored {
+            core::mirror_colors(&mut tiles, gamestate.topology);
+        }
+        if let Some(colors) = &start_settings.colors {
+            share::apply_colors(&mut tiles, colors);
+        }
+        summary.fairness = fairness
+            .or_else(|| core::board_fairness(&tiles, gamestate.topology, &gamestate.players));
+        let code = share::BoardCode::new(
+            &tiles,
+            &gamestate.players,
+            gamestate.topology,
+            gamestate.ids.len() as u32,
+        )
+        .encode();
+        debug!(code = %code, "board code");
+        summary.board_code = Some(code);
+        if start_settings.virus {
+            let virus = commands
+                .spawn()
+                .insert(virus::virus_player())
+                .insert(virus::Virus)
+                .insert(board::BoardEntity)
+                .id();
+            virus::plant(&mut tiles, virus);
+        }
+
+        let handicapped = gamestate
+            .players
+            .get(start_settings.handicapped_player)
+            .cloned();
+        gamestate.double_mover = None;
+        match (start_settings.handicap, handicapped) {
+            (core::Handicap::ExtraTiles { rings }, Some(player)) => {
+                core::grow_start_region(&mut tiles, gamestate.topology, player, rings)
+            }
+            (core::Handicap::DoubleMove, Some(player)) => gamestate.double_mover = Some(player),
+            _ => (),
+        }
+        gamestate.track_board(&tiles);
+        memo.0.clear();
+        // A bot game left paused would otherwise keep the bots in this one
+        // from moving
+        *playback = ai::Playback::default();
+
+        let max_row = tiles.iter().map(|tile| tile.row).max();
+        let max_column = tiles.iter().map(|tile| tile.column).max();
+        let (max_row, max_column) = max_row
+            .zip(max_column)
+            .expect("Unable to get board dimensions");
+        *board = board::Board::new(gamestate.topology, max_row + 1, max_column + 1);
+
+        let board_radius = board.size().length() / 2.0;
+        let batched = batch::is_batched(
+            tiles
+                .iter()
+                .filter(|tile| !matches!(tile.state, core::TileState::Empty))
+                .count(),
+        );
+        for tile in tiles {
+            let row = tile.row;
+            let column = tile.column;
+
+            let (draw_mode, z_pos) = match tile.state {
+                core::TileState::Owned(id) => {
+                    // Tiles owned by anyone but the players belong to the virus
+                    let color = gamestate
+                        .players
+                        .iter()
+                        .position(|player_id| *player_id == id)
+                        .map_or(virus::VIRUS_COLOR, |idx| start_settings.players[idx].color);
+                    (theme.owned_tile_mode(color), 1.0)
+                }
+                core::TileState::Unowned(id) => (theme.tile_mode(gamestate.ids[&id], false), 0.0),
+                core::TileState::Wall => (theme.tile_mode(theme.wall_color(), false), 0.0),
+                core::TileState::Empty => {
+                    commands.spawn().insert(tile).insert(board::BoardEntity);
+                    continue;
+                }
+            };
+
+            let transform =
+                Transform::from_translation(board.tile_to_world(row, column).extend(z_pos));
+
+            // Tiles grow in from the center of the board outwards
+            let spawn_delay =
+                BOARD_SPAWN_TIME * transform.translation.truncate().length() / board_radius;
+            let tween = tween::Tween::new(spawn_delay, TILE_SPAWN_TIME)
+                .fade_to(draw_mode)
+                .scale_to(Vec3::ONE);
+
+            let (initial_mode, initial_transform) = (
+                tween.initial_mode(draw_mode),
+                tween.initial_transform(transform),
+            );
+            let mut tile_entity = if batched {
+                let mut tile_entity = commands.spawn_bundle(batch::tile_sprite_bundle(
+                    &board,
+                    &textures,
+                    initial_mode,
+                    initial_transform,
+                ));
+                tile_entity.insert(initial_mode).insert(batch::BatchedTile);
+                tile_entity
+            } else {
+                commands.spawn_bundle(tile_shape_bundle(&board, initial_mode, initial_transform))
+            };
+            board.tiles.insert((row, column), tile_entity.id());
+            tile_entity
+                .with_children(|parent| {
+                    // Tiles worth more than one are labeled with their weight,
+                    // kept upright however the tile is turned
+                    if tile.weight > 1 {
+                        let upright = match board.topology {
+                            core::BoardTopology::Hex if !batched => {
+                                Quat::from_rotation_z(-PI as f32 / 6.0)
+                            }
+                            _ => Quat::IDENTITY,
+                        };
+                        parent.spawn_bundle(Text2dBundle {
+                            text: Text::with_section(
+                                tile.weight.to_string(),
+                                TextStyle {
+                                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                    font_size: board.tile_radius,
+                                    color: Color::WHITE,
+                                },
+                                TextAlignment {
+                                    vertical: VerticalAlign::Center,
+                                    horizontal: HorizontalAlign::Center,
+                                },
+                            ),
+                            transform: Transform::from_xyz(0.0, 0.0, 0.6).with_rotation(upright),
+                            ..default()
+                        });
+                    }
+
+                    let marker_mode = match tile.bonus {
+                        Some(core::TileBonus::ExtraTurn) => {
+                            DrawMode::Fill(FillMode::color(Color::WHITE))
+                        }
+                        Some(core::TileBonus::Points(_)) => {
+                            DrawMode::Stroke(StrokeMode::new(Color::WHITE, 2.0))
+                        }
+                        Some(core::TileBonus::Hill) => DrawMode::Outlined {
+                            fill_mode: FillMode::color(Color::GOLD),
+                            outline_mode: StrokeMode::new(Color::WHITE, 1.0),
+                        },
+                        None => return,
+                    };
+                    let marker = shapes::Circle {
+                        radius: TILE_RADIUS / 3.0,
+                        ..shapes::Circle::default()
+                    };
+                    let marker_tween =
+                        tween::Tween::new(spawn_delay, TILE_SPAWN_TIME).fade_to(marker_mode);
+                    parent
+                        .spawn_bundle(GeometryBuilder::build_as(
+                            &marker,
+                            marker_tween.initial_mode(marker_mode),
+                            Transform::from_xyz(0.0, 0.0, 0.5),
+                        ))
+                        .insert(marker_tween)
+                        .insert(BonusMarker);
+                })
+                .insert(tween)
+                .insert(tile)
+                .insert(board::BoardEntity);
+        }
+    }
+}
+
+// Everything spawned for a game, from the board to the scoreboard
+pub type GameEntities<'w, 's> =
+    Query<'w, 's, Entity, Or<(With<board::BoardEntity>, With<crate::ui::GameUi>)>>;
+
+// Removes the board and scoreboard of the previous game. The game is put back
+// into configuration, so nothing keeps running against the removed players
+pub fn clear_game(
+    mut state: ResMut<core::GameState>,
+    mut board: ResMut<board::Board>,
+    entities: GameEntities,
+    mut commands: Commands,
+) {
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    state.phase = core::GamePhase::Config;
+    *board = board::Board::default();
+}
+
+fn clear_game_on_start(
+    mut start_event: EventReader<GameStartEvent>,
+    state: ResMut<core::GameState>,
+    board: ResMut<board::Board>,
+    entities: GameEntities,
+    commands: Commands,
+) {
+    if start_event.iter().count() > 0 {
+        clear_game(state, board, entities, commands);
+    }
+}
+
+// Zooms the camera so the whole board fits in the window, unless that would
+// leave its tiles too small to see
+fn fit_board_to_window(
+    mut view: ResMut<BoardView>,
+    board: Res<board::Board>,
+    windows: Res<Windows>,
+    mut cameras: Query<&mut OrthographicProjection, With<BoardCamera>>,
+) {
+    let window = windows.primary();
+    let size = board.size();
+    if board.tiles.is_empty() || window.width() <= 0.0 || window.height() <= 0.0 {
+        return;
+    }
+
+    let scale = (size.x / (window.width() * BOARD_MARGIN))
+        .max(size.y / (window.height() * BOARD_MARGIN))
+        .max(1.0 / MAX_BOARD_ZOOM)
+        .min(board.tile_radius / MIN_TILE_RADIUS);
+    if (scale - view.scale).abs() < f32::EPSILON {
+        return;
+    }
+
+    view.scale = scale;
+    for mut projection in cameras.iter_mut() {
+        projection.scale = scale;
+    }
+}
+
+pub fn run_if_game_started(state: Res<core::GameState>) -> ShouldRun {
+    match state.phase {
+        core::GamePhase::Running => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
+
+fn run_step_if_game_started(In(step): In<ShouldRun>, state: Res<core::GameState>) -> ShouldRun {
+    match state.phase {
+        core::GamePhase::Running => step,
+        _ => ShouldRun::No,
+    }
+}
+
+pub fn run_if_game_over(state: Res<core::GameState>) -> ShouldRun {
+    match state.phase {
+        core::GamePhase::Over(_) | core::GamePhase::Draw => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
+
+pub fn run_if_counting_down(state: Res<core::GameState>) -> ShouldRun {
+    match state.phase {
+        core::GamePhase::Countdown => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
+
+pub fn run_if_paused(state: Res<core::GameState>) -> ShouldRun {
+    match state.phase {
+        core::GamePhase::Paused => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
+
+pub fn run_if_swap_offered(state: Res<core::GameState>) -> ShouldRun {
+    match state.phase {
+        core::GamePhase::Swap => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
+
+// The colors of a blind game go gray as soon as it starts
+fn update_countdown(
+    time: Res<Time>,
+    theme: Res<theme::Theme>,
+    mut countdown: ResMut<CountdownTimer>,
+    mut state: ResMut<core::GameState>,
+    mut tiles: Query<(&core::Tile, &mut DrawMode)>,
+) {
+    if !countdown.0.tick(time.delta()).finished() {
+        return;
+    }
+    state.phase = core::GamePhase::Running;
+    if !state.blind {
+        return;
+    }
+    for (tile, mut mode) in tiles.iter_mut() {
+        if let core::TileState::Unowned(id) = tile.state {
+            *mode = theme.tile_mode(unowned_tile_color(&state, id), false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn blind_games_hide_colors_once_started() {
+        let mut state = core::GameState {
+            players: vec![Entity::from_raw(0)],
+            ids: [(0, Color::RED)].into_iter().collect(),
+            phase: core::GamePhase::Countdown,
+            blind: true,
+            ..default()
+        };
+        assert_eq!(unowned_tile_color(&state, 0), Color::RED);
+
+        state.phase = core::GamePhase::Running;
+        assert_eq!(unowned_tile_color(&state, 0), BLIND_TILE_COLOR);
+        state.blind = false;
+        assert_eq!(unowned_tile_color(&state, 0), Color::RED);
+    }
+}
@@ -0,0 +1,210 @@
+// Coordinates on hex boards. Tiles are stored by row and column, with even
+// rows shifted over by half a tile, but hex math is simpler in axial
+// coordinates, where q runs along the rows and r down them. Neighbors, distances
+// and rotations are all worked out there and turned back into rows and columns
+
+// The six steps to a neighboring hex, going around clockwise from the right
+pub const DIRECTIONS: [Axial; 6] = [
+    Axial::new(1, 0),
+    Axial::new(0, 1),
+    Axial::new(-1, 1),
+    Axial::new(-1, 0),
+    Axial::new(0, -1),
+    Axial::new(1, -1),
+];
+
+// The same steps as row and column offsets, which depend on whether the row is
+// shifted
+const SHIFTED_OFFSETS: [(i32, i32); 6] = [(-1, 0), (-1, 1), (0, -1), (0, 1), (1, 0), (1, 1)];
+const UNSHIFTED_OFFSETS: [(i32, i32); 6] = [(-1, -1), (-1, 0), (0, -1), (0, 1), (1, -1), (1, 0)];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Axial {
+    pub q: i32,
+    pub r: i32,
+}
+
+impl Axial {
+    pub const fn new(q: i32, r: i32) -> Self {
+        Self { q, r }
+    }
+
+    pub fn from_offset(row: i32, column: i32) -> Self {
+        Self::new(column - (row + (row & 1)) / 2, row)
+    }
+
+    // The row and column of the hex
+    pub fn to_offset(self) -> (i32, i32) {
+        (self.r, self.q + (self.r + (self.r & 1)) / 2)
+    }
+
+    pub fn neighbors(self) -> impl Iterator<Item = Axial> {
+        DIRECTIONS
+            .into_iter()
+            .map(move |direction| self + direction)
+    }
+
+    // The fewest steps between the two hexes, ignoring what's on the board
+    pub fn distance(self, other: Axial) -> u32 {
+        let Axial { q, r } = self - other;
+        (q.unsigned_abs() + r.unsigned_abs() + (q + r).unsigned_abs()) / 2
+    }
+
+    // The hex the same distance away on the other side of the center
+    pub fn rotate_half(self, center: Axial) -> Axial {
+        center + center - self
+    }
+
+    // Every hex the given distance away, going around clockwise from the top
+    // left corner
+    pub fn ring(self, radius: u32) -> impl Iterator<Item = Axial> {
+        let start = self + DIRECTIONS[4] * radius as i32;
+        let steps = DIRECTIONS
+            .into_iter()
+            .flat_map(move |direction| core::iter::repeat(direction).take(radius as usize));
+        let around = steps.scan(start, |hex, step| {
+            let current = *hex;
+            *hex = *hex + step;
+            Some(current)
+        });
+        // Only the center itself is no distance away
+        core::iter::once(self)
+            .filter(move |_| radius == 0)
+            .chain(around)
+    }
+
+    // Every hex within the given distance, nearest rings first
+    pub fn spiral(self, radius: u32) -> impl Iterator<Item = Axial> {
+        (0..=radius).flat_map(move |ring| self.ring(ring))
+    }
+
+    // The center of the hex, in units of the hexes' outer radius. Rows go down
+    #[cfg(feature = "std")]
+    pub fn to_pixel(self) -> (f32, f32) {
+        let width = 3.0_f32.sqrt();
+        (
+            width * (self.q as f32 + self.r as f32 / 2.0),
+            1.5 * self.r as f32,
+        )
+    }
+
+    // The hex covering the given point, in the units of to_pixel
+    #[cfg(feature = "std")]
+    pub fn from_pixel(x: f32, y: f32) -> Self {
+        let r = y / 1.5;
+        let q = x / 3.0_f32.sqrt() - r / 2.0;
+        round(q, r)
+    }
+}
+
+impl core::ops::Add for Axial {
+    type Output = Axial;
+
+    fn add(self, other: Axial) -> Axial {
+        Axial::new(self.q + other.q, self.r + other.r)
+    }
+}
+
+impl core::ops::Sub for Axial {
+    type Output = Axial;
+
+    fn sub(self, other: Axial) -> Axial {
+        Axial::new(self.q - other.q, self.r - other.r)
+    }
+}
+
+impl core::ops::Mul<i32> for Axial {
+    type Output = Axial;
+
+    fn mul(self, scale: i32) -> Axial {
+        Axial::new(self.q * scale, self.r * scale)
+    }
+}
+
+// Rounds fractional axial coordinates to the hex containing them
+#[cfg(feature = "std")]
+fn round(q: f32, r: f32) -> Axial {
+    let s = -q - r;
+    let (mut rounded_q, mut rounded_r, rounded_s) = (q.round(), r.round(), s.round());
+    let (q_diff, r_diff, s_diff) = (
+        (rounded_q - q).abs(),
+        (rounded_r - r).abs(),
+        (rounded_s - s).abs(),
+    );
+
+    // The coordinate furthest from its rounded value is derived from the
+    // other two, keeping q + r + s = 0
+    if q_diff > r_diff && q_diff > s_diff {
+        rounded_q = -rounded_r - rounded_s;
+    } else if r_diff > s_diff {
+        rounded_r = -rounded_q - rounded_s;
+    }
+    Axial::new(rounded_q as i32, rounded_r as i32)
+}
+
+// Even rows are the ones shifted over by half a tile
+pub fn is_shifted(row: i32) -> bool {
+    row & 1 == 0
+}
+
+// The column counted in half tiles, which lines up the tiles of every row
+pub fn half_column(row: i32, column: i32) -> i32 {
+    column * 2 + is_shifted(row) as i32
+}
+
+// The row and column offsets to each neighbor of a tile in the given row
+pub fn neighbor_offsets(row: i32) -> &'static [(i32, i32); 6] {
+    match is_shifted(row) {
+        true => &SHIFTED_OFFSETS,
+        false => &UNSHIFTED_OFFSETS,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn offsets_agree_with_axial_neighbors() {
+        for (row, column) in [(0, 0), (1, 0), (4, 3), (7, 5), (-1, 2), (-2, -3)] {
+            let hex = Axial::from_offset(row, column);
+            assert_eq!(hex.to_offset(), (row, column));
+
+            let mut axial = hex.neighbors().map(Axial::to_offset).collect::<Vec<_>>();
+            axial.sort();
+            let offsets = neighbor_offsets(row)
+                .iter()
+                .map(|(row_offset, column_offset)| (row + row_offset, column + column_offset))
+                .collect::<Vec<_>>();
+            assert_eq!(axial, offsets);
+            assert!(hex.neighbors().all(|neighbor| hex.distance(neighbor) == 1));
+        }
+
+        // Turning around the center keeps the half columns lined up
+        let center = Axial::from_offset(6, 6);
+        let (row, column) = Axial::from_offset(3, 2).rotate_half(center).to_offset();
+        assert_eq!(
+            (row, half_column(row, column)),
+            (9, 2 * half_column(6, 6) - half_column(3, 2))
+        );
+    }
+
+    #[test]
+    fn rings_and_spirals_cover_each_distance() {
+        let center = Axial::new(2, -3);
+        assert_eq!(center.ring(0).collect::<Vec<_>>(), vec![center]);
+        for radius in 1..5 {
+            let ring = center.ring(radius).collect::<Vec<_>>();
+            assert_eq!(ring.len(), 6 * radius as usize);
+            assert!(ring.iter().all(|hex| hex.distance(center) == radius));
+            // Each hex steps to the next, all the way around
+            let next = ring.iter().cycle().skip(1);
+            assert!(ring.iter().zip(next).all(|(a, b)| a.distance(*b) == 1));
+        }
+
+        let spiral = center.spiral(3).collect::<BTreeSet<_>>();
+        assert_eq!(spiral.len(), 1 + 6 + 12 + 18);
+        assert!(spiral.iter().all(|hex| hex.distance(center) <= 3));
+    }
+}
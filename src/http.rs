@@ -0,0 +1,192 @@
+// Plain HTTP requests which never hold up a frame. Natively each request is
+// made on a thread of its own, and only http:// addresses are understood. In
+// the browser they're left to XMLHttpRequest
+
+#[cfg(not(target_family = "wasm"))]
+use std::io::{Read, Write};
+#[cfg(not(target_family = "wasm"))]
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(not(target_family = "wasm"))]
+use std::sync::{mpsc, Mutex};
+#[cfg(not(target_family = "wasm"))]
+use std::time::Duration;
+
+#[cfg(not(target_family = "wasm"))]
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+pub struct Request {
+    #[cfg(not(target_family = "wasm"))]
+    response: Mutex<mpsc::Receiver<Result<Response, String>>>,
+    #[cfg(target_family = "wasm")]
+    xhr: web_sys::XmlHttpRequest,
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl Request {
+    pub fn send(method: &str, url: &str, body: Option<String>) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or("Only http:// addresses are supported")?;
+        let (host, path) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx..]),
+            None => (rest, "/"),
+        };
+        let address = match host.contains(':') {
+            true => host.to_string(),
+            false => format!("{}:80", host),
+        };
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            host,
+            body.as_ref().map_or(0, |body| body.len()),
+            body.unwrap_or_default()
+        );
+
+        let (sender, response) = mpsc::channel();
+        std::thread::spawn(move || {
+            let exchange = || -> std::io::Result<Vec<u8>> {
+                let address = address.to_socket_addrs()?.next().ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::NotFound, "Unknown host")
+                })?;
+                let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+                stream.set_read_timeout(Some(TIMEOUT))?;
+                stream.write_all(request.as_bytes())?;
+                let mut received = vec![];
+                stream.read_to_end(&mut received)?;
+                Ok(received)
+            };
+            let response = exchange()
+                .map_err(|e| format!("Unable to reach the server: {}", e))
+                .and_then(|received| parse_response(&received));
+            // Nobody is waiting if the request was dropped
+            let _ = sender.send(response);
+        });
+        Ok(Self {
+            response: Mutex::new(response),
+        })
+    }
+
+    // The response, once it has arrived
+    pub fn poll(&self) -> Option<Result<Response, String>> {
+        let response = self.response.lock().ok()?;
+        match response.try_recv() {
+            Ok(response) => Some(response),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err("The request failed".into())),
+        }
+    }
+}
+
+// The game runs on the browser's one thread, so requests are never actually
+// shared between threads
+#[cfg(target_family = "wasm")]
+unsafe impl Send for Request {}
+#[cfg(target_family = "wasm")]
+unsafe impl Sync for Request {}
+
+#[cfg(target_family = "wasm")]
+impl Request {
+    pub fn send(method: &str, url: &str, body: Option<String>) -> Result<Self, String> {
+        let failed = |e| format!("Unable to send the request: {:?}", e);
+        let xhr = web_sys::XmlHttpRequest::new().map_err(failed)?;
+        xhr.open(method, url).map_err(failed)?;
+        xhr.send_with_opt_str(body.as_deref()).map_err(failed)?;
+        Ok(Self { xhr })
+    }
+
+    // The response, once it has arrived
+    pub fn poll(&self) -> Option<Result<Response, String>> {
+        if self.xhr.ready_state() != web_sys::XmlHttpRequest::DONE {
+            return None;
+        }
+        // Requests which never got an answer are left without a status
+        Some(match self.xhr.status() {
+            Ok(0) | Err(_) => Err("Unable to reach the server".into()),
+            Ok(status) => Ok(Response {
+                status,
+                body: self.xhr.response_text().ok().flatten().unwrap_or_default(),
+            }),
+        })
+    }
+}
+
+// The status and body of a whole HTTP/1.1 response
+#[cfg(not(target_family = "wasm"))]
+fn parse_response(received: &[u8]) -> Result<Response, String> {
+    let malformed = || "Malformed response from the server".to_string();
+    let text = String::from_utf8_lossy(received);
+    let (head, body) = text.split_once("\r\n\r\n").ok_or_else(malformed)?;
+    let mut lines = head.lines();
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(malformed)?;
+    let chunked = lines.any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+    if !chunked {
+        return Ok(Response {
+            status,
+            body: body.to_string(),
+        });
+    }
+
+    // Each chunk is preceded by its length in hex, ending with an empty one
+    let mut rest = body;
+    let mut body = String::new();
+    loop {
+        let (size, after) = rest.split_once("\r\n").ok_or_else(malformed)?;
+        let size = usize::from_str_radix(size.split(';').next().unwrap_or("").trim(), 16)
+            .map_err(|_| malformed())?;
+        if size == 0 {
+            break;
+        }
+        body.push_str(after.get(..size).ok_or_else(malformed)?);
+        rest = after
+            .get(size..)
+            .and_then(|rest| rest.strip_prefix("\r\n"))
+            .ok_or_else(malformed)?;
+    }
+    Ok(Response { status, body })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn responses_are_read_whole() {
+        let plain = b"HTTP/1.1 409 Conflict\r\ncontent-length: 5\r\n\r\nTaken";
+        assert_eq!(
+            parse_response(plain),
+            Ok(Response {
+                status: 409,
+                body: "Taken".into()
+            })
+        );
+
+        let chunked =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n(a:1\r\n1\r\n)\r\n0\r\n\r\n";
+        let response = parse_response(chunked).unwrap();
+        assert!(response.is_success());
+        assert_eq!(response.body, "(a:1)");
+
+        assert!(parse_response(b"nonsense").is_err());
+    }
+}
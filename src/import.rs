@@ -0,0 +1,129 @@
+use crate::palette;
+use bevy::prelude::*;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+// The widest and tallest image which can be turned into a level, in pixels
+pub const MAX_IMAGE_SIZE: u32 = 128;
+// Pixels less opaque than this leave a gap in the board
+const MIN_ALPHA: u8 = 128;
+
+// A level drawn in a paint program, along with the color picked for each of
+// its colored tiles in the order they're loaded
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportedLevel {
+    pub layout: String,
+    pub num_ids: u32,
+    pub colors: Vec<u32>,
+}
+
+// Every pixel of the image becomes a tile, colored whichever of the ids'
+// colors is closest to it. Transparent pixels are left without a tile. The
+// players start on the first and last tiles, which can be moved in the editor
+pub fn level_from_png(png: &[u8], ids: &BTreeMap<u32, Color>) -> Result<ImportedLevel, String> {
+    let (width, rgba) = decode_png(png)?;
+    let pixels = rgba.chunks_exact(4).collect::<Vec<_>>();
+    let opaque = |pixel: &&[u8]| pixel[3] >= MIN_ALPHA;
+    let first = pixels.iter().position(opaque);
+    let last = pixels.iter().rposition(opaque);
+    let (first, last) = match first.zip(last) {
+        Some((first, last)) if first != last => (first, last),
+        _ => return Err("The image needs at least two opaque pixels".into()),
+    };
+
+    let mut layout = String::new();
+    let mut colors = vec![];
+    for (index, pixel) in pixels.iter().enumerate() {
+        if index > 0 {
+            layout.push(if index % width as usize == 0 {
+                '\n'
+            } else {
+                ' '
+            });
+        }
+        if index == first {
+            layout.push('1');
+        } else if index == last {
+            layout.push('2');
+        } else if opaque(pixel) {
+            let color = Color::rgb_u8(pixel[0], pixel[1], pixel[2]);
+            let id = ids
+                .iter()
+                .min_by(|a, b| {
+                    palette::contrast(color, *a.1)
+                        .partial_cmp(&palette::contrast(color, *b.1))
+                        .unwrap_or(Ordering::Equal)
+                })
+                .map(|(id, _)| *id)
+                .ok_or("No colors to pick from")?;
+            layout.push('|');
+            colors.push(id);
+        } else {
+            layout.push('-');
+        }
+    }
+
+    Ok(ImportedLevel {
+        layout,
+        num_ids: ids.len() as u32,
+        colors,
+    })
+}
+
+// The image's width and its pixels as 8 bit RGBA, whatever format it was
+// saved in
+fn decode_png(png: &[u8]) -> Result<(u32, Vec<u8>), String> {
+    let invalid = |e: png::DecodingError| format!("Unable to read the image: {}", e);
+    let mut decoder = png::Decoder::new(png);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info().map_err(invalid)?;
+    if info.width > MAX_IMAGE_SIZE || info.height > MAX_IMAGE_SIZE {
+        return Err(format!(
+            "The image is {}x{}, larger than {}x{}",
+            info.width, info.height, MAX_IMAGE_SIZE, MAX_IMAGE_SIZE
+        ));
+    }
+    let mut buffer = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buffer).map_err(invalid)?;
+
+    let rgba = match reader.output_color_type().0 {
+        png::ColorType::RGBA => buffer,
+        png::ColorType::RGB => buffer
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], u8::MAX])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|gray| [gray[0], gray[0], gray[0], gray[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer
+            .iter()
+            .flat_map(|gray| [*gray, *gray, *gray, u8::MAX])
+            .collect(),
+        png::ColorType::Indexed => return Err("Unable to read the image's palette".into()),
+    };
+    Ok((info.width, rgba))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::export::encode_png;
+
+    #[test]
+    fn pixels_become_tiles() {
+        let (red, clear, blue) = ([250, 10, 10, 255], [0, 0, 0, 0], [20, 20, 240, 255]);
+        let rgba = [red, red, clear, blue, blue, red].concat();
+        let png = encode_png(3, 2, &rgba).unwrap();
+        let ids = BTreeMap::from([(0, Color::BLUE), (1, Color::RED)]);
+
+        let level = level_from_png(&png, &ids).unwrap();
+        assert_eq!(level.layout, "1 | -\n| | 2");
+        assert_eq!(level.colors, vec![1, 0, 0]);
+        assert_eq!(level.num_ids, 2);
+
+        let empty = encode_png(1, 1, &clear).unwrap();
+        assert!(level_from_png(&empty, &ids).is_err());
+        assert!(level_from_png(b"not an image", &ids).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use crate::{bindings, board, core, cursor, game, overlay, theme, tutorial};
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use std::collections::BTreeSet;
+
+// The minimum time between hover highlight updates, in seconds
+const HOVER_INTERVAL: f32 = 0.03;
+
+// The tiles the hovered color would capture, if any. The set is kept for the
+// hovered color so moving within the same group doesn't redo the flood fill
+pub struct HoverPreview {
+    pub captures: Option<usize>,
+    hovered: Option<u32>,
+    highlighted: BTreeSet<(i32, i32)>,
+    // The latest cursor position, waiting for the cooldown to finish
+    pending: Option<Vec2>,
+    cooldown: Timer,
+}
+
+impl Default for HoverPreview {
+    fn default() -> Self {
+        Self {
+            captures: None,
+            hovered: None,
+            highlighted: BTreeSet::new(),
+            pending: None,
+            cooldown: Timer::from_seconds(HOVER_INTERVAL, false),
+        }
+    }
+}
+
+impl HoverPreview {
+    fn clear(&mut self) {
+        self.captures = None;
+        self.hovered = None;
+        self.highlighted.clear();
+    }
+}
+
+// A pick waiting for the next fixed step. Clicks only register for a frame,
+// and at high framerates several frames can pass between steps, by which
+// time an event sent from the click would have been dropped
+#[derive(Default)]
+pub struct PendingSelection(Option<core::SelectEvent>);
+
+// Turns the cursor and keyboard input into moves and previews
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HoverPreview>()
+            .init_resource::<PendingSelection>()
+            .add_system(toggle_pause)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::run_if_game_started)
+                    .with_system(hover_tile.after(cursor::UpdateCursor))
+                    .with_system(
+                        select_tile
+                            .after(cursor::UpdateCursor)
+                            .before(send_selection),
+                    )
+                    .with_system(overlay::toggle_heat_map),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::GameStep)
+                    .with_system(send_selection.before(core::perform_selection)),
+            );
+    }
+}
+
+fn send_selection(
+    mut pending: ResMut<PendingSelection>,
+    mut selections: EventWriter<core::SelectEvent>,
+) {
+    if let Some(selection) = pending.0.take() {
+        selections.send(selection);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn select_tile(
+    state: Res<core::GameState>,
+    tutorial: Res<tutorial::Tutorial>,
+    mut pending: ResMut<PendingSelection>,
+    mut preview: ResMut<HoverPreview>,
+    cursor: Res<cursor::Cursor>,
+    board: Res<board::Board>,
+    players: Query<(Entity, &core::Player)>,
+    mut tiles: Query<&mut core::Tile>,
+) {
+    if state.phase.is_finished() {
+        return;
+    }
+
+    let player = players.get(state.players[0]).expect("Missing player");
+    if !matches!(player.1.kind, core::PlayerKind::Human) {
+        return;
+    }
+
+    if cursor.pressed {
+        let position = match cursor.position {
+            Some(position) => position,
+            None => return,
+        };
+
+        let tile = match board
+            .tile_at(position)
+            .and_then(|tile| tiles.get(tile).ok())
+        {
+            Some(tile) => tile.clone(),
+            None => return,
+        };
+
+        match tile.state {
+            core::TileState::Unowned(id) => {
+                if !state.is_selection_allowed(player.0, id) || !tutorial.allows(id) {
+                    return;
+                }
+
+                let mut valid = false;
+                core::for_each_selected_tile(
+                    tiles.iter_mut().collect(),
+                    state.topology,
+                    id,
+                    state.players[0],
+                    |valid_tile| {
+                        if valid_tile.row == tile.row && valid_tile.column == tile.column {
+                            valid = true;
+                        }
+                    },
+                );
+                if valid {
+                    preview.clear();
+                    pending.0 = Some(core::SelectEvent {
+                        id,
+                        player: player.0,
+                    })
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn hover_tile(
+    state: Res<core::GameState>,
+    theme: Res<theme::Theme>,
+    time: Res<Time>,
+    cache: Res<core::MoveCache>,
+    players: Query<&core::Player>,
+    cursor: Res<cursor::Cursor>,
+    mut tiles: Query<(&core::Tile, &mut DrawMode, &mut Transform)>,
+    mut preview: ResMut<HoverPreview>,
+    board: Res<board::Board>,
+) {
+    if state.phase.is_finished() {
+        return;
+    }
+
+    let (player_id, player_color) = match players.get(state.players[0]) {
+        Ok(player) => match player.kind {
+            core::PlayerKind::Human => (state.players[0], player.color),
+            _ => return,
+        },
+        Err(_) => return,
+    };
+
+    // Only the latest cursor position matters, and it is only looked at once
+    // the cooldown has finished
+    if cursor.is_changed() {
+        if let Some(position) = cursor.position {
+            preview.pending = Some(position);
+        }
+    }
+    preview.cooldown.tick(time.delta());
+    let mouse = match preview.pending {
+        Some(mouse) if preview.cooldown.finished() => mouse,
+        _ => return,
+    };
+    preview.pending = None;
+    preview.cooldown.reset();
+
+    // Any move invalidates the cached capture set
+    if cache.is_changed() {
+        preview.hovered = None;
+    }
+
+    let hover_info = board
+        .tile_at(mouse)
+        .and_then(|tile| tiles.get(tile).ok())
+        .and_then(|tile| match tile.0.state {
+            core::TileState::Unowned(id) if state.is_selection_allowed(player_id, id) => {
+                Some((id, tile.0.row, tile.0.column))
+            }
+            _ => None,
+        });
+
+    let mut selected_tiles = BTreeSet::new();
+    if let Some((id, row, column)) = hover_info {
+        if preview.hovered == Some(id) && preview.highlighted.contains(&(row, column)) {
+            return;
+        }
+
+        let mut board = tiles.iter().map(|t| t.0.clone()).collect::<Vec<_>>();
+        core::for_each_selected_tile(
+            board.iter_mut().collect(),
+            state.topology,
+            id,
+            player_id,
+            |tile| {
+                selected_tiles.insert((tile.row, tile.column));
+            },
+        );
+
+        if !selected_tiles.contains(&(row, column)) {
+            selected_tiles.clear();
+        }
+    }
+
+    let mut highlight_color = player_color.as_hsla();
+    if let Color::Hsla {
+        ref mut saturation,
+        ref mut lightness,
+        ..
+    } = highlight_color
+    {
+        *lightness = 0.6;
+        *saturation = 0.6;
+    }
+
+    // Only tiles entering or leaving the highlighted set are redrawn
+    for (tile, mut mode, mut transform) in tiles.iter_mut() {
+        let position = (tile.row, tile.column);
+        let highlighted = selected_tiles.contains(&position);
+        if highlighted == preview.highlighted.contains(&position) {
+            continue;
+        }
+
+        let (new_mode, z_pos) = match tile.state {
+            // Blind games show nothing else of the colors
+            core::TileState::Unowned(id) if highlighted && state.blind => {
+                (theme.tile_mode(state.ids[&id], true), 1.0)
+            }
+            core::TileState::Unowned(_) if highlighted => {
+                (theme.tile_mode(highlight_color, true), 1.0)
+            }
+            core::TileState::Unowned(id) => (
+                theme.tile_mode(game::unowned_tile_color(&state, id), false),
+                0.0,
+            ),
+            core::TileState::Owned(owner) => match players.get(owner) {
+                Ok(owner) => (theme.owned_tile_mode(owner.color), 1.0),
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+        *mode = new_mode;
+        transform.translation.z = z_pos;
+    }
+
+    preview.captures = (!selected_tiles.is_empty()).then(|| selected_tiles.len());
+    preview.hovered = hover_info
+        .filter(|_| !selected_tiles.is_empty())
+        .map(|(id, _, _)| id);
+    preview.highlighted = selected_tiles;
+}
+
+// Pauses and resumes a game in progress
+fn toggle_pause(input: bindings::ActionInput, mut state: ResMut<core::GameState>) {
+    if !input.just_pressed(bindings::Action::Pause) {
+        return;
+    }
+
+    state.phase = match state.phase {
+        core::GamePhase::Running => core::GamePhase::Paused,
+        core::GamePhase::Paused => core::GamePhase::Running,
+        _ => return,
+    };
+}
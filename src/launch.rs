@@ -0,0 +1,178 @@
+use crate::ai::Personality;
+use crate::{levels, palette};
+
+const USAGE: &str = "usage: assimilation [--level <name>] [--colors <count>] \
+    [--bot <personality|easy|normal|hard>] [--seed <number>] [--streamer] [--help]\n\
+    Flags can also be given as --flag=value";
+
+// How the game was asked to start: given as flags natively, and as the query
+// string of the page's URL in the browser (?level=Hexagon&colors=5). Giving
+// any of the game's settings skips the title screen
+#[derive(Clone, Default)]
+pub struct LaunchOptions {
+    pub level: Option<&'static levels::Level>,
+    pub num_ids: Option<u32>,
+    pub personality: Option<Personality>,
+    pub seed: Option<u64>,
+    pub streamer: bool,
+}
+
+impl LaunchOptions {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        // --flag=value is split into the flag and its value
+        let mut args = args.iter().flat_map(|arg| match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => vec![flag.into(), value.into()],
+            _ => vec![arg.clone()],
+        });
+        while let Some(arg) = args.next() {
+            if arg == "--streamer" {
+                options.streamer = true;
+                continue;
+            }
+            let value: &str = &args
+                .next()
+                .ok_or_else(|| format!("Missing value for {}\n{}", arg, USAGE))?;
+            match arg.as_str() {
+                "--level" => {
+                    options.level = Some(
+                        levels::all()
+                            .find(|level| level.name.eq_ignore_ascii_case(value))
+                            .ok_or_else(|| format!("Unknown level '{}'", value))?,
+                    )
+                }
+                "--colors" => {
+                    options.num_ids = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|num_ids| (2..=palette::MAX_IDS).contains(num_ids))
+                            .ok_or_else(|| format!("Invalid number of colors '{}'", value))?,
+                    )
+                }
+                "--bot" => {
+                    options.personality =
+                        Some(bot(value).ok_or_else(|| format!("Unknown bot '{}'", value))?)
+                }
+                "--seed" => {
+                    options.seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid seed '{}'", value))?,
+                    )
+                }
+                _ => return Err(format!("Unknown option {}\n{}", arg, USAGE)),
+            }
+        }
+        Ok(options)
+    }
+
+    // The query string's pairs are read as the flags of the same name
+    #[cfg(any(target_family = "wasm", test))]
+    pub fn from_query(query: &str) -> Result<Self, String> {
+        let args = query
+            .trim_start_matches('?')
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .flat_map(|pair| match pair.split_once('=') {
+                Some((key, value)) => vec![
+                    format!("--{}", key),
+                    crate::link::unescape(&value.replace('+', "%20"))
+                        .unwrap_or_else(|| value.into()),
+                ],
+                None => vec![format!("--{}", pair)],
+            })
+            .collect::<Vec<_>>();
+        Self::parse(&args)
+    }
+
+    pub fn starts_game(&self) -> bool {
+        self.level.is_some()
+            || self.num_ids.is_some()
+            || self.personality.is_some()
+            || self.seed.is_some()
+    }
+}
+
+// Bots are picked by personality, or by how hard they are to beat
+fn bot(name: &str) -> Option<Personality> {
+    match name.to_ascii_lowercase().as_str() {
+        "easy" => Some(Personality::Expansionist),
+        "normal" => Some(Personality::Aggressive),
+        "hard" => Some(Personality::Deep),
+        _ => Personality::ALL
+            .into_iter()
+            .find(|personality| personality.name().eq_ignore_ascii_case(name)),
+    }
+}
+
+// Anything else on the command line is left to game links and subcommands.
+// Options which can't be understood stop the game from starting at all, so a
+// typo isn't mistaken for the options being taken
+#[cfg(not(target_family = "wasm"))]
+pub fn read() -> LaunchOptions {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.first().map_or(false, |arg| arg.starts_with('-')) {
+        return LaunchOptions::default();
+    }
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        std::process::exit(0);
+    }
+    LaunchOptions::parse(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    })
+}
+
+#[cfg(target_family = "wasm")]
+pub fn read() -> LaunchOptions {
+    let query = web_sys::window().and_then(|window| window.location().search().ok());
+    match query.as_deref().map(LaunchOptions::from_query) {
+        Some(Ok(options)) => options,
+        Some(Err(e)) => {
+            bevy::log::warn!("{}", e);
+            LaunchOptions::default()
+        }
+        None => LaunchOptions::default(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flags_and_queries_pick_the_game() {
+        let args = [
+            "--level",
+            "hexagon",
+            "--colors=5",
+            "--bot",
+            "hard",
+            "--seed=42",
+        ]
+        .map(String::from);
+        let options = LaunchOptions::parse(&args).unwrap();
+        assert_eq!(options.level.map(|level| &*level.name), Some("Hexagon"));
+        assert_eq!(options.num_ids, Some(5));
+        assert_eq!(options.personality, Some(Personality::Deep));
+        assert_eq!(options.seed, Some(42));
+        assert!(options.starts_game() && !options.streamer);
+
+        let options =
+            LaunchOptions::from_query("?level=Grid+%28diagonals%29&bot=Defensive&streamer")
+                .unwrap();
+        assert_eq!(
+            options.level.map(|level| &*level.name),
+            Some("Grid (diagonals)")
+        );
+        assert_eq!(options.personality, Some(Personality::Defensive));
+        assert!(options.streamer);
+
+        let streaming = LaunchOptions::parse(&["--streamer".into()]).unwrap();
+        assert!(!streaming.starts_game());
+        assert!(LaunchOptions::parse(&["--colors".into(), "1".into()]).is_err());
+        assert!(LaunchOptions::from_query("level=Nowhere").is_err());
+    }
+}
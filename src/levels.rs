@@ -1,5 +1,89 @@
+use crate::core::{BoardTopology, MAX_PLAYERS, MAX_TILE_WEIGHT};
+use crate::share::MAX_LEVEL_SIDE;
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::reflect::TypeUuid;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+// Compiled in, so the pack's levels can be picked by name like the stock
+// ones before any asset has loaded
+const PACK: &str = include_str!("../site/assets/levels.pack");
+
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub name: Cow<'static, str>,
+    pub layout: Cow<'static, str>,
+    pub topology: BoardTopology,
+    pub info: LevelInfo,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+        }
+    }
+}
+
+// What the level selector shows about a level, beyond its name
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelInfo {
+    pub author: Option<Cow<'static, str>>,
+    pub difficulty: Option<Difficulty>,
+    // The number of colors the level plays best with
+    pub colors: Option<u32>,
+    // The fewest moves taking the board alone
+    pub par: Option<u32>,
+}
+
+impl LevelInfo {
+    pub const NONE: LevelInfo = LevelInfo {
+        author: None,
+        difficulty: None,
+        colors: None,
+        par: None,
+    };
+
+    const fn stock(difficulty: Difficulty, colors: u32) -> Self {
+        LevelInfo {
+            author: None,
+            difficulty: Some(difficulty),
+            colors: Some(colors),
+            par: None,
+        }
+    }
+
+    // A line for the level selector, empty when there's nothing to say
+    pub fn describe(&self) -> String {
+        let mut parts = vec![];
+        if let Some(difficulty) = self.difficulty {
+            parts.push(difficulty.name().to_string());
+        }
+        if let Some(author) = &self.author {
+            parts.push(format!("by {}", author));
+        }
+        if let Some(colors) = self.colors {
+            parts.push(format!("best with {} colors", colors));
+        }
+        if let Some(par) = self.par {
+            parts.push(format!("par {}", par));
+        }
+        parts.join(", ")
+    }
+}
+
 pub const SQUARE: &str = r#"
-| | | | | | | | | 2
+3 | | | | | | | | 2
 | | | | | | | | | |
 | | | | | | | | | |
 | | | | | | | | | |
@@ -8,11 +92,11 @@ pub const SQUARE: &str = r#"
 | | | | | | | | | |
 | | | | | | | | | |
 | | | | | | | | | |
-1 | | | | | | | | |
+1 | | | | | | | | 4
 "#;
 
 pub const HEXAGON: &str = r#"
-- - - | | | | | | | - - -
+- - - 3 | | | | | | - - -
 - - - | | | | | | | | - -
 - - | | | | | | | | | - -
 - - | | | | | | | | | | -
@@ -24,5 +108,462 @@ pub const HEXAGON: &str = r#"
 - - | | | | | | | | | | -
 - - | | | | | | | | | - -
 - - - | | | | | | | | - -
+- - - | | | | | | 4 - - -
+"#;
+
+// A tiny board used by the tutorial, not listed with the regular levels
+pub const TUTORIAL: &str = r#"
+- | | | -
+| | | | |
+1 | | | 2
+| | | | |
+- | | | -
+"#;
+
+// A small board for puzzles played alone, small enough to be solved in full.
+// Not listed with the regular levels either
+pub const PUZZLE: &str = r#"
+1 | | | | | |
+| | | | | | |
+| | | | | | |
+| | | | | | |
+| | | | | | |
+| | | | | | |
+| | | | | | |
+"#;
+
+pub const GRID: &str = r#"
+3 | | | | | | | | | | 2
+| | | | | | | | | | | |
+| | | | | | | | | | | |
+| | | | | | | | | | | |
+| | | | | | | | | | | |
+| | | | | | | | | | | |
+| | | | | | | | | | | |
+| | | | | | | | | | | |
+| | | | | | | | | | | |
+| | | | | | | | | | | |
+| | | | | | | | | | | |
+1 | | | | | | | | | | 4
+"#;
+
+pub const MAZE: &str = r#"
+- - - | | | | | | | - - -
+- - - | # # # # # | | - -
+- - | | * | | | # | | - -
+- - | # # | # | # | # | -
+- | | | # | # | | | # | -
+- | # | # | | | # | # | |
+1 | # | + | # | + | | # | 2
+- | # | # | | | # | # | |
+- | # | | | # | # | | | -
+- - | # | # | # | # # | -
+- - | | # | | | * | | - -
+- - - | | # # # # # | - -
 - - - | | | | | | | - - -
 "#;
+
+// Hills in the middle, for king of the hill games
+pub const HILL: &str = r#"
+- - - 3 | | | | | | - - -
+- - - | | | | | | | | - -
+- - | | | | | | | | | - -
+- - | | | | | | | | | | -
+- | | | | | | | | | | | -
+- | | | | | ^ ^ | | | | |
+1 | | | | | ^ ^ ^ | | | 2
+- | | | | | ^ ^ | | | | |
+- | | | | | | | | | | | -
+- - | | | | | | | | | | -
+- - | | | | | | | | | - -
+- - - | | | | | | | | - -
+- - - | | | | | | 4 - - -
+"#;
+
+pub const LEVELS: &[Level] = &[
+    Level {
+        name: Cow::Borrowed("Hexagon"),
+        layout: Cow::Borrowed(HEXAGON),
+        topology: BoardTopology::Hex,
+        info: LevelInfo::stock(Difficulty::Medium, 5),
+    },
+    Level {
+        name: Cow::Borrowed("Square"),
+        layout: Cow::Borrowed(SQUARE),
+        topology: BoardTopology::Hex,
+        info: LevelInfo::stock(Difficulty::Easy, 5),
+    },
+    Level {
+        name: Cow::Borrowed("Maze"),
+        layout: Cow::Borrowed(MAZE),
+        topology: BoardTopology::Hex,
+        info: LevelInfo::stock(Difficulty::Hard, 4),
+    },
+    Level {
+        name: Cow::Borrowed("Hill"),
+        layout: Cow::Borrowed(HILL),
+        topology: BoardTopology::Hex,
+        info: LevelInfo::stock(Difficulty::Medium, 5),
+    },
+    Level {
+        name: Cow::Borrowed("Grid"),
+        layout: Cow::Borrowed(GRID),
+        topology: BoardTopology::Square4,
+        info: LevelInfo::stock(Difficulty::Easy, 5),
+    },
+    Level {
+        name: Cow::Borrowed("Grid (diagonals)"),
+        layout: Cow::Borrowed(GRID),
+        topology: BoardTopology::Square8,
+        info: LevelInfo::stock(Difficulty::Medium, 6),
+    },
+];
+
+// The tiles which aren't plain colored ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpecialTile {
+    ExtraTurn,
+    Bonus,
+    Hill,
+    // A colored tile worth this much
+    Weight(u32),
+}
+
+// A level as saved in a RON file, with everything about it spelled out
+// instead of drawn. Every position in the rows and columns is a colored tile
+// unless it's listed otherwise. Positions are (row, column)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "3bd6b491-f649-49a0-a636-f9526da9e97b"]
+pub struct LevelFile {
+    pub name: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub colors: Option<u32>,
+    #[serde(default)]
+    pub par: Option<u32>,
+    #[serde(default)]
+    pub difficulty: Option<Difficulty>,
+    #[serde(default)]
+    pub topology: BoardTopology,
+    pub rows: u32,
+    pub columns: u32,
+    // Where each player starts, in order
+    pub starts: Vec<(u32, u32)>,
+    #[serde(default)]
+    pub holes: Vec<(u32, u32)>,
+    #[serde(default)]
+    pub walls: Vec<(u32, u32)>,
+    #[serde(default)]
+    pub special: Vec<((u32, u32), SpecialTile)>,
+}
+
+impl LevelFile {
+    // Converts a level drawn in the editor's grid format
+    pub fn from_grid(name: &str, layout: &str, topology: BoardTopology) -> Result<Self, String> {
+        let lines = layout.trim().lines().collect::<Vec<_>>();
+        let columns = lines
+            .iter()
+            .map(|line| line.split_whitespace().count())
+            .max()
+            .unwrap_or(0);
+        let mut level = LevelFile {
+            name: name.into(),
+            author: None,
+            colors: None,
+            par: None,
+            difficulty: None,
+            topology,
+            rows: lines.len() as u32,
+            columns: columns as u32,
+            starts: vec![],
+            holes: vec![],
+            walls: vec![],
+            special: vec![],
+        };
+        let mut starts = [None; MAX_PLAYERS];
+        for (row, line) in lines.iter().enumerate() {
+            let mut descs = line.split_whitespace();
+            for column in 0..columns {
+                let position = (row as u32, column as u32);
+                let desc = match descs.next() {
+                    Some(desc) => desc,
+                    // Short rows end in holes
+                    None => {
+                        level.holes.push(position);
+                        continue;
+                    }
+                };
+                let special = match desc {
+                    "|" => continue,
+                    "-" => {
+                        level.holes.push(position);
+                        continue;
+                    }
+                    "#" => {
+                        level.walls.push(position);
+                        continue;
+                    }
+                    "*" => SpecialTile::ExtraTurn,
+                    "+" => SpecialTile::Bonus,
+                    "^" => SpecialTile::Hill,
+                    _ => match desc.strip_prefix('|').map(str::parse) {
+                        Some(Ok(weight)) if (1..=MAX_TILE_WEIGHT).contains(&weight) => {
+                            SpecialTile::Weight(weight)
+                        }
+                        Some(_) => return Err(format!("Invalid tile weight in level: {}", desc)),
+                        None => {
+                            let player = desc
+                                .parse::<usize>()
+                                .ok()
+                                .filter(|player| (1..=MAX_PLAYERS).contains(player))
+                                .ok_or_else(|| format!("Unexpected value in level: {}", desc))?;
+                            starts[player - 1] = Some(position);
+                            continue;
+                        }
+                    },
+                };
+                level.special.push((position, special));
+            }
+        }
+
+        // Players are numbered from 1 without gaps
+        let count = starts.iter().take_while(|start| start.is_some()).count();
+        if starts[count..].iter().any(Option::is_some) {
+            return Err(format!("Player {} has no starting tile", count + 1));
+        }
+        level.starts = starts.iter().flatten().cloned().collect();
+        Ok(level)
+    }
+
+    // A level as it's played, along with what's known about it
+    pub fn from_level(level: &Level) -> Result<Self, String> {
+        let mut file = Self::from_grid(&level.name, &level.layout, level.topology)?;
+        file.author = level.info.author.as_ref().map(|author| author.to_string());
+        file.colors = level.info.colors;
+        file.par = level.info.par;
+        file.difficulty = level.info.difficulty;
+        Ok(file)
+    }
+
+    // Draws the level in the grid format the board is loaded from
+    pub fn to_level(&self) -> Result<Level, String> {
+        // Files can come from anywhere, so the size is checked before the grid
+        // is made
+        if self.rows > MAX_LEVEL_SIDE || self.columns > MAX_LEVEL_SIDE {
+            return Err(format!(
+                "The level is {}x{}, larger than the {}x{} allowed",
+                self.columns, self.rows, MAX_LEVEL_SIDE, MAX_LEVEL_SIDE
+            ));
+        }
+        let mut grid = vec![vec!["|".to_string(); self.columns as usize]; self.rows as usize];
+        let mut set = |(row, column): (u32, u32), desc: String| {
+            let cell = grid
+                .get_mut(row as usize)
+                .and_then(|line| line.get_mut(column as usize))
+                .ok_or_else(|| format!("Tile ({}, {}) is outside the level", row, column))?;
+            *cell = desc;
+            Ok::<_, String>(())
+        };
+        for position in &self.holes {
+            set(*position, "-".into())?;
+        }
+        for position in &self.walls {
+            set(*position, "#".into())?;
+        }
+        for (position, special) in &self.special {
+            let desc = match special {
+                SpecialTile::ExtraTurn => "*".into(),
+                SpecialTile::Bonus => "+".into(),
+                SpecialTile::Hill => "^".into(),
+                SpecialTile::Weight(weight) => format!("|{}", weight),
+            };
+            set(*position, desc)?;
+        }
+        if self.starts.len() > MAX_PLAYERS {
+            return Err(format!("Too many starting tiles (max {})", MAX_PLAYERS));
+        }
+        for (player, position) in self.starts.iter().enumerate() {
+            set(*position, (player + 1).to_string())?;
+        }
+
+        let layout = grid
+            .iter()
+            .map(|line| line.join(" "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(Level {
+            name: Cow::Owned(self.name.clone()),
+            layout: Cow::Owned(layout),
+            topology: self.topology,
+            info: LevelInfo {
+                author: self.author.clone().map(Cow::Owned),
+                difficulty: self.difficulty,
+                colors: self.colors,
+                par: self.par,
+            },
+        })
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, Default::default())
+            .map_err(|e| format!("Unable to write the level: {}", e))
+    }
+
+    // Level files may be written in either format. Grid levels take the
+    // topology they're given
+    pub fn read(name: &str, contents: &str, topology: BoardTopology) -> Result<Self, String> {
+        match contents.trim_start().starts_with('(') {
+            true => ron::from_str(contents).map_err(|e| format!("Invalid level file: {}", e)),
+            false => Self::from_grid(name, contents, topology),
+        }
+    }
+}
+
+// Reads level files as assets, in either format, so they're reloaded as
+// they're saved. Grid files don't say what they're drawn for, so are read as
+// hex boards
+#[derive(Default)]
+pub struct LevelFileLoader;
+
+impl AssetLoader for LevelFileLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let contents = std::str::from_utf8(bytes)?;
+            let file = LevelFile::read("Custom", contents, BoardTopology::Hex)
+                .map_err(anyhow::Error::msg)?;
+            load_context.set_default_asset(LoadedAsset::new(file));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level"]
+    }
+}
+
+// The levels shipped with the game beyond the stock ones
+pub fn pack() -> &'static [Level] {
+    static LEVELS: OnceCell<Vec<Level>> = OnceCell::new();
+    LEVELS.get_or_init(|| {
+        let files: Vec<LevelFile> = match ron::from_str(PACK) {
+            Ok(files) => files,
+            Err(e) => {
+                bevy::log::warn!("Unable to read the level pack: {}", e);
+                return vec![];
+            }
+        };
+        files
+            .iter()
+            .filter_map(|file| match file.to_level() {
+                Ok(level) => Some(level),
+                Err(e) => {
+                    bevy::log::warn!("Skipping level {}: {}", file.name, e);
+                    None
+                }
+            })
+            .collect()
+    })
+}
+
+// Every level which can be picked to play, stock ones first
+pub fn all() -> impl Iterator<Item = &'static Level> {
+    LEVELS.iter().chain(pack())
+}
+
+pub fn find(name: &str) -> Option<&'static Level> {
+    all().find(|level| level.name == name)
+}
+
+#[cfg(not(target_family = "wasm"))]
+const USAGE: &str = "usage: assimilation convert-level <file> [--name <name>] \
+    [--author <name>] [--topology <Hex|Square4|Square8>]";
+
+// Prints a level drawn in the grid format as a level file
+#[cfg(not(target_family = "wasm"))]
+pub fn convert(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or(USAGE)?;
+    let (mut name, mut author, mut topology) = ("Custom".to_string(), None, BoardTopology::Hex);
+    let mut options = args[1..].iter();
+    while let Some(arg) = options.next() {
+        let value = options
+            .next()
+            .ok_or_else(|| format!("Missing value for {}\n{}", arg, USAGE))?;
+        match arg.as_str() {
+            "--name" => name = value.clone(),
+            "--author" => author = Some(value.clone()),
+            "--topology" => {
+                topology =
+                    ron::from_str(value).map_err(|_| format!("Unknown topology '{}'", value))?
+            }
+            _ => return Err(format!("Unknown option {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let layout =
+        std::fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let mut level = LevelFile::from_grid(&name, &layout, topology)?;
+    level.author = author;
+    println!("{}", level.to_ron()?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::{load_level, TileState};
+    use bevy::prelude::Entity;
+
+    #[test]
+    fn grid_levels_convert_to_files() {
+        let players = [Entity::from_raw(0), Entity::from_raw(1)];
+        for level in LEVELS {
+            let file = LevelFile::from_grid(&level.name, &level.layout, level.topology).unwrap();
+            let contents = file.to_ron().unwrap();
+            let read = LevelFile::read("", &contents, BoardTopology::Hex).unwrap();
+            assert_eq!(read, file);
+
+            let converted = read.to_level().unwrap();
+            assert_eq!(converted.topology, level.topology);
+            // Short rows are filled out with holes, which make no difference
+            let tiles = |layout: &str| {
+                load_level(layout, &players, vec![0, 1, 2], Some(3))
+                    .into_iter()
+                    .filter(|tile| !matches!(tile.state, TileState::Empty))
+                    .map(|tile| format!("{:?}", tile))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(tiles(&converted.layout), tiles(&level.layout));
+        }
+
+        let gap = LevelFile::from_grid("Gap", "1 | 3", BoardTopology::Hex);
+        assert!(gap.is_err());
+
+        let mut huge = LevelFile::from_grid("Huge", "1 2", BoardTopology::Hex).unwrap();
+        huge.rows = u32::MAX;
+        assert!(huge.to_level().is_err());
+    }
+
+    #[test]
+    fn pack_levels_are_playable() {
+        let files: Vec<LevelFile> = ron::from_str(PACK).unwrap();
+        assert_eq!(pack().len(), files.len());
+        assert!((15..=20).contains(&pack().len()));
+
+        let players = [Entity::from_raw(0), Entity::from_raw(1)];
+        for level in pack() {
+            assert!(level.info.difficulty.is_some(), "{}", level.name);
+            let tiles = crate::core::parse_level(&level.layout, &players, vec![0], None).unwrap();
+            let starts = tiles
+                .iter()
+                .filter(|tile| matches!(tile.state, TileState::Owned(_)))
+                .count();
+            assert_eq!(starts, 2, "{}", level.name);
+            assert!(std::ptr::eq(find(&level.name).unwrap(), level));
+        }
+    }
+}
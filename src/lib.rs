@@ -0,0 +1,9 @@
+// The parts of the game usable outside of the app itself, like benchmarks and
+// the correspondence server. Without the default std feature it builds for
+// no_std targets with an allocator
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+extern crate alloc;
+
+pub mod hex;
+pub mod rooms;
+pub mod rules;
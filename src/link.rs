@@ -0,0 +1,137 @@
+use crate::ai::Personality;
+
+// The settings needed to play the same game again, shared on the web as the
+// fragment of the page's URL
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameLink {
+    pub level: String,
+    pub seed: u64,
+    pub num_ids: u32,
+    pub personality: Personality,
+}
+
+impl GameLink {
+    pub fn encode(&self) -> String {
+        format!(
+            "level={}&seed={}&colors={}&bot={}",
+            escape(&self.level),
+            self.seed,
+            self.num_ids,
+            self.personality.name()
+        )
+    }
+
+    pub fn decode(fragment: &str) -> Option<Self> {
+        let (mut level, mut seed, mut num_ids, mut personality) = (None, None, None, None);
+        for pair in fragment.trim_start_matches('#').split('&') {
+            let (key, value) = match pair.split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            match key {
+                "level" => level = unescape(value),
+                "seed" => seed = value.parse().ok(),
+                "colors" => num_ids = value.parse().ok(),
+                "bot" => {
+                    personality = Personality::ALL
+                        .into_iter()
+                        .find(|personality| personality.name() == value)
+                }
+                _ => (),
+            }
+        }
+
+        Some(Self {
+            level: level?,
+            seed: seed?,
+            num_ids: num_ids?,
+            personality: personality?,
+        })
+    }
+}
+
+// Percent-encodes everything but letters, digits and a few safe symbols
+fn escape(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+pub fn unescape(value: &str) -> Option<String> {
+    let mut bytes = vec![];
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(target_family = "wasm")]
+pub fn read() -> Option<GameLink> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    GameLink::decode(&hash)
+}
+
+#[cfg(target_family = "wasm")]
+pub fn write(link: &GameLink) {
+    if let Some(window) = web_sys::window() {
+        let _ = window.location().set_hash(&link.encode());
+    }
+}
+
+// Natively, the fragment is passed as the first argument and the one for
+// each game is printed
+#[cfg(not(target_family = "wasm"))]
+pub fn read() -> Option<GameLink> {
+    GameLink::decode(&std::env::args().nth(1)?)
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub fn write(link: &GameLink) {
+    bevy::log::info!("Game link: #{}", link.encode());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn links_round_trip() {
+        let link = GameLink {
+            level: "Grid (diagonals)".into(),
+            seed: 1234,
+            num_ids: 4,
+            personality: Personality::Defensive,
+        };
+
+        let encoded = link.encode();
+        assert_eq!(
+            encoded,
+            "level=Grid%20%28diagonals%29&seed=1234&colors=4&bot=Defensive"
+        );
+        assert_eq!(GameLink::decode(&format!("#{}", encoded)), Some(link));
+    }
+
+    #[test]
+    fn incomplete_links_are_ignored() {
+        assert_eq!(GameLink::decode(""), None);
+        assert_eq!(GameLink::decode("#level=Maze&seed=1&colors=4"), None);
+        assert_eq!(
+            GameLink::decode("#level=%2&seed=1&colors=4&bot=Defensive"),
+            None
+        );
+    }
+}
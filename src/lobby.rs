@@ -0,0 +1,646 @@
+use crate::menu::{self, AppState};
+use crate::{
+    avatar, chat, core, game, levels, net, palette, snapshot, stats, theme, GameConfigState,
+    GameStartEvent,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use bevy_prototype_lyon::prelude::*;
+use std::collections::VecDeque;
+
+// Which side of an online game this is
+pub enum Role {
+    Host(net::Host),
+    // Keeps the room code, to reconnect with
+    Guest(String),
+}
+
+pub struct Room {
+    pub role: Role,
+    // Missing until the guest has connected, and while the connection is lost
+    pub connection: Option<net::Connection>,
+    // Always known to the host, and sent to the guest once it joins
+    pub settings: Option<net::LobbySettings>,
+    pub guest: Option<net::PeerInfo>,
+    pub in_game: bool,
+    // Whether the game was paused because the connection was lost
+    pub paused: bool,
+    // Set once the game has been aborted, with the reason why
+    pub error: Option<String>,
+    // Moves made on the other side, waiting to be applied
+    pub incoming: VecDeque<RemoteMove>,
+    // How much of the game's history has been looked at for moves to send
+    pub sent: usize,
+    // Received from the host after a reconnect, waiting to be applied
+    pub snapshot: Option<snapshot::Snapshot>,
+    pub chat: chat::Chat,
+}
+
+impl Room {
+    fn new(role: Role, connection: Option<net::Connection>) -> Self {
+        Self {
+            role,
+            connection,
+            settings: None,
+            guest: None,
+            in_game: false,
+            paused: false,
+            error: None,
+            incoming: VecDeque::new(),
+            sent: 0,
+            snapshot: None,
+            chat: chat::Chat::default(),
+        }
+    }
+
+    // Stops the game on both sides
+    fn abort(&mut self, state: &mut core::GameState, reason: String) {
+        error!("Game aborted: {}", reason);
+        self.send(&net::Message::Abort(reason.clone()));
+        self.error = Some(reason);
+        self.incoming.clear();
+        state.phase = core::GamePhase::Paused;
+    }
+
+    fn is_host(&self) -> bool {
+        matches!(self.role, Role::Host(_))
+    }
+
+    fn peer_name(&self) -> &str {
+        let peer = match self.role {
+            Role::Host(_) => self.guest.as_ref(),
+            Role::Guest(_) => self.settings.as_ref().map(|settings| &settings.host),
+        };
+        peer.map(|peer| peer.name.as_str()).unwrap_or("?")
+    }
+
+    // Sends the message, dropping the connection if it has been lost
+    pub fn send(&mut self, message: &net::Message) {
+        if let Some(connection) = self.connection.as_mut() {
+            if let Err(e) = connection.send(message) {
+                warn!("Connection lost: {}", e);
+                self.connection = None;
+            }
+        }
+    }
+}
+
+pub struct RemoteMove {
+    pub turn: usize,
+    pub id: u32,
+    pub board_hash: u64,
+}
+
+#[derive(Default)]
+pub struct Lobby {
+    pub room: Option<Room>,
+    code: String,
+    error: Option<String>,
+}
+
+// Games between two people on different machines, kept in step by sending
+// each other their moves
+pub struct LobbyPlugin;
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Lobby>()
+            .add_system_set(SystemSet::on_update(AppState::Lobby).with_system(show_lobby))
+            .add_system_set(SystemSet::on_enter(AppState::MainMenu).with_system(leave_room))
+            .add_system(poll_room)
+            .add_system(send_moves.after(poll_room))
+            .add_system(apply_snapshot.after(poll_room))
+            .add_system(show_connection_problems)
+            .add_system(chat::show_chat)
+            .add_system(chat::send_emotes)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::GameStep)
+                    .with_system(
+                        apply_remote_moves
+                            .after(apply_snapshot)
+                            .before(core::perform_selection),
+                    ),
+            );
+    }
+}
+
+fn local_peer(config: &GameConfigState) -> net::PeerInfo {
+    net::PeerInfo {
+        name: config.names.player.clone(),
+        color: config.player_color,
+    }
+}
+
+// The host always moves first. Both sides list the players in the same order,
+// so their entities line up with the same moves. Settings from the other side
+// are checked before anything is built from them
+fn start_event(
+    settings: &net::LobbySettings,
+    guest: &net::PeerInfo,
+    is_host: bool,
+    seed: u64,
+) -> Result<GameStartEvent, String> {
+    let level = levels::find(&settings.level)
+        .ok_or_else(|| format!("Unknown level {}", settings.level))?
+        .clone();
+    if !(2..=palette::MAX_IDS).contains(&settings.num_ids) {
+        return Err(format!("Invalid number of colors {}", settings.num_ids));
+    }
+    let player = |peer: &net::PeerInfo, local: bool| core::Player {
+        color: peer.color,
+        name: peer.name.clone(),
+        avatar: avatar::Avatar::Person,
+        kind: if local {
+            core::PlayerKind::Human
+        } else {
+            core::PlayerKind::Remote
+        },
+        score: 0,
+        bonus: 0,
+    };
+
+    Ok(GameStartEvent {
+        players: vec![player(&settings.host, is_host), player(guest, !is_host)],
+        ids: palette::generate(settings.num_ids, &[settings.host.color, guest.color])
+            .into_iter()
+            .enumerate()
+            .map(|(id, color)| (id as u32, color))
+            .collect(),
+        level,
+        seed: Some(seed),
+        colors: None,
+        daily: None,
+        rules: core::RulesConfig::default(),
+        handicap: core::Handicap::None,
+        handicapped_player: 0,
+        mirrored: false,
+        virus: false,
+        tutorial: false,
+        training: false,
+        blind: false,
+        par: None,
+    })
+}
+
+pub fn show_lobby(
+    mut config: ResMut<GameConfigState>,
+    player_stats: Res<stats::Stats>,
+    mut lobby: ResMut<Lobby>,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut game_start: EventWriter<GameStartEvent>,
+) {
+    let lobby = &mut *lobby;
+    let mut start = false;
+    menu::menu_screen(&mut egui_ctx, &mut app_state, "Play Online", |ui| {
+        let room = match lobby.room.as_mut() {
+            Some(room) => room,
+            None => {
+                if ui.button("host a game").clicked() {
+                    match net::Host::open() {
+                        Ok(host) => lobby.room = Some(Room::new(Role::Host(host), None)),
+                        Err(e) => lobby.error = Some(format!("Unable to host: {}", e)),
+                    }
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Room code");
+                    ui.text_edit_singleline(&mut lobby.code);
+                    if ui.button("join").clicked() {
+                        match net::join(&lobby.code) {
+                            Ok(mut connection) => {
+                                lobby.error = connection
+                                    .send(&net::Message::Join(local_peer(&config)))
+                                    .err()
+                                    .map(|e| format!("Unable to join: {}", e));
+                                lobby.room = Some(Room::new(
+                                    Role::Guest(lobby.code.clone()),
+                                    Some(connection),
+                                ));
+                            }
+                            Err(e) => lobby.error = Some(format!("Unable to join: {}", e)),
+                        }
+                    }
+                });
+                if let Some(error) = &lobby.error {
+                    ui.colored_label(egui::Color32::LIGHT_RED, error);
+                }
+                return;
+            }
+        };
+
+        match &room.role {
+            Role::Host(host) => {
+                let code = host.code.clone();
+                ui.horizontal(|ui| {
+                    ui.label(format!("Room code: {}", code));
+                    if ui.button("copy").clicked() {
+                        ui.output().copied_text = code;
+                    }
+                });
+                menu::board_settings(ui, &mut config, &player_stats.completed_levels, None);
+
+                let settings = net::LobbySettings {
+                    level: config.level_name.into(),
+                    num_ids: config.num_ids,
+                    host: local_peer(&config),
+                };
+                if room.settings.as_ref() != Some(&settings) {
+                    room.settings = Some(settings.clone());
+                    if room.guest.is_some() {
+                        room.send(&net::Message::Lobby(settings));
+                    }
+                }
+
+                match (&room.guest, &room.connection) {
+                    (Some(guest), Some(_)) => {
+                        ui.label(format!("{} joined", guest.name));
+                        start = ui.button("start").clicked();
+                    }
+                    _ => {
+                        ui.label("Waiting for someone to join...");
+                    }
+                }
+            }
+            Role::Guest(_) => match (&room.settings, &room.connection) {
+                (Some(settings), Some(_)) => {
+                    ui.label(format!("Host: {}", settings.host.name));
+                    ui.label(format!("Level: {}", settings.level));
+                    ui.label(format!("Colors: {}", settings.num_ids));
+                    ui.label("Waiting for the host to start...");
+                }
+                (None, Some(_)) => {
+                    ui.label("Connecting...");
+                }
+                (_, None) => {
+                    ui.colored_label(egui::Color32::LIGHT_RED, "The host left");
+                }
+            },
+        }
+
+        if ui.button("leave").clicked() {
+            lobby.room = None;
+        }
+    });
+
+    let room = match lobby.room.as_mut() {
+        Some(room) if start => room,
+        _ => return,
+    };
+    let seed = rand::random();
+    let event = match room.settings.as_ref().zip(room.guest.as_ref()) {
+        Some((settings, guest)) => start_event(settings, guest, true, seed),
+        None => return,
+    };
+    match event {
+        Ok(event) => {
+            room.send(&net::Message::Start { seed });
+            room.in_game = true;
+            room.sent = 0;
+            room.incoming.clear();
+            game_start.send(event);
+            app_state.set(AppState::InGame).ok();
+        }
+        Err(e) => error!("Unable to start the game: {}", e),
+    }
+}
+
+fn leave_room(mut lobby: ResMut<Lobby>) {
+    lobby.room = None;
+}
+
+// Handles everything the other side sent, and connections being made or lost
+#[allow(clippy::too_many_arguments)]
+fn poll_room(
+    config: Res<GameConfigState>,
+    time: Res<Time>,
+    mut lobby: ResMut<Lobby>,
+    mut state: ResMut<core::GameState>,
+    mut app_state: ResMut<State<AppState>>,
+    mut game_start: EventWriter<GameStartEvent>,
+    tiles: Query<&core::Tile>,
+    players: Query<&core::Player>,
+) {
+    let room = match lobby.room.as_mut() {
+        Some(room) => room,
+        None => return,
+    };
+
+    if let (Role::Host(host), None) = (&room.role, &room.connection) {
+        match host.accept() {
+            Ok(connection) => room.connection = connection,
+            Err(e) => warn!("Unable to accept a connection: {}", e),
+        }
+    }
+
+    let messages = match room
+        .connection
+        .as_mut()
+        .map(|connection| connection.receive())
+    {
+        Some(Ok(messages)) => messages,
+        Some(Err(e)) => {
+            warn!("Connection lost: {}", e);
+            room.connection = None;
+            vec![]
+        }
+        None => vec![],
+    };
+
+    for message in messages {
+        match message {
+            net::Message::Join(guest) if room.is_host() => {
+                info!("{} joined", guest.name);
+                room.guest = Some(guest);
+                if let Some(settings) = room.settings.clone() {
+                    room.send(&net::Message::Lobby(settings));
+                }
+
+                // A guest coming back mid-game may have missed moves, or made
+                // some which never arrived, so it's caught up with the game as
+                // it is here instead
+                if room.in_game && room.error.is_none() {
+                    let tiles: Vec<core::Tile> = tiles.iter().cloned().collect();
+                    let snapshot = snapshot::Snapshot::capture(&state, &tiles, |player| {
+                        players.get(player).map(|player| player.bonus).unwrap_or(0)
+                    });
+                    room.incoming.clear();
+                    room.send(&net::Message::Snapshot(snapshot));
+                }
+            }
+            net::Message::Snapshot(snapshot) if room.in_game && !room.is_host() => {
+                room.incoming.clear();
+                room.snapshot = Some(snapshot);
+            }
+            net::Message::Lobby(settings) if !room.is_host() => room.settings = Some(settings),
+            net::Message::Start { seed } if !room.is_host() => {
+                let event = room
+                    .settings
+                    .as_ref()
+                    .ok_or_else(|| "The host hasn't picked a level".to_string())
+                    .and_then(|settings| start_event(settings, &local_peer(&config), false, seed));
+                match event {
+                    Ok(event) => {
+                        room.in_game = true;
+                        room.sent = 0;
+                        room.incoming.clear();
+                        game_start.send(event);
+                        app_state.set(AppState::InGame).ok();
+                    }
+                    Err(e) => error!("Unable to start the game: {}", e),
+                }
+            }
+            net::Message::Select {
+                turn,
+                id,
+                board_hash,
+            } if room.in_game => room.incoming.push_back(RemoteMove {
+                turn,
+                id,
+                board_hash,
+            }),
+            net::Message::Chat(text) if room.in_game => {
+                let name = room.peer_name().to_string();
+                room.chat
+                    .receive(&name, &text, time.seconds_since_startup());
+            }
+            net::Message::Abort(reason) if room.in_game => {
+                error!("Game aborted by the other player: {}", reason);
+                room.error = Some(reason);
+                state.phase = core::GamePhase::Paused;
+            }
+            message => warn!("Unexpected message {:?}", message),
+        }
+    }
+
+    // Nobody moves until the other side is back, or at all once the game has
+    // been aborted
+    let stopped = room.connection.is_none() || room.error.is_some();
+    if room.in_game && stopped && state.phase == core::GamePhase::Running {
+        state.phase = core::GamePhase::Paused;
+        room.paused = true;
+    } else if room.paused && !stopped {
+        state.phase = core::GamePhase::Running;
+        room.paused = false;
+    }
+}
+
+// Sends the moves made on this side
+fn send_moves(
+    mut lobby: ResMut<Lobby>,
+    state: Res<core::GameState>,
+    players: Query<&core::Player>,
+) {
+    let room = match lobby.room.as_mut() {
+        Some(room) if room.in_game && room.connection.is_some() => room,
+        _ => return,
+    };
+
+    for (turn, made) in state.local_moves(&players, room.sent) {
+        room.send(&net::Message::Select {
+            turn,
+            id: made.id,
+            board_hash: made.board_hash,
+        });
+    }
+    room.sent = state.history.len();
+}
+
+// Catches the guest up with the host's game, redrawing whatever changed
+fn apply_snapshot(
+    mut lobby: ResMut<Lobby>,
+    mut state: ResMut<core::GameState>,
+    mut cache: ResMut<core::MoveCache>,
+    theme: Res<theme::Theme>,
+    mut tiles: Query<(&mut core::Tile, &mut DrawMode, &mut Transform)>,
+    mut players: ParamSet<(Query<&core::Player>, Query<&mut core::Player>)>,
+) {
+    let room = match lobby.room.as_mut() {
+        Some(room) if room.in_game && room.error.is_none() => room,
+        _ => return,
+    };
+    let snapshot = match room.snapshot.take() {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+
+    let mut bonus = vec![];
+    let restored = snapshot.restore(
+        &mut state,
+        tiles.iter_mut().map(|(tile, _, _)| tile),
+        |player, points| bonus.push((player, points)),
+    );
+    let changed = match restored {
+        Ok(changed) => changed,
+        Err(reason) => return room.abort(&mut state, reason),
+    };
+    info!(
+        "Caught up with the host at move {}, {} tiles changed",
+        state.history.len(),
+        changed.len()
+    );
+
+    for (player, points) in bonus {
+        if let Ok(mut player) = players.p1().get_mut(player) {
+            player.bonus = points;
+        }
+    }
+    for (tile, mut mode, mut transform) in tiles.iter_mut() {
+        if !changed.contains(&(tile.row, tile.column)) {
+            continue;
+        }
+        if let Some((new_mode, z_pos)) =
+            game::tile_appearance(&theme, &state, &players.p0(), &tile.state)
+        {
+            *mode = new_mode;
+            transform.translation.z = z_pos;
+        }
+    }
+    cache.stale = true;
+    room.sent = state.history.len();
+}
+
+// Whether a move from the other side was made on the same game as this one
+fn verify_move(state: &core::GameState, remote: &RemoteMove) -> Result<(), String> {
+    if remote.turn != state.history.len() {
+        return Err(format!(
+            "Received move {} while expecting move {}",
+            remote.turn + 1,
+            state.history.len() + 1
+        ));
+    }
+    if remote.board_hash != state.board_hash.value {
+        return Err(format!(
+            "The boards no longer match at move {}",
+            remote.turn + 1
+        ));
+    }
+    Ok(())
+}
+
+// Moves from the other side are applied on the game's step, one at a time
+fn apply_remote_moves(
+    mut lobby: ResMut<Lobby>,
+    mut state: ResMut<core::GameState>,
+    players: Query<&core::Player>,
+    mut selections: EventWriter<core::SelectEvent>,
+) {
+    // Moves made before the snapshot are already part of it
+    let room = match lobby.room.as_mut() {
+        Some(room) if room.in_game && room.error.is_none() && room.snapshot.is_none() => room,
+        _ => return,
+    };
+
+    let remote = state.players.first().filter(|player| {
+        players
+            .get(**player)
+            .map(|player| matches!(player.kind, core::PlayerKind::Remote))
+            .unwrap_or(false)
+    });
+    let player = match remote {
+        Some(player) => *player,
+        None => return,
+    };
+    if let Some(remote) = room.incoming.pop_front() {
+        match verify_move(&state, &remote) {
+            Ok(()) => selections.send(core::SelectEvent {
+                id: remote.id,
+                player,
+            }),
+            Err(reason) => room.abort(&mut state, reason),
+        }
+    }
+}
+
+// Shown when the game has been aborted, or the connection has been lost
+fn show_connection_problems(
+    config: Res<GameConfigState>,
+    mut lobby: ResMut<Lobby>,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    let room = match lobby.room.as_mut() {
+        Some(room) if room.in_game && (room.error.is_some() || room.connection.is_none()) => room,
+        _ => return,
+    };
+
+    let title = match room.error {
+        Some(_) => "Game Aborted",
+        None => "Connection Lost",
+    };
+    let mut leave = false;
+    egui::Window::new(title)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            match (&room.error, &room.role) {
+                (Some(error), _) => {
+                    ui.label(error);
+                }
+                (None, Role::Host(_)) => {
+                    ui.label("Waiting for the other player to reconnect...");
+                }
+                (None, Role::Guest(code)) => {
+                    if ui.button("reconnect").clicked() {
+                        match net::join(code) {
+                            Ok(connection) => {
+                                room.connection = Some(connection);
+                                room.send(&net::Message::Join(local_peer(&config)));
+                            }
+                            Err(e) => warn!("Unable to reconnect: {}", e),
+                        }
+                    }
+                }
+            }
+            leave = ui.button("leave").clicked();
+        });
+
+    if leave {
+        app_state.set(AppState::MainMenu).ok();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn moves_must_match_the_game() {
+        let mut state = core::GameState::default();
+        state.history.push(core::Move {
+            player: Entity::from_raw(0),
+            id: 1,
+            board_hash: 7,
+        });
+        state.board_hash.value = 42;
+
+        let remote = |turn, board_hash| RemoteMove {
+            turn,
+            id: 0,
+            board_hash,
+        };
+        assert!(verify_move(&state, &remote(1, 42)).is_ok());
+        assert!(verify_move(&state, &remote(2, 42)).is_err());
+        assert!(verify_move(&state, &remote(0, 42)).is_err());
+        assert!(verify_move(&state, &remote(1, 7)).is_err());
+    }
+
+    #[test]
+    fn settings_from_the_host_are_checked() {
+        let peer = |name: &str, color| net::PeerInfo {
+            name: name.into(),
+            color,
+        };
+        let settings = |level: &str, num_ids| net::LobbySettings {
+            level: level.into(),
+            num_ids,
+            host: peer("Host", Color::RED),
+        };
+        let guest = peer("Guest", Color::BLUE);
+        assert!(start_event(&settings("Hexagon", 5), &guest, false, 1).is_ok());
+        assert!(start_event(&settings("Hexagon", u32::MAX), &guest, false, 1).is_err());
+        assert!(start_event(&settings("Hexagon", 1), &guest, false, 1).is_err());
+        assert!(start_event(&settings("Nowhere", 5), &guest, false, 1).is_err());
+    }
+}
@@ -0,0 +1,1470 @@
+use crate::{
+    ai, alert, announce, avatar, bindings, core, daily, import, launch, levels, link, minimap,
+    palette, puzzle, resume, series, share, stats, storage, theme, ui, GameConfigState,
+    GameStartEvent, BOT_COLOR, PLAYER_COLOR,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AppState {
+    MainMenu,
+    PlaySetup,
+    HowToPlay,
+    Stats,
+    Settings,
+    // Rebinding the keys and buttons
+    Controls,
+    LevelEditor,
+    // Setting up a game against someone on another machine
+    Lobby,
+    // Starting or picking back up a game played through a server
+    Correspondence,
+    InGame,
+}
+
+const NAMES_KEY: &str = "names";
+const MAX_NAME_LENGTH: usize = 16;
+const DAILY_COLORS: u32 = 5;
+// The most room the level preview on the setup screens takes up, in pixels
+const LEVEL_PREVIEW_SIZE: f32 = 96.0;
+// The level browser's cards, and how many fit on a row
+const LEVEL_THUMBNAIL_SIZE: f32 = 72.0;
+const LEVEL_BROWSER_COLUMNS: usize = 5;
+const LEVEL_BROWSER_HEIGHT: f32 = 400.0;
+
+// The names last used on the setup screen, remembered between sessions
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerNames {
+    pub player: String,
+    pub bot: String,
+}
+
+impl Default for PlayerNames {
+    fn default() -> Self {
+        Self {
+            player: "Player".into(),
+            bot: "Bot".into(),
+        }
+    }
+}
+
+impl PlayerNames {
+    pub fn load() -> Self {
+        storage::load(NAMES_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(NAMES_KEY, self)
+    }
+}
+
+pub struct EditorState {
+    pub layout: String,
+    pub topology: core::BoardTopology,
+    pub error: Option<String>,
+    // The image last imported, whose colors are kept as long as the layout
+    // isn't changed
+    pub image_path: String,
+    pub imported: Option<import::ImportedLevel>,
+    // The level file loaded, from the assets folder, which is reloaded
+    // whenever it changes
+    pub level_path: String,
+    pub level_file: Option<Handle<levels::LevelFile>>,
+    // Whether the layout has been filled in from the file since it was loaded
+    pub level_file_read: bool,
+    // What the level file or level code said about the level
+    pub info: levels::LevelInfo,
+    // The name level codes are made with
+    pub name: String,
+    // A level code being pasted in
+    pub level_code: String,
+}
+
+impl EditorState {
+    // The level being edited, as it's shared
+    fn level(&self) -> levels::Level {
+        levels::Level {
+            name: Cow::Owned(self.name.trim().to_string()),
+            layout: Cow::Owned(self.layout.clone()),
+            topology: self.topology,
+            info: self.info.clone(),
+        }
+    }
+
+    // Starts editing a shared level, leaving behind any file or image it came
+    // from
+    pub fn open(&mut self, level: levels::Level) {
+        self.layout = level.layout.into_owned();
+        self.topology = level.topology;
+        self.info = level.info;
+        self.name = level.name.into_owned();
+        self.imported = None;
+        self.level_file = None;
+        self.error = None;
+    }
+}
+
+impl Default for EditorState {
+    fn default() -> Self {
+        Self {
+            layout: levels::HEXAGON.trim().into(),
+            topology: core::BoardTopology::Hex,
+            error: None,
+            image_path: String::new(),
+            imported: None,
+            level_path: String::new(),
+            level_file: None,
+            level_file_read: false,
+            info: levels::LevelInfo::NONE,
+            name: "Custom".into(),
+            level_code: String::new(),
+        }
+    }
+}
+
+// Shows the contents of a menu screen centered on the window, with a common
+// title and an optional 'back' button returning to the main menu
+pub fn menu_screen(
+    egui_ctx: &mut EguiContext,
+    app_state: &mut State<AppState>,
+    title: &str,
+    add_contents: impl FnOnce(&mut egui::Ui),
+) {
+    egui::Area::new("main")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.label(egui::RichText::new(title).size(30.0));
+            ui.add_space(30.0);
+
+            add_contents(ui);
+
+            if *app_state.current() != AppState::MainMenu {
+                ui.add_space(20.0);
+                if ui.button("back").clicked() {
+                    app_state.set(AppState::MainMenu).ok();
+                }
+            }
+        });
+}
+
+// Colors the players can pick from, beyond the usual ones a rainbow which the
+// board's colors turn away from
+fn player_palette() -> Vec<Color> {
+    let mut palette = vec![PLAYER_COLOR, BOT_COLOR, Color::WHITE, Color::ORANGE];
+    palette.extend(palette::generate(6, &[]));
+    palette
+}
+
+fn color_allowed(num_ids: u32, color: Color, other_player: Color) -> bool {
+    palette::distinguishable(color, other_player)
+        && palette::generate(num_ids, &[color, other_player])
+            .into_iter()
+            .all(|id_color| palette::distinguishable(color, id_color))
+}
+
+pub fn to_color32(color: Color) -> egui::Color32 {
+    let [r, g, b, _] = color.as_rgba_f32();
+    egui::Rgba::from_rgb(r, g, b).into()
+}
+
+// A row of clickable color swatches. Colors which can't be told apart from
+// the board or the other player are crossed out
+fn color_picker(
+    ui: &mut egui::Ui,
+    label: &str,
+    selected: &mut Color,
+    allowed: impl Fn(Color) -> bool,
+) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        for color in player_palette() {
+            let (rect, response) =
+                ui.allocate_exact_size(egui::vec2(18.0, 18.0), egui::Sense::click());
+            ui.painter().rect_filled(rect, 2.0, to_color32(color));
+
+            if *selected == color {
+                ui.painter()
+                    .rect_stroke(rect, 2.0, egui::Stroke::new(2.0, egui::Color32::WHITE));
+            }
+
+            if !allowed(color) {
+                ui.painter().line_segment(
+                    [rect.left_top(), rect.right_bottom()],
+                    egui::Stroke::new(2.0, egui::Color32::BLACK),
+                );
+            } else if response.clicked() {
+                *selected = color;
+            }
+        }
+    });
+}
+
+fn selected_ids(num_ids: u32, config: &GameConfigState) -> BTreeMap<u32, Color> {
+    palette::generate(num_ids, &[config.player_color, config.bot_color])
+        .into_iter()
+        .enumerate()
+        .map(|(id, color)| (id as u32, color))
+        .collect()
+}
+
+// The rule variants picked on the setup screen
+fn rules(config: &GameConfigState) -> core::RulesConfig {
+    core::RulesConfig {
+        forbid_opponent_color: config.forbid_opponent_color,
+        forbid_repeat_color: config.forbid_repeat_color,
+        objective: if config.king_of_the_hill {
+            core::Objective::KingOfTheHill {
+                rounds: config.hill_rounds,
+            }
+        } else {
+            core::Objective::Territory
+        },
+        turn_limit: config.limit_turns.then(|| config.max_turns),
+        simultaneous: config.simultaneous,
+        chain_scoring: config.chain_scoring,
+        pie_rule: config.pie_rule,
+        decay: config.decay.then(|| config.decay_rounds),
+    }
+}
+
+// Sets the setup screen's toggles to give the rules. Rounds are left as they
+// were for variants the rules turn off
+fn set_rules(config: &mut GameConfigState, rules: &core::RulesConfig) {
+    config.forbid_opponent_color = rules.forbid_opponent_color;
+    config.forbid_repeat_color = rules.forbid_repeat_color;
+    config.king_of_the_hill = false;
+    if let core::Objective::KingOfTheHill { rounds } = rules.objective {
+        config.king_of_the_hill = true;
+        config.hill_rounds = rounds;
+    }
+    config.limit_turns = rules.turn_limit.is_some();
+    if let Some(turns) = rules.turn_limit {
+        config.max_turns = turns;
+    }
+    config.simultaneous = rules.simultaneous;
+    config.chain_scoring = rules.chain_scoring;
+    config.pie_rule = rules.pie_rule;
+    config.decay = rules.decay.is_some();
+    if let Some(rounds) = rules.decay {
+        config.decay_rounds = rounds;
+    }
+}
+
+fn start_event(
+    config: &GameConfigState,
+    players: Vec<core::Player>,
+    level: levels::Level,
+) -> GameStartEvent {
+    GameStartEvent {
+        players,
+        level,
+        ids: selected_ids(config.num_ids, config),
+        seed: None,
+        colors: None,
+        daily: None,
+        rules: rules(config),
+        handicap: config.handicap,
+        handicapped_player: config.handicapped_player,
+        mirrored: config.mirrored,
+        virus: config.virus,
+        tutorial: false,
+        training: false,
+        blind: config.blind,
+        par: None,
+    }
+}
+
+fn default_players(config: &GameConfigState) -> Vec<core::Player> {
+    let player = core::Player {
+        name: config.names.player.trim().into(),
+        avatar: config.avatars.player,
+        score: 0,
+        bonus: 0,
+        kind: core::PlayerKind::Human,
+        color: config.player_color,
+    };
+    let bot = core::Player {
+        name: config.names.bot.trim().into(),
+        avatar: config.avatars.bot,
+        score: 0,
+        bonus: 0,
+        kind: core::PlayerKind::Bot(
+            Timer::from_seconds(config.bot_think_time, false),
+            config.bot_personality,
+        ),
+        color: config.bot_color,
+    };
+    vec![player, bot]
+}
+
+// Everyone plays the daily board by the same rules, against the same bot
+fn daily_event(config: &GameConfigState, day: u64) -> GameStartEvent {
+    let mut players = default_players(config);
+    players[1].kind = core::PlayerKind::Bot(
+        Timer::from_seconds(config.bot_think_time, false),
+        ai::Personality::Aggressive,
+    );
+
+    GameStartEvent {
+        players,
+        level: daily::level(day),
+        ids: selected_ids(DAILY_COLORS, config),
+        seed: Some(daily::seed(day)),
+        colors: None,
+        daily: Some(day),
+        rules: core::RulesConfig::default(),
+        handicap: core::Handicap::None,
+        handicapped_player: 0,
+        mirrored: false,
+        virus: false,
+        tutorial: false,
+        training: false,
+        blind: false,
+        par: None,
+    }
+}
+
+// A freshly generated board to take alone, by the puzzle rules
+fn puzzle_event(config: &GameConfigState, seed: u64) -> Result<GameStartEvent, String> {
+    let ids = selected_ids(puzzle::PUZZLE_COLORS, config);
+    let (seed, par) = puzzle::generate(&ids.keys().cloned().collect::<Vec<_>>(), seed)
+        .ok_or("Unable to come up with a puzzle")?;
+    let mut players = default_players(config);
+    players.truncate(1);
+
+    Ok(GameStartEvent {
+        players,
+        level: puzzle::level(),
+        ids,
+        seed: Some(seed),
+        colors: None,
+        daily: None,
+        rules: core::Ruleset::Puzzle.rules(),
+        handicap: core::Handicap::None,
+        handicapped_player: 0,
+        mirrored: true,
+        virus: false,
+        tutorial: false,
+        training: false,
+        blind: false,
+        par: Some(par),
+    })
+}
+
+// A slow game between two bots by the plain rules, played behind the title
+// screen while it sits idle
+pub fn demo_event(config: &GameConfigState, think_time: f32) -> GameStartEvent {
+    let mut players = default_players(config);
+    for (player, personality) in players
+        .iter_mut()
+        .zip([config.spectator_personality, config.bot_personality])
+    {
+        player.name = personality.name().into();
+        player.kind = core::PlayerKind::Bot(Timer::from_seconds(think_time, false), personality);
+    }
+    let level = levels::find(config.level_name)
+        .expect("Unknown level")
+        .clone();
+
+    GameStartEvent {
+        rules: core::RulesConfig::default(),
+        handicap: core::Handicap::None,
+        mirrored: false,
+        virus: false,
+        blind: false,
+        ..start_event(config, players, level)
+    }
+}
+
+// A game on a board shared as a code, against the bot picked on the setup
+// screen
+fn board_code_event(config: &GameConfigState, code: &str) -> Result<GameStartEvent, String> {
+    let code = share::BoardCode::decode(code)?;
+    let players = default_players(config);
+    if code.players() > players.len() {
+        return Err(format!("The board is for {} players", code.players()));
+    }
+    if !(2..=palette::MAX_IDS).contains(&code.num_ids) {
+        return Err("The board code is damaged".into());
+    }
+    let (level, colors) = code.level();
+    Ok(GameStartEvent {
+        ids: selected_ids(code.num_ids, config),
+        colors: Some(colors),
+        ..start_event(config, players, level)
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn show_main_menu(
+    config: Res<GameConfigState>,
+    last_game: Res<resume::LastGame>,
+    mut resume: ResMut<resume::Resume>,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut game_start: EventWriter<GameStartEvent>,
+    mut board_code: Local<String>,
+    mut error: Local<Option<String>>,
+) {
+    let mut next = None;
+    let mut start_daily = false;
+    let mut load_code = false;
+    let mut continue_game = false;
+    let mut start_puzzle = false;
+    menu_screen(&mut egui_ctx, &mut app_state, "Assimilation", |ui| {
+        if let Some(saved) = &last_game.game {
+            continue_game = ui
+                .button("Continue")
+                .on_hover_text(format!(
+                    "{}, move {}",
+                    saved.start.level_name,
+                    saved.snapshot.history.len() + 1
+                ))
+                .clicked();
+        }
+        if ui.button("Play").clicked() {
+            next = Some(AppState::PlaySetup);
+        }
+        start_daily = ui.button("Daily Board").clicked();
+        start_puzzle = ui.button("Puzzle").clicked();
+        if cfg!(not(target_family = "wasm")) && ui.button("Play Online").clicked() {
+            next = Some(AppState::Lobby);
+        }
+        if ui.button("Play by Correspondence").clicked() {
+            next = Some(AppState::Correspondence);
+        }
+        for (label, screen) in [
+            ("How to Play", AppState::HowToPlay),
+            ("Stats", AppState::Stats),
+            ("Settings", AppState::Settings),
+            ("Controls", AppState::Controls),
+            ("Level Editor", AppState::LevelEditor),
+        ] {
+            if ui.button(label).clicked() {
+                next = Some(screen);
+            }
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut *board_code)
+                    .hint_text("Load board code")
+                    .desired_width(160.0),
+            );
+            load_code = ui.button("load").clicked();
+        });
+        if let Some(error) = &*error {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+    });
+
+    let started = match &last_game.game {
+        Some(saved) if continue_game => Some(resume.continue_game(saved)),
+        _ if load_code => Some(board_code_event(&config, &board_code)),
+        _ if start_puzzle => Some(puzzle_event(&config, rand::random())),
+        _ => None,
+    };
+    if let Some(started) = started {
+        match started {
+            Ok(event) => {
+                *error = None;
+                game_start.send(event);
+                app_state.set(AppState::InGame).ok();
+            }
+            Err(e) => *error = Some(e),
+        }
+    } else if start_daily {
+        game_start.send(daily_event(&config, daily::today()));
+        app_state.set(AppState::InGame).ok();
+    } else if let Some(next) = next {
+        app_state.set(next).ok();
+    }
+}
+
+// The level and number of colors to play with, next to a preview of the level.
+// Levels are picked from the browser, where level codes can also be pasted if
+// there's somewhere to put the level
+pub fn board_settings(
+    ui: &mut egui::Ui,
+    config: &mut GameConfigState,
+    completed: &BTreeSet<String>,
+    pasted: Option<&mut Option<levels::Level>>,
+) {
+    let browser = egui::Id::new("level_browser");
+    let mut browsing = ui.memory().data.get_temp::<bool>(browser).unwrap_or(false);
+    let level = levels::find(config.level_name);
+    ui.horizontal(|ui| {
+        ui.vertical(|ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("Level: {}", config.level_name));
+                if ui.button("browse levels").clicked() {
+                    browsing = !browsing;
+                }
+            });
+            let info = level.map(|level| level.info.describe()).unwrap_or_default();
+            if !info.is_empty() {
+                ui.label(info);
+            }
+
+            ui.add(egui::Slider::new(&mut config.num_ids, 2..=palette::MAX_IDS).text("Colors"));
+        });
+        if let Some((texture, size)) = level.and_then(|level| level_preview(ui, level, config)) {
+            let scale = LEVEL_PREVIEW_SIZE / size.x.max(size.y).max(1.0);
+            ui.image(texture.id(), [size.x * scale, size.y * scale]);
+        }
+    });
+
+    if browsing {
+        browsing = show_level_browser(ui.ctx(), config, completed, pasted);
+    }
+    ui.memory().data.insert_temp(browser, browsing);
+}
+
+// Every level as a card, with its thumbnail, how hard it is and a check once
+// the bot has been beaten on it. Stays open until a level is picked or pasted
+fn show_level_browser(
+    ctx: &egui::Context,
+    config: &mut GameConfigState,
+    completed: &BTreeSet<String>,
+    pasted: Option<&mut Option<levels::Level>>,
+) -> bool {
+    let mut open = true;
+    let mut picked = false;
+    let code_id = egui::Id::new("level_browser_code");
+    let (mut code, mut error) = ctx
+        .memory()
+        .data
+        .get_temp::<(String, Option<String>)>(code_id)
+        .unwrap_or_default();
+    egui::Window::new("Levels")
+        .open(&mut open)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(LEVEL_BROWSER_HEIGHT)
+                .show(ui, |ui| {
+                    egui::Grid::new("level_browser_cards")
+                        .spacing([10.0, 10.0])
+                        .show(ui, |ui| {
+                            for (idx, level) in levels::all().enumerate() {
+                                if idx > 0 && idx % LEVEL_BROWSER_COLUMNS == 0 {
+                                    ui.end_row();
+                                }
+                                ui.vertical(|ui| {
+                                    if level_card(ui, level, config, completed) {
+                                        config.level_name = &*level.name;
+                                        picked = true;
+                                    }
+                                });
+                            }
+                        });
+                });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("copy level code").clicked() {
+                    let code = levels::find(config.level_name)
+                        .ok_or_else(|| format!("Unknown level {}", config.level_name))
+                        .and_then(share::level_code);
+                    match code {
+                        Ok(code) => ui.output().copied_text = code,
+                        Err(e) => error = Some(e),
+                    }
+                }
+                let pasted = match pasted {
+                    Some(pasted) => pasted,
+                    None => return,
+                };
+                ui.add(
+                    egui::TextEdit::singleline(&mut code)
+                        .hint_text("Level code")
+                        .desired_width(160.0),
+                );
+                // Pasted levels are opened in the editor, to be played from there
+                if ui.button("paste level code").clicked() {
+                    match share::read_level_code(&code) {
+                        Ok(level) => {
+                            *pasted = Some(level);
+                            code.clear();
+                            error = None;
+                            picked = true;
+                        }
+                        Err(e) => error = Some(e),
+                    }
+                }
+            });
+            if let Some(error) = &error {
+                ui.colored_label(egui::Color32::LIGHT_RED, error);
+            }
+        });
+    ctx.memory().data.insert_temp(code_id, (code, error));
+    open && !picked
+}
+
+// Whether the card was clicked
+fn level_card(
+    ui: &mut egui::Ui,
+    level: &levels::Level,
+    config: &GameConfigState,
+    completed: &BTreeSet<String>,
+) -> bool {
+    let selected = level.name == config.level_name;
+    let clicked = match level_preview(ui, level, config) {
+        Some((texture, size)) => {
+            let scale = LEVEL_THUMBNAIL_SIZE / size.x.max(size.y).max(1.0);
+            let thumbnail = egui::ImageButton::new(texture.id(), [size.x * scale, size.y * scale])
+                .selected(selected);
+            ui.add(thumbnail)
+        }
+        None => ui.selectable_label(selected, "no preview"),
+    }
+    .on_hover_text(level.info.describe())
+    .clicked();
+
+    let name = match completed.contains(level.name.as_ref()) {
+        true => format!("{} \u{2714}", level.name),
+        false => level.name.to_string(),
+    };
+    ui.label(name);
+    if let Some(difficulty) = level.info.difficulty {
+        let color = match difficulty {
+            levels::Difficulty::Easy => egui::Color32::LIGHT_GREEN,
+            levels::Difficulty::Medium => egui::Color32::YELLOW,
+            levels::Difficulty::Hard => egui::Color32::LIGHT_RED,
+        };
+        ui.colored_label(color, difficulty.name());
+    }
+    clicked
+}
+
+// Previews are only drawn again once the player colors change
+fn level_preview(
+    ui: &mut egui::Ui,
+    level: &levels::Level,
+    config: &GameConfigState,
+) -> Option<(egui::TextureHandle, Vec2)> {
+    let key = format!("{:?} {:?}", config.player_color, config.bot_color);
+    let id = egui::Id::new(("level_preview", level.name.as_ref()));
+    let cached = ui
+        .memory()
+        .data
+        .get_temp::<(String, egui::TextureHandle, Vec2)>(id)
+        .filter(|(drawn, _, _)| *drawn == key);
+    if let Some((_, texture, size)) = cached {
+        return Some((texture, size));
+    }
+
+    let colors = [config.player_color, config.bot_color];
+    let (image, size) = match minimap::level_preview(level, &colors) {
+        Ok(preview) => preview,
+        Err(e) => {
+            warn!("Unable to preview level {}: {}", level.name, e);
+            return None;
+        }
+    };
+    let texture = ui
+        .ctx()
+        .load_texture(format!("level_preview {}", level.name), image);
+    ui.memory()
+        .data
+        .insert_temp(id, (key, texture.clone(), size));
+    Some((texture, size))
+}
+
+// What picks the moves of the bot played against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BotSource {
+    Personality,
+    // A script written on the setup screen
+    Script,
+    // A separate program, talking to the game over stdio
+    #[cfg(not(target_family = "wasm"))]
+    Program,
+}
+
+impl BotSource {
+    #[cfg(not(target_family = "wasm"))]
+    const ALL: &'static [BotSource] = &[
+        BotSource::Personality,
+        BotSource::Script,
+        BotSource::Program,
+    ];
+    #[cfg(target_family = "wasm")]
+    const ALL: &'static [BotSource] = &[BotSource::Personality, BotSource::Script];
+
+    fn name(&self) -> &'static str {
+        match self {
+            BotSource::Personality => "Built in",
+            BotSource::Script => "Your script",
+            #[cfg(not(target_family = "wasm"))]
+            BotSource::Program => "External program",
+        }
+    }
+}
+
+// The bot's kind when it isn't one of the personalities, saving what it was
+// made from for next time
+fn bot_kind(config: &GameConfigState) -> Result<Option<core::PlayerKind>, String> {
+    let timer = Timer::from_seconds(config.bot_think_time, false);
+    match config.bot_source {
+        BotSource::Personality => Ok(None),
+        BotSource::Script => {
+            config.bot_script.save();
+            let script = config.bot_script.compile()?;
+            Ok(Some(core::PlayerKind::Scripted(timer, script)))
+        }
+        #[cfg(not(target_family = "wasm"))]
+        BotSource::Program => {
+            config.bot_command.save();
+            let bot = config.bot_command.spawn()?;
+            Ok(Some(core::PlayerKind::External(timer, bot)))
+        }
+    }
+}
+
+fn match_name(best_of: u32) -> String {
+    match best_of {
+        1 => "Single game".into(),
+        _ => format!("Best of {}", best_of),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn show_play_setup(
+    mut config: ResMut<GameConfigState>,
+    player_stats: Res<stats::Stats>,
+    mut editor: ResMut<EditorState>,
+    mut series: ResMut<series::Series>,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut game_start: EventWriter<GameStartEvent>,
+    mut bot_error: Local<Option<String>>,
+) {
+    let mut players = None;
+    let mut pasted = None;
+    menu_screen(&mut egui_ctx, &mut app_state, "Play", |ui| {
+        board_settings(
+            ui,
+            &mut config,
+            &player_stats.completed_levels,
+            Some(&mut pasted),
+        );
+
+        let names = &mut config.as_mut().names;
+        for (label, name) in [
+            ("Your name", &mut names.player),
+            ("Bot name", &mut names.bot),
+        ] {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                ui.text_edit_singleline(name);
+            });
+            if name.chars().count() > MAX_NAME_LENGTH {
+                *name = name.chars().take(MAX_NAME_LENGTH).collect();
+            }
+        }
+
+        let avatars = &mut config.as_mut().avatars;
+        for (label, avatar) in [
+            ("Your avatar", &mut avatars.player),
+            ("Bot avatar", &mut avatars.bot),
+        ] {
+            egui::ComboBox::from_label(label)
+                .selected_text(avatar.name())
+                .show_ui(ui, |ui| {
+                    for option in avatar::Avatar::ALL {
+                        ui.selectable_value(avatar, option, option.name());
+                    }
+                });
+        }
+
+        let (num_ids, player_color, bot_color) =
+            (config.num_ids, config.player_color, config.bot_color);
+        color_picker(
+            ui,
+            "Your color",
+            &mut config.as_mut().player_color,
+            |color| color_allowed(num_ids, color, bot_color),
+        );
+        color_picker(ui, "Bot color", &mut config.as_mut().bot_color, |color| {
+            color_allowed(num_ids, color, player_color)
+        });
+        let current = core::Ruleset::matching(&rules(&config));
+        egui::ComboBox::from_label("Ruleset")
+            .selected_text(current.map_or("Custom", |ruleset| ruleset.name()))
+            .show_ui(ui, |ui| {
+                for ruleset in core::Ruleset::ALL {
+                    let selected = current == Some(ruleset);
+                    if ui.selectable_label(selected, ruleset.name()).clicked() && !selected {
+                        set_rules(config.as_mut(), &ruleset.rules());
+                    }
+                }
+            });
+        egui::ComboBox::from_label("Match")
+            .selected_text(match_name(config.best_of))
+            .show_ui(ui, |ui| {
+                for best_of in series::MATCH_LENGTHS {
+                    ui.selectable_value(&mut config.as_mut().best_of, best_of, match_name(best_of));
+                }
+            });
+        ui.checkbox(
+            &mut config.as_mut().training,
+            "Training (take moves back and try other lines, unrated)",
+        );
+        egui::CollapsingHeader::new("Advanced").show(ui, |ui| {
+            ui.checkbox(
+                &mut config.as_mut().forbid_opponent_color,
+                "Forbid opponent's color",
+            );
+            ui.checkbox(
+                &mut config.as_mut().forbid_repeat_color,
+                "Forbid repeating your color",
+            );
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut config.as_mut().king_of_the_hill, "King of the hill");
+                if config.king_of_the_hill {
+                    ui.add(
+                        egui::Slider::new(&mut config.as_mut().hill_rounds, 5..=50).text("Rounds"),
+                    );
+                }
+            });
+            ui.checkbox(
+                &mut config.as_mut().simultaneous,
+                "Simultaneous moves (hidden picks, shared tiles stay neutral)",
+            );
+            ui.checkbox(
+                &mut config.as_mut().chain_scoring,
+                format!(
+                    "Chain scoring (a bonus point per {} tiles captured at once)",
+                    core::CHAIN_SIZE
+                ),
+            );
+            ui.checkbox(
+                &mut config.as_mut().mirrored,
+                "Mirrored board (every player starts among the same colors)",
+            );
+            ui.checkbox(
+                &mut config.as_mut().pie_rule,
+                "Pie rule (after the first move, the next player may swap sides)",
+            );
+            ui.checkbox(
+                &mut config.as_mut().virus,
+                "Virus (a gray faction spreads by a tile every round)",
+            );
+            ui.checkbox(
+                &mut config.as_mut().blind,
+                "Blind colors (the board goes gray after the countdown, hovering shows your captures)",
+            );
+            ui.horizontal(|ui| {
+                ui.checkbox(
+                    &mut config.as_mut().decay,
+                    "Decay (territory away from your frontier reverts)",
+                );
+                if config.decay {
+                    ui.add(
+                        egui::Slider::new(&mut config.as_mut().decay_rounds, 5..=30).text("Rounds"),
+                    );
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut config.as_mut().limit_turns, "Turn limit");
+                if config.limit_turns {
+                    ui.add(
+                        egui::Slider::new(&mut config.as_mut().max_turns, 5..=100).text("Turns"),
+                    );
+                }
+            });
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Handicap")
+                    .selected_text(config.handicap.name())
+                    .show_ui(ui, |ui| {
+                        for handicap in [
+                            core::Handicap::None,
+                            core::Handicap::ExtraTiles { rings: 1 },
+                            core::Handicap::DoubleMove,
+                        ] {
+                            let selected = std::mem::discriminant(&config.handicap)
+                                == std::mem::discriminant(&handicap);
+                            if ui.selectable_label(selected, handicap.name()).clicked() && !selected
+                            {
+                                config.as_mut().handicap = handicap;
+                            }
+                        }
+                    });
+                if config.handicap != core::Handicap::None {
+                    ui.radio_value(&mut config.as_mut().handicapped_player, 0, "for player");
+                    ui.radio_value(&mut config.as_mut().handicapped_player, 1, "for bot");
+                }
+            });
+            if let core::Handicap::ExtraTiles { mut rings } = config.handicap {
+                if ui
+                    .add(egui::Slider::new(&mut rings, 1..=3).text("Rings"))
+                    .changed()
+                {
+                    config.as_mut().handicap = core::Handicap::ExtraTiles { rings };
+                }
+            }
+        });
+        egui::ComboBox::from_label("Bot")
+            .selected_text(config.bot_source.name())
+            .show_ui(ui, |ui| {
+                for &source in BotSource::ALL {
+                    ui.selectable_value(&mut config.as_mut().bot_source, source, source.name());
+                }
+            });
+        match config.bot_source {
+            BotSource::Personality => {
+                egui::ComboBox::from_label("Bot personality")
+                    .selected_text(config.bot_personality.name())
+                    .show_ui(ui, |ui| {
+                        for personality in ai::Personality::ALL {
+                            ui.selectable_value(
+                                &mut config.as_mut().bot_personality,
+                                personality,
+                                personality.name(),
+                            );
+                        }
+                    });
+            }
+            BotSource::Script => {
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut config.as_mut().bot_script.source)
+                                .code_editor()
+                                .desired_rows(10),
+                        );
+                    });
+            }
+            #[cfg(not(target_family = "wasm"))]
+            BotSource::Program => {
+                ui.horizontal(|ui| {
+                    ui.label("Command");
+                    ui.text_edit_singleline(&mut config.as_mut().bot_command.command);
+                });
+                ui.label("e.g. python3 bots/greedy.py");
+            }
+        }
+        if let Some(error) = bot_error.as_ref() {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Spectator opponent");
+            egui::ComboBox::from_id_source("spectator_personality")
+                .selected_text(config.spectator_personality.name())
+                .show_ui(ui, |ui| {
+                    for personality in ai::Personality::ALL {
+                        ui.selectable_value(
+                            &mut config.as_mut().spectator_personality,
+                            personality,
+                            personality.name(),
+                        );
+                    }
+                });
+        });
+
+        let colors_valid = color_allowed(config.num_ids, config.player_color, config.bot_color)
+            && color_allowed(config.num_ids, config.bot_color, config.player_color);
+        if !colors_valid {
+            ui.colored_label(
+                egui::Color32::LIGHT_RED,
+                "Player colors must differ from each other and the board",
+            );
+        }
+        let names_valid =
+            !config.names.player.trim().is_empty() && !config.names.bot.trim().is_empty();
+        if !names_valid {
+            ui.colored_label(egui::Color32::LIGHT_RED, "Players need a name");
+        }
+
+        ui.add_enabled_ui(colors_valid && names_valid, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("start").clicked() {
+                    config.names.save();
+                    config.avatars.save();
+                    match bot_kind(&config) {
+                        Ok(kind) => {
+                            let mut chosen = default_players(&config);
+                            if let Some(kind) = kind {
+                                chosen[1].kind = kind;
+                            }
+                            *bot_error = None;
+                            players = Some(chosen);
+                        }
+                        Err(e) => *bot_error = Some(e),
+                    }
+                }
+
+                if ui.button("watch bots").clicked() {
+                    let mut bots = default_players(&config);
+                    bots[0] = core::Player {
+                        name: config.spectator_personality.name().into(),
+                        avatar: avatar::Avatar::Robot,
+                        kind: core::PlayerKind::Bot(
+                            Timer::from_seconds(config.bot_think_time, false),
+                            config.spectator_personality,
+                        ),
+                        ..bots[0].clone()
+                    };
+                    bots[1].name = config.bot_personality.name().into();
+                    players = Some(bots);
+                }
+            })
+        });
+    });
+
+    if let Some(level) = pasted {
+        editor.open(level);
+        app_state.set(AppState::LevelEditor).ok();
+        return;
+    }
+    if let Some(players) = players {
+        let level = levels::find(config.level_name)
+            .expect("Unknown level")
+            .clone();
+
+        // Every game gets a seed, so its link reproduces the same board
+        let seed = rand::random();
+        link::write(&link::GameLink {
+            level: level.name.to_string(),
+            seed,
+            num_ids: config.num_ids,
+            personality: config.bot_personality,
+        });
+
+        let mut event = start_event(&config, players, level);
+        event.seed = Some(seed);
+        event.training = config.training;
+        *series = series::Series::new(config.best_of, &event);
+        game_start.send(event);
+        app_state.set(AppState::InGame).ok();
+    }
+}
+
+// Opening a shared link fills in the setup screen and starts its game
+pub fn start_linked_game(
+    mut config: ResMut<GameConfigState>,
+    mut app_state: ResMut<State<AppState>>,
+    mut game_start: EventWriter<GameStartEvent>,
+) {
+    let link = match link::read() {
+        Some(link) => link,
+        None => return,
+    };
+    let level = match levels::find(&link.level) {
+        Some(level) => level,
+        None => return,
+    };
+    if !(2..=palette::MAX_IDS).contains(&link.num_ids) {
+        return;
+    }
+
+    config.level_name = &*level.name;
+    config.num_ids = link.num_ids;
+    config.bot_personality = link.personality;
+
+    let mut event = start_event(&config, default_players(&config), level.clone());
+    event.seed = Some(link.seed);
+    game_start.send(event);
+    app_state.set(AppState::InGame).ok();
+}
+
+// Starts the game given on the command line or in the page's query string,
+// unless a game link was followed. Anything left out is taken from the setup
+pub fn start_launched_game(
+    options: Res<launch::LaunchOptions>,
+    mut config: ResMut<GameConfigState>,
+    mut streamer: ResMut<ui::StreamerMode>,
+    mut app_state: ResMut<State<AppState>>,
+    mut game_start: EventWriter<GameStartEvent>,
+) {
+    if options.streamer {
+        streamer.enabled = true;
+    }
+    if !options.starts_game() || link::read().is_some() {
+        return;
+    }
+
+    if let Some(level) = options.level {
+        config.level_name = &*level.name;
+    }
+    if let Some(num_ids) = options.num_ids {
+        config.num_ids = num_ids;
+    }
+    if let Some(personality) = options.personality {
+        config.bot_personality = personality;
+    }
+    let level = levels::find(config.level_name).unwrap_or(&levels::LEVELS[0]);
+
+    let mut event = start_event(&config, default_players(&config), level.clone());
+    event.seed = options.seed;
+    game_start.send(event);
+    app_state.set(AppState::InGame).ok();
+}
+
+pub fn show_how_to_play(
+    config: Res<GameConfigState>,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut game_start: EventWriter<GameStartEvent>,
+) {
+    let mut start_tutorial = false;
+    menu_screen(&mut egui_ctx, &mut app_state, "How to Play", |ui| {
+        ui.label("Each turn, pick a color by clicking a tile next to your territory.");
+        ui.label("Every tile of that color connected to your territory becomes yours.");
+        ui.label("The game ends when nobody can capture any more tiles.");
+        ui.label("Whoever owns the most tiles wins.");
+        ui.add_space(10.0);
+        start_tutorial = ui.button("start tutorial").clicked();
+    });
+
+    if !start_tutorial {
+        return;
+    }
+
+    let level = levels::Level {
+        name: Cow::Borrowed("Tutorial"),
+        layout: Cow::Borrowed(levels::TUTORIAL),
+        topology: core::BoardTopology::Hex,
+        info: levels::LevelInfo::NONE,
+    };
+    let mut event = start_event(&config, default_players(&config), level);
+    // Fewer colors keep the first few moves easy to follow
+    event.ids.retain(|id, _| *id < 3);
+    event.tutorial = true;
+
+    game_start.send(event);
+    app_state.set(AppState::InGame).ok();
+}
+
+pub fn show_stats(
+    player_stats: Res<stats::Stats>,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    menu_screen(&mut egui_ctx, &mut app_state, "Stats", |ui| {
+        stats::stats_ui(ui, &player_stats);
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn show_settings(
+    mut config: ResMut<GameConfigState>,
+    mut player_stats: ResMut<stats::Stats>,
+    mut theme: ResMut<theme::Theme>,
+    mut scoreboard: ResMut<ui::ScoreboardAnchor>,
+    mut streamer: ResMut<ui::StreamerMode>,
+    mut announcer: ResMut<announce::Announcer>,
+    mut alerts: ResMut<alert::TurnAlerts>,
+    mut win_chances: ResMut<ai::WinChanceDisplay>,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    menu_screen(&mut egui_ctx, &mut app_state, "Settings", |ui| {
+        ui.add(
+            egui::Slider::new(&mut config.as_mut().bot_think_time, 0.0..=3.0)
+                .text("Bot think time (s)"),
+        );
+
+        let mut selected = *theme;
+        egui::ComboBox::from_label("Theme")
+            .selected_text(selected.name())
+            .show_ui(ui, |ui| {
+                for option in theme::Theme::ALL {
+                    ui.selectable_value(&mut selected, option, option.name());
+                }
+            });
+        if selected != *theme {
+            *theme = selected;
+            theme.save();
+        }
+
+        let mut anchor = *scoreboard;
+        egui::ComboBox::from_label("Scoreboard")
+            .selected_text(anchor.name())
+            .show_ui(ui, |ui| {
+                for option in ui::ScoreboardAnchor::ALL {
+                    ui.selectable_value(&mut anchor, option, option.name());
+                }
+            });
+        if anchor != *scoreboard {
+            *scoreboard = anchor;
+            scoreboard.save();
+        }
+
+        if ui
+            .checkbox(
+                &mut streamer.enabled,
+                "Streamer layout (large scoreboard, turn clock, no debug text)",
+            )
+            .changed()
+        {
+            streamer.save();
+        }
+
+        if ui
+            .checkbox(&mut announcer.enabled, "Announce moves for screen readers")
+            .changed()
+        {
+            announcer.save();
+        }
+
+        // Natively there's no chime, only the window asking for attention
+        let flash = match cfg!(target_family = "wasm") {
+            true => "Flash the tab's title when it's your turn again",
+            false => "Flash the window when it's your turn again",
+        };
+        let mut changed = ui.checkbox(&mut alerts.flash, flash).changed();
+        if cfg!(target_family = "wasm") {
+            changed |= ui
+                .checkbox(&mut alerts.chime, "Chime when it's your turn again")
+                .changed();
+        }
+        if changed {
+            alerts.save();
+        }
+
+        if ui
+            .checkbox(
+                &mut win_chances.enabled,
+                "Show each player's chance of winning",
+            )
+            .changed()
+        {
+            win_chances.save();
+        }
+
+        if ui.button("reset stats").clicked() {
+            *player_stats = stats::Stats::default();
+            player_stats.save();
+        }
+    });
+}
+
+// Lists what each action is bound to. Rebinding an action waits for the next
+// key or button pressed, which replaces the binding on that device
+pub fn show_controls(
+    mut bindings: ResMut<bindings::Bindings>,
+    mut rebinding: Local<Option<bindings::Action>>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    buttons: Res<Input<GamepadButton>>,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    // Clicks on the menu itself are meant for the menu
+    let over_menu = egui_ctx.ctx_mut().is_pointer_over_area();
+    if let Some(action) = *rebinding {
+        let pressed = bindings::pressed_binding(&keys, &mouse, &buttons)
+            .filter(|binding| !(over_menu && matches!(binding, bindings::Binding::Mouse(_))));
+        if let Some(binding) = pressed {
+            bindings.rebind(action, binding);
+            bindings.save();
+            *rebinding = None;
+        }
+    }
+
+    menu_screen(&mut egui_ctx, &mut app_state, "Controls", |ui| {
+        egui::Grid::new("bindings").show(ui, |ui| {
+            for action in bindings::Action::ALL {
+                // Release builds have no console to open
+                if action == bindings::Action::Console && !cfg!(debug_assertions) {
+                    continue;
+                }
+                ui.label(action.name());
+                let bound = bindings
+                    .get(action)
+                    .iter()
+                    .map(|binding| binding.name())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                ui.label(if bound.is_empty() { "-".into() } else { bound });
+                if *rebinding == Some(action) {
+                    ui.label("press a key or button...");
+                    if ui.button("cancel").clicked() {
+                        *rebinding = None;
+                    }
+                } else if ui.button("rebind").clicked() {
+                    *rebinding = Some(action);
+                }
+                ui.end_row();
+            }
+        });
+
+        if ui.button("reset to defaults").clicked() {
+            *bindings = bindings::Bindings::default();
+            bindings.save();
+            *rebinding = None;
+        }
+    });
+}
+
+pub fn show_level_editor(
+    config: Res<GameConfigState>,
+    asset_server: Res<AssetServer>,
+    mut editor: ResMut<EditorState>,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut app_state: ResMut<State<AppState>>,
+    mut game_start: EventWriter<GameStartEvent>,
+) {
+    let mut play = false;
+    let mut import = false;
+    let mut load = false;
+    let mut paste = false;
+    menu_screen(&mut egui_ctx, &mut app_state, "Level Editor", |ui| {
+        ui.label("'|' colored tile, '-' no tile, '#' wall, '*' extra turn, '+' bonus points,");
+        ui.label("'^' hill for king of the hill games, '|2' to '|9' tiles worth more,");
+        ui.label("'1' to '4' player starting tiles, unused ones become regular tiles");
+
+        egui::ComboBox::from_label("Topology")
+            .selected_text(format!("{:?}", editor.topology))
+            .show_ui(ui, |ui| {
+                for topology in [
+                    core::BoardTopology::Hex,
+                    core::BoardTopology::Square4,
+                    core::BoardTopology::Square8,
+                ] {
+                    ui.selectable_value(
+                        &mut editor.as_mut().topology,
+                        topology,
+                        format!("{:?}", topology),
+                    );
+                }
+            });
+
+        ui.add(
+            egui::TextEdit::multiline(&mut editor.as_mut().layout)
+                .code_editor()
+                .desired_rows(13),
+        );
+
+        // Images are read from disk, which the browser can't do
+        if cfg!(not(target_family = "wasm")) {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut editor.as_mut().image_path)
+                        .hint_text("PNG image")
+                        .desired_width(240.0),
+                );
+                import = ui.button("import").clicked();
+            });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut editor.as_mut().level_path)
+                        .hint_text("Level file in the assets folder")
+                        .desired_width(240.0),
+                );
+                load = ui.button("load").clicked();
+            });
+            if editor.level_file.is_some() {
+                ui.label("The level file is reloaded whenever it's saved");
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.add(egui::TextEdit::singleline(&mut editor.as_mut().name).desired_width(160.0));
+            if ui.button("copy level code").clicked() {
+                match share::level_code(&editor.level()) {
+                    Ok(code) => ui.output().copied_text = code,
+                    Err(e) => editor.error = Some(e),
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut editor.as_mut().level_code)
+                    .hint_text("Level code")
+                    .desired_width(240.0),
+            );
+            paste = ui.button("paste level code").clicked();
+        });
+
+        if let Some(error) = &editor.error {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+
+        play = ui.button("play").clicked();
+    });
+
+    if paste {
+        match share::read_level_code(&editor.level_code) {
+            Ok(level) => {
+                editor.open(level);
+                editor.level_code.clear();
+            }
+            Err(e) => editor.error = Some(e),
+        }
+    }
+
+    if import {
+        let ids = selected_ids(config.num_ids, &config);
+        let imported = std::fs::read(editor.image_path.trim())
+            .map_err(|e| format!("Unable to open the image: {}", e))
+            .and_then(|png| import::level_from_png(&png, &ids));
+        match imported {
+            Ok(imported) => {
+                editor.layout = imported.layout.clone();
+                editor.imported = Some(imported);
+                editor.error = None;
+            }
+            Err(e) => editor.error = Some(e),
+        }
+    }
+    // The layout is filled in once the file has loaded. Only files among the
+    // assets can be watched for changes
+    if load {
+        let path = editor.level_path.trim().to_string();
+        let outside = std::path::Path::new(&path)
+            .components()
+            .any(|part| !matches!(part, std::path::Component::Normal(_)));
+        if path.is_empty() || outside {
+            editor.error = Some("Level files are loaded from the assets folder".into());
+        } else {
+            editor.level_file = Some(asset_server.load(path.as_str()));
+            editor.level_file_read = false;
+            editor.error = None;
+        }
+    }
+    if !play {
+        return;
+    }
+
+    let players = default_players(&config);
+    let placeholders = (0..players.len() as u32)
+        .map(Entity::from_raw)
+        .collect::<Vec<_>>();
+    if let Err(e) = core::parse_level(&editor.layout, &placeholders, vec![0], None) {
+        editor.error = Some(e);
+        return;
+    }
+    editor.error = None;
+
+    let level = levels::Level {
+        name: Cow::Borrowed("Custom"),
+        layout: Cow::Owned(editor.layout.clone()),
+        topology: editor.topology,
+        info: editor.info.clone(),
+    };
+    let mut event = start_event(&config, players, level);
+    if let Some(imported) = editor
+        .imported
+        .as_ref()
+        .filter(|imported| imported.layout == editor.layout)
+    {
+        event.ids = selected_ids(imported.num_ids, &config);
+        event.colors = Some(imported.colors.clone());
+    }
+    game_start.send(event);
+    app_state.set(AppState::InGame).ok();
+}
@@ -0,0 +1,284 @@
+use crate::board::Board;
+use crate::core::{parse_level, BoardTopology, GameState, Player, Tile, TileState};
+use crate::{attract, game, levels, menu, theme, virus, BoardCamera};
+use assimilation::hex;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+// The most room the minimap takes up on either side, in pixels
+const MINIMAP_SIZE: f32 = 180.0;
+// Unowned tiles are darkened, so territory stands out at a glance
+const UNOWNED_BRIGHTNESS: f32 = 0.45;
+// Level previews only show the shape of the board, not its colors
+const PREVIEW_TILE: Color = Color::rgb(0.6, 0.6, 0.6);
+const PREVIEW_WALL: Color = Color::rgb(0.15, 0.15, 0.15);
+
+// The board drawn a pixel per tile, redrawn whenever the board changes
+#[derive(Default)]
+pub struct Minimap {
+    texture: Option<egui::TextureHandle>,
+    drawn: Option<(u64, theme::Theme)>,
+}
+
+// Shows a map of the whole board when it doesn't fit in the window, which can
+// be clicked or dragged on to move the camera
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Minimap>()
+            .add_system(keep_view_on_board)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::run_if_game_started.chain(attract::unless_demo))
+                    .with_system(show_minimap.before(keep_view_on_board)),
+            );
+    }
+}
+
+// How far the camera can move from the middle of the board, while still only
+// showing the board. Nothing on sides where the whole board fits
+fn pan_limit(board_size: Vec2, view_size: Vec2) -> Vec2 {
+    ((board_size - view_size) / 2.0).max(Vec2::ZERO)
+}
+
+fn view_size(windows: &Windows, projection: &OrthographicProjection) -> Vec2 {
+    let window = windows.primary();
+    Vec2::new(window.width(), window.height()) * projection.scale
+}
+
+// Where each tile goes on the map. Rows of hex boards are offset by half a
+// tile, so they take up two pixels each
+fn pixel(topology: BoardTopology, row: i32, column: i32) -> (usize, usize) {
+    let x = match topology {
+        BoardTopology::Hex => hex::half_column(row, column),
+        BoardTopology::Square4 | BoardTopology::Square8 => column,
+    };
+    (x as usize, row as usize)
+}
+
+fn map_image(
+    board: &Board,
+    tiles: &[Tile],
+    color: impl Fn(&TileState) -> Option<egui::Color32>,
+) -> egui::ColorImage {
+    let (width, tile_width) = match board.topology {
+        BoardTopology::Hex => (board.columns as usize * 2 + 1, 2),
+        BoardTopology::Square4 | BoardTopology::Square8 => (board.columns as usize, 1),
+    };
+    let mut image = egui::ColorImage::new(
+        [width.max(1), (board.rows as usize).max(1)],
+        egui::Color32::TRANSPARENT,
+    );
+    for tile in tiles {
+        let color = match color(&tile.state) {
+            Some(color) => color,
+            None => continue,
+        };
+        let (x, y) = pixel(board.topology, tile.row, tile.column);
+        for x in x..(x + tile_width).min(width) {
+            if let Some(pixel) = image.pixels.get_mut(y * width + x) {
+                *pixel = color;
+            }
+        }
+    }
+    image
+}
+
+// The level drawn a pixel per tile like the minimap, with the starting tiles of
+// each player in the given colors, along with the size of its board
+pub fn level_preview(
+    level: &levels::Level,
+    colors: &[Color],
+) -> Result<(egui::ColorImage, Vec2), String> {
+    let players = (0..colors.len() as u32)
+        .map(Entity::from_raw)
+        .collect::<Vec<_>>();
+    let tiles = parse_level(&level.layout, &players, vec![0], Some(0))?;
+    let rows = tiles.iter().map(|tile| tile.row + 1).max().unwrap_or(0);
+    let columns = tiles.iter().map(|tile| tile.column + 1).max().unwrap_or(0);
+    let board = Board::new(level.topology, rows, columns);
+    let image = map_image(&board, &tiles, |state| {
+        let color = match state {
+            TileState::Empty => return None,
+            TileState::Wall => PREVIEW_WALL,
+            TileState::Owned(owner) => *colors.get(owner.id() as usize)?,
+            TileState::Unowned(_) => PREVIEW_TILE,
+        };
+        Some(menu::to_color32(color))
+    });
+    Ok((image, board.size()))
+}
+
+fn darken(color: Color, brightness: f32) -> Color {
+    let [r, g, b, a] = color.as_rgba_f32();
+    Color::rgba(r * brightness, g * brightness, b * brightness, a)
+}
+
+// Keeps the camera over the board, and in the middle of it when it fits
+fn keep_view_on_board(
+    board: Res<Board>,
+    windows: Res<Windows>,
+    mut cameras: Query<(&OrthographicProjection, &mut Transform), With<BoardCamera>>,
+) {
+    for (projection, mut transform) in cameras.iter_mut() {
+        let limit = pan_limit(board.size(), view_size(&windows, projection));
+        let position = transform.translation.truncate().clamp(-limit, limit);
+        if position != transform.translation.truncate() {
+            transform.translation = position.extend(transform.translation.z);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn show_minimap(
+    mut minimap: ResMut<Minimap>,
+    state: Res<GameState>,
+    board: Res<Board>,
+    theme: Res<theme::Theme>,
+    windows: Res<Windows>,
+    tiles: Query<&Tile>,
+    players: Query<&Player>,
+    mut cameras: Query<(&OrthographicProjection, &mut Transform), With<BoardCamera>>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    let (projection, mut transform) = match cameras.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let board_size = board.size();
+    let view = view_size(&windows, projection);
+    if board_size.x <= view.x && board_size.y <= view.y {
+        return;
+    }
+
+    let ctx = egui_ctx.ctx_mut();
+    let drawn = Some((state.board_hash.value, *theme));
+    if minimap.texture.is_none() || minimap.drawn != drawn {
+        let tiles = tiles.iter().cloned().collect::<Vec<_>>();
+        let image = map_image(&board, &tiles, |tile_state| {
+            let color = match tile_state {
+                TileState::Empty => return None,
+                TileState::Wall => theme.wall_color(),
+                TileState::Owned(owner) => players
+                    .get(*owner)
+                    .map_or(virus::VIRUS_COLOR, |player| player.color),
+                TileState::Unowned(_) if state.blind => {
+                    darken(game::BLIND_TILE_COLOR, UNOWNED_BRIGHTNESS)
+                }
+                TileState::Unowned(id) => darken(
+                    *state.ids.get(id).unwrap_or(&Color::GRAY),
+                    UNOWNED_BRIGHTNESS,
+                ),
+            };
+            Some(menu::to_color32(color))
+        });
+        match minimap.texture.as_mut() {
+            Some(texture) => texture.set(image),
+            None => minimap.texture = Some(ctx.load_texture("minimap", image)),
+        }
+        minimap.drawn = drawn;
+    }
+    let texture = match minimap.texture.as_ref() {
+        Some(texture) => texture,
+        None => return,
+    };
+
+    let scale = MINIMAP_SIZE / board_size.x.max(board_size.y);
+    let size = egui::vec2(board_size.x * scale, board_size.y * scale);
+    let mut target = None;
+    egui::Window::new("Map")
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+        .resizable(false)
+        .show(ctx, |ui| {
+            let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+            let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+            ui.painter().add(egui::Shape::image(
+                texture.id(),
+                rect,
+                uv,
+                egui::Color32::WHITE,
+            ));
+
+            // The part of the board in the window. World y points up, while
+            // the map's points down
+            let to_map =
+                |position: Vec2| rect.center() + egui::vec2(position.x, -position.y) * scale;
+            let camera = transform.translation.truncate();
+            ui.painter().rect_stroke(
+                egui::Rect::from_two_pos(to_map(camera - view / 2.0), to_map(camera + view / 2.0))
+                    .intersect(rect),
+                0.0,
+                egui::Stroke::new(1.5, egui::Color32::WHITE),
+            );
+
+            if let Some(pointer) = response.interact_pointer_pos() {
+                let offset = (pointer - rect.center()) / scale;
+                target = Some(Vec2::new(offset.x, -offset.y));
+            }
+        });
+
+    // Clamped to the board by keep_view_on_board
+    if let Some(target) = target {
+        transform.translation = target.extend(transform.translation.z);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::load_level;
+
+    #[test]
+    fn map_offsets_hex_rows() {
+        let player = Entity::from_raw(0);
+        let tiles = load_level("1 | |\n| | |", &[player], vec![0], None);
+        let board = Board::new(BoardTopology::Hex, 2, 3);
+        let image = map_image(&board, &tiles, |state| match state {
+            TileState::Owned(_) => Some(egui::Color32::RED),
+            _ => Some(egui::Color32::BLUE),
+        });
+
+        assert_eq!(image.size, [7, 2]);
+        let row = |y: usize| image.pixels[y * 7..(y + 1) * 7].to_vec();
+        // Even rows sit half a tile to the right of odd ones
+        assert_eq!(row(0)[0], egui::Color32::TRANSPARENT);
+        assert_eq!(row(0)[1..3], [egui::Color32::RED; 2]);
+        assert_eq!(row(0)[3], egui::Color32::BLUE);
+        assert_eq!(row(1)[0], egui::Color32::BLUE);
+        assert_eq!(row(1)[6], egui::Color32::TRANSPARENT);
+    }
+
+    #[test]
+    fn previews_show_where_players_start() {
+        let level = levels::Level {
+            name: "Preview".into(),
+            layout: "1 # -\n| | 2".into(),
+            topology: BoardTopology::Square4,
+            info: levels::LevelInfo::NONE,
+        };
+        let (image, _) = level_preview(&level, &[Color::RED, Color::BLUE]).unwrap();
+
+        assert_eq!(image.size, [3, 2]);
+        let color = |color| menu::to_color32(color);
+        assert_eq!(
+            image.pixels,
+            vec![
+                color(Color::RED),
+                color(PREVIEW_WALL),
+                egui::Color32::TRANSPARENT,
+                color(PREVIEW_TILE),
+                color(PREVIEW_TILE),
+                color(Color::BLUE),
+            ]
+        );
+    }
+
+    #[test]
+    fn camera_only_pans_over_oversized_sides() {
+        assert_eq!(
+            pan_limit(Vec2::new(1000.0, 200.0), Vec2::new(400.0, 300.0)),
+            Vec2::new(300.0, 0.0)
+        );
+    }
+}
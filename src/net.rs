@@ -0,0 +1,256 @@
+use crate::snapshot::Snapshot;
+use assimilation::rooms::CODE_ALPHABET;
+use bevy::prelude::Color;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddrV4, TcpListener, TcpStream, UdpSocket};
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const CODE_LENGTH: usize = 10;
+
+// Who is playing on one side of an online game
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub name: String,
+    pub color: Color,
+}
+
+// What the host picked for the game
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LobbySettings {
+    pub level: String,
+    pub num_ids: u32,
+    pub host: PeerInfo,
+}
+
+// Online games are played in lockstep. Both sides color the board from the
+// same seed and only send each other their moves, each tagged with its place
+// in the game and the hash of the board it was made on, so a game which has
+// gone out of sync is noticed at the next move
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+    // Sent by the guest whenever it connects, including after a reconnect
+    Join(PeerInfo),
+    // Sent by the host whenever the settings change
+    Lobby(LobbySettings),
+    // The board is colored from the seed, so it comes out the same on both sides
+    Start {
+        seed: u64,
+    },
+    Select {
+        turn: usize,
+        id: u32,
+        board_hash: u64,
+    },
+    // The whole game as the host sees it, sent when the guest reconnects in
+    // case moves were lost along with the connection
+    Snapshot(Snapshot),
+    // Ends the game, as the two sides no longer agree on it
+    Abort(String),
+    Chat(String),
+}
+
+// A connection to the other side of an online game, exchanging one message
+// per line. Never blocks, messages are buffered until they can be sent or
+// have been received in full
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            incoming: vec![],
+            outgoing: vec![],
+        })
+    }
+
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        let line =
+            ron::to_string(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.outgoing.extend(line.as_bytes());
+        self.outgoing.push(b'\n');
+        self.flush()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(count) => {
+                    self.outgoing.drain(..count);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    // Every message received in full since the last call. Fails once the
+    // connection is lost
+    pub fn receive(&mut self) -> io::Result<Vec<Message>> {
+        self.flush()?;
+
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(count) => self.incoming.extend(&buffer[..count]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut messages = vec![];
+        while let Some(end) = self.incoming.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.incoming.drain(..=end).collect();
+            let message = std::str::from_utf8(&line)
+                .ok()
+                .and_then(|line| ron::from_str(line.trim()).ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed message"))?;
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+}
+
+// Waits for a guest to connect, with a room code telling them where to find it
+pub struct Host {
+    listener: TcpListener,
+    pub code: String,
+}
+
+impl Host {
+    pub fn open() -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        Ok(Self {
+            listener,
+            code: room_code(SocketAddrV4::new(local_ip(), port)),
+        })
+    }
+
+    // The next guest to connect, if any are waiting
+    pub fn accept(&self) -> io::Result<Option<Connection>> {
+        match self.listener.accept() {
+            Ok((stream, _)) => Connection::new(stream).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+pub fn join(code: &str) -> io::Result<Connection> {
+    let address = parse_room_code(code)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid room code"))?;
+    Connection::new(TcpStream::connect_timeout(
+        &address.into(),
+        CONNECT_TIMEOUT,
+    )?)
+}
+
+// The address other machines on the network reach this one at. Connecting a
+// UDP socket only picks the route, nothing is actually sent
+fn local_ip() -> Ipv4Addr {
+    UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9))?;
+            socket.local_addr()
+        })
+        .ok()
+        .and_then(|address| match address.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .unwrap_or(Ipv4Addr::LOCALHOST)
+}
+
+// The host's address and port, written out in base 32
+pub fn room_code(address: SocketAddrV4) -> String {
+    let value = (u64::from(u32::from(*address.ip())) << 16) | u64::from(address.port());
+    let code: String = (0..CODE_LENGTH)
+        .rev()
+        .map(|idx| CODE_ALPHABET[((value >> (idx * 5)) & 31) as usize] as char)
+        .collect();
+    format!("{}-{}", &code[..CODE_LENGTH / 2], &code[CODE_LENGTH / 2..])
+}
+
+pub fn parse_room_code(code: &str) -> Option<SocketAddrV4> {
+    let mut value: u64 = 0;
+    let mut length = 0;
+    for c in code.chars().filter(|c| !c.is_whitespace() && *c != '-') {
+        let digit = CODE_ALPHABET
+            .iter()
+            .position(|other| *other as char == c.to_ascii_uppercase())?;
+        value = (value << 5) | digit as u64;
+        length += 1;
+    }
+    if length != CODE_LENGTH || value >> 48 != 0 {
+        return None;
+    }
+    Some(SocketAddrV4::new(
+        Ipv4Addr::from((value >> 16) as u32),
+        value as u16,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn room_codes_round_trip() {
+        let address = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 23), 50_123);
+        let code = room_code(address);
+        assert_eq!(code.len(), CODE_LENGTH + 1);
+        assert_eq!(parse_room_code(&code), Some(address));
+        assert_eq!(
+            parse_room_code(&code.to_lowercase().replace('-', " ")),
+            Some(address)
+        );
+
+        assert_eq!(parse_room_code(""), None);
+        assert_eq!(parse_room_code("ZZZZZ-ZZZZZ"), None);
+        assert_eq!(parse_room_code("UUUUU-00000"), None);
+    }
+
+    #[test]
+    fn messages_arrive_in_order() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut guest = Connection::new(TcpStream::connect(address).unwrap()).unwrap();
+        let mut host = Connection::new(listener.accept().unwrap().0).unwrap();
+
+        let sent = vec![
+            Message::Join(PeerInfo {
+                name: "Guest".into(),
+                color: Color::RED,
+            }),
+            Message::Select {
+                turn: 4,
+                id: 3,
+                board_hash: u64::MAX,
+            },
+        ];
+        for message in sent.iter() {
+            guest.send(message).unwrap();
+        }
+
+        let mut received = vec![];
+        while received.len() < sent.len() {
+            received.extend(host.receive().unwrap());
+        }
+        assert_eq!(received, sent);
+
+        drop(guest);
+        while host.receive().is_ok() {}
+    }
+}
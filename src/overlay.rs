@@ -0,0 +1,248 @@
+use crate::bindings::{Action, ActionInput};
+use crate::board::BoardEntity;
+use crate::core::{BoardTopology, GameState, MoveCache, Player, Tile, TileState};
+use crate::TILE_RADIUS;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+const OVERLAY_ALPHA: f32 = 0.6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeatMapMode {
+    Off,
+    // Which player could reach each tile in the fewest moves
+    Reach,
+    // How much the current player would capture with each color
+    Potential,
+}
+
+impl Default for HeatMapMode {
+    fn default() -> Self {
+        HeatMapMode::Off
+    }
+}
+
+impl HeatMapMode {
+    fn next(self) -> Self {
+        match self {
+            HeatMapMode::Off => HeatMapMode::Reach,
+            HeatMapMode::Reach => HeatMapMode::Potential,
+            HeatMapMode::Potential => HeatMapMode::Off,
+        }
+    }
+}
+
+// Analysis overlay, cycled through with Tab
+#[derive(Default)]
+pub struct HeatMap {
+    pub mode: HeatMapMode,
+}
+
+#[derive(Component)]
+pub struct HeatMapMarker;
+
+// The minimum number of moves each player needs to reach every unowned tile.
+// Moving onto a tile of the same color as the last one is free, as both are
+// captured by the same move
+fn reach_distances(
+    tiles: &[Tile],
+    topology: BoardTopology,
+    player: Entity,
+) -> HashMap<(i32, i32), u32> {
+    let board = tiles
+        .iter()
+        .map(|tile| ((tile.row, tile.column), &tile.state))
+        .collect::<HashMap<_, _>>();
+
+    let mut distances = HashMap::new();
+    let mut queue = VecDeque::new();
+    for tile in tiles {
+        if matches!(tile.state, TileState::Owned(owner) if owner == player) {
+            distances.insert((tile.row, tile.column), 0);
+            queue.push_back((tile.row, tile.column));
+        }
+    }
+
+    while let Some((row, column)) = queue.pop_front() {
+        let distance = distances[&(row, column)];
+        let color = match board[&(row, column)] {
+            TileState::Unowned(id) => Some(*id),
+            _ => None,
+        };
+
+        for (row_offset, column_offset) in topology.neighbor_offsets(row) {
+            let neighbor = (row + row_offset, column + column_offset);
+            let cost = match board.get(&neighbor) {
+                Some(TileState::Unowned(id)) if Some(*id) == color => 0,
+                Some(TileState::Unowned(_)) => 1,
+                _ => continue,
+            };
+
+            let new_distance = distance + cost;
+            match distances.get(&neighbor) {
+                Some(old) if *old <= new_distance => continue,
+                _ => distances.insert(neighbor, new_distance),
+            };
+            if cost == 0 {
+                queue.push_front(neighbor);
+            } else {
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    distances
+}
+
+// The player able to reach each unowned tile strictly sooner than the others
+pub fn reach_owners(
+    tiles: &[Tile],
+    topology: BoardTopology,
+    players: &[Entity],
+) -> HashMap<(i32, i32), Entity> {
+    let distances = players
+        .iter()
+        .map(|player| (*player, reach_distances(tiles, topology, *player)))
+        .collect::<Vec<_>>();
+
+    let mut owners = HashMap::new();
+    for tile in tiles {
+        if !matches!(tile.state, TileState::Unowned(_)) {
+            continue;
+        }
+
+        let position = (tile.row, tile.column);
+        let mut reach = distances
+            .iter()
+            .filter_map(|(player, distances)| Some((*distances.get(&position)?, *player)))
+            .collect::<Vec<_>>();
+        reach.sort_by_key(|(distance, _)| *distance);
+
+        match reach.as_slice() {
+            [(_, player)] => {
+                owners.insert(position, *player);
+            }
+            [(best, player), (second, _), ..] if best < second => {
+                owners.insert(position, *player);
+            }
+            _ => (),
+        }
+    }
+    owners
+}
+
+pub fn toggle_heat_map(input: ActionInput, mut heat_map: ResMut<HeatMap>) {
+    if input.just_pressed(Action::HeatMap) {
+        heat_map.mode = heat_map.mode.next();
+    }
+}
+
+// Only recomputed when the overlay is toggled or the board changes
+pub fn update_heat_map(
+    heat_map: Res<HeatMap>,
+    state: Res<GameState>,
+    cache: Res<MoveCache>,
+    players: Query<&Player>,
+    tiles: Query<(&Tile, &Transform)>,
+    markers: Query<Entity, With<HeatMapMarker>>,
+    mut commands: Commands,
+) {
+    if !(heat_map.is_changed() || cache.is_changed()) {
+        return;
+    }
+
+    for marker in markers.iter() {
+        commands.entity(marker).despawn();
+    }
+
+    let board = tiles
+        .iter()
+        .map(|(tile, _)| tile.clone())
+        .collect::<Vec<_>>();
+    // The overlays would show where each color lies in a blind game
+    let colors: HashMap<(i32, i32), Color> = match heat_map.mode {
+        _ if state.blind => return,
+        HeatMapMode::Off => return,
+        HeatMapMode::Reach => reach_owners(&board, state.topology, &state.players)
+            .into_iter()
+            .filter_map(|(position, owner)| Some((position, players.get(owner).ok()?.color)))
+            .collect(),
+        HeatMapMode::Potential => {
+            let player = state.players[0];
+            let color = match players.get(player) {
+                Ok(player) => player.color,
+                Err(_) => return,
+            };
+            let best = state
+                .ids
+                .keys()
+                .map(|id| cache.capture_count(player, *id))
+                .max()
+                .unwrap_or(0)
+                .max(1);
+
+            board
+                .iter()
+                .filter_map(|tile| match tile.state {
+                    TileState::Unowned(id) if cache.capture_count(player, id) > 0 => {
+                        let potential = cache.capture_count(player, id) as f32 / best as f32;
+                        let mut color = color;
+                        color.set_a(potential * OVERLAY_ALPHA);
+                        Some(((tile.row, tile.column), color))
+                    }
+                    _ => None,
+                })
+                .collect()
+        }
+    };
+
+    let marker = shapes::Circle {
+        radius: TILE_RADIUS / 2.0,
+        ..shapes::Circle::default()
+    };
+    for (tile, transform) in tiles.iter() {
+        let mut color = match colors.get(&(tile.row, tile.column)) {
+            Some(color) => *color,
+            None => continue,
+        };
+        if heat_map.mode == HeatMapMode::Reach {
+            color.set_a(OVERLAY_ALPHA);
+        }
+
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &marker,
+                DrawMode::Outlined {
+                    fill_mode: FillMode::color(color),
+                    outline_mode: StrokeMode::new(*Color::BLACK.clone().set_a(color.a()), 1.0),
+                },
+                Transform::from_translation(transform.translation.truncate().extend(2.5)),
+            ))
+            .insert(HeatMapMarker)
+            .insert(BoardEntity);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::load_level;
+
+    #[test]
+    fn nearer_player_owns_tile() {
+        let players = [Entity::from_raw(0), Entity::from_raw(1)];
+        let tiles = load_level("1 | | | | 2", &players, vec![0], None);
+        let owners = reach_owners(&tiles, BoardTopology::Square4, &players);
+
+        // A single color is captured all at once, so every tile is a tie
+        assert!(owners.is_empty());
+
+        let mut tiles = tiles;
+        tiles[3].state = TileState::Unowned(1);
+        let owners = reach_owners(&tiles, BoardTopology::Square4, &players);
+        assert_eq!(owners.get(&(0, 1)), Some(&players[0]));
+        assert_eq!(owners.get(&(0, 4)), Some(&players[1]));
+        assert_eq!(owners.get(&(0, 3)), None);
+    }
+}
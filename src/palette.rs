@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+use std::cmp::Ordering;
+
+// The most colors a board can be made of
+pub const MAX_IDS: u32 = 10;
+// Colors closer together than this are too easy to mix up
+const MIN_CONTRAST: f32 = 0.3;
+// How many ways of turning the color wheel are tried
+const ROTATION_STEPS: u32 = 12;
+// With more colors than this, neighboring hues are too close to tell apart
+// without varying their lightness as well
+const MAX_HUES: u32 = 6;
+
+pub fn contrast(a: Color, b: Color) -> f32 {
+    let [ar, ag, ab, _] = a.as_rgba_f32();
+    let [br, bg, bb, _] = b.as_rgba_f32();
+    ((ar - br).powi(2) + (ag - bg).powi(2) + (ab - bb).powi(2)).sqrt()
+}
+
+// Whether two colors are far enough apart to tell them apart on the board
+pub fn distinguishable(a: Color, b: Color) -> bool {
+    contrast(a, b) > MIN_CONTRAST
+}
+
+// What the color would usually be called, for describing it in words
+pub fn color_name(color: Color) -> String {
+    const HUES: [(f32, &str); 9] = [
+        (0.0, "red"),
+        (30.0, "orange"),
+        (60.0, "yellow"),
+        (120.0, "green"),
+        (175.0, "teal"),
+        (230.0, "blue"),
+        (275.0, "purple"),
+        (320.0, "pink"),
+        (360.0, "red"),
+    ];
+    let (hue, saturation, lightness) = match color.as_hsla() {
+        Color::Hsla {
+            hue,
+            saturation,
+            lightness,
+            ..
+        } => (hue, saturation, lightness),
+        _ => unreachable!(),
+    };
+    if saturation < 0.15 {
+        return match lightness {
+            l if l < 0.2 => "black",
+            l if l > 0.8 => "white",
+            _ => "gray",
+        }
+        .to_string();
+    }
+
+    let name = HUES
+        .iter()
+        .min_by(|a, b| {
+            (a.0 - hue)
+                .abs()
+                .partial_cmp(&(b.0 - hue).abs())
+                .unwrap_or(Ordering::Equal)
+        })
+        .map(|(_, name)| *name)
+        .unwrap_or("red");
+    if lightness < 0.4 {
+        format!("dark {}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+// Tile colors with evenly spaced hues, turned to stay as far as possible from
+// each other and the given colors (usually the players')
+pub fn generate(num_ids: u32, avoid: &[Color]) -> Vec<Color> {
+    let spacing = 360.0 / num_ids.max(1) as f32;
+    let lowest_contrast = |colors: &[Color]| {
+        colors
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, color)| {
+                colors[idx + 1..]
+                    .iter()
+                    .chain(avoid)
+                    .map(|other| contrast(*color, *other))
+            })
+            .fold(f32::MAX, f32::min)
+    };
+
+    let mut best: Option<(f32, Vec<Color>)> = None;
+    for step in 0..ROTATION_STEPS {
+        let colors = wheel(num_ids, step as f32 * spacing / ROTATION_STEPS as f32);
+        let score = lowest_contrast(&colors);
+        match best {
+            Some((best_score, _)) if best_score >= score => (),
+            _ => best = Some((score, colors)),
+        }
+    }
+    best.map(|(_, colors)| colors).unwrap_or_default()
+}
+
+fn wheel(num_ids: u32, offset: f32) -> Vec<Color> {
+    let spacing = 360.0 / num_ids.max(1) as f32;
+    (0..num_ids)
+        .map(|idx| {
+            let lightness = if num_ids > MAX_HUES && idx % 2 == 1 {
+                0.35
+            } else {
+                0.55
+            };
+            Color::hsl(
+                (230.0 + offset + idx as f32 * spacing) % 360.0,
+                0.7,
+                lightness,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generated_colors_are_distinct() {
+        let players = [Color::CYAN, Color::RED];
+        for num_ids in 2..=MAX_IDS {
+            let colors = generate(num_ids, &players);
+            assert_eq!(colors.len(), num_ids as usize);
+            for (idx, color) in colors.iter().enumerate() {
+                for other in colors.iter().skip(idx + 1) {
+                    assert!(distinguishable(*color, *other));
+                }
+            }
+        }
+
+        // Up to the usual number of colors there's room to avoid the players
+        for num_ids in 2..=MAX_HUES {
+            for color in generate(num_ids, &players) {
+                assert!(players.iter().all(|player| distinguishable(color, *player)));
+            }
+        }
+    }
+
+    #[test]
+    fn colors_are_named() {
+        assert_eq!(color_name(Color::hsl(225.0, 0.7, 0.55)), "blue");
+        assert_eq!(color_name(Color::hsl(350.0, 0.7, 0.35)), "dark red");
+        assert_eq!(color_name(Color::rgb(0.5, 0.5, 0.5)), "gray");
+    }
+}
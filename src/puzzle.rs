@@ -0,0 +1,119 @@
+use crate::core::{self, GameState};
+use crate::{attract, levels, GameStartEvent};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use std::borrow::Cow;
+
+pub const PUZZLE_COLORS: u32 = 4;
+// Boards solved in fewer moves are too easy to bother with, and ones taking
+// more are passed over rather than searched to the end
+const MIN_PAR: usize = 7;
+const MAX_PAR: usize = 12;
+// Boards tried before settling for whatever the last one was
+const MAX_ATTEMPTS: u64 = 20;
+
+// The puzzle being played, if any, and the fewest moves it can be taken in
+#[derive(Default)]
+pub struct Puzzle(pub Option<u32>);
+
+pub struct PuzzlePlugin;
+
+impl Plugin for PuzzlePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Puzzle>()
+            .add_system(start_puzzle.after(crate::game::game_start))
+            .add_system(show_par);
+    }
+}
+
+pub fn level() -> levels::Level {
+    levels::Level {
+        name: Cow::Borrowed("Puzzle"),
+        layout: Cow::Borrowed(levels::PUZZLE),
+        topology: core::BoardTopology::Hex,
+        info: levels::LevelInfo::NONE,
+    }
+}
+
+// Finds a mirrored board, colored from a seed, which takes a fair number of
+// moves to take alone. Gives the seed coloring it and its par
+pub fn generate(ids: &[u32], seed: u64) -> Option<(u64, u32)> {
+    let level = level();
+    let player = Entity::from_raw(0);
+    let mut found = None;
+    for seed in seed..seed.wrapping_add(MAX_ATTEMPTS) {
+        let mut tiles = core::load_level(&level.layout, &[player], ids.to_vec(), Some(seed));
+        core::mirror_colors(&mut tiles, level.topology);
+        let par = match core::solve(&tiles, level.topology, player, MAX_PAR) {
+            Some(solution) => solution.len(),
+            None => continue,
+        };
+        found = Some((seed, par as u32));
+        if par >= MIN_PAR {
+            break;
+        }
+    }
+    found
+}
+
+fn start_puzzle(mut start_event: EventReader<GameStartEvent>, mut puzzle: ResMut<Puzzle>) {
+    if let Some(event) = start_event.iter().last() {
+        puzzle.0 = event.par;
+    }
+}
+
+// How the moves made so far compare with the board's par
+fn show_par(
+    puzzle: Res<Puzzle>,
+    state: Res<GameState>,
+    attract: Res<attract::Attract>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    let par = match puzzle.0 {
+        Some(par) if state.phase != core::GamePhase::Config && !attract.demo => par,
+        _ => return,
+    };
+
+    let moves = state.history.len() as u32;
+    egui::Window::new("Puzzle")
+        .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+        .resizable(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.label("Take the whole board in as few moves as you can");
+            ui.label(format!("Par: {}", par));
+            ui.label(format!("Moves: {}", moves));
+            if state.phase.is_finished() {
+                ui.label(match moves.cmp(&par) {
+                    std::cmp::Ordering::Less => "Under par!",
+                    std::cmp::Ordering::Equal => "Solved at par",
+                    std::cmp::Ordering::Greater => "Solved, try again for par",
+                });
+            }
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generated_puzzles_replay_with_their_par() {
+        let ids = (0..PUZZLE_COLORS).collect::<Vec<_>>();
+        let (seed, par) = generate(&ids, 7).expect("A puzzle");
+        assert!(par as usize <= MAX_PAR);
+
+        // Playing the solution takes the whole board
+        let level = level();
+        let player = Entity::from_raw(0);
+        let mut tiles = core::load_level(&level.layout, &[player], ids, Some(seed));
+        core::mirror_colors(&mut tiles, level.topology);
+        let solution = core::solve(&tiles, level.topology, player, MAX_PAR).unwrap();
+        assert_eq!(solution.len() as u32, par);
+        for id in solution {
+            core::simulate_selection(&mut tiles, level.topology, id, player);
+        }
+        assert!(tiles
+            .iter()
+            .all(|tile| matches!(tile.state, core::TileState::Owned(_))));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::menu::{AppState, EditorState};
+use crate::{core, levels, GameStartEvent};
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+
+// Picks up changes to the level file loaded in the editor as it's saved, so a
+// level can be worked on in another editor. Level files are assets, reloaded
+// by the asset server as they change. Whatever is on screen is rebuilt: the
+// editor's layout, or the board if the level is being played
+pub struct ReloadPlugin;
+
+impl Plugin for ReloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<levels::LevelFile>()
+            .init_asset_loader::<levels::LevelFileLoader>()
+            .add_startup_system(watch_for_changes)
+            .add_system(reload_level.after(crate::game::game_start));
+    }
+}
+
+fn watch_for_changes(asset_server: Res<AssetServer>) {
+    if let Err(e) = asset_server.watch_for_changes() {
+        warn!("Unable to watch level files for changes: {:?}", e);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn reload_level(
+    mut editor: ResMut<EditorState>,
+    app_state: Res<State<AppState>>,
+    asset_server: Res<AssetServer>,
+    files: Res<Assets<levels::LevelFile>>,
+    mut file_events: EventReader<AssetEvent<levels::LevelFile>>,
+    mut start_event: EventReader<GameStartEvent>,
+    mut game_start: EventWriter<GameStartEvent>,
+    mut playing: Local<Option<GameStartEvent>>,
+) {
+    if let Some(event) = start_event.iter().last() {
+        *playing = Some(event.clone());
+    }
+    let modified = file_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let handle = match &editor.level_file {
+        Some(handle) => handle.clone(),
+        None => return,
+    };
+    let path = editor.level_path.trim().to_string();
+
+    // Reading the file failed, either when it was loaded or since
+    if asset_server.get_load_state(&handle) == LoadState::Failed {
+        if editor.error.is_none() {
+            editor.error = Some(format!("Unable to read the level file {}", path));
+        }
+        return;
+    }
+    // The file as it was loaded is the starting point, only the editor is
+    // filled in from it
+    let loaded = !editor.level_file_read;
+    if !loaded && !modified.contains(&handle) {
+        return;
+    }
+    let level = match files.get(&handle) {
+        Some(file) => file.to_level(),
+        None => return,
+    };
+    editor.level_file_read = true;
+    let level = match level {
+        Ok(level) => level,
+        Err(e) => return editor.error = Some(e),
+    };
+    let layout = level.layout.into_owned();
+    let placeholders = [Entity::from_raw(0), Entity::from_raw(1)];
+    if let Err(e) = core::parse_level(&layout, &placeholders, vec![0], None) {
+        warn!("Not reloading {}: {}", path, e);
+        return editor.error = Some(e);
+    }
+    editor.error = None;
+    editor.topology = level.topology;
+    editor.info = level.info.clone();
+    let previous = std::mem::replace(&mut editor.layout, layout.clone());
+    if loaded {
+        return info!("Loaded {}", path);
+    }
+    info!("Reloaded {}", path);
+
+    // Only the editor's own level is rebuilt, starting it over as it was
+    let event = match playing.as_ref() {
+        Some(event) if *app_state.current() == AppState::InGame => event,
+        _ => return,
+    };
+    if event.level.name != "Custom" || event.level.layout != previous {
+        return;
+    }
+    let mut event = event.clone();
+    event.level.layout = layout.into();
+    event.level.topology = level.topology;
+    event.level.info = level.info;
+    // Colors imported from an image only fit the layout they came with
+    event.colors = None;
+    game_start.send(event);
+}
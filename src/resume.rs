@@ -0,0 +1,382 @@
+use crate::ai::Personality;
+use crate::avatar::Avatar;
+use crate::core::{self, Handicap, PlayerKind, RulesConfig};
+use crate::snapshot::Snapshot;
+use crate::{attract, game, share, stats, storage, theme, GameStartEvent};
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+const LAST_GAME_KEY: &str = "last_game";
+
+// How a player's moves are made. Bots written as scripts or programs are
+// started again from whatever was last set up for them
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SavedKind {
+    Human,
+    Bot(Personality),
+    Scripted,
+    External,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedPlayer {
+    pub name: String,
+    pub color: Color,
+    #[serde(default)]
+    pub avatar: Avatar,
+    pub kind: SavedKind,
+    pub think_time: f32,
+}
+
+// The settings a game was started with, enough to start it again
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedStart {
+    pub players: Vec<SavedPlayer>,
+    pub ids: BTreeMap<u32, Color>,
+    pub level_name: String,
+    // The board as it started, colors and all
+    pub board_code: String,
+    pub seed: Option<u64>,
+    pub daily: Option<u64>,
+    pub rules: RulesConfig,
+    pub handicap: Handicap,
+    pub handicapped_player: usize,
+    pub virus: bool,
+    #[serde(default)]
+    pub blind: bool,
+    #[serde(default)]
+    pub par: Option<u32>,
+}
+
+impl SavedStart {
+    // Online games and tutorials can't be picked back up alone, nor training
+    // games, as the lines tried aren't kept
+    pub fn new(event: &GameStartEvent, board_code: String) -> Option<Self> {
+        if event.tutorial || event.training {
+            return None;
+        }
+        let players = event
+            .players
+            .iter()
+            .map(|player| {
+                let (kind, timer) = match &player.kind {
+                    PlayerKind::Human => (SavedKind::Human, None),
+                    PlayerKind::Bot(timer, personality) => {
+                        (SavedKind::Bot(*personality), Some(timer))
+                    }
+                    PlayerKind::Scripted(timer, _) => (SavedKind::Scripted, Some(timer)),
+                    #[cfg(not(target_family = "wasm"))]
+                    PlayerKind::External(timer, _) => (SavedKind::External, Some(timer)),
+                    PlayerKind::Remote | PlayerKind::Neutral => return None,
+                };
+                Some(SavedPlayer {
+                    name: player.name.clone(),
+                    color: player.color,
+                    avatar: player.avatar,
+                    kind,
+                    think_time: timer.map_or(0.0, |timer| timer.duration().as_secs_f32()),
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Self {
+            players,
+            ids: event.ids.clone(),
+            level_name: event.level.name.to_string(),
+            board_code,
+            seed: event.seed,
+            daily: event.daily,
+            rules: event.rules.clone(),
+            handicap: event.handicap,
+            handicapped_player: event.handicapped_player,
+            virus: event.virus,
+            blind: event.blind,
+            par: event.par,
+        })
+    }
+
+    // Starts the same game again, on the same board
+    pub fn event(&self) -> Result<GameStartEvent, String> {
+        let code = share::BoardCode::decode(&self.board_code)?;
+        let (mut level, colors) = code.level();
+        level.name = Cow::Owned(self.level_name.clone());
+
+        let players = self
+            .players
+            .iter()
+            .map(|player| {
+                let timer = Timer::from_seconds(player.think_time, false);
+                let kind = match player.kind {
+                    SavedKind::Human => PlayerKind::Human,
+                    SavedKind::Bot(personality) => PlayerKind::Bot(timer, personality),
+                    SavedKind::Scripted => {
+                        let script = crate::script::BotScript::load().compile()?;
+                        PlayerKind::Scripted(timer, script)
+                    }
+                    #[cfg(not(target_family = "wasm"))]
+                    SavedKind::External => {
+                        PlayerKind::External(timer, crate::external::BotCommand::load().spawn()?)
+                    }
+                    #[cfg(target_family = "wasm")]
+                    SavedKind::External => return Err("Bot programs can't be run here".into()),
+                };
+                Ok(core::Player {
+                    name: player.name.clone(),
+                    color: player.color,
+                    avatar: player.avatar,
+                    kind,
+                    score: 0,
+                    bonus: 0,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(GameStartEvent {
+            players,
+            ids: self.ids.clone(),
+            level,
+            seed: self.seed,
+            colors: Some(colors),
+            daily: self.daily,
+            rules: self.rules.clone(),
+            handicap: self.handicap,
+            handicapped_player: self.handicapped_player,
+            mirrored: false,
+            virus: self.virus,
+            tutorial: false,
+            training: false,
+            blind: self.blind,
+            par: self.par,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedGame {
+    pub start: SavedStart,
+    pub snapshot: Snapshot,
+}
+
+// The game in progress when the app was last closed, remembered between
+// sessions so it can be continued from the title screen
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LastGame {
+    pub game: Option<SavedGame>,
+}
+
+impl LastGame {
+    pub fn load() -> Self {
+        storage::load(LAST_GAME_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(LAST_GAME_KEY, self)
+    }
+}
+
+// The game being played, if it can be continued later, and a saved game
+// waiting to be brought back once its board is in place
+#[derive(Default)]
+pub struct Resume {
+    start: Option<SavedStart>,
+    // How many moves had been made when the game was last saved
+    saved_moves: Option<usize>,
+    pending: Option<Snapshot>,
+}
+
+impl Resume {
+    // Continues the saved game, once the event starting it has been handled
+    pub fn continue_game(&mut self, saved: &SavedGame) -> Result<GameStartEvent, String> {
+        let event = saved.start.event()?;
+        self.pending = Some(saved.snapshot.clone());
+        Ok(event)
+    }
+}
+
+pub struct ResumePlugin;
+
+impl Plugin for ResumePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LastGame::load())
+            .init_resource::<Resume>()
+            .add_system(remember_start.after(game::game_start))
+            .add_system(restore_saved_game.after(remember_start))
+            .add_system(autosave.after(restore_saved_game));
+    }
+}
+
+fn remember_start(
+    mut start_event: EventReader<GameStartEvent>,
+    mut resume: ResMut<Resume>,
+    summary: Res<stats::GameSummary>,
+    attract: Res<attract::Attract>,
+) {
+    let event = match start_event.iter().last() {
+        Some(event) => event,
+        None => return,
+    };
+    resume.saved_moves = None;
+    resume.start = match &summary.board_code {
+        Some(code) if !attract.demo => SavedStart::new(event, code.clone()),
+        _ => None,
+    };
+    if resume.start.is_none() {
+        resume.pending = None;
+    }
+}
+
+// Brings the saved game's board, scores and turn back, the frame after its
+// board was set up
+fn restore_saved_game(
+    mut start_event: EventReader<GameStartEvent>,
+    mut resume: ResMut<Resume>,
+    mut state: ResMut<core::GameState>,
+    mut cache: ResMut<core::MoveCache>,
+    theme: Res<theme::Theme>,
+    mut tiles: Query<(&mut core::Tile, &mut DrawMode, &mut Transform)>,
+    mut players: ParamSet<(Query<&core::Player>, Query<&mut core::Player>)>,
+) {
+    if start_event.iter().count() > 0 {
+        return;
+    }
+    let snapshot = match resume.pending.take() {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+
+    let mut bonus = vec![];
+    let restored = snapshot.restore(
+        &mut state,
+        tiles.iter_mut().map(|(tile, _, _)| tile),
+        |player, points| bonus.push((player, points)),
+    );
+    let changed = match restored {
+        Ok(changed) => changed,
+        Err(e) => return warn!("Unable to continue the last game: {}", e),
+    };
+    info!("Continued the last game at move {}", state.history.len());
+
+    for (player, points) in bonus {
+        if let Ok(mut player) = players.p1().get_mut(player) {
+            player.bonus = points;
+        }
+    }
+    for (tile, mut mode, mut transform) in tiles.iter_mut() {
+        if !changed.contains(&(tile.row, tile.column)) {
+            continue;
+        }
+        if let Some((new_mode, z_pos)) =
+            game::tile_appearance(&theme, &state, &players.p0(), &tile.state)
+        {
+            *mode = new_mode;
+            transform.translation.z = z_pos;
+        }
+    }
+    cache.stale = true;
+}
+
+// Saves the game after every move, and forgets it once it's over, so closing
+// the app midway loses nothing
+fn autosave(
+    mut start_event: EventReader<GameStartEvent>,
+    mut resume: ResMut<Resume>,
+    mut last_game: ResMut<LastGame>,
+    state: Res<core::GameState>,
+    tiles: Query<&core::Tile>,
+    players: Query<&core::Player>,
+) {
+    // The board isn't in place until the frame after a game starts
+    if start_event.iter().count() > 0 || resume.pending.is_some() {
+        return;
+    }
+    let start = match &resume.start {
+        Some(start) => start.clone(),
+        None => return,
+    };
+
+    if state.phase.is_finished() {
+        resume.start = None;
+        last_game.game = None;
+        last_game.save();
+        return;
+    }
+    if resume.saved_moves == Some(state.history.len()) {
+        return;
+    }
+    resume.saved_moves = Some(state.history.len());
+
+    let tiles = tiles.iter().cloned().collect::<Vec<_>>();
+    let snapshot = Snapshot::capture(&state, &tiles, |player| {
+        players.get(player).map(|player| player.bonus).unwrap_or(0)
+    });
+    last_game.game = Some(SavedGame { start, snapshot });
+    last_game.save();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::levels;
+
+    #[test]
+    fn saved_games_start_again() {
+        let player = |name: &str, kind| core::Player {
+            name: name.into(),
+            color: Color::WHITE,
+            avatar: Avatar::Person,
+            kind,
+            score: 0,
+            bonus: 0,
+        };
+        let bot = PlayerKind::Bot(Timer::from_seconds(0.5, false), Personality::Aggressive);
+        let mut event = GameStartEvent {
+            players: vec![player("human", PlayerKind::Human), player("bot", bot)],
+            ids: (0..4).map(|id| (id, Color::NONE)).collect(),
+            level: levels::LEVELS[0].clone(),
+            seed: Some(1),
+            colors: None,
+            daily: None,
+            rules: RulesConfig::default(),
+            handicap: Handicap::DoubleMove,
+            handicapped_player: 1,
+            mirrored: false,
+            virus: false,
+            tutorial: false,
+            training: false,
+            blind: false,
+            par: None,
+        };
+        let entities = [Entity::from_raw(0), Entity::from_raw(1)];
+        let tiles = core::load_level(&event.level.layout, &entities, vec![0, 1, 2, 3], Some(1));
+        let code = share::BoardCode::new(&tiles, &entities, event.level.topology, 4);
+
+        let start = SavedStart::new(&event, code.encode()).unwrap();
+        let saved = ron::from_str::<SavedStart>(&ron::to_string(&start).unwrap()).unwrap();
+        assert_eq!(saved, start);
+
+        let again = saved.event().unwrap();
+        assert_eq!(again.level.name, event.level.name);
+        assert_eq!(again.handicapped_player, 1);
+        assert!(matches!(
+            again.players[1].kind,
+            PlayerKind::Bot(ref timer, Personality::Aggressive) if timer.duration().as_secs_f32() == 0.5
+        ));
+        let mut replayed = core::load_level(&again.level.layout, &entities, vec![0], None);
+        share::apply_colors(&mut replayed, again.colors.as_ref().unwrap());
+        let states = |tiles: &[core::Tile]| {
+            tiles
+                .iter()
+                .map(|tile| format!("{:?}", tile.state))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(states(&replayed), states(&tiles));
+
+        // Online games can't be continued alone
+        event.players[1].kind = PlayerKind::Remote;
+        assert!(SavedStart::new(&event, code.encode()).is_none());
+    }
+}
@@ -0,0 +1,212 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+// Correspondence games are played through a small server keeping a room for
+// each game, with how it was set up and every move made so far. Either side
+// can pick the game back up at any time by replaying it, so the two players
+// needn't be around at the same time. The server never looks inside the setup
+// and only checks that moves arrive in order from one of the two seats. Whose
+// turn it is depends on the level and the rules, so the players check that
+// along with the rest
+
+// Room ids and the codes of games on the local network are written with these,
+// which avoid letters easily mistaken for digits
+pub const CODE_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+// Room ids double as file names on the server, so are kept short and plain
+pub const MAX_ROOM_ID_LENGTH: usize = 32;
+// The most the setup or the guest may take up, in bytes
+pub const MAX_TEXT_LENGTH: usize = 4096;
+// Far longer than any game on the stock levels lasts
+pub const MAX_MOVES: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomMove {
+    pub id: u32,
+    // The board the move was made on, so a game out of sync is noticed
+    pub board_hash: u64,
+}
+
+// A move sent to the server, only accepted as the next one in the game and
+// from one of its seats
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MoveSubmission {
+    // Handed out to the seat when the room was created or joined
+    pub secret: String,
+    pub turn: usize,
+    pub made: RoomMove,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Room {
+    pub setup: String,
+    // Whoever took the second seat, once someone has
+    pub guest: Option<String>,
+    pub moves: Vec<RoomMove>,
+    // What each seat's moves are sent with. They're never sent back out
+    pub host_secret: String,
+    pub guest_secret: Option<String>,
+}
+
+// A room as fetched by one of its players, without the moves they've already
+// seen
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoomUpdate {
+    pub setup: String,
+    pub guest: Option<String>,
+    // How many moves were made before the first of these
+    pub first: usize,
+    pub moves: Vec<RoomMove>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoomError {
+    SeatTaken,
+    // Nobody has joined yet, so there's nobody to play against
+    NoGuest,
+    // The move came from neither seat
+    NotSeated,
+    OutOfTurn { expected: usize },
+    TooLong,
+}
+
+impl fmt::Display for RoomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RoomError::SeatTaken => write!(f, "Someone else has already joined"),
+            RoomError::NoGuest => write!(f, "Nobody has joined yet"),
+            RoomError::NotSeated => write!(f, "Not one of the room's players"),
+            RoomError::OutOfTurn { expected } => {
+                write!(f, "Out of turn, expecting move {}", expected + 1)
+            }
+            RoomError::TooLong => write!(f, "Too long"),
+        }
+    }
+}
+
+impl Room {
+    // The secrets are picked by the server, which hands each to its seat
+    pub fn new(setup: String, host_secret: String) -> Result<Self, RoomError> {
+        if setup.len() > MAX_TEXT_LENGTH {
+            return Err(RoomError::TooLong);
+        }
+        Ok(Self {
+            setup,
+            guest: None,
+            moves: Vec::new(),
+            host_secret,
+            guest_secret: None,
+        })
+    }
+
+    // The seat is only ever taken once. Anyone could send the same guest, so
+    // it's the secret which lets the guest back in
+    pub fn join(&mut self, guest: String, secret: String) -> Result<(), RoomError> {
+        if guest.len() > MAX_TEXT_LENGTH {
+            return Err(RoomError::TooLong);
+        }
+        if self.guest.is_some() {
+            return Err(RoomError::SeatTaken);
+        }
+        self.guest = Some(guest);
+        self.guest_secret = Some(secret);
+        Ok(())
+    }
+
+    // Players can move more than once in a row, with extra turn tiles, the
+    // double move handicap or an opponent with no move, so either seat may
+    // send any move. Sending the same move again is fine, in case the answer
+    // was lost
+    pub fn submit(&mut self, submission: MoveSubmission) -> Result<(), RoomError> {
+        if self.guest.is_none() {
+            return Err(RoomError::NoGuest);
+        }
+        let seated = submission.secret == self.host_secret
+            || self.guest_secret.as_ref() == Some(&submission.secret);
+        if !seated {
+            return Err(RoomError::NotSeated);
+        }
+        if self.moves.get(submission.turn) == Some(&submission.made) {
+            return Ok(());
+        }
+        if submission.turn != self.moves.len() {
+            return Err(RoomError::OutOfTurn {
+                expected: self.moves.len(),
+            });
+        }
+        if self.moves.len() >= MAX_MOVES {
+            return Err(RoomError::TooLong);
+        }
+        self.moves.push(submission.made);
+        Ok(())
+    }
+
+    pub fn since(&self, first: usize) -> RoomUpdate {
+        let first = first.min(self.moves.len());
+        RoomUpdate {
+            setup: self.setup.clone(),
+            guest: self.guest.clone(),
+            first,
+            moves: self.moves[first..].to_vec(),
+        }
+    }
+}
+
+pub fn is_valid_room_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ROOM_ID_LENGTH
+        && id
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn moves_are_taken_in_order() {
+        let made = |id| RoomMove { id, board_hash: 0 };
+        let submit = |secret: &str, turn, id| MoveSubmission {
+            secret: secret.into(),
+            turn,
+            made: made(id),
+        };
+        let mut room = Room::new("setup".into(), "host".into()).unwrap();
+        assert_eq!(room.submit(submit("host", 0, 1)), Err(RoomError::NoGuest));
+
+        room.join("guest".into(), "guest".into()).unwrap();
+        // Sending the guest again doesn't take the seat over
+        assert_eq!(
+            room.join("guest".into(), "other".into()),
+            Err(RoomError::SeatTaken)
+        );
+        assert_eq!(
+            room.submit(submit("other", 0, 1)),
+            Err(RoomError::NotSeated)
+        );
+
+        room.submit(submit("host", 0, 1)).unwrap();
+        room.submit(submit("host", 0, 1)).unwrap();
+        // The same seat can move twice in a row, after an extra turn
+        room.submit(submit("host", 1, 2)).unwrap();
+        room.submit(submit("guest", 2, 3)).unwrap();
+        assert_eq!(
+            room.submit(submit("guest", 2, 4)),
+            Err(RoomError::OutOfTurn { expected: 3 })
+        );
+        assert_eq!(
+            room.submit(submit("host", 5, 4)),
+            Err(RoomError::OutOfTurn { expected: 3 })
+        );
+
+        let update = room.since(1);
+        assert_eq!((update.first, update.moves), (1, vec![made(2), made(3)]));
+        assert_eq!(room.since(10).first, 3);
+
+        assert!(is_valid_room_id("AB12-CD34"));
+        assert!(!is_valid_room_id("../rooms"));
+        assert!(!is_valid_room_id(""));
+    }
+}
@@ -0,0 +1,675 @@
+use crate::hex;
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+// Without std, ordered collections stand in for the hashed ones
+#[cfg(not(feature = "std"))]
+use alloc::collections::{BTreeMap as HashMap, BTreeSet as HashSet};
+
+// The board and the capture rules, free of any ECS state so they can be used
+// by benchmarks, tests and servers without an App. Tiles are owned by any
+// copyable player id, which the game fills in with its player entities
+
+#[derive(Clone, Debug)]
+pub enum TileState<P> {
+    Empty,
+    Wall,
+    Owned(P),
+    Unowned(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TileBonus {
+    // The capturing player moves again immediately
+    ExtraTurn,
+    // The capturing player is awarded extra score
+    Points(u32),
+    // Stays in play, scoring for whoever holds it in king of the hill games
+    Hill,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Component))]
+pub struct Tile<P> {
+    pub row: i32,
+    pub column: i32,
+    pub state: TileState<P>,
+    pub bonus: Option<TileBonus>,
+    // How much the tile adds to its owner's score
+    pub weight: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoardTopology {
+    // Rows are offset by half a tile, so each tile has six neighbors
+    Hex,
+    // Square grid, orthogonal neighbors only
+    Square4,
+    // Square grid, including diagonal neighbors
+    Square8,
+}
+
+impl Default for BoardTopology {
+    fn default() -> Self {
+        BoardTopology::Hex
+    }
+}
+
+impl BoardTopology {
+    pub fn neighbor_offsets(&self, row: i32) -> &'static [(i32, i32)] {
+        match self {
+            BoardTopology::Hex => hex::neighbor_offsets(row),
+            BoardTopology::Square4 => &[(-1, 0), (0, -1), (0, 1), (1, 0)],
+            BoardTopology::Square8 => &[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+        }
+    }
+}
+
+pub fn for_each_selected_tile<T, P>(
+    mut tiles: Vec<T>,
+    topology: BoardTopology,
+    selection: u32,
+    player: P,
+    mut callback: impl FnMut(&mut Tile<P>),
+) where
+    T: core::ops::DerefMut<Target = Tile<P>>,
+    P: Copy + PartialEq,
+{
+    let mut owned_tiles = tiles
+        .iter()
+        .filter(|tile| match tile.state {
+            TileState::Owned(owner) => owner == player,
+            _ => false,
+        })
+        .map(|tile| (tile.row, tile.column))
+        .collect::<HashSet<(i32, i32)>>();
+
+    loop {
+        let mut did_capture = false;
+
+        for tile in tiles.iter_mut() {
+            if owned_tiles.contains(&(tile.row, tile.column)) {
+                continue;
+            }
+
+            match tile.state {
+                TileState::Unowned(id) if id == selection => (),
+                _ => continue,
+            }
+
+            let is_adjacent =
+                topology
+                    .neighbor_offsets(tile.row)
+                    .iter()
+                    .any(|(row_offset, column_offset)| {
+                        owned_tiles.contains(&(tile.row + row_offset, tile.column + column_offset))
+                    });
+
+            if is_adjacent {
+                owned_tiles.insert((tile.row, tile.column));
+                did_capture = true;
+                callback(tile);
+            }
+        }
+
+        if !did_capture {
+            break;
+        }
+    }
+}
+
+pub fn simulate_selection<P: Copy + PartialEq>(
+    tiles: &mut [Tile<P>],
+    topology: BoardTopology,
+    selection: u32,
+    player: P,
+) -> u32 {
+    let mut captured = 0;
+    for_each_selected_tile(
+        tiles.iter_mut().collect(),
+        topology,
+        selection,
+        player,
+        |tile| {
+            tile.state = TileState::Owned(player);
+            captured += 1;
+        },
+    );
+    captured
+}
+
+// Like simulate_selection, but returns the score the captured tiles are worth
+pub fn simulate_selection_value<P: Copy + PartialEq>(
+    tiles: &mut [Tile<P>],
+    topology: BoardTopology,
+    selection: u32,
+    player: P,
+) -> u32 {
+    let mut value = 0;
+    for_each_selected_tile(
+        tiles.iter_mut().collect(),
+        topology,
+        selection,
+        player,
+        |tile| {
+            tile.state = TileState::Owned(player);
+            value += tile.weight;
+        },
+    );
+    value
+}
+
+// The fewest colors a player on their own needs to pick to take every tile
+// they can reach, in the order to pick them. Every order is searched, so this
+// is only meant for small boards, and gives up past the given number of moves
+pub fn solve<P: Copy + PartialEq>(
+    tiles: &[Tile<P>],
+    topology: BoardTopology,
+    player: P,
+    max_moves: usize,
+) -> Option<Vec<u32>> {
+    let mut ids = tiles
+        .iter()
+        .filter_map(|tile| match tile.state {
+            TileState::Unowned(id) => Some(id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+    // Positions are told apart by which tiles the player holds
+    let owned = |tiles: &[Tile<P>]| {
+        let mut bits = vec![0u64; (tiles.len() + 63) / 64];
+        for (idx, tile) in tiles.iter().enumerate() {
+            if matches!(tile.state, TileState::Owned(owner) if owner == player) {
+                bits[idx / 64] |= 1 << (idx % 64);
+            }
+        }
+        bits
+    };
+
+    let mut seen = HashSet::new();
+    seen.insert(owned(tiles));
+    let mut queue = VecDeque::new();
+    queue.push_back((tiles.to_vec(), Vec::new()));
+    // Searched breadth first, so the first position with nothing left to take
+    // is reached in the fewest moves
+    while let Some((board, picked)) = queue.pop_front() {
+        let mut finished = true;
+        for id in ids.iter() {
+            let mut next = board.clone();
+            if simulate_selection(&mut next, topology, *id, player) == 0 {
+                continue;
+            }
+            finished = false;
+            if picked.len() < max_moves && seen.insert(owned(&next)) {
+                let mut picked = picked.clone();
+                picked.push(*id);
+                queue.push_back((next, picked));
+            }
+        }
+        if finished {
+            return Some(picked);
+        }
+    }
+    None
+}
+
+// Gives the player every unowned tile within the given number of rings of
+// their starting tiles
+pub fn grow_start_region<P: Copy + PartialEq>(
+    tiles: &mut [Tile<P>],
+    topology: BoardTopology,
+    player: P,
+    rings: u32,
+) {
+    for _ in 0..rings {
+        let owned = tiles
+            .iter()
+            .filter(|tile| matches!(tile.state, TileState::Owned(owner) if owner == player))
+            .map(|tile| (tile.row, tile.column))
+            .collect::<HashSet<_>>();
+
+        for tile in tiles.iter_mut() {
+            if !matches!(tile.state, TileState::Unowned(_)) {
+                continue;
+            }
+            let (row, column) = (tile.row, tile.column);
+            if topology
+                .neighbor_offsets(row)
+                .iter()
+                .any(|(row_offset, column_offset)| {
+                    owned.contains(&(row + row_offset, column + column_offset))
+                })
+            {
+                tile.state = TileState::Owned(player);
+                if tile.bonus != Some(TileBonus::Hill) {
+                    tile.bonus = None;
+                }
+            }
+        }
+    }
+}
+
+// Recolors the board so it looks the same after turning it halfway around its
+// center, which is where the stock levels put each player's opponent. Tiles
+// whose counterpart can't be colored keep their own color
+pub fn mirror_colors<P>(tiles: &mut [Tile<P>], topology: BoardTopology) {
+    // Columns are counted in half tiles, as rows of hex boards are shifted
+    let position = |tile: &Tile<P>| match topology {
+        BoardTopology::Hex => (tile.row, hex::half_column(tile.row, tile.column)),
+        BoardTopology::Square4 | BoardTopology::Square8 => (tile.row, tile.column * 2),
+    };
+    let (rows, columns): (Vec<_>, Vec<_>) = tiles
+        .iter()
+        .filter(|tile| !matches!(tile.state, TileState::Empty))
+        .map(position)
+        .unzip();
+    // Positions turned around the center add up to the first and last ones
+    let span = |values: Vec<i32>| Some(values.iter().min()? + values.iter().max()?);
+    let (rows, columns) = match (span(rows), span(columns)) {
+        (Some(rows), Some(columns)) => (rows, columns),
+        _ => return,
+    };
+
+    let colors = tiles
+        .iter()
+        .filter_map(|tile| match tile.state {
+            TileState::Unowned(id) => Some((position(tile), id)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    for tile in tiles.iter_mut() {
+        let (row, column) = position(tile);
+        let counterpart = (rows - row, columns - column);
+        // Only the first of each pair is kept, so both end up with its color
+        if counterpart >= (row, column) {
+            continue;
+        }
+        if let (TileState::Unowned(_), Some(id)) = (&tile.state, colors.get(&counterpart)) {
+            tile.state = TileState::Unowned(*id);
+        }
+    }
+}
+
+// The score a player's tiles are worth, before any bonus points
+pub fn territory_score<'a, P: PartialEq + 'a>(
+    tiles: impl IntoIterator<Item = &'a Tile<P>>,
+    player: P,
+) -> u32 {
+    tiles
+        .into_iter()
+        .filter(|tile| matches!(&tile.state, TileState::Owned(owner) if *owner == player))
+        .map(|tile| tile.weight)
+        .sum()
+}
+
+// How much of the board each starting position ends up with on average, over
+// games played out with every player picking colors at random. Who moves
+// first goes around from game to game, so only the board's coloring tells the
+// starts apart
+pub fn capture_potential<P: Copy + PartialEq>(
+    tiles: &[Tile<P>],
+    topology: BoardTopology,
+    players: &[P],
+    playouts: u32,
+    seed: u64,
+) -> Vec<f32> {
+    let mut ids = tiles
+        .iter()
+        .filter_map(|tile| match tile.state {
+            TileState::Unowned(id) => Some(id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+    let total = tiles
+        .iter()
+        .filter(|tile| !matches!(tile.state, TileState::Empty | TileState::Wall))
+        .map(|tile| tile.weight)
+        .sum::<u32>()
+        .max(1);
+
+    let mut state = seed;
+    let mut potential = vec![0.0; players.len()];
+    for playout in 0..playouts {
+        let mut board = tiles.to_vec();
+        let mut order = players.to_vec();
+        if !order.is_empty() {
+            order.rotate_left(playout as usize % players.len());
+        }
+        // Played until a whole round goes by without a capture
+        let mut idle = 0;
+        while idle < order.len() {
+            for player in order.iter() {
+                let mut colors = ids.clone();
+                shuffle(&mut colors, &mut state);
+                let captured = colors
+                    .iter()
+                    .any(|id| simulate_selection(&mut board, topology, *id, *player) > 0);
+                idle = if captured { 0 } else { idle + 1 };
+            }
+        }
+        for (potential, player) in potential.iter_mut().zip(players) {
+            *potential += territory_score(board.iter(), *player) as f32 / total as f32;
+        }
+    }
+    for potential in potential.iter_mut() {
+        *potential /= playouts.max(1) as f32;
+    }
+    potential
+}
+
+// The weakest start's potential against the strongest's, from 0 for a board
+// one start can't win to 1 for a perfectly fair one
+pub fn fairness(potential: &[f32]) -> f32 {
+    let strongest = potential.iter().cloned().fold(0.0, f32::max);
+    let weakest = potential.iter().cloned().fold(f32::INFINITY, f32::min);
+    match strongest > 0.0 {
+        true => weakest / strongest,
+        false => 1.0,
+    }
+}
+
+// A Fisher-Yates shuffle, with splitmix64 standing in for a random number
+// generator so the rules need nothing beyond core
+fn shuffle<T>(values: &mut [T], state: &mut u64) {
+    for idx in (1..values.len()).rev() {
+        *state = state.wrapping_add(1);
+        values.swap(idx, (mix(*state) % (idx as u64 + 1)) as usize);
+    }
+}
+
+// A Zobrist-style hash of the board, built from a key per tile state so it can
+// be updated one tile at a time. Owners are keyed by their place in the
+// starting player order, so the same game always hashes the same way
+#[derive(Clone, Debug)]
+pub struct BoardHash<P> {
+    pub value: u64,
+    players: Vec<P>,
+}
+
+impl<P> Default for BoardHash<P> {
+    fn default() -> Self {
+        Self {
+            value: 0,
+            players: Vec::new(),
+        }
+    }
+}
+
+impl<P: Copy + PartialEq> BoardHash<P> {
+    pub fn new(players: &[P], tiles: &[Tile<P>]) -> Self {
+        let mut hash = Self {
+            value: 0,
+            players: players.to_vec(),
+        };
+        for tile in tiles {
+            hash.value ^= hash.tile_key(tile.row, tile.column, &tile.state);
+        }
+        hash
+    }
+
+    // Must be called before the tile is changed
+    pub fn update(&mut self, tile: &Tile<P>, state: &TileState<P>) {
+        self.value ^= self.tile_key(tile.row, tile.column, &tile.state)
+            ^ self.tile_key(tile.row, tile.column, state);
+    }
+
+    // The players in the order the game started with
+    pub fn players(&self) -> &[P] {
+        &self.players
+    }
+
+    pub fn player_key(&self, player: P) -> u64 {
+        let idx = self.players.iter().position(|other| *other == player);
+        mix(0x5EED ^ idx.map_or(u64::MAX, |idx| idx as u64))
+    }
+
+    // The board along with whose turn it is and the colors each player picked
+    // last, which together decide every move that follows
+    pub fn position<'a>(
+        &self,
+        to_move: P,
+        last_selections: impl IntoIterator<Item = (&'a P, &'a u32)>,
+    ) -> u64
+    where
+        P: 'a,
+    {
+        last_selections.into_iter().fold(
+            self.value ^ mix(self.player_key(to_move)),
+            |hash, (player, id)| hash ^ mix(self.player_key(*player) ^ mix(*id as u64)),
+        )
+    }
+
+    fn tile_key(&self, row: i32, column: i32, state: &TileState<P>) -> u64 {
+        let state = match state {
+            TileState::Empty => 0,
+            TileState::Wall => 1,
+            TileState::Unowned(id) => mix(2 + *id as u64),
+            TileState::Owned(player) => self.player_key(*player),
+        };
+        mix(mix(((row as u32 as u64) << 32) | column as u32 as u64) ^ state)
+    }
+}
+
+// The splitmix64 finalizer, spreading every input bit over the whole output
+fn mix(value: u64) -> u64 {
+    let mut value = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    const NUM_IDS: u32 = 4;
+    // Tile descriptions beyond the colors
+    const OWNED: u32 = NUM_IDS;
+    const WALL: u32 = NUM_IDS + 1;
+    // Owned by the other player, only used on boards written out by hand
+    const OPPONENT: u32 = NUM_IDS + 2;
+
+    type Tile = super::Tile<u32>;
+
+    fn player() -> u32 {
+        0
+    }
+
+    fn opponent() -> u32 {
+        1
+    }
+
+    fn tile(row: i32, column: i32, desc: u32) -> Tile {
+        Tile {
+            row,
+            column,
+            state: match desc {
+                OWNED => TileState::Owned(player()),
+                OPPONENT => TileState::Owned(opponent()),
+                WALL => TileState::Wall,
+                id => TileState::Unowned(id),
+            },
+            bonus: None,
+            weight: 1,
+        }
+    }
+
+    // A board written out a row of tile descriptions at a time
+    fn tiles_from_rows(rows: &[&[u32]]) -> Vec<Tile> {
+        rows.iter()
+            .enumerate()
+            .flat_map(|(row, descs)| {
+                descs
+                    .iter()
+                    .enumerate()
+                    .map(move |(column, desc)| tile(row as i32, column as i32, *desc))
+            })
+            .collect()
+    }
+
+    // Boards of up to 12x12 tiles, some owned by the player and some walls
+    fn boards() -> impl Strategy<Value = (BoardTopology, Vec<Tile>)> {
+        let topology = prop_oneof![
+            Just(BoardTopology::Hex),
+            Just(BoardTopology::Square4),
+            Just(BoardTopology::Square8),
+        ];
+        (topology, 1..12i32, 1..12i32).prop_flat_map(|(topology, rows, columns)| {
+            prop::collection::vec(0..=WALL, (rows * columns) as usize).prop_map(move |descs| {
+                let tiles = descs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, desc)| tile(idx as i32 / columns, idx as i32 % columns, desc))
+                    .collect();
+                (topology, tiles)
+            })
+        })
+    }
+
+    fn owned(tiles: &[Tile]) -> HashSet<(i32, i32)> {
+        tiles
+            .iter()
+            .filter(|tile| matches!(tile.state, TileState::Owned(_)))
+            .map(|tile| (tile.row, tile.column))
+            .collect()
+    }
+
+    fn neighbors(topology: BoardTopology, (row, column): (i32, i32)) -> Vec<(i32, i32)> {
+        topology
+            .neighbor_offsets(row)
+            .iter()
+            .map(|(row_offset, column_offset)| (row + row_offset, column + column_offset))
+            .collect()
+    }
+
+    #[test]
+    fn puzzles_are_solved_in_the_fewest_moves() {
+        let tiles = tiles_from_rows(&[&[OWNED, 0, 1], &[1, 0, 0]]);
+
+        // Starting with the other color would take three moves
+        assert_eq!(
+            solve(&tiles, BoardTopology::Square4, player(), 10),
+            Some(vec![0, 1])
+        );
+        assert_eq!(solve(&tiles, BoardTopology::Square4, player(), 1), None);
+    }
+
+    #[test]
+    fn walled_in_starts_are_unfair() {
+        let tiles = tiles_from_rows(&[&[OWNED, 0, 1, 0, WALL, OPPONENT]]);
+
+        // The first player always ends up with everything but the other's start
+        let players = [player(), opponent()];
+        let potential = capture_potential(&tiles, BoardTopology::Square4, &players, 8, 1);
+        assert!((potential[0] - 0.8).abs() < 1e-5 && (potential[1] - 0.2).abs() < 1e-5);
+        assert!((fairness(&potential) - 0.25).abs() < 1e-5);
+        assert_eq!(fairness(&[0.5, 0.5]), 1.0);
+    }
+
+    #[test]
+    fn board_hash_updates_incrementally() {
+        let other = opponent();
+        let mut tiles = tiles_from_rows(&[&[OWNED, 1, 0, 1, 0, OPPONENT]]);
+
+        let mut hash = BoardHash::new(&[player(), other], &tiles);
+        let initial = hash.value;
+        for tile in tiles.iter_mut().filter(|tile| tile.column < 3) {
+            let state = TileState::Owned(player());
+            hash.update(tile, &state);
+            tile.state = state;
+        }
+        assert_ne!(hash.value, initial);
+        assert_eq!(hash.value, BoardHash::new(&[player(), other], &tiles).value);
+
+        // The same tiles owned by the other player hash differently
+        tiles[1].state = TileState::Owned(other);
+        assert_ne!(hash.value, BoardHash::new(&[player(), other], &tiles).value);
+    }
+
+    proptest! {
+        #[test]
+        fn only_the_selected_color_is_captured(
+            (topology, tiles) in boards(),
+            selection in 0..NUM_IDS,
+        ) {
+            let mut after = tiles.clone();
+            let captured = simulate_selection(&mut after, topology, selection, player());
+
+            let mut changed = 0;
+            for (before, after) in tiles.iter().zip(after.iter()) {
+                if let (TileState::Unowned(id), TileState::Owned(_)) = (&before.state, &after.state) {
+                    prop_assert_eq!(*id, selection);
+                    changed += 1;
+                }
+            }
+            prop_assert_eq!(captured, changed);
+            prop_assert_eq!(owned(&after).len(), owned(&tiles).len() + changed as usize);
+        }
+
+        #[test]
+        fn captures_are_connected_to_the_territory(
+            (topology, tiles) in boards(),
+            selection in 0..NUM_IDS,
+        ) {
+            let mut after = tiles.clone();
+            simulate_selection(&mut after, topology, selection, player());
+
+            // Walk outwards from the original territory through owned tiles
+            let territory = owned(&after);
+            let mut reached = owned(&tiles);
+            let mut frontier: Vec<_> = reached.iter().cloned().collect();
+            while let Some(position) = frontier.pop() {
+                for neighbor in neighbors(topology, position) {
+                    if territory.contains(&neighbor) && reached.insert(neighbor) {
+                        frontier.push(neighbor);
+                    }
+                }
+            }
+            prop_assert_eq!(reached, territory);
+        }
+
+        #[test]
+        fn captures_are_complete_and_idempotent(
+            (topology, tiles) in boards(),
+            selection in 0..NUM_IDS,
+        ) {
+            let mut after = tiles;
+            simulate_selection(&mut after, topology, selection, player());
+
+            // No tile of the selected color is left touching the territory
+            let territory = owned(&after);
+            let positions: HashMap<_, _> = after
+                .iter()
+                .map(|tile| ((tile.row, tile.column), tile.state.clone()))
+                .collect();
+            for (position, state) in positions.iter() {
+                if matches!(state, TileState::Unowned(id) if *id == selection) {
+                    prop_assert!(neighbors(topology, *position)
+                        .iter()
+                        .all(|neighbor| !territory.contains(neighbor)));
+                }
+            }
+
+            let mut again = after.clone();
+            prop_assert_eq!(simulate_selection(&mut again, topology, selection, player()), 0);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use crate::ai::{self, Playback};
+use crate::core::{GameState, MoveCache, Player, PlayerKind, SelectEvent, Tile, TileState};
+use crate::{storage, TIME_STEP};
+use bevy::prelude::*;
+use bevy::utils::Instant;
+use rhai::{module_resolvers::DummyModuleResolver, Array, Dynamic, Engine, Map, Scope, AST};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+const BOT_SCRIPT_KEY: &str = "bot_script";
+// The function every bot script defines, called with the turn once per move
+const ENTRY_POINT: &str = "select";
+// How long a script may take over a move, and how much work it may do,
+// before it's cut short and the move is picked for it
+const TIME_BUDGET: Duration = Duration::from_millis(50);
+const MAX_OPERATIONS: u64 = 5_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_COLLECTION_SIZE: usize = 1_000_000;
+
+const EXAMPLE_SCRIPT: &str = r#"// Called on every turn of the bot. The turn holds:
+//   colors: the colors which may be picked
+//   captures: the tiles each color would capture, keyed by color
+//   board: every tile, as #{ row, column, owner, color, weight }, where
+//     owner is "me", "opponent", "none" or "wall"
+//   round, score and opponent_score
+// and the color to pick is returned.
+fn select(turn) {
+    let best = turn.colors[0];
+    for color in turn.colors {
+        if turn.captures[color.to_string()] > turn.captures[best.to_string()] {
+            best = color;
+        }
+    }
+    best
+}
+"#;
+
+// A compiled bot script, ready to be asked for moves
+#[derive(Clone, Debug)]
+pub struct Script(Arc<AST>);
+
+// The script written on the setup screen, remembered between sessions
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BotScript {
+    pub source: String,
+}
+
+impl Default for BotScript {
+    fn default() -> Self {
+        Self {
+            source: EXAMPLE_SCRIPT.into(),
+        }
+    }
+}
+
+impl BotScript {
+    pub fn load() -> Self {
+        storage::load(BOT_SCRIPT_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(BOT_SCRIPT_KEY, self)
+    }
+
+    pub fn compile(&self) -> Result<Script, String> {
+        let ast = engine(None)
+            .compile(&self.source)
+            .map_err(|e| e.to_string())?;
+        if !ast
+            .iter_functions()
+            .any(|function| function.name == ENTRY_POINT)
+        {
+            return Err(format!(
+                "The script needs a '{}(turn)' function",
+                ENTRY_POINT
+            ));
+        }
+        Ok(Script(Arc::new(ast)))
+    }
+}
+
+// Scripts only see what they're given. They can't load other files or build
+// code to run, and are stopped once they've used up their budget
+fn engine(deadline: Option<Instant>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .disable_symbol("eval")
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_array_size(MAX_COLLECTION_SIZE)
+        .set_max_map_size(MAX_COLLECTION_SIZE)
+        .on_print(|text| info!("bot script: {}", text))
+        .on_debug(|text, _, position| debug!(%position, "bot script: {}", text));
+    if let Some(deadline) = deadline {
+        engine.on_progress(move |_| (Instant::now() > deadline).then(|| "Out of time".into()));
+    }
+    engine
+}
+
+// What the script is told about the turn, from the point of view of the
+// player it moves for
+fn turn(
+    tiles: &[Tile],
+    state: &GameState,
+    cache: &MoveCache,
+    player: Entity,
+    scores: &[(Entity, u32)],
+) -> Map {
+    let colors = state
+        .ids
+        .keys()
+        .filter(|id| state.is_selection_allowed(player, **id))
+        .map(|id| Dynamic::from(*id as i64))
+        .collect::<Array>();
+    let captures = state
+        .ids
+        .keys()
+        .map(|id| {
+            let count = cache.capture_count(player, *id) as i64;
+            (id.to_string().into(), Dynamic::from(count))
+        })
+        .collect::<Map>();
+    let board = tiles
+        .iter()
+        .filter_map(|tile| {
+            let (owner, color) = match tile.state {
+                TileState::Empty => return None,
+                TileState::Wall => ("wall", -1),
+                TileState::Unowned(id) => ("none", id as i64),
+                TileState::Owned(owner) if owner == player => ("me", -1),
+                TileState::Owned(_) => ("opponent", -1),
+            };
+            let mut tile_map = Map::new();
+            tile_map.insert("row".into(), Dynamic::from(tile.row as i64));
+            tile_map.insert("column".into(), Dynamic::from(tile.column as i64));
+            tile_map.insert("owner".into(), owner.into());
+            tile_map.insert("color".into(), Dynamic::from(color));
+            tile_map.insert("weight".into(), Dynamic::from(tile.weight as i64));
+            Some(Dynamic::from_map(tile_map))
+        })
+        .collect::<Array>();
+
+    let score = |mine: bool| {
+        scores
+            .iter()
+            .filter(|(other, _)| (*other == player) == mine)
+            .map(|(_, score)| *score as i64)
+            .max()
+            .unwrap_or(0)
+    };
+    let mut turn = Map::new();
+    turn.insert("colors".into(), colors.into());
+    turn.insert("captures".into(), captures.into());
+    turn.insert("board".into(), board.into());
+    turn.insert("round".into(), Dynamic::from(state.round() as i64));
+    turn.insert("score".into(), Dynamic::from(score(true)));
+    turn.insert("opponent_score".into(), Dynamic::from(score(false)));
+    turn
+}
+
+// Asks the script for its move, which must be one of the turn's colors
+fn run(script: &Script, turn: Map, budget: Duration) -> Result<u32, String> {
+    let colors = turn
+        .get("colors")
+        .and_then(|colors| colors.clone().try_cast::<Array>())
+        .unwrap_or_default();
+    let engine = engine(Some(Instant::now() + budget));
+    let id = engine
+        .call_fn::<i64>(
+            &mut Scope::new(),
+            &script.0,
+            ENTRY_POINT,
+            (Dynamic::from_map(turn),),
+        )
+        .map_err(|e| e.to_string())?;
+    if !colors.iter().any(|color| color.as_int() == Ok(id)) {
+        return Err(format!(
+            "{} isn't one of the colors which may be picked",
+            id
+        ));
+    }
+    Ok(id as u32)
+}
+
+// Runs on the fixed timestep like the built in bots, so scripted ones take
+// as long to think. Scripts which fail or run out of time have the biggest
+// capture picked for them
+pub fn perform_scripted_move(
+    state: Res<GameState>,
+    mut players: Query<&mut Player>,
+    cache: Res<MoveCache>,
+    playback: Res<Playback>,
+    tiles: Query<&Tile>,
+    mut selections: EventWriter<SelectEvent>,
+) {
+    let player = match state.players.first() {
+        Some(player) if !playback.paused => *player,
+        _ => return,
+    };
+    let scores = state
+        .players
+        .iter()
+        .filter_map(|player| Some((*player, players.get(*player).ok()?.score)))
+        .collect::<Vec<_>>();
+    let script = match players.get_mut(player) {
+        Ok(mut player) => match player.kind {
+            PlayerKind::Scripted(ref mut timer, ref script) => {
+                let step = Duration::from_secs_f32(TIME_STEP * playback.speed);
+                if !timer.tick(step).finished() {
+                    return;
+                }
+                timer.reset();
+                script.clone()
+            }
+            _ => return,
+        },
+        Err(_) => return,
+    };
+
+    let tiles = tiles.iter().cloned().collect::<Vec<_>>();
+    let turn = turn(&tiles, &state, &cache, player, &scores);
+    let _turn = info_span!("turn", number = state.moves, player = ?player).entered();
+    let id = match run(&script, turn, TIME_BUDGET) {
+        Ok(id) => id,
+        Err(e) => {
+            warn!("bot script failed: {}", e);
+            match ai::biggest_capture(&state, &cache, player) {
+                Some(id) => id,
+                None => return,
+            }
+        }
+    };
+    debug!(id, "scripted bot picked a color");
+    selections.send(SelectEvent { player, id });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_turn() -> Map {
+        let mut captures = Map::new();
+        captures.insert("0".into(), Dynamic::from(2_i64));
+        captures.insert("1".into(), Dynamic::from(5_i64));
+        let mut turn = Map::new();
+        let colors: Array = vec![Dynamic::from(0_i64), Dynamic::from(1_i64)];
+        turn.insert("colors".into(), colors.into());
+        turn.insert("captures".into(), captures.into());
+        turn
+    }
+
+    #[test]
+    fn scripts_pick_legal_colors_within_budget() {
+        let script = BotScript::default().compile().unwrap();
+        assert_eq!(run(&script, test_turn(), TIME_BUDGET), Ok(1));
+
+        let script = BotScript {
+            source: "fn select(turn) { 7 }".into(),
+        };
+        assert!(run(&script.compile().unwrap(), test_turn(), TIME_BUDGET).is_err());
+
+        // Scripts which never return are stopped
+        let script = BotScript {
+            source: "fn select(turn) { loop {} }".into(),
+        };
+        assert!(run(&script.compile().unwrap(), test_turn(), TIME_BUDGET).is_err());
+
+        let script = BotScript {
+            source: "fn pick(turn) { 0 }".into(),
+        };
+        assert!(script.compile().is_err());
+    }
+}
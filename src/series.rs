@@ -0,0 +1,274 @@
+use crate::core::{GamePhase, GameState, Player};
+use crate::menu::{self, AppState};
+use crate::stats::{self, Outcome};
+use crate::{attract, game, GameStartEvent};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+// The lengths of match offered on the setup screen. A single game isn't a
+// match at all
+pub const MATCH_LENGTHS: [u32; 3] = [1, 3, 5];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeriesResult {
+    // Index into the first game's players of the match winner
+    Won(usize),
+    Drawn,
+}
+
+// A best of N match, played as a run of games with the first move passing
+// from player to player
+#[derive(Default)]
+pub struct Series {
+    pub best_of: u32,
+    // The first game, with the players in their starting order
+    start: Option<GameStartEvent>,
+    // Games won by each player, in the first game's order
+    pub wins: Vec<u32>,
+    pub draws: u32,
+}
+
+impl Series {
+    pub fn new(best_of: u32, start: &GameStartEvent) -> Self {
+        Self {
+            best_of,
+            start: Some(start.clone()),
+            wins: vec![0; start.players.len()],
+            draws: 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.best_of > 1 && self.start.is_some()
+    }
+
+    pub fn games_played(&self) -> u32 {
+        self.wins.iter().sum::<u32>() + self.draws
+    }
+
+    // How far the players of the current game are moved around from the
+    // first, so each gets a turn moving first
+    fn rotation(&self) -> usize {
+        self.games_played() as usize % self.wins.len().max(1)
+    }
+
+    // The first game's player at the given position in the current game
+    pub fn original_index(&self, position: usize) -> usize {
+        (position + self.rotation()) % self.wins.len().max(1)
+    }
+
+    // Adds the result of the current game, given as the index of its winner
+    // among the current game's players
+    pub fn record(&mut self, winner: Option<usize>) {
+        match winner.map(|position| self.original_index(position)) {
+            Some(winner) => self.wins[winner] += 1,
+            None => self.draws += 1,
+        }
+    }
+
+    // Decided once a player has won more than half the games, or all of them
+    // have been played
+    pub fn result(&self) -> Option<SeriesResult> {
+        if let Some(winner) = self.wins.iter().position(|wins| wins * 2 > self.best_of) {
+            return Some(SeriesResult::Won(winner));
+        }
+        if self.games_played() < self.best_of {
+            return None;
+        }
+
+        let most = self.wins.iter().max().copied().unwrap_or(0);
+        let mut leaders = self
+            .wins
+            .iter()
+            .enumerate()
+            .filter(|(_, wins)| **wins == most);
+        match (leaders.next(), leaders.next()) {
+            (Some((winner, _)), None) => Some(SeriesResult::Won(winner)),
+            _ => Some(SeriesResult::Drawn),
+        }
+    }
+
+    // The next game of the match, on a fresh board, unless it's already over
+    pub fn next_game(&self) -> Option<GameStartEvent> {
+        if self.result().is_some() {
+            return None;
+        }
+        let mut event = self.start.clone()?;
+        let (count, rotation) = (event.players.len(), self.rotation());
+        event.players.rotate_left(rotation);
+        event.handicapped_player = (event.handicapped_player + count - rotation) % count.max(1);
+        event.seed = Some(rand::random());
+        Some(event)
+    }
+
+    fn players(&self) -> &[Player] {
+        self.start
+            .as_ref()
+            .map_or(&[], |start| start.players.as_slice())
+    }
+}
+
+pub struct SeriesPlugin;
+
+impl Plugin for SeriesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Series>()
+            .add_system_set(SystemSet::on_enter(AppState::MainMenu).with_system(end_series))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::GameStep)
+                    .with_system(record_series_game.after(crate::core::update_scores)),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::run_if_game_over.chain(attract::unless_demo))
+                    .with_system(show_series),
+            );
+    }
+}
+
+fn end_series(mut series: ResMut<Series>) {
+    *series = Series::default();
+}
+
+fn record_series_game(
+    state: Res<GameState>,
+    players: Query<&Player>,
+    mut series: ResMut<Series>,
+    mut player_stats: ResMut<stats::Stats>,
+) {
+    if !series.is_active() || series.result().is_some() {
+        return;
+    }
+    let winner = match state.phase {
+        GamePhase::Over(winner) => state.players.iter().position(|player| *player == winner),
+        GamePhase::Draw => None,
+        _ => return,
+    };
+    // Found before recording, while the players are still in this game's order
+    let rated = stats::rated_opponent(&state, &players).and_then(|(human, opponent)| {
+        let position = state.players.iter().position(|player| *player == human)?;
+        Some((series.original_index(position), opponent))
+    });
+
+    series.record(winner);
+    let result = match series.result() {
+        Some(result) => result,
+        None => return,
+    };
+    info!(?result, wins = ?series.wins, "match over");
+
+    if let Some((human, opponent)) = rated {
+        let outcome = match result {
+            SeriesResult::Won(winner) if winner == human => Outcome::Win,
+            SeriesResult::Won(_) => Outcome::Loss,
+            SeriesResult::Drawn => Outcome::Draw,
+        };
+        player_stats.record_match(opponent, outcome);
+        player_stats.save();
+    }
+}
+
+// The match score between games, and the way on to the next one
+fn show_series(
+    series: Res<Series>,
+    mut game_start: EventWriter<GameStartEvent>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    if !series.is_active() {
+        return;
+    }
+
+    let mut next = false;
+    egui::Window::new(format!("Best of {}", series.best_of))
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -10.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            for (player, wins) in series.players().iter().zip(series.wins.iter()) {
+                ui.colored_label(
+                    menu::to_color32(player.color),
+                    format!("{}: {}", player.name, wins),
+                );
+            }
+            if series.draws > 0 {
+                ui.label(format!("Draws: {}", series.draws));
+            }
+
+            ui.separator();
+            match series.result() {
+                Some(SeriesResult::Won(winner)) => {
+                    let name = series
+                        .players()
+                        .get(winner)
+                        .map_or("", |player| &player.name);
+                    ui.strong(format!("{} wins the match!", name));
+                }
+                Some(SeriesResult::Drawn) => {
+                    ui.strong("The match is drawn");
+                }
+                None => next = ui.button("next game").clicked(),
+            }
+        });
+
+    if next {
+        if let Some(event) = series.next_game() {
+            game_start.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{core, levels};
+    use std::collections::BTreeMap;
+
+    fn player(name: &str) -> Player {
+        Player {
+            name: name.into(),
+            score: 0,
+            bonus: 0,
+            avatar: Default::default(),
+            kind: core::PlayerKind::Human,
+            color: Color::WHITE,
+        }
+    }
+
+    #[test]
+    fn matches_alternate_the_first_player() {
+        let start = GameStartEvent {
+            players: vec![player("first"), player("second")],
+            ids: BTreeMap::new(),
+            level: levels::LEVELS[0].clone(),
+            seed: Some(1),
+            colors: None,
+            daily: None,
+            rules: core::RulesConfig::default(),
+            handicap: core::Handicap::DoubleMove,
+            handicapped_player: 0,
+            mirrored: false,
+            virus: false,
+            tutorial: false,
+            training: false,
+            blind: false,
+            par: None,
+        };
+        let mut series = Series::new(3, &start);
+
+        series.record(Some(0));
+        let next = series.next_game().unwrap();
+        assert_eq!(next.players[0].name, "second");
+        // The handicap stays with the player it was given to
+        assert_eq!(next.handicapped_player, 1);
+
+        // The second game's first mover is the first game's second player
+        series.record(Some(0));
+        assert_eq!(series.wins, vec![1, 1]);
+        assert_eq!(series.result(), None);
+
+        series.record(None);
+        assert_eq!(series.result(), Some(SeriesResult::Drawn));
+        assert!(series.next_game().is_none());
+    }
+}
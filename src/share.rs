@@ -0,0 +1,323 @@
+use crate::core::{BoardTopology, Tile, TileBonus, TileState, MAX_PLAYERS, MAX_TILE_WEIGHT};
+use crate::levels;
+use bevy::prelude::*;
+use std::borrow::Cow;
+
+// Bumped whenever the packing changes, so old codes are turned away rather
+// than read wrong
+const CODE_VERSION: u8 = 1;
+
+// Each tile is packed into a byte. The low two bits give its kind, the rest
+// the player starting there, or the color and bonus of a colored tile
+const EMPTY: u8 = 0;
+const WALL: u8 = 1;
+const START: u8 = 2;
+const COLORED: u8 = 3;
+
+// Level codes are numbered from 128 so they are never taken for board codes
+const LEVEL_CODE_VERSION: u8 = 128;
+// A level code is never unpacked past this, however it was doctored
+const MAX_LEVEL_CODE_BYTES: usize = 64 * 1024;
+// The most rows or columns a level can have, whether shared or read from a
+// file
+pub const MAX_LEVEL_SIDE: u32 = 128;
+
+// A whole colored board as it was at the start of a game, rather than the
+// layout it was colored from, so a particular random board can be played
+// again. Shared as a short string of URL safe base64
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardCode {
+    pub topology: BoardTopology,
+    pub num_ids: u32,
+    pub rows: u16,
+    pub columns: u16,
+    // A byte per tile, row by row
+    cells: Vec<u8>,
+    // Tiles worth more than one, by their index into the cells
+    weights: Vec<(u16, u8)>,
+}
+
+impl BoardCode {
+    // The board of a game just loaded, with its players in turn order
+    pub fn new(tiles: &[Tile], players: &[Entity], topology: BoardTopology, num_ids: u32) -> Self {
+        let rows = tiles.iter().map(|tile| tile.row + 1).max().unwrap_or(0) as u16;
+        let columns = tiles.iter().map(|tile| tile.column + 1).max().unwrap_or(0) as u16;
+        let mut cells = vec![EMPTY; rows as usize * columns as usize];
+        let mut weights = vec![];
+        for tile in tiles {
+            let index = tile.row as usize * columns as usize + tile.column as usize;
+            cells[index] = match tile.state {
+                TileState::Empty => EMPTY,
+                TileState::Wall => WALL,
+                // Only the players start out owning tiles, anything else
+                // owned is left to the rules to hand out again
+                TileState::Owned(owner) => match players.iter().position(|player| *player == owner)
+                {
+                    Some(player) => START | (player as u8) << 2,
+                    None => continue,
+                },
+                TileState::Unowned(id) => {
+                    let bonus = match tile.bonus {
+                        None => 0,
+                        Some(TileBonus::ExtraTurn) => 1,
+                        Some(TileBonus::Points(_)) => 2,
+                        Some(TileBonus::Hill) => 3,
+                    };
+                    COLORED | (id as u8 & 0xf) << 2 | bonus << 6
+                }
+            };
+            if tile.weight > 1 {
+                weights.push((index as u16, tile.weight as u8));
+            }
+        }
+
+        Self {
+            topology,
+            num_ids,
+            rows,
+            columns,
+            cells,
+            weights,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let topology = match self.topology {
+            BoardTopology::Hex => 0,
+            BoardTopology::Square4 => 1,
+            BoardTopology::Square8 => 2,
+        };
+        let mut bytes = vec![CODE_VERSION, topology, self.num_ids as u8];
+        bytes.extend(self.rows.to_le_bytes());
+        bytes.extend(self.columns.to_le_bytes());
+        bytes.extend(&self.cells);
+        bytes.extend((self.weights.len() as u16).to_le_bytes());
+        for (index, weight) in self.weights.iter() {
+            bytes.extend(index.to_le_bytes());
+            bytes.push(*weight);
+        }
+        base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(code: &str) -> Result<Self, String> {
+        let invalid = || "That isn't a board code".to_string();
+        let bytes =
+            base64::decode_config(code.trim(), base64::URL_SAFE_NO_PAD).map_err(|_| invalid())?;
+        let mut rest = bytes.as_slice();
+        let mut take = |count: usize| -> Result<&[u8], String> {
+            if rest.len() < count {
+                return Err(invalid());
+            }
+            let (taken, tail) = rest.split_at(count);
+            rest = tail;
+            Ok(taken)
+        };
+        let u16_at = |bytes: &[u8]| u16::from_le_bytes([bytes[0], bytes[1]]);
+
+        let header = take(3)?;
+        if header[0] != CODE_VERSION {
+            return Err("The board code is from another version of the game".into());
+        }
+        let topology = match header[1] {
+            0 => BoardTopology::Hex,
+            1 => BoardTopology::Square4,
+            2 => BoardTopology::Square8,
+            _ => return Err(invalid()),
+        };
+        let num_ids = header[2] as u32;
+        let rows = u16_at(take(2)?);
+        let columns = u16_at(take(2)?);
+        let cells = take(rows as usize * columns as usize)?.to_vec();
+        let mut weights = vec![];
+        for _ in 0..u16_at(take(2)?) {
+            let entry = take(3)?;
+            weights.push((u16_at(entry), entry[2]));
+        }
+
+        let code = Self {
+            topology,
+            num_ids,
+            rows,
+            columns,
+            cells,
+            weights,
+        };
+        code.check()?;
+        Ok(code)
+    }
+
+    // How many players the board has starting positions for
+    pub fn players(&self) -> usize {
+        self.cells
+            .iter()
+            .filter(|cell| *cell & 0b11 == START)
+            .map(|cell| (cell >> 2) as usize + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Turns away codes which were mangled into something unplayable
+    fn check(&self) -> Result<(), String> {
+        let valid_cell = |cell: &u8| match cell & 0b11 {
+            START => ((cell >> 2) as usize) < MAX_PLAYERS,
+            COLORED => ((cell >> 2) & 0xf) < self.num_ids as u8,
+            _ => cell >> 2 == 0,
+        };
+        let valid_weight = |(index, weight): &(u16, u8)| {
+            matches!(self.cells.get(*index as usize), Some(cell) if cell & 0b11 == COLORED)
+                && (2..=MAX_TILE_WEIGHT).contains(&(*weight as u32))
+        };
+        if self.cells.iter().all(valid_cell) && self.weights.iter().all(valid_weight) {
+            Ok(())
+        } else {
+            Err("The board code is damaged".into())
+        }
+    }
+
+    // The board as a level, along with the color of each of its colored
+    // tiles in the order they are loaded
+    pub fn level(&self) -> (levels::Level, Vec<u32>) {
+        let mut layout = String::new();
+        let mut colors = vec![];
+        for (index, cell) in self.cells.iter().enumerate() {
+            if index > 0 && index % self.columns as usize == 0 {
+                layout.push('\n');
+            } else if index > 0 {
+                layout.push(' ');
+            }
+
+            match cell & 0b11 {
+                EMPTY => layout.push('-'),
+                WALL => layout.push('#'),
+                START => layout += &((cell >> 2) + 1).to_string(),
+                _ => {
+                    colors.push(((cell >> 2) & 0xf) as u32);
+                    layout.push(match cell >> 6 {
+                        1 => '*',
+                        2 => '+',
+                        3 => '^',
+                        _ => '|',
+                    });
+                    let weight = self
+                        .weights
+                        .iter()
+                        .find(|(weighted, _)| *weighted as usize == index);
+                    if let (Some((_, weight)), 0) = (weight, cell >> 6) {
+                        layout += &weight.to_string();
+                    }
+                }
+            }
+        }
+
+        let level = levels::Level {
+            name: Cow::Borrowed("Board code"),
+            layout: Cow::Owned(layout),
+            topology: self.topology,
+            info: levels::LevelInfo::NONE,
+        };
+        (level, colors)
+    }
+}
+
+// Colors the board's colored tiles as given, in the order they were loaded
+pub fn apply_colors(tiles: &mut [Tile], colors: &[u32]) {
+    let unowned = tiles
+        .iter_mut()
+        .filter(|tile| matches!(tile.state, TileState::Unowned(_)));
+    for (tile, id) in unowned.zip(colors.iter()) {
+        tile.state = TileState::Unowned(*id);
+    }
+}
+
+// A level with everything known about it, packed as a compressed level file
+// short enough to paste into a chat or a forum post
+pub fn level_code(level: &levels::Level) -> Result<String, String> {
+    let file = levels::LevelFile::from_level(level)?;
+    let contents =
+        ron::to_string(&file).map_err(|e| format!("Unable to write the level: {}", e))?;
+    let mut bytes = vec![LEVEL_CODE_VERSION];
+    bytes.extend(miniz_oxide::deflate::compress_to_vec(
+        contents.as_bytes(),
+        9,
+    ));
+    Ok(base64::encode_config(bytes, base64::URL_SAFE_NO_PAD))
+}
+
+// The level a code was made from, as long as it can be played
+pub fn read_level_code(code: &str) -> Result<levels::Level, String> {
+    let bytes = base64::decode_config(code.trim(), base64::URL_SAFE_NO_PAD)
+        .map_err(|_| "That isn't a level code".to_string())?;
+    match bytes.first() {
+        None => return Err("The level code is empty".into()),
+        Some(&LEVEL_CODE_VERSION) => (),
+        Some(&CODE_VERSION) => {
+            return Err("That's a board code, which is loaded from the main menu".into())
+        }
+        Some(_) => return Err("The level code is from another version of the game".into()),
+    }
+    let contents =
+        miniz_oxide::inflate::decompress_to_vec_with_limit(&bytes[1..], MAX_LEVEL_CODE_BYTES)
+            .map_err(|_| "The level code is damaged or cut short".to_string())?;
+    let contents =
+        String::from_utf8(contents).map_err(|_| "The level code is damaged".to_string())?;
+    let file: levels::LevelFile = ron::from_str(&contents)
+        .map_err(|e| format!("The level code doesn't hold a level: {}", e))?;
+
+    if file.starts.len() < 2 {
+        return Err("The level needs starting tiles for at least two players".into());
+    }
+    let level = file.to_level()?;
+    let placeholders = (0..MAX_PLAYERS as u32)
+        .map(Entity::from_raw)
+        .collect::<Vec<_>>();
+    crate::core::parse_level(&level.layout, &placeholders, vec![0], None)
+        .map_err(|e| format!("The level can't be played: {}", e))?;
+    Ok(level)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::load_level;
+
+    #[test]
+    fn codes_replay_the_same_board() {
+        let players = [Entity::from_raw(0), Entity::from_raw(1)];
+        let layout = "1 | * -\n#  |3 ^ 3\n+ | | 2";
+        let tiles = load_level(layout, &players, vec![0, 1, 2, 3], Some(5));
+        let code = BoardCode::new(&tiles, &players, BoardTopology::Square8, 4);
+        let decoded = BoardCode::decode(&code.encode()).unwrap();
+        assert_eq!(decoded, code);
+
+        let (level, colors) = decoded.level();
+        let mut replayed = load_level(&level.layout, &players, vec![0], None);
+        apply_colors(&mut replayed, &colors);
+        let describe = |tiles: &[Tile]| {
+            tiles
+                .iter()
+                .map(|tile| format!("{:?} {:?} {}", tile.state, tile.bonus, tile.weight))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(describe(&replayed), describe(&tiles));
+
+        assert!(BoardCode::decode("not a code").is_err());
+    }
+
+    #[test]
+    fn level_codes_keep_the_level() {
+        let level = crate::levels::find("Diamond").unwrap();
+        let code = level_code(level).unwrap();
+        assert!(!code.contains(char::is_whitespace));
+        let read = read_level_code(&format!("  {}\n", code)).unwrap();
+        assert_eq!(read.name, level.name);
+        assert_eq!(read.topology, level.topology);
+        assert_eq!(read.info, level.info);
+        assert_eq!(read.layout, level.layout);
+
+        let error = |code: &str| read_level_code(code).unwrap_err();
+        assert_eq!(error("not a code!"), "That isn't a level code");
+        assert!(error(&code[..code.len() / 8 * 4]).contains("damaged"));
+        let board = BoardCode::new(&[], &[], BoardTopology::Hex, 2).encode();
+        assert!(error(&board).contains("board code"));
+    }
+}
@@ -0,0 +1,269 @@
+use crate::core::{GameState, Move, Tile, TileBonus, TileState};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::DerefMut;
+
+// A tile's state, with owners given by their place in the starting order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SavedState {
+    Empty,
+    Wall,
+    Unowned(u32),
+    Owned(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedTile {
+    pub row: i32,
+    pub column: i32,
+    pub state: SavedState,
+    pub bonus: Option<TileBonus>,
+}
+
+// Everything needed to pick a game back up, without the entities which only
+// mean something on the machine that made them. Players are given by their
+// place in the starting order instead
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub tiles: Vec<SavedTile>,
+    // The bonus points of each player
+    pub bonus: Vec<u32>,
+    // The players still in the game, starting with whoever moves next
+    pub order: Vec<usize>,
+    pub moves: u32,
+    pub moved_this_turn: bool,
+    pub last_selections: Vec<(usize, u32)>,
+    // The player, color and board hash of every move so far
+    pub history: Vec<(usize, u32, u64)>,
+    pub board_hash: u64,
+    pub positions: Vec<(u64, u32)>,
+}
+
+impl Snapshot {
+    pub fn capture(state: &GameState, tiles: &[Tile], bonus: impl Fn(Entity) -> u32) -> Self {
+        let players = state.board_hash.players();
+        let place = |player: Entity| {
+            players
+                .iter()
+                .position(|other| *other == player)
+                .expect("Unknown player")
+        };
+
+        Self {
+            tiles: tiles
+                .iter()
+                .map(|tile| SavedTile {
+                    row: tile.row,
+                    column: tile.column,
+                    state: match tile.state {
+                        TileState::Empty => SavedState::Empty,
+                        TileState::Wall => SavedState::Wall,
+                        TileState::Unowned(id) => SavedState::Unowned(id),
+                        TileState::Owned(player) => SavedState::Owned(place(player)),
+                    },
+                    bonus: tile.bonus,
+                })
+                .collect(),
+            bonus: players.iter().map(|player| bonus(*player)).collect(),
+            order: state.players.iter().map(|player| place(*player)).collect(),
+            moves: state.moves,
+            moved_this_turn: state.moved_this_turn,
+            last_selections: {
+                let mut selections: Vec<_> = state
+                    .last_selections
+                    .iter()
+                    .map(|(player, id)| (place(*player), *id))
+                    .collect();
+                selections.sort_unstable();
+                selections
+            },
+            history: state
+                .history
+                .iter()
+                .map(|made| (place(made.player), made.id, made.board_hash))
+                .collect(),
+            board_hash: state.board_hash.value,
+            positions: {
+                let mut positions: Vec<_> = state
+                    .positions
+                    .iter()
+                    .map(|(hash, count)| (*hash, *count))
+                    .collect();
+                positions.sort_unstable();
+                positions
+            },
+        }
+    }
+
+    // Brings a game started from the same settings up to the snapshot,
+    // returning the positions of the tiles which changed. Everything is checked
+    // before anything is changed, so a snapshot which doesn't fit leaves the
+    // game as it was
+    pub fn restore(
+        &self,
+        state: &mut GameState,
+        tiles: impl IntoIterator<Item = impl DerefMut<Target = Tile>>,
+        mut set_bonus: impl FnMut(Entity, u32),
+    ) -> Result<Vec<(i32, i32)>, String> {
+        let players = state.board_hash.players().to_vec();
+        let player = |place: usize| {
+            players
+                .get(place)
+                .cloned()
+                .ok_or_else(|| format!("Unknown player {}", place))
+        };
+
+        if self.order.is_empty() {
+            return Err("The snapshot has no players left in the game".into());
+        }
+        if self.bonus.len() != players.len() {
+            return Err(format!(
+                "The snapshot has bonus points for {} players, not {}",
+                self.bonus.len(),
+                players.len()
+            ));
+        }
+        let order = self
+            .order
+            .iter()
+            .map(|place| player(*place))
+            .collect::<Result<Vec<_>, String>>()?;
+        let last_selections = self
+            .last_selections
+            .iter()
+            .map(|(place, id)| Ok((player(*place)?, *id)))
+            .collect::<Result<_, String>>()?;
+        let history = self
+            .history
+            .iter()
+            .map(|(place, id, board_hash)| {
+                Ok(Move {
+                    player: player(*place)?,
+                    id: *id,
+                    board_hash: *board_hash,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        let saved: HashMap<(i32, i32), &SavedTile> = self
+            .tiles
+            .iter()
+            .map(|tile| ((tile.row, tile.column), tile))
+            .collect();
+        let mut tiles = tiles.into_iter().collect::<Vec<_>>();
+        if saved.len() != self.tiles.len() || saved.len() != tiles.len() {
+            return Err("The snapshot doesn't match the board".into());
+        }
+        // The board as it would be after restoring, hashed on the side
+        let mut board_hash = state.board_hash.clone();
+        let mut restored = vec![];
+        let mut changed = vec![];
+        for tile in tiles.iter() {
+            let saved = saved
+                .get(&(tile.row, tile.column))
+                .ok_or_else(|| format!("Missing tile {} {}", tile.row, tile.column))?;
+            let restored_state = match saved.state {
+                SavedState::Empty => TileState::Empty,
+                SavedState::Wall => TileState::Wall,
+                SavedState::Unowned(id) => TileState::Unowned(id),
+                SavedState::Owned(place) => TileState::Owned(player(place)?),
+            };
+
+            let before = board_hash.value;
+            board_hash.update(tile, &restored_state);
+            if board_hash.value != before {
+                changed.push((tile.row, tile.column));
+            }
+            restored.push((restored_state, saved.bonus));
+        }
+        if board_hash.value != self.board_hash {
+            return Err("The snapshot doesn't match the board".into());
+        }
+
+        for (tile, (restored_state, bonus)) in tiles.iter_mut().zip(restored) {
+            state.update_tile(tile, &restored_state);
+            tile.state = restored_state;
+            tile.bonus = bonus;
+        }
+        for (place, bonus) in self.bonus.iter().enumerate() {
+            set_bonus(player(place)?, *bonus);
+        }
+        state.players = order;
+        state.moves = self.moves;
+        state.moved_this_turn = self.moved_this_turn;
+        state.last_selections = last_selections;
+        state.history = history;
+        state.positions = self.positions.iter().cloned().collect();
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::{load_level, simulate_selection, BoardTopology};
+
+    #[test]
+    fn snapshots_restore_the_game() {
+        let players = vec![Entity::from_raw(0), Entity::from_raw(1)];
+        let mut state = GameState {
+            players: players.clone(),
+            ids: (0..3).map(|id| (id, Color::NONE)).collect(),
+            topology: BoardTopology::Hex,
+            ..default()
+        };
+        let mut tiles = load_level(
+            "1 | | | | | | 2",
+            &players,
+            state.ids.keys().cloned().collect(),
+            Some(1),
+        );
+        state.track_board(&tiles);
+
+        // The second player has moved, but the snapshot went out before
+        let snapshot = Snapshot::capture(&state, &tiles, |_| 0);
+        let mut moved = tiles.clone();
+        for id in 0..3 {
+            let mut board = moved.clone();
+            simulate_selection(&mut board, state.topology, id, players[1]);
+            for (tile, after) in moved.iter_mut().zip(board) {
+                state.update_tile(tile, &after.state);
+                tile.state = after.state;
+            }
+        }
+        state.end_turn();
+        state.moves = 1;
+
+        let restored = ron::from_str::<Snapshot>(&ron::to_string(&snapshot).unwrap()).unwrap();
+        let mut bonus = vec![];
+        let changed = restored
+            .restore(&mut state, moved.iter_mut(), |player, points| {
+                bonus.push((player, points))
+            })
+            .expect("Matching snapshot");
+
+        assert!(!changed.is_empty());
+        assert_eq!(state.players, players);
+        assert_eq!(state.moves, 0);
+        assert_eq!(bonus, vec![(players[0], 0), (players[1], 0)]);
+        assert_eq!(Snapshot::capture(&state, &moved, |_| 0), snapshot);
+
+        // Snapshots of some other board are turned down, leaving the game as
+        // it was
+        tiles[1].state = TileState::Wall;
+        let other = Snapshot::capture(&state, &tiles, |_| 0);
+        let before = Snapshot::capture(&state, &moved, |_| 0);
+        assert!(other
+            .restore(&mut state, moved.iter_mut(), |_, _| ())
+            .is_err());
+        assert_eq!(Snapshot::capture(&state, &moved, |_| 0), before);
+
+        let mut empty = snapshot.clone();
+        empty.order.clear();
+        assert!(empty
+            .restore(&mut state, moved.iter_mut(), |_, _| ())
+            .is_err());
+        assert_eq!(state.players, players);
+    }
+}
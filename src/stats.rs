@@ -0,0 +1,350 @@
+use crate::ai::Personality;
+use crate::core::{CaptureEvent, GamePhase, GameState, Player, PlayerKind, SelectEvent};
+use crate::{levels, storage, training};
+use bevy::prelude::*;
+use bevy_egui::egui;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+const STATS_KEY: &str = "stats";
+const INITIAL_RATING: f32 = 1200.0;
+const RATING_K_FACTOR: f32 = 32.0;
+
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Record {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    pub rating: f32,
+    // Keyed by the name of the bot personality played against
+    pub records: BTreeMap<String, Record>,
+    // Best of N matches, keyed the same way
+    pub match_records: BTreeMap<String, Record>,
+    pub moves: u32,
+    pub tiles_captured: u32,
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    // The levels the bot has been beaten on, by name
+    pub completed_levels: BTreeSet<String>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            rating: INITIAL_RATING,
+            records: BTreeMap::new(),
+            match_records: BTreeMap::new(),
+            moves: 0,
+            tiles_captured: 0,
+            current_streak: 0,
+            longest_streak: 0,
+            completed_levels: BTreeSet::new(),
+        }
+    }
+}
+
+impl Stats {
+    pub fn load() -> Self {
+        storage::load(STATS_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(STATS_KEY, self)
+    }
+
+    pub fn average_capture(&self) -> f32 {
+        if self.moves == 0 {
+            return 0.0;
+        }
+        self.tiles_captured as f32 / self.moves as f32
+    }
+
+    pub fn record(&mut self, opponent: Personality, outcome: Outcome, moves: u32, captured: u32) {
+        let record = self.records.entry(opponent.name().into()).or_default();
+        let score = match outcome {
+            Outcome::Win => {
+                record.wins += 1;
+                self.current_streak += 1;
+                self.longest_streak = self.longest_streak.max(self.current_streak);
+                1.0
+            }
+            Outcome::Loss => {
+                record.losses += 1;
+                self.current_streak = 0;
+                0.0
+            }
+            Outcome::Draw => {
+                record.draws += 1;
+                self.current_streak = 0;
+                0.5
+            }
+        };
+
+        let expected = expected_score(self.rating, bot_rating(opponent));
+        self.rating += RATING_K_FACTOR * (score - expected);
+        self.moves += moves;
+        self.tiles_captured += captured;
+    }
+
+    // Matches are tallied on their own, as their games are already rated
+    pub fn record_match(&mut self, opponent: Personality, outcome: Outcome) {
+        let record = self
+            .match_records
+            .entry(opponent.name().into())
+            .or_default();
+        match outcome {
+            Outcome::Win => record.wins += 1,
+            Outcome::Loss => record.losses += 1,
+            Outcome::Draw => record.draws += 1,
+        }
+    }
+}
+
+// Nominal ratings for the bots, used as the opponent rating in Elo updates
+pub fn bot_rating(personality: Personality) -> f32 {
+    match personality {
+        Personality::Aggressive => 1200.0,
+        Personality::Defensive => 1300.0,
+        Personality::Expansionist => 1100.0,
+        Personality::Deep => 1400.0,
+    }
+}
+
+fn expected_score(rating: f32, opponent_rating: f32) -> f32 {
+    1.0 / (1.0 + 10.0_f32.powf((opponent_rating - rating) / 400.0))
+}
+
+// Moves and captures made by the human player in the current game
+#[derive(Default)]
+pub struct GameTally {
+    pub moves: u32,
+    pub captured: u32,
+    // The color picked each move
+    pub colors: Vec<u32>,
+}
+
+// How the whole game went, for the end screen
+#[derive(Default)]
+pub struct GameSummary {
+    // Every player's score at the start and after each move, with the players
+    // in the order the game started with
+    pub timeline: Vec<Vec<u32>>,
+    // The most tiles taken in a single move, and who took them
+    pub biggest_capture: Option<(Entity, u32)>,
+    // The board as it started, to be played again from its code
+    pub board_code: Option<String>,
+    // How evenly the board's coloring treats the starting positions
+    pub fairness: Option<f32>,
+    pub level: String,
+}
+
+impl GameSummary {
+    // Adds the scores once the given number of moves have been made
+    pub fn record_scores(&mut self, moves: usize, scores: Vec<u32>) {
+        if self.timeline.len() <= moves {
+            self.timeline.push(scores);
+        }
+    }
+
+    pub fn record_capture(&mut self, player: Entity, captured: u32) {
+        match self.biggest_capture {
+            Some((_, biggest)) if biggest >= captured => (),
+            _ => self.biggest_capture = Some((player, captured)),
+        }
+    }
+}
+
+pub fn track_summary(
+    state: Res<GameState>,
+    mut summary: ResMut<GameSummary>,
+    mut captures: EventReader<CaptureEvent>,
+    players: Query<&Player>,
+) {
+    let mut captured = BTreeMap::new();
+    for capture in captures.iter() {
+        *captured.entry(capture.player).or_insert(0) += 1;
+    }
+    for (player, count) in captured {
+        summary.record_capture(player, count);
+    }
+
+    let scores = state
+        .board_hash
+        .players()
+        .iter()
+        .map(|player| players.get(*player).map(|player| player.score).unwrap_or(0))
+        .collect();
+    summary.record_scores(state.history.len(), scores);
+}
+
+pub fn track_moves(
+    mut tally: ResMut<GameTally>,
+    mut selections: EventReader<SelectEvent>,
+    mut captures: EventReader<CaptureEvent>,
+    players: Query<&Player>,
+) {
+    let is_human = |player| {
+        players
+            .get(player)
+            .map(|player| matches!(player.kind, PlayerKind::Human))
+            .unwrap_or(false)
+    };
+
+    for selection in selections.iter() {
+        if is_human(selection.player) {
+            tally.moves += 1;
+            tally.colors.push(selection.id);
+        }
+    }
+
+    for capture in captures.iter() {
+        if is_human(capture.player) {
+            tally.captured += 1;
+        }
+    }
+}
+
+// The human player and the bot they're up against. Only games between a
+// single human and a single bot are rated
+pub fn rated_opponent(
+    state: &GameState,
+    players: &Query<&Player>,
+) -> Option<(Entity, Personality)> {
+    let (first, second) = match state.players.as_slice() {
+        [first, second] => (*first, *second),
+        _ => return None,
+    };
+    match (
+        &players.get(first).ok()?.kind,
+        &players.get(second).ok()?.kind,
+    ) {
+        (PlayerKind::Human, PlayerKind::Bot(_, personality)) => Some((first, *personality)),
+        (PlayerKind::Bot(_, personality), PlayerKind::Human) => Some((second, *personality)),
+        _ => None,
+    }
+}
+
+pub fn record_game_result(
+    state: Res<GameState>,
+    players: Query<&Player>,
+    tally: Res<GameTally>,
+    summary: Res<GameSummary>,
+    training: Res<training::Training>,
+    mut stats: ResMut<Stats>,
+) {
+    // Games with takebacks don't count towards the rating
+    if training.enabled {
+        return;
+    }
+    let winner = match state.phase {
+        GamePhase::Over(winner) => Some(winner),
+        GamePhase::Draw => None,
+        _ => return,
+    };
+
+    let (human, opponent) = match rated_opponent(&state, &players) {
+        Some(rated) => rated,
+        None => return,
+    };
+
+    let outcome = match winner {
+        Some(winner) if winner == human => Outcome::Win,
+        Some(_) => Outcome::Loss,
+        None => Outcome::Draw,
+    };
+
+    if matches!(outcome, Outcome::Win) && levels::find(&summary.level).is_some() {
+        stats.completed_levels.insert(summary.level.clone());
+    }
+    stats.record(opponent, outcome, tally.moves, tally.captured);
+    stats.save();
+}
+
+pub fn stats_ui(ui: &mut egui::Ui, stats: &Stats) {
+    ui.label(format!("Rating: {:.0}", stats.rating));
+    ui.label(format!(
+        "Average tiles captured per move: {:.1}",
+        stats.average_capture()
+    ));
+    ui.label(format!("Longest win streak: {}", stats.longest_streak));
+    ui.label(format!(
+        "Levels completed: {}/{}",
+        stats.completed_levels.len(),
+        levels::all().count()
+    ));
+    ui.add_space(10.0);
+
+    records_grid(ui, "stats_records", &stats.records);
+    if !stats.match_records.is_empty() {
+        ui.add_space(10.0);
+        ui.label("Matches");
+        records_grid(ui, "stats_match_records", &stats.match_records);
+    }
+}
+
+fn records_grid(ui: &mut egui::Ui, id: &str, records: &BTreeMap<String, Record>) {
+    egui::Grid::new(id).show(ui, |ui| {
+        ui.label("Opponent");
+        ui.label("Wins");
+        ui.label("Losses");
+        ui.label("Draws");
+        ui.end_row();
+
+        for (opponent, record) in records.iter() {
+            ui.label(opponent);
+            ui.label(record.wins.to_string());
+            ui.label(record.losses.to_string());
+            ui.label(record.draws.to_string());
+            ui.end_row();
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rating_updates() {
+        let mut stats = Stats::default();
+
+        stats.record(Personality::Aggressive, Outcome::Win, 10, 50);
+        assert_eq!(stats.rating, INITIAL_RATING + RATING_K_FACTOR / 2.0);
+        assert_eq!(stats.current_streak, 1);
+
+        stats.record(Personality::Aggressive, Outcome::Loss, 10, 10);
+        assert!(stats.rating < INITIAL_RATING + RATING_K_FACTOR / 2.0);
+        assert_eq!(stats.current_streak, 0);
+        assert_eq!(stats.longest_streak, 1);
+        assert_eq!(stats.average_capture(), 3.0);
+
+        let record = &stats.records["Aggressive"];
+        assert_eq!((record.wins, record.losses, record.draws), (1, 1, 0));
+    }
+
+    #[test]
+    fn summaries_follow_the_game() {
+        let (first, second) = (Entity::from_raw(0), Entity::from_raw(1));
+        let mut summary = GameSummary::default();
+        summary.record_scores(0, vec![1, 1]);
+        summary.record_scores(0, vec![1, 1]);
+        summary.record_scores(1, vec![4, 1]);
+        assert_eq!(summary.timeline, vec![vec![1, 1], vec![4, 1]]);
+
+        summary.record_capture(first, 3);
+        summary.record_capture(second, 2);
+        summary.record_capture(second, 3);
+        assert_eq!(summary.biggest_capture, Some((first, 3)));
+    }
+}
@@ -0,0 +1,65 @@
+use bevy::log::error;
+use serde::{de::DeserializeOwned, Serialize};
+
+// Small key/value persistence, backed by local storage in the browser and by
+// RON files in the user's data directory natively
+
+pub fn load<T: DeserializeOwned + Default>(key: &str) -> T {
+    read(key)
+        .and_then(|contents| ron::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+pub fn save<T: Serialize>(key: &str, value: &T) {
+    match ron::to_string(value) {
+        Ok(contents) => write(key, &contents),
+        Err(e) => error!("Unable to serialize '{}': {}", key, e),
+    }
+}
+
+#[cfg(target_family = "wasm")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_family = "wasm")]
+fn read(key: &str) -> Option<String> {
+    local_storage()?
+        .get_item(&format!("assimilation.{}", key))
+        .ok()?
+}
+
+#[cfg(target_family = "wasm")]
+fn write(key: &str, contents: &str) {
+    if let Some(storage) = local_storage() {
+        let _ = storage.set_item(&format!("assimilation.{}", key), contents);
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn data_dir() -> std::path::PathBuf {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .map(|home| std::path::PathBuf::from(home).join(".local").join("share"))
+        })
+        .or_else(|| std::env::var_os("APPDATA").map(std::path::PathBuf::from))
+        .unwrap_or_else(|| std::path::PathBuf::from("."));
+    base.join("assimilation")
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn read(key: &str) -> Option<String> {
+    std::fs::read_to_string(data_dir().join(format!("{}.ron", key))).ok()
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn write(key: &str, contents: &str) {
+    let dir = data_dir();
+    let result = std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(dir.join(format!("{}.ron", key)), contents));
+    if let Err(e) = result {
+        error!("Unable to save '{}': {}", key, e);
+    }
+}
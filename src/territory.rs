@@ -0,0 +1,155 @@
+use crate::board::{Board, BoardEntity};
+use crate::core::{BoardTopology, GameState, Player, Tile, TileState};
+use crate::theme;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use std::collections::HashMap;
+
+const GLOW_WIDTH: f32 = 6.0;
+const PULSE_SPEED: f32 = 3.0;
+
+// The outline around all of the tiles a player owns. Each player gets a wide,
+// faint glow layer beneath a thin bright one
+#[derive(Component)]
+pub struct TerritoryBorder {
+    player: Entity,
+    glow: bool,
+}
+
+// Offsets to the tiles sharing an edge with the given tile. Diagonal neighbors
+// only share a corner, so they never contribute to the border
+fn edge_offsets(topology: BoardTopology, row: i32) -> &'static [(i32, i32)] {
+    match topology {
+        BoardTopology::Square8 => BoardTopology::Square4.neighbor_offsets(row),
+        _ => topology.neighbor_offsets(row),
+    }
+}
+
+fn edge_length(board: &Board) -> f32 {
+    match board.topology {
+        BoardTopology::Hex => board.tile_radius,
+        BoardTopology::Square4 | BoardTopology::Square8 => board.spacing().0,
+    }
+}
+
+// The line segments separating a player's tiles from everything else
+fn territory_edges(board: &Board, tiles: &[&Tile], player: Entity) -> Vec<(Vec2, Vec2)> {
+    let owners = tiles
+        .iter()
+        .map(|tile| ((tile.row, tile.column), &tile.state))
+        .collect::<HashMap<_, _>>();
+    let half_edge = edge_length(board) / 2.0;
+
+    let mut edges = vec![];
+    for tile in tiles {
+        if !matches!(tile.state, TileState::Owned(owner) if owner == player) {
+            continue;
+        }
+
+        let center = board.tile_to_world(tile.row, tile.column);
+        for (row_offset, column_offset) in edge_offsets(board.topology, tile.row) {
+            let neighbor = (tile.row + row_offset, tile.column + column_offset);
+            if matches!(owners.get(&neighbor), Some(TileState::Owned(owner)) if *owner == player) {
+                continue;
+            }
+
+            let direction = board.tile_to_world(neighbor.0, neighbor.1) - center;
+            let midpoint = center + direction / 2.0;
+            let along = direction.perp().normalize() * half_edge;
+            edges.push((midpoint - along, midpoint + along));
+        }
+    }
+    edges
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_territory_borders(
+    state: Res<GameState>,
+    board: Res<Board>,
+    players: Query<&Player>,
+    tiles: Query<&Tile>,
+    added: Query<(), Added<Tile>>,
+    borders: Query<Entity, With<TerritoryBorder>>,
+    mut drawn: Local<Option<u64>>,
+    mut commands: Commands,
+) {
+    // Redrawn whenever the board changes, whether by a capture or by the
+    // players swapping sides
+    if *drawn == Some(state.board_hash.value) && added.is_empty() {
+        return;
+    }
+    *drawn = Some(state.board_hash.value);
+
+    for border in borders.iter() {
+        commands.entity(border).despawn();
+    }
+
+    let tiles = tiles.iter().collect::<Vec<_>>();
+
+    for player_id in state.players.iter() {
+        let player = match players.get(*player_id) {
+            Ok(player) => player,
+            Err(_) => continue,
+        };
+
+        let edges = territory_edges(&board, &tiles, *player_id);
+        for glow in [true, false] {
+            let builder = edges
+                .iter()
+                .fold(GeometryBuilder::new(), |builder, (start, end)| {
+                    builder.add(&shapes::Line(*start, *end))
+                });
+            commands
+                .spawn_bundle(builder.build(
+                    DrawMode::Stroke(StrokeMode::new(player.color, 1.0)),
+                    Transform::from_xyz(0.0, 0.0, if glow { 2.0 } else { 2.1 }),
+                ))
+                .insert(TerritoryBorder {
+                    player: *player_id,
+                    glow,
+                })
+                .insert(BoardEntity);
+        }
+    }
+}
+
+pub fn animate_territory_borders(
+    time: Res<Time>,
+    players: Query<&Player>,
+    mut borders: Query<(&TerritoryBorder, &mut DrawMode)>,
+) {
+    let pulse = 0.5 + 0.5 * (time.seconds_since_startup() as f32 * PULSE_SPEED).sin();
+
+    for (border, mut mode) in borders.iter_mut() {
+        let color = match players.get(border.player) {
+            Ok(player) => theme::shade(player.color, 0.2),
+            Err(_) => continue,
+        };
+
+        *mode = if border.glow {
+            DrawMode::Stroke(StrokeMode::new(
+                *color.clone().set_a(0.2 + 0.2 * pulse),
+                GLOW_WIDTH + 2.0 * pulse,
+            ))
+        } else {
+            DrawMode::Stroke(StrokeMode::new(color, 1.5))
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::load_level;
+
+    #[test]
+    fn border_surrounds_territory() {
+        let player = Entity::from_raw(0);
+        let tiles = load_level("1 1 |\n| | |", &[player], vec![0], None);
+        let board = Board::new(BoardTopology::Square4, 2, 3);
+
+        // Two adjacent squares share one edge, leaving six on the outside
+        let edges = territory_edges(&board, &tiles.iter().collect::<Vec<_>>(), player);
+        assert_eq!(edges.len(), 6);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::storage;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use bevy_prototype_lyon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const THEME_KEY: &str = "theme";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    Flat,
+    Dark,
+    Light,
+    Beveled,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::Flat
+    }
+}
+
+impl Theme {
+    pub const ALL: [Theme; 4] = [Theme::Flat, Theme::Dark, Theme::Light, Theme::Beveled];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Flat => "Flat",
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+            Theme::Beveled => "Beveled",
+        }
+    }
+
+    pub fn load() -> Self {
+        storage::load(THEME_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(THEME_KEY, self)
+    }
+
+    pub fn background(&self) -> Color {
+        match self {
+            Theme::Flat | Theme::Beveled => Color::rgb(0.4, 0.4, 0.4),
+            Theme::Dark => Color::rgb(0.08, 0.08, 0.1),
+            Theme::Light => Color::rgb(0.9, 0.9, 0.88),
+        }
+    }
+
+    pub fn wall_color(&self) -> Color {
+        match self {
+            Theme::Flat | Theme::Beveled => Color::rgb(0.15, 0.15, 0.15),
+            Theme::Dark => Color::rgb(0.25, 0.25, 0.28),
+            Theme::Light => Color::rgb(0.55, 0.55, 0.55),
+        }
+    }
+
+    pub fn text_color(&self) -> Color {
+        match self {
+            Theme::Light => Color::BLACK,
+            _ => Color::WHITE,
+        }
+    }
+
+    // Owned and highlighted tiles are outlined differently from the rest
+    fn outline(&self, fill: Color, highlighted: bool) -> StrokeMode {
+        match (self, highlighted) {
+            (Theme::Flat, false) | (Theme::Light, false) => StrokeMode::new(Color::BLACK, 1.0),
+            (Theme::Flat, true) | (Theme::Dark, true) => StrokeMode::new(Color::WHITE, 1.0),
+            (Theme::Dark, false) => StrokeMode::new(Color::rgb(0.05, 0.05, 0.05), 1.5),
+            (Theme::Light, true) => StrokeMode::new(Color::rgb(0.2, 0.2, 0.2), 2.0),
+            // Shading the outline from the fill gives each tile a raised edge
+            (Theme::Beveled, highlighted) => {
+                StrokeMode::new(shade(fill, if highlighted { 0.25 } else { -0.2 }), 2.5)
+            }
+        }
+    }
+
+    pub fn tile_mode(&self, fill: Color, highlighted: bool) -> DrawMode {
+        DrawMode::Outlined {
+            fill_mode: FillMode::color(fill),
+            outline_mode: self.outline(fill, highlighted),
+        }
+    }
+
+    // Owned tiles blend together, with the territory border drawn around them
+    pub fn owned_tile_mode(&self, fill: Color) -> DrawMode {
+        DrawMode::Outlined {
+            fill_mode: FillMode::color(fill),
+            outline_mode: StrokeMode::new(fill, 1.0),
+        }
+    }
+
+    pub fn visuals(&self) -> egui::Visuals {
+        let mut visuals = match self {
+            Theme::Light => egui::Visuals::light(),
+            _ => egui::Visuals::dark(),
+        };
+        let [r, g, b, _] = self.text_color().as_rgba_f32();
+        visuals.override_text_color = Some(egui::Rgba::from_rgb(r, g, b).into());
+        visuals
+    }
+}
+
+// Lightens or darkens a color by the given amount
+pub fn shade(color: Color, amount: f32) -> Color {
+    match color.as_hsla() {
+        Color::Hsla {
+            hue,
+            saturation,
+            lightness,
+            alpha,
+        } => Color::hsla(hue, saturation, (lightness + amount).clamp(0.0, 1.0), alpha),
+        _ => unreachable!(),
+    }
+}
+
+pub fn apply_theme(
+    theme: Res<Theme>,
+    mut clear_color: ResMut<ClearColor>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    clear_color.0 = theme.background();
+    egui_ctx.ctx_mut().set_visuals(theme.visuals());
+}
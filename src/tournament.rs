@@ -0,0 +1,234 @@
+use crate::ai::{self, Personality};
+use crate::{core, levels, palette};
+use bevy::prelude::*;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+// Games still going after this many moves are called a draw
+const MAX_MOVES: u32 = 1000;
+
+const USAGE: &str = "usage: assimilation tournament [--games <count>] \
+    [--bots <personality>,<personality>,...] [--level <name>] [--colors <count>]";
+
+// Round-robins between bots, played without opening a window
+pub struct Options {
+    // The number of games played by each pair of bots
+    pub games: u32,
+    pub bots: Vec<Personality>,
+    pub level: levels::Level,
+    pub num_ids: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            games: 100,
+            bots: Personality::ALL.to_vec(),
+            level: levels::LEVELS[0].clone(),
+            num_ids: 5,
+        }
+    }
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for {}\n{}", arg, USAGE))?;
+            match arg.as_str() {
+                "--games" => {
+                    options.games = value
+                        .parse()
+                        .map_err(|_| format!("Invalid number of games '{}'", value))?
+                }
+                "--bots" => {
+                    options.bots = value
+                        .split(',')
+                        .map(|name| {
+                            Personality::ALL
+                                .into_iter()
+                                .find(|personality| personality.name().eq_ignore_ascii_case(name))
+                                .ok_or_else(|| format!("Unknown bot '{}'", name))
+                        })
+                        .collect::<Result<_, _>>()?
+                }
+                "--level" => {
+                    options.level = levels::all()
+                        .find(|level| level.name.eq_ignore_ascii_case(value))
+                        .cloned()
+                        .ok_or_else(|| format!("Unknown level '{}'", value))?
+                }
+                "--colors" => {
+                    options.num_ids = value
+                        .parse()
+                        .ok()
+                        .filter(|num_ids| (2..=palette::MAX_IDS).contains(num_ids))
+                        .ok_or_else(|| format!("Invalid number of colors '{}'", value))?
+                }
+                _ => return Err(format!("Unknown option {}\n{}", arg, USAGE)),
+            }
+        }
+
+        if options.bots.len() < 2 {
+            return Err("At least two bots are needed".into());
+        }
+        Ok(options)
+    }
+}
+
+// Plays a single game on the board generated from the seed, returning the
+// index of the winning bot. Bonus tiles are ignored, only territory counts
+fn play(options: &Options, bots: [Personality; 2], seed: u64) -> Option<usize> {
+    let players: Vec<Entity> = (0..2).map(Entity::from_raw).collect();
+    let personalities: HashMap<Entity, Personality> = players.iter().cloned().zip(bots).collect();
+    let mut state = core::GameState {
+        players: players.clone(),
+        phase: core::GamePhase::Running,
+        ids: (0..options.num_ids).map(|id| (id, Color::NONE)).collect(),
+        topology: options.level.topology,
+        seed: Some(seed),
+        ..default()
+    };
+    let mut tiles = core::load_level(
+        &options.level.layout,
+        &players,
+        state.ids.keys().cloned().collect(),
+        Some(seed),
+    );
+
+    while state.moves < MAX_MOVES {
+        let cache = core::MoveCache::new(&state, &tiles);
+        if !players.iter().any(|player| cache.has_move(&state, *player)) {
+            break;
+        }
+
+        let player = state.players[0];
+        if let Some(id) = ai::choose_move(&tiles, &state, &cache, player, personalities[&player]) {
+            core::simulate_selection(&mut tiles, state.topology, id, player);
+            state.last_selections.insert(player, id);
+        }
+        state.moves += 1;
+        state.end_turn();
+    }
+
+    let score = |player| {
+        tiles
+            .iter()
+            .filter(|tile| matches!(tile.state, core::TileState::Owned(owner) if owner == player))
+            .map(|tile| tile.weight)
+            .sum::<u32>()
+    };
+    match score(players[0]).cmp(&score(players[1])) {
+        Ordering::Greater => Some(0),
+        Ordering::Less => Some(1),
+        Ordering::Equal => None,
+    }
+}
+
+// The share of points each bot took from each other bot, with a draw worth
+// half a win. The bots take turns moving first
+pub fn round_robin(options: &Options) -> Vec<Vec<f32>> {
+    let count = options.bots.len();
+    let mut rates = vec![vec![0.0; count]; count];
+    let pairings =
+        (0..count).flat_map(|first| (first + 1..count).map(move |second| (first, second)));
+    for (first, second) in pairings {
+        let mut points = 0.0;
+        for game in 0..options.games {
+            let swapped = game % 2 == 1;
+            let bots = if swapped {
+                [options.bots[second], options.bots[first]]
+            } else {
+                [options.bots[first], options.bots[second]]
+            };
+            points += match play(options, bots, game as u64) {
+                Some(winner) if (winner == 0) != swapped => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+        }
+
+        let rate = points / options.games.max(1) as f32;
+        rates[first][second] = rate;
+        rates[second][first] = 1.0 - rate;
+    }
+    rates
+}
+
+pub fn run(options: &Options) {
+    let rates = round_robin(options);
+    let names: Vec<&str> = options.bots.iter().map(|bot| bot.name()).collect();
+    let width = names
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(7);
+
+    println!(
+        "Win rates over {} games per pairing on {}, with {} colors",
+        options.games, options.level.name, options.num_ids
+    );
+    print!("{:width$}", "", width = width);
+    for name in names.iter() {
+        print!("  {:>width$}", name, width = width);
+    }
+    println!("  {:>width$}", "Overall", width = width);
+
+    for (idx, name) in names.iter().enumerate() {
+        print!("{:width$}", name, width = width);
+        for (opponent, rate) in rates[idx].iter().enumerate() {
+            if opponent == idx {
+                print!("  {:>width$}", "-", width = width);
+            } else {
+                print!("  {:>width$.1}%", rate * 100.0, width = width - 1);
+            }
+        }
+        let overall = rates[idx].iter().sum::<f32>() / (names.len() - 1) as f32;
+        println!("  {:>width$.1}%", overall * 100.0, width = width - 1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parse_options() {
+        let options = Options::parse(&args("--games 10 --bots aggressive,Defensive --colors 3"))
+            .expect("Valid options");
+        assert_eq!(options.games, 10);
+        assert_eq!(
+            options.bots,
+            vec![Personality::Aggressive, Personality::Defensive]
+        );
+        assert_eq!(options.num_ids, 3);
+
+        assert!(Options::parse(&args("--games")).is_err());
+        assert!(Options::parse(&args("--bots aggressive")).is_err());
+        assert!(Options::parse(&args("--bots aggressive,minimax")).is_err());
+        assert!(Options::parse(&args("--colors 11")).is_err());
+    }
+
+    #[test]
+    fn win_rates_add_up() {
+        let options = Options {
+            games: 4,
+            ..Options::default()
+        };
+
+        let rates = round_robin(&options);
+        for (idx, row) in rates.iter().enumerate() {
+            for (opponent, rate) in row.iter().enumerate().skip(idx + 1) {
+                assert_eq!(rate + rates[opponent][idx], 1.0);
+            }
+        }
+    }
+}
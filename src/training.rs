@@ -0,0 +1,340 @@
+use crate::core::{self, GameState};
+use crate::snapshot::Snapshot;
+use crate::{attract, game, stats, theme, GameStartEvent};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use bevy_prototype_lyon::prelude::*;
+
+// The move list never takes over the screen, however long the game runs
+const MAX_LIST_HEIGHT: f32 = 300.0;
+
+// A position reached in a training game, and every move tried from it
+#[derive(Clone, Debug)]
+struct Position {
+    parent: Option<usize>,
+    // The line played first comes first, then the ones tried after going back
+    children: Vec<usize>,
+    snapshot: Snapshot,
+}
+
+// Every line played in a training game, branching wherever the player went
+// back and tried something else
+#[derive(Clone, Debug)]
+pub struct MoveTree {
+    positions: Vec<Position>,
+    current: usize,
+}
+
+impl MoveTree {
+    pub fn new(start: Snapshot) -> Self {
+        Self {
+            positions: vec![Position {
+                parent: None,
+                children: vec![],
+                snapshot: start,
+            }],
+            current: 0,
+        }
+    }
+
+    // Moves on to a position reached from the current one, following the line
+    // already there if the same moves were made before
+    pub fn advance(&mut self, snapshot: Snapshot) -> usize {
+        let current = self.current;
+        let existing = self.positions[current]
+            .children
+            .iter()
+            .find(|child| self.positions[**child].snapshot.history == snapshot.history);
+        self.current = match existing {
+            Some(child) => *child,
+            None => {
+                self.positions.push(Position {
+                    parent: Some(current),
+                    children: vec![],
+                    snapshot,
+                });
+                let child = self.positions.len() - 1;
+                self.positions[current].children.push(child);
+                child
+            }
+        };
+        self.current
+    }
+
+    // The number of moves made to reach the current position
+    pub fn moves(&self) -> usize {
+        self.positions[self.current].snapshot.history.len()
+    }
+}
+
+// Training games let the player take moves back, going back to any earlier
+// position and trying another line from there, without the game counting
+#[derive(Default)]
+pub struct Training {
+    pub enabled: bool,
+    tree: Option<MoveTree>,
+    // The position picked from the move list, to go back to next frame
+    rewind: Option<usize>,
+}
+
+pub struct TrainingPlugin;
+
+impl Plugin for TrainingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Training>()
+            .add_system(start_training.after(game::game_start))
+            .add_system(rewind.after(start_training))
+            .add_system(show_move_tree.after(rewind))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::GameStep)
+                    .with_system(record_moves.after(core::update_scores)),
+            );
+    }
+}
+
+fn start_training(mut start_event: EventReader<GameStartEvent>, mut training: ResMut<Training>) {
+    if let Some(event) = start_event.iter().last() {
+        // Picks hidden until the round is over can't be taken back one by one
+        training.enabled = event.training && !event.rules.simultaneous;
+        training.tree = None;
+        training.rewind = None;
+    }
+}
+
+// Keeps the position after every move, once the step making it has finished
+fn record_moves(
+    mut training: ResMut<Training>,
+    state: Res<GameState>,
+    tiles: Query<&core::Tile>,
+    players: Query<&core::Player>,
+) {
+    if !training.enabled {
+        return;
+    }
+    let moved = training
+        .tree
+        .as_ref()
+        .map_or(true, |tree| tree.moves() != state.history.len());
+    if !moved {
+        return;
+    }
+
+    let tiles = tiles.iter().cloned().collect::<Vec<_>>();
+    let snapshot = Snapshot::capture(&state, &tiles, |player| {
+        players.get(player).map(|player| player.bonus).unwrap_or(0)
+    });
+    match training.tree.as_mut() {
+        Some(tree) => {
+            tree.advance(snapshot);
+        }
+        None => training.tree = Some(MoveTree::new(snapshot)),
+    }
+}
+
+// Brings back the position picked from the move list, redrawing whatever
+// changed. A finished game carries on from there
+fn rewind(
+    mut training: ResMut<Training>,
+    mut state: ResMut<GameState>,
+    mut cache: ResMut<core::MoveCache>,
+    mut summary: ResMut<stats::GameSummary>,
+    theme: Res<theme::Theme>,
+    mut tiles: Query<(&mut core::Tile, &mut DrawMode, &mut Transform)>,
+    mut players: ParamSet<(Query<&core::Player>, Query<&mut core::Player>)>,
+) {
+    let target = match training.rewind.take() {
+        Some(target) => target,
+        None => return,
+    };
+    let tree = match training.tree.as_mut() {
+        Some(tree) if target < tree.positions.len() => tree,
+        _ => return,
+    };
+
+    let mut bonus = vec![];
+    let restored = tree.positions[target].snapshot.restore(
+        &mut state,
+        tiles.iter_mut().map(|(tile, _, _)| tile),
+        |player, points| bonus.push((player, points)),
+    );
+    let changed = match restored {
+        Ok(changed) => changed,
+        Err(e) => return warn!("Unable to go back to move {}: {}", target, e),
+    };
+    tree.current = target;
+    info!("Went back to move {}", state.history.len());
+
+    for (player, points) in bonus {
+        if let Ok(mut player) = players.p1().get_mut(player) {
+            player.bonus = points;
+        }
+    }
+    for (tile, mut mode, mut transform) in tiles.iter_mut() {
+        if !changed.contains(&(tile.row, tile.column)) {
+            continue;
+        }
+        if let Some((new_mode, z_pos)) =
+            game::tile_appearance(&theme, &state, &players.p0(), &tile.state)
+        {
+            *mode = new_mode;
+            transform.translation.z = z_pos;
+        }
+    }
+    if state.phase.is_finished() {
+        state.phase = core::GamePhase::Running;
+    }
+    state.stalemate = false;
+    // The chart picks up again from the restored scores
+    summary.timeline.truncate(state.history.len());
+    cache.stale = true;
+}
+
+// Every move made so far, with the lines tried instead indented under the
+// move they replaced. Picking a move goes back to the position after it
+fn show_move_tree(
+    mut training: ResMut<Training>,
+    state: Res<GameState>,
+    attract: Res<attract::Attract>,
+    players: Query<&core::Player>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    if state.phase == core::GamePhase::Config || attract.demo {
+        return;
+    }
+    let tree = match &training.tree {
+        Some(tree) => tree,
+        None => return,
+    };
+
+    let mut picked = None;
+    egui::Window::new("Moves")
+        .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+        .resizable(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(MAX_LIST_HEIGHT)
+                .show(ui, |ui| {
+                    if ui.selectable_label(tree.current == 0, "Start").clicked() {
+                        picked = Some(0);
+                    }
+                    if let Some(first) = tree.positions[0].children.first() {
+                        show_line(ui, tree, *first, &state, &players, &mut picked);
+                    }
+                });
+        });
+
+    if picked.map_or(false, |picked| picked != tree.current) {
+        training.rewind = picked;
+    }
+}
+
+// A line of moves from the given position on, with the alternatives to each
+// move right after it
+fn show_line(
+    ui: &mut egui::Ui,
+    tree: &MoveTree,
+    first: usize,
+    state: &GameState,
+    players: &Query<&core::Player>,
+    picked: &mut Option<usize>,
+) {
+    let mut next = Some(first);
+    while let Some(position) = next {
+        show_move(ui, tree, position, state, players, picked);
+
+        let siblings = tree.positions[position]
+            .parent
+            .map_or(&[][..], |parent| &tree.positions[parent].children);
+        if siblings.first() == Some(&position) {
+            for alternative in &siblings[1..] {
+                ui.indent(alternative, |ui| {
+                    show_line(ui, tree, *alternative, state, players, picked)
+                });
+            }
+        }
+        next = tree.positions[position].children.first().cloned();
+    }
+}
+
+fn show_move(
+    ui: &mut egui::Ui,
+    tree: &MoveTree,
+    position: usize,
+    state: &GameState,
+    players: &Query<&core::Player>,
+    picked: &mut Option<usize>,
+) {
+    let history = &tree.positions[position].snapshot.history;
+    let (place, id, _) = match history.last() {
+        Some(made) => *made,
+        None => return,
+    };
+    let name = state
+        .board_hash
+        .players()
+        .get(place)
+        .and_then(|player| players.get(*player).ok())
+        .map_or("?", |player| player.name.as_str());
+
+    ui.horizontal(|ui| {
+        let [r, g, b, _] = state
+            .ids
+            .get(&id)
+            .map_or(Color::GRAY, |color| *color)
+            .as_rgba_f32();
+        let (swatch, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+        ui.painter()
+            .rect_filled(swatch, 2.0, egui::Rgba::from_rgb(r, g, b));
+        let label = format!("{}. {}", history.len(), name);
+        if ui
+            .selectable_label(tree.current == position, label)
+            .clicked()
+        {
+            *picked = Some(position);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn after(moves: &[u32]) -> Snapshot {
+        Snapshot {
+            tiles: vec![],
+            bonus: vec![],
+            order: vec![],
+            moves: moves.len() as u32,
+            moved_this_turn: false,
+            last_selections: vec![],
+            history: moves
+                .iter()
+                .enumerate()
+                .map(|(turn, id)| (turn % 2, *id, 0))
+                .collect(),
+            board_hash: 0,
+            positions: vec![],
+        }
+    }
+
+    #[test]
+    fn going_back_branches_the_game() {
+        let mut tree = MoveTree::new(after(&[]));
+        let first = tree.advance(after(&[1]));
+        let second = tree.advance(after(&[1, 2]));
+        assert_eq!(tree.moves(), 2);
+        assert_eq!(tree.positions[second].parent, Some(first));
+
+        // Trying another color after going back starts a new line
+        tree.current = first;
+        let other = tree.advance(after(&[1, 3]));
+        assert_ne!(other, second);
+        assert_eq!(tree.positions[first].children, vec![second, other]);
+
+        // Making the same move again follows the line already played
+        tree.current = first;
+        assert_eq!(tree.advance(after(&[1, 2])), second);
+        assert_eq!(tree.positions.len(), 4);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::ai::{self, Personality};
+use crate::board::BoardEntity;
+use crate::core::{
+    for_each_selected_tile, GameState, MoveCache, Player, PlayerKind, SelectEvent, Tile,
+};
+use crate::cursor::{Cursor, CursorSource};
+use crate::TILE_RADIUS;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use bevy_prototype_lyon::prelude::*;
+
+const STEPS: [&str; 3] = [
+    "Click an adjacent color to absorb it. The arrow points at a good choice.",
+    "Every tile of that color touching your territory joined it. \
+     After the bot moves, absorb the color under the arrow again.",
+    "Bigger groups grow your territory faster. One more time!",
+];
+
+const FINISHED: &str = "That's it! Keep absorbing colors. \
+    Whoever owns the most tiles once nobody can move wins.";
+
+// Progress through the scripted tutorial. While active, the human player may
+// only select the expected color
+#[derive(Default)]
+pub struct Tutorial {
+    pub active: bool,
+    pub step: usize,
+    pub expected: Option<u32>,
+}
+
+impl Tutorial {
+    pub fn new(active: bool) -> Self {
+        Self {
+            active,
+            ..default()
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.step >= STEPS.len()
+    }
+
+    pub fn allows(&self, id: u32) -> bool {
+        !self.active || self.is_finished() || self.expected == Some(id)
+    }
+}
+
+#[derive(Component)]
+pub struct TutorialArrow;
+
+pub fn update_tutorial(
+    mut tutorial: ResMut<Tutorial>,
+    state: Res<GameState>,
+    cache: Res<MoveCache>,
+    players: Query<&Player>,
+    tiles: Query<&Tile>,
+    mut selections: EventReader<SelectEvent>,
+) {
+    if !tutorial.active {
+        return;
+    }
+
+    let is_human = |player| {
+        players
+            .get(player)
+            .map(|player| matches!(player.kind, PlayerKind::Human))
+            .unwrap_or(false)
+    };
+
+    for selection in selections.iter() {
+        if is_human(selection.player) && tutorial.expected == Some(selection.id) {
+            tutorial.step += 1;
+            tutorial.expected = None;
+        }
+    }
+
+    if tutorial.is_finished() || !(cache.is_changed() || state.is_changed()) {
+        return;
+    }
+
+    let player = state.players[0];
+    let expected = if is_human(player) {
+        let tiles = tiles.iter().cloned().collect::<Vec<_>>();
+        ai::choose_move(&tiles, &state, &cache, player, Personality::Aggressive)
+    } else {
+        None
+    };
+
+    if tutorial.expected != expected {
+        tutorial.expected = expected;
+    }
+}
+
+// Points an arrow at one of the tiles the expected move would capture, and
+// moves the cursor there to preview the move
+pub fn point_tutorial_arrow(
+    tutorial: Res<Tutorial>,
+    state: Res<GameState>,
+    mut cursor: ResMut<Cursor>,
+    tiles: Query<(&Tile, &Transform)>,
+    arrows: Query<Entity, With<TutorialArrow>>,
+    mut commands: Commands,
+) {
+    if !tutorial.is_changed() {
+        return;
+    }
+
+    for arrow in arrows.iter() {
+        commands.entity(arrow).despawn();
+    }
+
+    let id = match tutorial.expected {
+        Some(id) if tutorial.active && !tutorial.is_finished() => id,
+        _ => return,
+    };
+
+    let mut board = tiles
+        .iter()
+        .map(|(tile, _)| tile.clone())
+        .collect::<Vec<_>>();
+    let mut target = None;
+    for_each_selected_tile(
+        board.iter_mut().collect(),
+        state.topology,
+        id,
+        state.players[0],
+        |tile| {
+            target.get_or_insert((tile.row, tile.column));
+        },
+    );
+
+    let transform = match target.and_then(|(row, column)| {
+        tiles
+            .iter()
+            .find(|(tile, _)| tile.row == row && tile.column == column)
+    }) {
+        Some((_, transform)) => transform,
+        None => return,
+    };
+    cursor.move_to(transform.translation.truncate(), CursorSource::Tutorial);
+
+    let arrow = shapes::RegularPolygon {
+        sides: 3,
+        feature: shapes::RegularPolygonFeature::Radius(TILE_RADIUS / 2.0),
+        ..shapes::RegularPolygon::default()
+    };
+    let arrow_transform =
+        Transform::from_translation(transform.translation + Vec3::new(0.0, TILE_RADIUS * 1.5, 2.0))
+            .with_rotation(Quat::from_rotation_z(std::f32::consts::PI));
+
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &arrow,
+            DrawMode::Outlined {
+                fill_mode: FillMode::color(Color::WHITE),
+                outline_mode: StrokeMode::new(Color::BLACK, 1.0),
+            },
+            arrow_transform,
+        ))
+        .insert(TutorialArrow)
+        .insert(BoardEntity);
+}
+
+pub fn show_tutorial(tutorial: Res<Tutorial>, mut egui_ctx: ResMut<EguiContext>) {
+    if !tutorial.active {
+        return;
+    }
+
+    let text = STEPS.get(tutorial.step).unwrap_or(&FINISHED);
+    egui::Window::new("Tutorial")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 10.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.label(*text);
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gates_selection_to_expected_move() {
+        let mut tutorial = Tutorial::new(true);
+        tutorial.expected = Some(2);
+        assert!(tutorial.allows(2));
+        assert!(!tutorial.allows(1));
+
+        tutorial.step = STEPS.len();
+        assert!(tutorial.allows(1));
+
+        assert!(Tutorial::new(false).allows(1));
+    }
+}
@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+// Animates an entity's draw mode and/or scale from nothing to the given
+// values, after waiting for the delay. Removed once finished
+#[derive(Component, Clone)]
+pub struct Tween {
+    pub delay: f32,
+    pub duration: f32,
+    pub fade_to: Option<DrawMode>,
+    pub scale_to: Option<Vec3>,
+    elapsed: f32,
+}
+
+impl Tween {
+    pub fn new(delay: f32, duration: f32) -> Self {
+        Self {
+            delay,
+            duration,
+            fade_to: None,
+            scale_to: None,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn fade_to(mut self, mode: DrawMode) -> Self {
+        self.fade_to = Some(mode);
+        self
+    }
+
+    pub fn scale_to(mut self, scale: Vec3) -> Self {
+        self.scale_to = Some(scale);
+        self
+    }
+
+    // Eased progress from 0 to 1
+    fn progress(&self) -> f32 {
+        let t = ((self.elapsed - self.delay) / self.duration).clamp(0.0, 1.0);
+        1.0 - (1.0 - t).powi(3)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.delay + self.duration
+    }
+
+    // The draw mode and scale the entity should start out with
+    pub fn initial_mode(&self, mode: DrawMode) -> DrawMode {
+        match self.fade_to {
+            Some(_) => fade(mode, 0.0),
+            None => mode,
+        }
+    }
+
+    pub fn initial_transform(&self, transform: Transform) -> Transform {
+        match self.scale_to {
+            Some(_) => transform.with_scale(Vec3::ZERO),
+            None => transform,
+        }
+    }
+}
+
+// Scales the alpha of every color in the draw mode
+pub fn fade(mode: DrawMode, amount: f32) -> DrawMode {
+    match mode {
+        DrawMode::Fill(mut fill_mode) => {
+            fill_mode.color.set_a(fill_mode.color.a() * amount);
+            DrawMode::Fill(fill_mode)
+        }
+        DrawMode::Stroke(mut outline_mode) => {
+            outline_mode.color.set_a(outline_mode.color.a() * amount);
+            DrawMode::Stroke(outline_mode)
+        }
+        DrawMode::Outlined {
+            mut fill_mode,
+            mut outline_mode,
+        } => {
+            fill_mode.color.set_a(fill_mode.color.a() * amount);
+            outline_mode.color.set_a(outline_mode.color.a() * amount);
+            DrawMode::Outlined {
+                fill_mode,
+                outline_mode,
+            }
+        }
+    }
+}
+
+pub fn animate_tweens(
+    time: Res<Time>,
+    mut tweens: Query<(
+        Entity,
+        &mut Tween,
+        Option<&mut DrawMode>,
+        Option<&mut Transform>,
+    )>,
+    mut commands: Commands,
+) {
+    for (entity, mut tween, mode, transform) in tweens.iter_mut() {
+        tween.elapsed += time.delta_seconds();
+        let progress = tween.progress();
+
+        if let (Some(target), Some(mut mode)) = (tween.fade_to, mode) {
+            *mode = fade(target, progress);
+        }
+        if let (Some(target), Some(mut transform)) = (tween.scale_to, transform) {
+            transform.scale = target * progress;
+        }
+
+        if tween.is_finished() {
+            commands.entity(entity).remove::<Tween>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn progress_waits_for_delay() {
+        let mut tween = Tween::new(1.0, 2.0);
+        assert_eq!(tween.progress(), 0.0);
+
+        tween.elapsed = 2.0;
+        assert!(tween.progress() > 0.0 && tween.progress() < 1.0);
+
+        tween.elapsed = 3.0;
+        assert_eq!(tween.progress(), 1.0);
+        assert!(tween.is_finished());
+    }
+}
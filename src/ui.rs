@@ -0,0 +1,897 @@
+use crate::{
+    ai, attract, avatar, board, core, daily, effects, export, game, input, menu, stats, storage,
+    theme, tutorial, GameStartEvent,
+};
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, plot},
+    EguiContext,
+};
+use serde::{Deserialize, Serialize};
+
+const COUNTDOWN_TEXT: [&str; 3] = ["Ready", "Set", "Go!"];
+
+#[derive(Component)]
+struct ScoreBoardEntry {
+    player: Entity,
+}
+
+#[derive(Component)]
+struct WinnerText;
+
+const SCOREBOARD_KEY: &str = "scoreboard";
+// The size of the square of each player's color next to their score, and of
+// their avatar
+const SWATCH_SIZE: f32 = 12.0;
+const AVATAR_SIZE: f32 = 14.0;
+// How far the scoreboard is kept from the edge of the window
+const SCOREBOARD_MARGIN: f32 = 10.0;
+const SCOREBOARD_FONT_SIZE: f32 = 10.0;
+const STREAMER_KEY: &str = "streamer";
+// How much bigger the scoreboard is drawn for an audience
+const STREAMER_SCALE: f32 = 2.0;
+
+// The edge of the window the scoreboard is shown along, picked in the settings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScoreboardAnchor {
+    Bottom,
+    Top,
+    Left,
+    Right,
+}
+
+impl Default for ScoreboardAnchor {
+    fn default() -> Self {
+        ScoreboardAnchor::Bottom
+    }
+}
+
+impl ScoreboardAnchor {
+    pub const ALL: [ScoreboardAnchor; 4] = [
+        ScoreboardAnchor::Bottom,
+        ScoreboardAnchor::Top,
+        ScoreboardAnchor::Left,
+        ScoreboardAnchor::Right,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScoreboardAnchor::Bottom => "Bottom",
+            ScoreboardAnchor::Top => "Top",
+            ScoreboardAnchor::Left => "Left",
+            ScoreboardAnchor::Right => "Right",
+        }
+    }
+
+    pub fn load() -> Self {
+        storage::load(SCOREBOARD_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(SCOREBOARD_KEY, self)
+    }
+
+    // Entries run across the window along the top and bottom, and down it
+    // along the sides. The UI's y axis points up, so columns are reversed to
+    // list the players from the top
+    fn style(&self) -> Style {
+        let (size, flex_direction, align_items) = match self {
+            ScoreboardAnchor::Bottom | ScoreboardAnchor::Top => (
+                Size::new(Val::Percent(100.0), Val::Auto),
+                FlexDirection::Row,
+                AlignItems::Center,
+            ),
+            ScoreboardAnchor::Left => (
+                Size::new(Val::Auto, Val::Percent(100.0)),
+                FlexDirection::ColumnReverse,
+                AlignItems::FlexStart,
+            ),
+            ScoreboardAnchor::Right => (
+                Size::new(Val::Auto, Val::Percent(100.0)),
+                FlexDirection::ColumnReverse,
+                AlignItems::FlexEnd,
+            ),
+        };
+        let margin = Val::Px(SCOREBOARD_MARGIN);
+        let position = match self {
+            // Clear of the hints and controls along the bottom of the window
+            ScoreboardAnchor::Bottom => Rect {
+                left: Val::Px(0.0),
+                bottom: Val::Px(50.0),
+                ..default()
+            },
+            ScoreboardAnchor::Top => Rect {
+                left: Val::Px(0.0),
+                top: margin,
+                ..default()
+            },
+            ScoreboardAnchor::Left => Rect {
+                left: margin,
+                bottom: Val::Px(0.0),
+                ..default()
+            },
+            ScoreboardAnchor::Right => Rect {
+                right: margin,
+                bottom: Val::Px(0.0),
+                ..default()
+            },
+        };
+
+        Style {
+            position_type: PositionType::Absolute,
+            position,
+            size,
+            flex_direction,
+            align_items,
+            justify_content: JustifyContent::Center,
+            ..default()
+        }
+    }
+}
+
+// A layout for casting games to an audience: the scoreboard is drawn larger,
+// with each player's name on a line of its own and a clock counting the
+// current turn, and the debugging overlays stay hidden. Also turned on when
+// launched with --streamer, without being saved
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamerMode {
+    pub enabled: bool,
+}
+
+impl StreamerMode {
+    pub fn load() -> Self {
+        storage::load(STREAMER_KEY)
+    }
+
+    pub fn save(&self) {
+        storage::save(STREAMER_KEY, self)
+    }
+
+    fn scale(&self) -> f32 {
+        match self.enabled {
+            true => STREAMER_SCALE,
+            false => 1.0,
+        }
+    }
+}
+
+// The player whose turn it is, the move it is, and when it started
+#[derive(Default)]
+struct TurnClock {
+    turn: Option<(Entity, usize)>,
+    started: f64,
+}
+
+// The scoreboard shown during a game
+#[derive(Component)]
+pub struct GameUi;
+
+// The menus, and everything drawn over the board during a game
+pub struct UiPlugin;
+
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(theme::Theme::load())
+            .insert_resource(ScoreboardAnchor::load())
+            .insert_resource(StreamerMode::load())
+            .init_resource::<TurnClock>()
+            .init_resource::<menu::EditorState>()
+            .add_state(menu::AppState::MainMenu)
+            .add_system_set(
+                SystemSet::on_update(menu::AppState::MainMenu).with_system(menu::show_main_menu),
+            )
+            .add_system_set(
+                SystemSet::on_update(menu::AppState::PlaySetup).with_system(menu::show_play_setup),
+            )
+            .add_system_set(
+                SystemSet::on_update(menu::AppState::HowToPlay).with_system(menu::show_how_to_play),
+            )
+            .add_system_set(
+                SystemSet::on_update(menu::AppState::Stats).with_system(menu::show_stats),
+            )
+            .add_system_set(
+                SystemSet::on_update(menu::AppState::Settings).with_system(menu::show_settings),
+            )
+            .add_system_set(
+                SystemSet::on_update(menu::AppState::Controls).with_system(menu::show_controls),
+            )
+            .add_system_set(
+                SystemSet::on_update(menu::AppState::LevelEditor)
+                    .with_system(menu::show_level_editor),
+            )
+            .add_startup_system(avatar::load_avatars)
+            .add_startup_system_to_stage(StartupStage::PostStartup, menu::start_linked_game)
+            .add_startup_system_to_stage(StartupStage::PostStartup, menu::start_launched_game)
+            .add_system(theme::apply_theme)
+            .add_system(spawn_scoreboard.after(game::game_start))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::run_if_counting_down.chain(attract::unless_demo))
+                    .with_system(show_countdown),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::run_if_game_over.chain(attract::unless_demo))
+                    .with_system(show_game_over),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::run_if_paused)
+                    .with_system(show_pause_menu),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::run_if_swap_offered)
+                    .with_system(show_swap_offer),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::run_if_game_started.chain(attract::unless_demo))
+                    .with_system(show_hover_preview.after(input::hover_tile))
+                    .with_system(show_spectator_controls)
+                    .with_system(show_color_legend)
+                    .with_system(show_score_history)
+                    .with_system(tutorial::show_tutorial),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::run_if_game_started)
+                    .with_system(time_turns)
+                    .with_system(
+                        update_scoreboard
+                            .after(core::update_scores)
+                            .after(time_turns),
+                    ),
+            );
+    }
+}
+
+// Lays out an entry for every player along the chosen edge of the window, each
+// with a swatch of the player's color and their avatar
+#[allow(clippy::too_many_arguments)]
+fn spawn_scoreboard(
+    state: Res<core::GameState>,
+    theme: Res<theme::Theme>,
+    anchor: Res<ScoreboardAnchor>,
+    streamer: Res<StreamerMode>,
+    avatars: Res<avatar::AvatarImages>,
+    mut start_event: EventReader<GameStartEvent>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let start = match start_event.iter().last() {
+        Some(start) => start,
+        None => return,
+    };
+
+    let text = || TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(5.0)),
+            ..default()
+        },
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: SCOREBOARD_FONT_SIZE * streamer.scale(),
+                color: theme.text_color(),
+            },
+            Default::default(),
+        ),
+        ..default()
+    };
+    // Along the top and bottom the round sits between the players, down the
+    // sides it comes last
+    let players = state.players.iter().zip(start.players.iter());
+    let round_position = match *anchor {
+        ScoreboardAnchor::Bottom | ScoreboardAnchor::Top => (state.players.len() + 1) / 2,
+        ScoreboardAnchor::Left | ScoreboardAnchor::Right => state.players.len(),
+    };
+    let swatch_size = SWATCH_SIZE * streamer.scale();
+    let avatar_size = AVATAR_SIZE * streamer.scale();
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: anchor.style(),
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(GameUi)
+        .with_children(|parent| {
+            for (index, (entity, player)) in players.enumerate() {
+                if index == round_position {
+                    parent.spawn_bundle(text()).insert(WinnerText);
+                }
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            margin: Rect::all(Val::Px(5.0)),
+                            ..default()
+                        },
+                        color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(swatch_size), Val::Px(swatch_size)),
+                                ..default()
+                            },
+                            color: player.color.into(),
+                            ..default()
+                        });
+                        parent.spawn_bundle(ImageBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(avatar_size), Val::Px(avatar_size)),
+                                margin: Rect {
+                                    left: Val::Px(5.0),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            image: avatars.get(player.avatar).into(),
+                            color: theme.text_color().into(),
+                            ..default()
+                        });
+                        parent
+                            .spawn_bundle(text())
+                            .insert(ScoreBoardEntry { player: *entity });
+                    });
+            }
+            if round_position == state.players.len() {
+                parent.spawn_bundle(text()).insert(WinnerText);
+            }
+        });
+}
+
+// Restarts the clock whenever the turn passes, or the same player moves again
+fn time_turns(time: Res<Time>, state: Res<core::GameState>, mut clock: ResMut<TurnClock>) {
+    let turn = state
+        .players
+        .first()
+        .map(|player| (*player, state.history.len()));
+    if turn != clock.turn {
+        clock.turn = turn;
+        clock.started = time.seconds_since_startup();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_scoreboard(
+    time: Res<Time>,
+    state: Res<core::GameState>,
+    streamer: Res<StreamerMode>,
+    clock: Res<TurnClock>,
+    search: Res<ai::BotSearch>,
+    win_chances: Res<ai::WinChances>,
+    display: Res<ai::WinChanceDisplay>,
+    players: Query<&core::Player>,
+    mut scores: Query<(&ScoreBoardEntry, &mut Text), Without<WinnerText>>,
+    mut winner_display: Query<(&mut WinnerText, &mut Text)>,
+) {
+    for mut score in scores.iter_mut() {
+        let player = match players.get(score.0.player) {
+            Ok(player) => player,
+            Err(_) => continue,
+        };
+
+        let thinking = matches!(state.phase, core::GamePhase::Running)
+            && player.kind.is_bot()
+            && state.players.first() == Some(&score.0.player);
+
+        // Bonus points are shown apart from the owned territory
+        let mut score_text = if player.bonus > 0 {
+            format!(
+                "{} ({} tiles + {} bonus)",
+                player.score,
+                player.score - player.bonus,
+                player.bonus
+            )
+        } else {
+            player.score.to_string()
+        };
+        if let Some(chance) = win_chances
+            .0
+            .get(&score.0.player)
+            .filter(|_| display.enabled)
+        {
+            score_text += &format!(" [{:.0}% to win]", chance * 100.0);
+        }
+        let to_move = state.players.first() == Some(&score.0.player);
+        if streamer.enabled && to_move && state.phase == core::GamePhase::Running {
+            let elapsed = (time.seconds_since_startup() - clock.started) as u64;
+            score_text += &format!("  {}:{:02}", elapsed / 60, elapsed % 60);
+        }
+        // Names get a line of their own on stream, to be read from afar
+        let separator = match streamer.enabled {
+            true => "\n",
+            false => " Score: ",
+        };
+        score.1.sections[0].value = match (thinking, search.progress(score.0.player)) {
+            // Deep searches take a while, so their progress is shown below
+            (true, Some(progress)) => format!(
+                "{}{}{}\nthinking... {:.0}%",
+                player.name,
+                separator,
+                score_text,
+                progress * 100.0
+            ),
+            (true, None) => format!("{}{}{} (thinking...)", player.name, separator, score_text),
+            (false, _) => format!("{}{}{}", player.name, separator, score_text),
+        };
+    }
+
+    let result = match (state.phase, state.last_round()) {
+        // The end screen announces the result
+        (core::GamePhase::Over(_) | core::GamePhase::Draw, _) => String::new(),
+        (_, Some(last_round)) => {
+            format!("Round {}/{}", state.round().min(last_round), last_round)
+        }
+        _ => return,
+    };
+
+    let mut display = winner_display
+        .iter_mut()
+        .next()
+        .expect("Missing winner display");
+    display.1.sections[0].value = result;
+}
+
+fn show_hover_preview(preview: Res<input::HoverPreview>, mut egui_ctx: ResMut<EguiContext>) {
+    let captures = match preview.captures {
+        Some(captures) => captures,
+        None => return,
+    };
+
+    egui::show_tooltip_at_pointer(egui_ctx.ctx_mut(), egui::Id::new("hover_preview"), |ui| {
+        ui.label(format!("+{}", captures));
+    });
+}
+
+fn show_countdown(countdown: Res<game::CountdownTimer>, mut egui_ctx: ResMut<EguiContext>) {
+    let step = (countdown.0.percent() * COUNTDOWN_TEXT.len() as f32) as usize;
+    let text = COUNTDOWN_TEXT[step.min(COUNTDOWN_TEXT.len() - 1)];
+
+    egui::Area::new("countdown")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .interactable(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.label(egui::RichText::new(text).size(40.0).strong());
+        });
+}
+
+fn show_pause_menu(mut state: ResMut<core::GameState>, mut egui_ctx: ResMut<EguiContext>) {
+    egui::Window::new("Paused")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            if ui.button("resume").clicked() {
+                state.phase = core::GamePhase::Running;
+            }
+        });
+}
+
+// Asks a human player offered to swap sides what they'd like to do
+fn show_swap_offer(
+    state: Res<core::GameState>,
+    players: Query<&core::Player>,
+    mut decisions: EventWriter<core::SwapEvent>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    let (player, first) = match (state.players.first(), state.history.first()) {
+        (Some(player), Some(first)) => (*player, first.player),
+        _ => return,
+    };
+    let is_human = matches!(
+        players.get(player).map(|player| &player.kind),
+        Ok(core::PlayerKind::Human)
+    );
+    if !is_human {
+        return;
+    }
+    let first_name = players
+        .get(first)
+        .map(|first| first.name.clone())
+        .unwrap_or_default();
+
+    egui::Window::new("Swap sides?")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.label(format!(
+                "{} has made the first move. Take over their side, or play on from yours?",
+                first_name
+            ));
+            ui.horizontal(|ui| {
+                for (label, swap) in [("swap", true), ("play on", false)] {
+                    if ui.button(label).clicked() {
+                        decisions.send(core::SwapEvent { player, swap });
+                    }
+                }
+            });
+        });
+}
+
+fn show_spectator_controls(
+    state: Res<core::GameState>,
+    players: Query<&core::Player>,
+    evaluations: Res<ai::BotEvaluations>,
+    mut playback: ResMut<ai::Playback>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    // Only shown for games without any human players
+    let spectating = state.players.iter().all(|player| {
+        players
+            .get(*player)
+            .map(|player| player.kind.is_bot())
+            .unwrap_or(false)
+    });
+    if !spectating {
+        return;
+    }
+
+    egui::Window::new("Spectating")
+        .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+        .resizable(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.toggle_value(&mut playback.paused, "pause");
+                for speed in [1.0, 4.0] {
+                    if ui
+                        .selectable_label(
+                            !playback.paused && playback.speed == speed,
+                            format!("{}x", speed),
+                        )
+                        .clicked()
+                    {
+                        playback.paused = false;
+                        playback.speed = speed;
+                    }
+                }
+            });
+
+            for player_id in state.players.iter() {
+                let player = match players.get(*player_id) {
+                    Ok(player) => player,
+                    Err(_) => continue,
+                };
+
+                ui.separator();
+                ui.label(&player.name);
+                ui.horizontal(|ui| {
+                    for (id, score) in evaluations.0.get(player_id).into_iter().flatten() {
+                        let [r, g, b, _] = state.ids[id].as_rgba_f32();
+                        ui.colored_label(egui::Rgba::from_rgb(r, g, b), format!("{}", score));
+                    }
+                });
+            }
+        });
+}
+
+// Every color along the edge of the screen, with how many unowned tiles of it
+// are left
+fn show_color_legend(state: Res<core::GameState>, mut egui_ctx: ResMut<EguiContext>) {
+    // The counts would give too much away in a blind game
+    if state.blind {
+        return;
+    }
+    egui::Area::new("color_legend")
+        .anchor(egui::Align2::LEFT_CENTER, [10.0, 0.0])
+        .interactable(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            for (id, count) in state.unowned.iter() {
+                let [r, g, b, _] = game::unowned_tile_color(&state, *id).as_rgba_f32();
+                ui.horizontal(|ui| {
+                    let (swatch, _) =
+                        ui.allocate_exact_size(egui::vec2(16.0, 16.0), egui::Sense::hover());
+                    ui.painter()
+                        .rect_filled(swatch, 2.0, egui::Rgba::from_rgb(r, g, b));
+                    ui.label(count.to_string());
+                });
+            }
+        });
+}
+
+// Every player's score after each move, to see the game swinging back and
+// forth. Can be collapsed out of the way
+fn show_score_history(
+    state: Res<core::GameState>,
+    summary: Res<stats::GameSummary>,
+    players: Query<&core::Player>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    // The end screen has its own chart
+    if matches!(
+        state.phase,
+        core::GamePhase::Over(_) | core::GamePhase::Draw
+    ) {
+        return;
+    }
+
+    egui::Window::new("Score History")
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+        .resizable(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            let lines: Vec<plot::Line> = state
+                .board_hash
+                .players()
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, player)| {
+                    let player = players.get(*player).ok()?;
+                    let [r, g, b, _] = player.color.as_rgba_f32();
+                    let scores =
+                        summary
+                            .timeline
+                            .iter()
+                            .enumerate()
+                            .filter_map(|(moves, scores)| {
+                                Some(plot::Value::new(moves as f64, *scores.get(idx)? as f64))
+                            });
+                    Some(
+                        plot::Line::new(plot::Values::from_values_iter(scores))
+                            .color(egui::Rgba::from_rgb(r, g, b))
+                            .name(&player.name),
+                    )
+                })
+                .collect();
+
+            plot::Plot::new("score_history")
+                .width(240.0)
+                .height(120.0)
+                .include_y(0.0)
+                .allow_drag(false)
+                .allow_zoom(false)
+                .allow_scroll(false)
+                .allow_boxed_zoom(false)
+                .legend(plot::Legend::default())
+                .show(ui, |plot_ui| {
+                    for line in lines {
+                        plot_ui.line(line);
+                    }
+                });
+        });
+}
+
+// The result of a daily game, to be shared
+fn daily_share_text(
+    day: u64,
+    state: &core::GameState,
+    players: &Query<&core::Player>,
+    tally: &stats::GameTally,
+) -> Option<String> {
+    let scores: Vec<_> = state
+        .players
+        .iter()
+        .filter_map(|id| players.get(*id).ok().map(|player| (*id, player)))
+        .collect();
+    let (human, human_score) = scores
+        .iter()
+        .find(|(_, player)| matches!(player.kind, core::PlayerKind::Human))
+        .map(|(id, player)| (*id, player.score))?;
+    let opponent_score = scores
+        .iter()
+        .filter(|(id, _)| *id != human)
+        .map(|(_, player)| player.score)
+        .max()
+        .unwrap_or(0);
+
+    let outcome = match state.phase {
+        core::GamePhase::Over(winner) if winner == human => "Won",
+        core::GamePhase::Over(_) => "Lost",
+        _ => "Drew",
+    };
+    let result = format!("{} {}-{}", outcome, human_score, opponent_score);
+    let moves: Vec<Color> = tally
+        .colors
+        .iter()
+        .filter_map(|id| state.ids.get(id).cloned())
+        .collect();
+    Some(daily::share_text(day, &result, &moves))
+}
+
+fn export_board(
+    board: &board::Board,
+    tiles: &Query<&core::Tile>,
+    players: &Query<&core::Player>,
+    state: &core::GameState,
+    theme: theme::Theme,
+) {
+    let colors: export::TileColors = tiles
+        .iter()
+        .filter_map(|tile| {
+            let color = match tile.state {
+                core::TileState::Owned(player) => (players.get(player).ok()?.color, Some(player)),
+                core::TileState::Unowned(id) => (state.ids[&id], None),
+                core::TileState::Wall => (theme.wall_color(), None),
+                core::TileState::Empty => return None,
+            };
+            Some(((tile.row, tile.column), color))
+        })
+        .collect();
+
+    let (width, height, rgba) = export::render_board(board, &colors, theme.background());
+    match export::encode_png(width, height, &rgba) {
+        Ok(png) => export::save_png(&png),
+        Err(e) => error!("Unable to encode board image: {}", e),
+    }
+}
+
+// Each player's score over the course of the game, one line per player
+fn draw_timeline(ui: &mut egui::Ui, timeline: &[Vec<u32>], colors: &[egui::Color32]) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(240.0, 80.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_stroke(rect, 2.0, ui.visuals().widgets.noninteractive.bg_stroke);
+
+    let max_score = timeline.iter().flatten().max().cloned().unwrap_or(0).max(1);
+    let last_move = timeline.len().saturating_sub(1).max(1);
+    for (idx, color) in colors.iter().enumerate() {
+        let points = timeline
+            .iter()
+            .enumerate()
+            .filter_map(|(moves, scores)| {
+                let score = *scores.get(idx)?;
+                Some(egui::pos2(
+                    rect.left() + rect.width() * moves as f32 / last_move as f32,
+                    rect.bottom() - rect.height() * score as f32 / max_score as f32,
+                ))
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(2.0, *color)));
+    }
+}
+
+// How the owned tiles are split between the players, as one bar
+fn draw_territory_split(ui: &mut egui::Ui, territory: &[(u32, egui::Color32)]) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(240.0, 16.0), egui::Sense::hover());
+    let total = territory
+        .iter()
+        .map(|(tiles, _)| *tiles)
+        .sum::<u32>()
+        .max(1);
+    let mut left = rect.left();
+    for (tiles, color) in territory {
+        let width = rect.width() * *tiles as f32 / total as f32;
+        let part = egui::Rect::from_min_size(
+            egui::pos2(left, rect.top()),
+            egui::vec2(width, rect.height()),
+        );
+        ui.painter().rect_filled(part, 0.0, *color);
+        left += width;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn show_game_over(
+    daily: Res<daily::DailyBoard>,
+    state: Res<core::GameState>,
+    players: Query<&core::Player>,
+    tiles: Query<&core::Tile>,
+    tally: Res<stats::GameTally>,
+    summary: Res<stats::GameSummary>,
+    celebration: Res<effects::Celebration>,
+    time: Res<Time>,
+    board: Res<board::Board>,
+    theme: Res<theme::Theme>,
+    avatars: Res<avatar::AvatarImages>,
+    mut egui_ctx: ResMut<EguiContext>,
+) {
+    let share_text = daily
+        .0
+        .and_then(|day| daily_share_text(day, &state, &players, &tally));
+
+    let (title, winner) = match state.phase {
+        core::GamePhase::Over(winner) => match players.get(winner) {
+            Ok(winner) => (format!("{} wins!", winner.name), Some(winner)),
+            Err(_) => return,
+        },
+        _ => ("Draw!".to_string(), None),
+    };
+    let starting: Vec<&core::Player> = state
+        .board_hash
+        .players()
+        .iter()
+        .filter_map(|player| players.get(*player).ok())
+        .collect();
+    let colors: Vec<egui::Color32> = starting
+        .iter()
+        .map(|player| {
+            let [r, g, b, _] = player.color.as_rgba_f32();
+            egui::Rgba::from_rgb(r, g, b).into()
+        })
+        .collect();
+    let territory: Vec<(u32, egui::Color32)> = starting
+        .iter()
+        .zip(colors.iter())
+        .map(|(player, color)| (player.score - player.bonus, *color))
+        .collect();
+    let total_territory = territory
+        .iter()
+        .map(|(tiles, _)| *tiles)
+        .sum::<u32>()
+        .max(1);
+    let avatar_textures: Vec<egui::TextureId> = starting
+        .iter()
+        .map(|player| egui_ctx.add_image(avatars.get(player.avatar)))
+        .collect();
+    let winner_avatar = winner.map(|winner| egui_ctx.add_image(avatars.get(winner.avatar)));
+
+    let mut export = false;
+    egui::Window::new("Game Over")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            let (scale, opacity) = celebration.entrance(time.seconds_since_startup());
+            let color = ui.visuals().strong_text_color().linear_multiply(opacity);
+            let size = 24.0 * scale.max(0.01);
+            ui.horizontal(|ui| {
+                if let Some(texture) = winner_avatar {
+                    ui.add(egui::Image::new(texture, [size, size]).tint(color));
+                }
+                ui.label(egui::RichText::new(title).size(size).strong().color(color));
+            });
+
+            draw_territory_split(ui, &territory);
+            for ((player, (tiles, color)), texture) in starting
+                .iter()
+                .zip(territory.iter())
+                .zip(avatar_textures.iter())
+            {
+                ui.horizontal(|ui| {
+                    ui.add(egui::Image::new(*texture, [AVATAR_SIZE, AVATAR_SIZE]).tint(*color));
+                    ui.colored_label(
+                        *color,
+                        format!(
+                            "{}: {} tiles ({:.0}%)",
+                            player.name,
+                            tiles,
+                            *tiles as f32 * 100.0 / total_territory as f32
+                        ),
+                    );
+                });
+            }
+
+            ui.separator();
+            ui.label(format!(
+                "{} moves over {} rounds",
+                state.history.len(),
+                state.round()
+            ));
+            if let Some((player, captured)) = summary.biggest_capture {
+                if let Ok(player) = players.get(player) {
+                    ui.label(format!(
+                        "Biggest capture: {} tiles by {}",
+                        captured, player.name
+                    ));
+                }
+            }
+            draw_timeline(ui, &summary.timeline, &colors);
+            if let Some(code) = &summary.board_code {
+                ui.horizontal(|ui| {
+                    ui.label("Board code:");
+                    ui.add(egui::TextEdit::singleline(&mut code.as_str()).desired_width(160.0));
+                    if ui.button("copy").clicked() {
+                        ui.output().copied_text = code.clone();
+                    }
+                });
+            }
+
+            if let Some(text) = &share_text {
+                ui.separator();
+                ui.add(egui::TextEdit::multiline(&mut text.as_str()).desired_rows(4));
+                if ui.button("copy").clicked() {
+                    ui.output().copied_text = text.clone();
+                }
+            }
+            export = ui.button("export image").clicked();
+        });
+
+    if export {
+        export_board(&board, &tiles, &players, &state, *theme);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::avatar::Avatar;
+use crate::core::{BoardTopology, CaptureEvent, GameState, Player, PlayerKind, Tile, TileState};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::collections::{BTreeSet, HashSet};
+
+pub const VIRUS_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
+
+// The pseudo-player owning the virus's tiles. It never takes a turn, and
+// isn't one of the game's players
+#[derive(Component)]
+pub struct Virus;
+
+pub fn virus_player() -> Player {
+    Player {
+        color: VIRUS_COLOR,
+        name: "Virus".into(),
+        avatar: Avatar::Ghost,
+        kind: PlayerKind::Neutral,
+        score: 0,
+        bonus: 0,
+    }
+}
+
+// Hands the virus the unowned tile closest to the middle of the board
+pub fn plant(tiles: &mut [Tile], virus: Entity) {
+    let (rows, columns): (Vec<_>, Vec<_>) = tiles
+        .iter()
+        .filter(|tile| !matches!(tile.state, TileState::Empty))
+        .map(|tile| (tile.row, tile.column))
+        .unzip();
+    let middle = |values: Vec<i32>| Some(values.iter().min()? + values.iter().max()?);
+    let (rows, columns) = match (middle(rows), middle(columns)) {
+        (Some(rows), Some(columns)) => (rows, columns),
+        _ => return,
+    };
+
+    // Distances are doubled, so the middle of the board stays a whole number
+    let start = tiles
+        .iter_mut()
+        .filter(|tile| matches!(tile.state, TileState::Unowned(_)))
+        .min_by_key(|tile| (tile.row * 2 - rows).pow(2) + (tile.column * 2 - columns).pow(2));
+    if let Some(tile) = start {
+        tile.state = TileState::Owned(virus);
+        tile.bonus = None;
+    }
+}
+
+// The tile the virus spreads into next: one next to it, of a color picked at
+// random from those it touches. The same seed always picks the same tile
+pub fn next_infection(
+    tiles: &[Tile],
+    topology: BoardTopology,
+    virus: Entity,
+    seed: u64,
+) -> Option<(i32, i32)> {
+    let infected = tiles
+        .iter()
+        .filter(|tile| matches!(tile.state, TileState::Owned(owner) if owner == virus))
+        .map(|tile| (tile.row, tile.column))
+        .collect::<HashSet<_>>();
+
+    let frontier = tiles
+        .iter()
+        .filter_map(|tile| match tile.state {
+            TileState::Unowned(id) => Some((id, (tile.row, tile.column))),
+            _ => None,
+        })
+        .filter(|(_, (row, column))| {
+            topology
+                .neighbor_offsets(*row)
+                .iter()
+                .any(|(row_offset, column_offset)| {
+                    infected.contains(&(row + row_offset, column + column_offset))
+                })
+        })
+        .collect::<BTreeSet<_>>();
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let colors = frontier
+        .iter()
+        .map(|(id, _)| *id)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let id = *colors.choose(&mut rng)?;
+    frontier
+        .iter()
+        .filter(|(other, _)| *other == id)
+        .map(|(_, position)| *position)
+        .collect::<Vec<_>>()
+        .choose(&mut rng)
+        .cloned()
+}
+
+// Spreads the virus by a tile at the end of every round. It is seeded from
+// the board, so replays of a game spread it the same way
+pub fn spread_virus(
+    mut state: ResMut<GameState>,
+    virus: Query<Entity, With<Virus>>,
+    mut tiles: Query<&mut Tile>,
+    mut captures: EventWriter<CaptureEvent>,
+    mut rounds_spread: Local<u32>,
+) {
+    let virus = match virus.get_single() {
+        Ok(virus) => virus,
+        Err(_) => return,
+    };
+
+    let rounds = state.round() - 1;
+    // A new game has started
+    if rounds < *rounds_spread {
+        *rounds_spread = rounds;
+    }
+    if rounds == *rounds_spread {
+        return;
+    }
+    *rounds_spread = rounds;
+
+    let board = tiles.iter().cloned().collect::<Vec<_>>();
+    let seed = state.board_hash.value ^ u64::from(rounds);
+    let position = match next_infection(&board, state.topology, virus, seed) {
+        Some(position) => position,
+        None => return,
+    };
+
+    for mut tile in tiles.iter_mut() {
+        if (tile.row, tile.column) != position {
+            continue;
+        }
+        let infected = TileState::Owned(virus);
+        state.update_tile(&tile, &infected);
+        tile.state = infected;
+        tile.bonus = None;
+        debug!(row = tile.row, column = tile.column, "virus spread");
+        captures.send(CaptureEvent {
+            row: tile.row,
+            column: tile.column,
+            player: virus,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::load_level;
+
+    #[test]
+    fn virus_spreads_next_to_itself() {
+        let player = Entity::from_raw(0);
+        let virus = Entity::from_raw(1);
+        let mut tiles = load_level(
+            "1 | | | |\n| | | | |\n| | | | |",
+            &[player],
+            vec![0, 1],
+            None,
+        );
+        plant(&mut tiles, virus);
+        assert!(matches!(tiles[7].state, TileState::Owned(owner) if owner == virus));
+
+        for seed in 0..10 {
+            let (row, column) =
+                next_infection(&tiles, BoardTopology::Square4, virus, seed).unwrap();
+            assert_eq!((row - 1).abs() + (column - 2).abs(), 1);
+            assert_eq!(
+                next_infection(&tiles, BoardTopology::Square4, virus, seed),
+                Some((row, column))
+            );
+        }
+
+        // Walled in, it has nowhere to go
+        for tile in tiles.iter_mut() {
+            if !matches!(tile.state, TileState::Owned(_)) {
+                tile.state = TileState::Wall;
+            }
+        }
+        assert_eq!(
+            next_infection(&tiles, BoardTopology::Square4, virus, 0),
+            None
+        );
+    }
+}